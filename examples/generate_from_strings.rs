@@ -0,0 +1,40 @@
+//! Runs the whole pipeline from in-memory strings, without an API key or a cache directory.
+//!
+//! `cargo run --example generate_from_strings`
+
+use anyhow::Result;
+use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+
+const STRUCTURE: &str = r#"
+NAME = {
+    # prefix: ARK_
+    ship_names = {
+        # Proud warship names
+        #@ sort: alpha
+        military = {
+        }
+        # Humble science vessel names
+        #@ decorate: "{name} Institute Vessel"
+        science = {
+        }
+    }
+}
+"#;
+
+#[tokio::main]
+async fn main() -> Result<()> {
+    let result = Generation::builder()
+        .structure(STRUCTURE)
+        .lore("The Ark drifts between dying stars, crewed by the last of an ancient people.")
+        .generator(MockGenerator::new(["Lantern", "Vigil", "Cinder"]))
+        .cache(InMemoryCache::new())
+        .run()
+        .await?;
+
+    println!("{}", result.name_list);
+    println!("{}", result.localisation_yml());
+    for block in &result.report.blocks {
+        println!("{}: {} names", block.path.join("/"), block.names);
+    }
+    Ok(())
+}
@@ -0,0 +1,112 @@
+//! Abbreviation maps decoupling a compact key from a spelled-out display value.
+//!
+//! ```
+//! use stellaris_name_gen::Abbreviations;
+//!
+//! let map = Abbreviations::new([("St", "Saint"), ("Ft", "Fort")]).unwrap();
+//! assert_eq!(map.expand("St. Vex of Ft Dawn"), "Saint Vex of Fort Dawn");
+//! assert_eq!(map.compact("Saint Vex of Fort Dawn"), "St Vex of Ft Dawn");
+//! // Only whole words are touched
+//! assert_eq!(map.expand("Stellar Fortune"), "Stellar Fortune");
+//! ```
+//!
+//! Blocks opt in with `#@ abbreviate: yes`. Names that compact to the same key but read
+//! differently keep unique keys:
+//!
+//! ```
+//! use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+//!
+//! let result = futures::executor::block_on(
+//!     Generation::builder()
+//!         .structure("NAME = {\n    ## Station names\n    #@ abbreviate: yes\n    stations = {\n    }\n}\n")
+//!         .lore("")
+//!         .generator(MockGenerator::new(["St Vex", "Saint Vex", "Station Vex"]))
+//!         .abbreviation("St", "Saint")
+//!         .abbreviation("St", "Station")
+//!         .cache(InMemoryCache::new())
+//!         .run(),
+//! )
+//! .unwrap();
+//! assert_eq!(
+//!     result.localisation,
+//!     vec![
+//!         ("ST_VEX".to_string(), "Saint Vex".to_string()),
+//!         ("ST_VEX_2".to_string(), "Station Vex".to_string()),
+//!     ]
+//! );
+//! ```
+
+use anyhow::{Context, Result};
+use regex::Regex;
+use std::collections::HashMap;
+
+/// Pairs of abbreviation and spelled-out word
+#[derive(Debug, Clone)]
+pub struct Abbreviations {
+    /// Abbreviation to its first spelled-out form
+    expansions: HashMap<String, String>,
+    /// Spelled-out form to its abbreviation
+    compactions: HashMap<String, String>,
+    abbreviated: Regex,
+    spelled_out: Regex,
+}
+
+/// Matches any of `words` as a whole word, longest first so `Saint` wins over `Sai`
+fn word_regex<'a>(words: impl Iterator<Item = &'a String>, suffix: &str) -> Result<Regex> {
+    let mut words: Vec<&String> = words.collect();
+    words.sort_by_key(|word| std::cmp::Reverse(word.len()));
+    let alternatives: Vec<String> = words.iter().map(|word| regex::escape(word)).collect();
+    // An empty alternation would match everywhere
+    let pattern = if alternatives.is_empty() {
+        r"[^\s\S]".to_string()
+    } else {
+        format!(r"\b(?:{})\b{}", alternatives.join("|"), suffix)
+    };
+    Regex::new(&pattern).context("Invalid abbreviation")
+}
+
+impl Abbreviations {
+    /// Builds a map from (abbreviation, spelled-out) pairs. An abbreviation listed twice
+    /// expands to its first form
+    pub fn new<A: Into<String>, F: Into<String>>(
+        pairs: impl IntoIterator<Item = (A, F)>,
+    ) -> Result<Self> {
+        let mut expansions = HashMap::new();
+        let mut compactions = HashMap::new();
+        for (abbreviation, full) in pairs {
+            let (abbreviation, full) = (abbreviation.into(), full.into());
+            let abbreviation = abbreviation.trim().trim_end_matches('.').to_string();
+            if abbreviation.is_empty() || full.trim().is_empty() {
+                anyhow::bail!("Abbreviations need both a short and a full form");
+            }
+            compactions.insert(full.trim().to_string(), abbreviation.clone());
+            expansions
+                .entry(abbreviation)
+                .or_insert(full.trim().to_string());
+        }
+        Ok(Self {
+            abbreviated: word_regex(expansions.keys(), r"\.?")?,
+            spelled_out: word_regex(compactions.keys(), "")?,
+            expansions,
+            compactions,
+        })
+    }
+
+    /// Spells out every abbreviation, with or without a trailing period
+    pub fn expand(&self, name: &str) -> String {
+        self.abbreviated
+            .replace_all(name, |caps: &regex::Captures| {
+                self.expansions[caps[0].trim_end_matches('.')].clone()
+            })
+            .into_owned()
+    }
+
+    /// Abbreviates every spelled-out word
+    pub fn compact(&self, name: &str) -> String {
+        self.spelled_out
+            .replace_all(name, |caps: &regex::Captures| {
+                self.compactions[&caps[0]].clone()
+            })
+            .into_owned()
+    }
+}
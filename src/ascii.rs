@@ -0,0 +1,80 @@
+//! Transliteration of names to pure ASCII, for games that cannot render anything else.
+//!
+//! ```
+//! use stellaris_name_gen::ascii::to_ascii;
+//!
+//! assert_eq!(to_ascii("Ærønde Vàlquez"), "Aeronde Valquez");
+//! assert_eq!(to_ascii("Straße “Łódź” — Ōkami’s"), "Strasse \"Lodz\" - Okami's");
+//! // Characters without a Latin reading are dropped
+//! assert_eq!(to_ascii("Vex 星"), "Vex");
+//! ```
+
+/// Latin letters with diacritics, ligatures and typographic punctuation, to their ASCII reading
+const TABLE: &[(&str, &str)] = &[
+    ("ÀÁÂÃÄÅĀĂĄ", "A"),
+    ("àáâãäåāăą", "a"),
+    ("ÇĆĈĊČ", "C"),
+    ("çćĉċč", "c"),
+    ("ĎĐÐ", "D"),
+    ("ďđð", "d"),
+    ("ÈÉÊËĒĔĖĘĚ", "E"),
+    ("èéêëēĕėęě", "e"),
+    ("ĜĞĠĢ", "G"),
+    ("ĝğġģ", "g"),
+    ("ĤĦ", "H"),
+    ("ĥħ", "h"),
+    ("ÌÍÎÏĨĪĬĮİ", "I"),
+    ("ìíîïĩīĭįı", "i"),
+    ("Ĵ", "J"),
+    ("ĵ", "j"),
+    ("Ķ", "K"),
+    ("ķĸ", "k"),
+    ("ĹĻĽĿŁ", "L"),
+    ("ĺļľŀł", "l"),
+    ("ÑŃŅŇŊ", "N"),
+    ("ñńņňŋ", "n"),
+    ("ÒÓÔÕÖØŌŎŐ", "O"),
+    ("òóôõöøōŏő", "o"),
+    ("ŔŖŘ", "R"),
+    ("ŕŗř", "r"),
+    ("ŚŜŞŠ", "S"),
+    ("śŝşšſ", "s"),
+    ("ŢŤŦ", "T"),
+    ("ţťŧ", "t"),
+    ("ÙÚÛÜŨŪŬŮŰŲ", "U"),
+    ("ùúûüũūŭůűų", "u"),
+    ("Ŵ", "W"),
+    ("ŵ", "w"),
+    ("ÝŸŶ", "Y"),
+    ("ýÿŷ", "y"),
+    ("ŹŻŽ", "Z"),
+    ("źżž", "z"),
+    ("Æ", "Ae"),
+    ("æ", "ae"),
+    ("Œ", "Oe"),
+    ("œ", "oe"),
+    ("ß", "ss"),
+    ("Þ", "Th"),
+    ("þ", "th"),
+    ("Ĳ", "IJ"),
+    ("ĳ", "ij"),
+    ("‘’‚‛ʼ´`", "'"),
+    ("“”„‟«»", "\""),
+    ("‐‑‒–—―", "-"),
+    ("…", "..."),
+    ("\u{a0}\u{2009}\u{202f}", " "),
+];
+
+/// Transliterates `text` to ASCII. Characters without a reading are dropped, along with any
+/// whitespace that leaves doubled or dangling
+pub fn to_ascii(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c.is_ascii() {
+            out.push(c);
+        } else if let Some((_, ascii)) = TABLE.iter().find(|(chars, _)| chars.contains(c)) {
+            out.push_str(ascii);
+        }
+    }
+    out.split_whitespace().collect::<Vec<_>>().join(" ")
+}
@@ -0,0 +1,138 @@
+//! A hard cap on what a run may spend, checked before every request to a model.
+//!
+//! Once the budget is exhausted, blocks are only read from the cache. Blocks without cached
+//! names come out empty and are flagged in the report:
+//!
+//! ```
+//! use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+//!
+//! let structure = "NAME = {\n    ## Ship names\n    ships = {\n    }\n    ## Fleet names\n    fleets = {\n    }\n}\n";
+//! let result = futures::executor::block_on(
+//!     Generation::builder()
+//!         .structure(structure)
+//!         .lore("")
+//!         // Every call reports 60 prompt and 40 completion tokens
+//!         .generator(MockGenerator::new(["Vex", "Ember"]).with_usage(60, 40))
+//!         .budget_tokens(100)
+//!         .cache(InMemoryCache::new())
+//!         .run(),
+//! )
+//! .unwrap();
+//! let report = &result.report;
+//! assert!(!report.blocks[0].budget_exhausted);
+//! assert_eq!(report.blocks[0].names, 2);
+//! assert!(report.blocks[1].budget_exhausted);
+//! assert_eq!(report.blocks[1].names, 0);
+//! let budget = report.budget.as_ref().unwrap();
+//! assert_eq!((budget.requests, budget.tokens, budget.max_tokens), (1, 100, Some(100)));
+//! assert!(!budget.tokens_estimated);
+//! ```
+//!
+//! An inline line whose values are not cached is left out, the rest of the run is written:
+//!
+//! ```
+//! use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+//!
+//! let structure = "NAME = {\n    motto = \"{{generate: imperial motto}}\"\n    # Ship names\n    ships = {\n    }\n}\n";
+//! let result = futures::executor::block_on(
+//!     Generation::builder()
+//!         .structure(structure)
+//!         .lore("")
+//!         .min_names(1)
+//!         .generator(MockGenerator::new(["Vex", "Ember"]))
+//!         .budget_requests(1)
+//!         .cache(InMemoryCache::new())
+//!         .run(),
+//! )
+//! .unwrap();
+//! assert_eq!(result.report.budget_skipped, ["file_structure.txt:2"]);
+//! assert!(!result.name_list.contains("motto"));
+//! assert_eq!(result.report.blocks[0].names, 2);
+//! ```
+
+use crate::generator::TokenUsage;
+use serde::Serialize;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Requests and tokens a run may spend, shared by every concurrent block
+#[derive(Debug, Default)]
+pub struct Budget {
+    max_requests: Option<u64>,
+    max_tokens: Option<u64>,
+    requests: AtomicU64,
+    /// Estimated from prompt and response lengths, used while the provider reports no usage
+    estimated_tokens: AtomicU64,
+}
+
+/// What a run spent against its budget
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct BudgetUsage {
+    pub requests: u64,
+    pub max_requests: Option<u64>,
+    pub tokens: u64,
+    pub max_tokens: Option<u64>,
+    /// The provider reported no usage, so tokens are estimated at 4 bytes per token
+    pub tokens_estimated: bool,
+}
+
+impl Budget {
+    pub fn new(max_requests: Option<u64>, max_tokens: Option<u64>) -> Self {
+        Self {
+            max_requests,
+            max_tokens,
+            ..Self::default()
+        }
+    }
+
+    /// Whether any limit is set
+    pub fn is_limited(&self) -> bool {
+        self.max_requests.is_some() || self.max_tokens.is_some()
+    }
+
+    /// Reserves one request if neither limit is reached yet. `reported` is the generator's
+    /// usage so far. In-flight requests may still take the tokens past their limit
+    pub fn try_acquire(&self, reported: Option<TokenUsage>) -> bool {
+        if let Some(max) = self.max_tokens
+            && self.usage(reported).tokens >= max
+        {
+            return false;
+        }
+        match self.max_requests {
+            Some(max) => self
+                .requests
+                .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |spent| {
+                    (spent < max).then_some(spent + 1)
+                })
+                .is_ok(),
+            None => {
+                self.requests.fetch_add(1, Ordering::SeqCst);
+                true
+            }
+        }
+    }
+
+    /// Accounts for an answered request in the token estimate
+    pub fn record(&self, prompt: &str, response: &str) {
+        let estimate = estimate_tokens(prompt.len() + response.len());
+        self.estimated_tokens.fetch_add(estimate, Ordering::SeqCst);
+    }
+
+    /// Spending so far, in provider reported tokens when there are any
+    pub fn usage(&self, reported: Option<TokenUsage>) -> BudgetUsage {
+        let reported = reported
+            .map(|usage| usage.prompt_tokens + usage.completion_tokens)
+            .filter(|tokens| *tokens > 0);
+        BudgetUsage {
+            requests: self.requests.load(Ordering::SeqCst),
+            max_requests: self.max_requests,
+            tokens: reported.unwrap_or_else(|| self.estimated_tokens.load(Ordering::SeqCst)),
+            max_tokens: self.max_tokens,
+            tokens_estimated: reported.is_none(),
+        }
+    }
+}
+
+/// Tokens in a text of `bytes` bytes, at 4 bytes per token when no provider reports them
+pub fn estimate_tokens(bytes: usize) -> u64 {
+    bytes.div_ceil(4) as u64
+}
@@ -0,0 +1,549 @@
+use crate::draft::DRAFT_TIER;
+use crate::hash::digest;
+use anyhow::{Context, Result};
+use rusqlite::{Connection, OptionalExtension, params};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Suffixes of the entries cached beside a block's own, ie its scores
+pub const DERIVED_SUFFIXES: [&str; 4] = ["~scores", "~diverse", "~balance", "~embeddings"];
+
+/// Identifies a block's cache entry, derived from its path in the structure
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct BlockKey(String);
+
+impl BlockKey {
+    /// Joins the path's keys, each made safe for a file name. Keys holding anything but ASCII
+    /// letters, digits, `_` and `-` have it replaced and are suffixed with a hash of the original
+    /// key, so `my.mod` and `my_mod` never share an entry:
+    ///
+    /// ```
+    /// use stellaris_name_gen::BlockKey;
+    ///
+    /// let key = |path: &[&str]| {
+    ///     let path: Vec<String> = path.iter().map(|key| key.to_string()).collect();
+    ///     BlockKey::from_path(&path).as_str().to_string()
+    /// };
+    /// assert_eq!(key(&["NAME", "pc_desert"]), "NAME_pc_desert");
+    /// assert_eq!(key(&["NAME", "3"]), "NAME_3");
+    /// let dotted = key(&["NAME", "my.mod.block"]);
+    /// assert!(dotted.starts_with("NAME_my_mod_block-"));
+    /// assert_ne!(dotted, key(&["NAME", "my_mod_block"]));
+    /// let quoted = key(&["NAME", "\"deep space\""]);
+    /// assert!(quoted.starts_with("NAME_deep_space-"));
+    /// assert!(quoted.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-'));
+    /// ```
+    pub fn from_path(path: &[String]) -> Self {
+        let segments: Vec<String> = path.iter().map(|key| file_safe(key)).collect();
+        Self(segments.join("_"))
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl From<&str> for BlockKey {
+    fn from(key: &str) -> Self {
+        Self(key.to_string())
+    }
+}
+
+/// A block key as written in the structure, fit for a file name
+fn file_safe(key: &str) -> String {
+    let safe = |c: char| c.is_ascii_alphanumeric() || c == '_' || c == '-';
+    if !key.is_empty() && key.chars().all(safe) {
+        return key.to_string();
+    }
+    let stem: String = key
+        .trim_matches('"')
+        .chars()
+        .map(|c| if safe(c) { c } else { '_' })
+        .collect();
+    format!("{}-{}", stem, &digest(key)[..8])
+}
+
+/// A block's raw generated output along with how it was produced
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct CachedBlock {
+    pub raw: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    /// Unix timestamp in seconds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<u64>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_hash: Option<String>,
+    /// Digest of the prompt without its flavors, telling themes apart across runs, see
+    /// [`crate::merge`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub theme_hash: Option<String>,
+    /// Stylistic flavors appended to the prompt, see [`crate::flavor`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub flavors: Vec<String>,
+    /// `max_tokens` the provider accepted for the request
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_tokens: Option<u32>,
+    /// Species class and hint added to the prompt, see [`crate::species`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub species_hint: Option<String>,
+    /// Sampling temperature of the request, `None` if the generator has none
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub temperature: Option<f64>,
+    /// Version of the prompt template, see [`crate::replay::PROMPT_VERSION`]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prompt_version: Option<u32>,
+    /// Prefix the block's keys had, after composing it with the inherited ones
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+    /// Caches and models whose names were combined into the entry, see [`crate::merge`]
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub sources: Vec<String>,
+    /// Unrepaired model output kept for debugging (`--keep-raw`), only persisted by [`FsCache`]
+    #[serde(skip)]
+    pub raw_stream: Option<String>,
+}
+
+impl CachedBlock {
+    pub fn new(raw: impl Into<String>) -> Self {
+        Self {
+            raw: raw.into(),
+            ..Self::default()
+        }
+    }
+}
+
+/// Storage for generated blocks
+pub trait NameCache: Send {
+    /// Returns the cached block, treating empty entries as missing
+    fn get(&self, key: &BlockKey) -> Option<CachedBlock>;
+    fn put(&mut self, key: &BlockKey, block: CachedBlock) -> Result<()>;
+    /// Deletes an entry along with its metadata, missing entries are ignored
+    fn remove(&mut self, key: &BlockKey) -> Result<()>;
+    /// Every stored key, sorted
+    fn keys(&self) -> Result<Vec<BlockKey>>;
+}
+
+impl NameCache for Box<dyn NameCache> {
+    fn get(&self, key: &BlockKey) -> Option<CachedBlock> {
+        (**self).get(key)
+    }
+
+    fn put(&mut self, key: &BlockKey, block: CachedBlock) -> Result<()> {
+        (**self).put(key, block)
+    }
+
+    fn remove(&mut self, key: &BlockKey) -> Result<()> {
+        (**self).remove(key)
+    }
+
+    fn keys(&self) -> Result<Vec<BlockKey>> {
+        (**self).keys()
+    }
+}
+
+/// One `<key>.txt` file per block inside a directory, metadata in a `<key>.meta.json` sidecar
+/// and the unrepaired stream in an optional `<key>.raw` sidecar
+#[derive(Debug, Clone)]
+pub struct FsCache {
+    dir: PathBuf,
+}
+
+impl FsCache {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    pub fn path(&self, key: &BlockKey) -> PathBuf {
+        self.dir.join(format!("{}.txt", key.as_str()))
+    }
+
+    fn meta_path(&self, key: &BlockKey) -> PathBuf {
+        self.dir.join(format!("{}.meta.json", key.as_str()))
+    }
+
+    fn raw_path(&self, key: &BlockKey) -> PathBuf {
+        self.dir.join(format!("{}.raw", key.as_str()))
+    }
+}
+
+impl Default for FsCache {
+    fn default() -> Self {
+        Self::new("cache")
+    }
+}
+
+impl NameCache for FsCache {
+    fn get(&self, key: &BlockKey) -> Option<CachedBlock> {
+        let raw = fs::read_to_string(self.path(key))
+            .ok()
+            .filter(|raw| !raw.trim().is_empty())?;
+        let meta: CachedBlock = fs::read_to_string(self.meta_path(key))
+            .ok()
+            .and_then(|meta| serde_json::from_str(&meta).ok())
+            .unwrap_or_default();
+        Some(CachedBlock {
+            raw,
+            raw_stream: fs::read_to_string(self.raw_path(key)).ok(),
+            ..meta
+        })
+    }
+
+    fn put(&mut self, key: &BlockKey, block: CachedBlock) -> Result<()> {
+        fs::create_dir_all(&self.dir).context("Failed to create cache dir")?;
+        let path = self.path(key);
+        fs::write(&path, &block.raw).context("Failed to write cache file")?;
+        let meta = CachedBlock {
+            raw: String::new(),
+            ..block.clone()
+        };
+        fs::write(self.meta_path(key), serde_json::to_string_pretty(&meta)?)
+            .context("Failed to write cache metadata")?;
+        if let Some(stream) = &block.raw_stream {
+            fs::write(self.raw_path(key), stream).context("Failed to write raw cache sidecar")?;
+        }
+        println!(
+            "[AI] Cached {} bytes to '{}'",
+            block.raw.len(),
+            path.display()
+        );
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &BlockKey) -> Result<()> {
+        for path in [self.path(key), self.meta_path(key), self.raw_path(key)] {
+            match fs::remove_file(&path) {
+                Err(e) if e.kind() != std::io::ErrorKind::NotFound => {
+                    return Err(e).with_context(|| format!("Failed to remove {}", path.display()));
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+
+    fn keys(&self) -> Result<Vec<BlockKey>> {
+        let mut keys = Vec::new();
+        let Ok(entries) = fs::read_dir(&self.dir) else {
+            return Ok(keys);
+        };
+        for entry in entries {
+            let name = entry?.file_name().to_string_lossy().to_string();
+            if let Some(stem) = name.strip_suffix(".txt") {
+                keys.push(BlockKey::from(stem));
+            }
+        }
+        keys.sort();
+        Ok(keys)
+    }
+}
+
+/// Keeps everything in memory, for tests and previews that must not touch the disk
+#[derive(Debug, Clone, Default)]
+pub struct InMemoryCache {
+    entries: BTreeMap<BlockKey, CachedBlock>,
+}
+
+impl InMemoryCache {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+impl NameCache for InMemoryCache {
+    fn get(&self, key: &BlockKey) -> Option<CachedBlock> {
+        self.entries
+            .get(key)
+            .filter(|block| !block.raw.trim().is_empty())
+            .cloned()
+    }
+
+    fn put(&mut self, key: &BlockKey, block: CachedBlock) -> Result<()> {
+        self.entries.insert(key.clone(), block);
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &BlockKey) -> Result<()> {
+        self.entries.remove(key);
+        Ok(())
+    }
+
+    fn keys(&self) -> Result<Vec<BlockKey>> {
+        Ok(self.entries.keys().cloned().collect())
+    }
+}
+
+/// All blocks in a single SQLite file, handy to sync between machines
+pub struct SqliteCache {
+    conn: Connection,
+}
+
+impl SqliteCache {
+    /// Opens (or creates) the database and its schema
+    pub fn open(path: &Path) -> Result<Self> {
+        let conn =
+            Connection::open(path).with_context(|| format!("Failed to open {}", path.display()))?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS blocks (
+                key TEXT PRIMARY KEY,
+                raw TEXT NOT NULL,
+                model TEXT,
+                timestamp INTEGER,
+                prompt_hash TEXT,
+                flavors TEXT,
+                max_tokens INTEGER,
+                species_hint TEXT,
+                temperature REAL,
+                prompt_version INTEGER,
+                prefix TEXT,
+                sources TEXT,
+                theme_hash TEXT
+            );",
+        )
+        .context("Failed to create cache schema")?;
+        // Databases created before these were recorded lack the column
+        for (column, kind) in [
+            ("flavors", "TEXT"),
+            ("max_tokens", "INTEGER"),
+            ("species_hint", "TEXT"),
+            ("temperature", "REAL"),
+            ("prompt_version", "INTEGER"),
+            ("prefix", "TEXT"),
+            ("sources", "TEXT"),
+            ("theme_hash", "TEXT"),
+        ] {
+            let exists = conn
+                .prepare("SELECT 1 FROM pragma_table_info('blocks') WHERE name = ?1")?
+                .exists([column])?;
+            if !exists {
+                conn.execute_batch(&format!(
+                    "ALTER TABLE blocks ADD COLUMN {} {};",
+                    column, kind
+                ))
+                .context("Failed to migrate cache schema")?;
+            }
+        }
+        Ok(Self { conn })
+    }
+}
+
+impl NameCache for SqliteCache {
+    fn get(&self, key: &BlockKey) -> Option<CachedBlock> {
+        self.conn
+            .query_row(
+                "SELECT raw, model, timestamp, prompt_hash, flavors, max_tokens, species_hint, temperature, prompt_version, prefix, sources, theme_hash FROM blocks WHERE key = ?1",
+                params![key.as_str()],
+                |row| {
+                    Ok(CachedBlock {
+                        raw: row.get(0)?,
+                        model: row.get(1)?,
+                        timestamp: row.get::<_, Option<i64>>(2)?.map(|t| t as u64),
+                        prompt_hash: row.get(3)?,
+                        flavors: row
+                            .get::<_, Option<String>>(4)?
+                            .and_then(|flavors| serde_json::from_str(&flavors).ok())
+                            .unwrap_or_default(),
+                        max_tokens: row.get(5)?,
+                        species_hint: row.get(6)?,
+                        temperature: row.get(7)?,
+                        prompt_version: row.get(8)?,
+                        prefix: row.get(9)?,
+                        sources: row
+                            .get::<_, Option<String>>(10)?
+                            .and_then(|sources| serde_json::from_str(&sources).ok())
+                            .unwrap_or_default(),
+                        theme_hash: row.get(11)?,
+                        raw_stream: None,
+                    })
+                },
+            )
+            .optional()
+            .ok()
+            .flatten()
+            .filter(|block| !block.raw.trim().is_empty())
+    }
+
+    fn put(&mut self, key: &BlockKey, block: CachedBlock) -> Result<()> {
+        self.conn
+            .execute(
+                "INSERT INTO blocks (key, raw, model, timestamp, prompt_hash, flavors, max_tokens, species_hint, temperature, prompt_version, prefix, sources, theme_hash)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13)
+                 ON CONFLICT(key) DO UPDATE SET raw = ?2, model = ?3, timestamp = ?4, prompt_hash = ?5, flavors = ?6, max_tokens = ?7, species_hint = ?8, temperature = ?9, prompt_version = ?10, prefix = ?11, sources = ?12, theme_hash = ?13",
+                params![
+                    key.as_str(),
+                    block.raw,
+                    block.model,
+                    block.timestamp.map(|t| t as i64),
+                    block.prompt_hash,
+                    (!block.flavors.is_empty())
+                        .then(|| serde_json::to_string(&block.flavors))
+                        .transpose()?,
+                    block.max_tokens,
+                    block.species_hint,
+                    block.temperature,
+                    block.prompt_version,
+                    block.prefix,
+                    (!block.sources.is_empty())
+                        .then(|| serde_json::to_string(&block.sources))
+                        .transpose()?,
+                    block.theme_hash
+                ],
+            )
+            .context("Failed to write cache entry")?;
+        Ok(())
+    }
+
+    fn remove(&mut self, key: &BlockKey) -> Result<()> {
+        self.conn
+            .execute("DELETE FROM blocks WHERE key = ?1", params![key.as_str()])
+            .context("Failed to remove cache entry")?;
+        Ok(())
+    }
+
+    fn keys(&self) -> Result<Vec<BlockKey>> {
+        let mut stmt = self.conn.prepare("SELECT key FROM blocks ORDER BY key")?;
+        let keys = stmt
+            .query_map([], |row| row.get::<_, String>(0))?
+            .map(|key| key.map(|key| BlockKey::from(key.as_str())))
+            .collect::<rusqlite::Result<_>>()?;
+        Ok(keys)
+    }
+}
+
+/// Which cache backend a run uses, see [`CacheConfig`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CacheBackend {
+    #[default]
+    Fs,
+    Memory,
+    Sqlite,
+}
+
+/// `[cache]` section of the config file
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct CacheConfig {
+    pub backend: CacheBackend,
+    /// Directory of the `fs` backend
+    pub dir: PathBuf,
+    /// Database file of the `sqlite` backend
+    pub path: PathBuf,
+    /// Namespace isolating the names of experiments, see [`namespaced`](Self::namespaced)
+    pub namespace: Option<String>,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            backend: CacheBackend::Fs,
+            dir: PathBuf::from("cache"),
+            path: PathBuf::from("cache.sqlite"),
+            namespace: None,
+        }
+    }
+}
+
+impl CacheConfig {
+    /// The same backend for a named tier of names kept apart from the main cache, ie the
+    /// `draft` tier of [`crate::draft`]. The `fs` tier is a folder inside the cache directory,
+    /// which the main cache never lists, the `sqlite` tier a database beside the main one:
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// use stellaris_name_gen::cache::CacheConfig;
+    ///
+    /// let tier = CacheConfig::default().tier("draft");
+    /// assert_eq!(tier.dir, Path::new("cache/draft"));
+    /// assert_eq!(tier.path, Path::new("cache.draft.sqlite"));
+    /// ```
+    pub fn tier(&self, name: &str) -> Self {
+        let stem = self.path.file_stem().unwrap_or_default().to_string_lossy();
+        let file = match self.path.extension() {
+            Some(ext) => format!("{}.{}.{}", stem, name, ext.to_string_lossy()),
+            None => format!("{}.{}", stem, name),
+        };
+        Self {
+            backend: self.backend,
+            dir: self.dir.join(name),
+            path: self.path.with_file_name(file),
+            namespace: self.namespace.clone(),
+        }
+    }
+
+    /// The cache of the configured namespace, a tier of its own, or the flat main cache without
+    /// one. Experiments in a namespace never read or write the main cache, and deleting its
+    /// folder discards them whole:
+    ///
+    /// ```
+    /// use std::path::Path;
+    /// use stellaris_name_gen::cache::CacheConfig;
+    ///
+    /// let config = CacheConfig {
+    ///     namespace: Some("short-prompts".to_string()),
+    ///     ..CacheConfig::default()
+    /// };
+    /// let cache = config.namespaced().unwrap();
+    /// assert_eq!(cache.dir, Path::new("cache/short-prompts"));
+    /// assert_eq!(cache.path, Path::new("cache.short-prompts.sqlite"));
+    /// // Drafts of an experiment stay in it too
+    /// assert_eq!(cache.tier("draft").dir, Path::new("cache/short-prompts/draft"));
+    /// assert_eq!(CacheConfig::default().namespaced().unwrap().dir, Path::new("cache"));
+    ///
+    /// let namespace = |name: &str| CacheConfig {
+    ///     namespace: Some(name.to_string()),
+    ///     ..CacheConfig::default()
+    /// };
+    /// assert!(namespace("../cache").namespaced().is_err());
+    /// assert!(namespace("draft").namespaced().is_err());
+    /// ```
+    pub fn namespaced(&self) -> Result<Self> {
+        let Some(namespace) = &self.namespace else {
+            return Ok(self.clone());
+        };
+        if namespace.is_empty()
+            || !namespace
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            anyhow::bail!(
+                "Cache namespace '{}' may only hold ASCII letters, digits, '-' and '_'",
+                namespace
+            );
+        }
+        if namespace == DRAFT_TIER {
+            anyhow::bail!(
+                "'{}' is the tier of draft runs, not a cache namespace",
+                namespace
+            );
+        }
+        Ok(Self {
+            namespace: None,
+            ..self.tier(namespace)
+        })
+    }
+
+    /// Opens the configured backend
+    pub fn open(&self) -> Result<Box<dyn NameCache>> {
+        Ok(match self.backend {
+            CacheBackend::Fs => Box::new(FsCache::new(&self.dir)),
+            CacheBackend::Memory => Box::new(InMemoryCache::new()),
+            CacheBackend::Sqlite => Box::new(SqliteCache::open(&self.path)?),
+        })
+    }
+}
+
+/// Copies every entry of `from` into `to`, returning how many were copied
+pub fn migrate(from: &dyn NameCache, to: &mut dyn NameCache) -> Result<usize> {
+    let mut copied = 0;
+    for key in from.keys()? {
+        if let Some(block) = from.get(&key) {
+            to.put(&key, block)?;
+            println!("[Cache] Migrated '{}'", key.as_str());
+            copied += 1;
+        }
+    }
+    Ok(copied)
+}
@@ -0,0 +1,235 @@
+//! Keeps the capitalization of a block's names uniform without being told the scheme. The
+//! scheme most names follow, Title Case, ALL CAPS or lowercase, is detected per block, and the
+//! names written in another one are reported or, with `--consistency-fix`, rewritten in it.
+//! Names fitting no scheme, ie `iPhone` or `Silent dawn`, are left alone, and so are blocks
+//! without a clear majority:
+//!
+//! ```
+//! use stellaris_name_gen::casing::{self, Casing};
+//!
+//! let mut names: Vec<String> = [
+//!     "Silent Dawn", "VEX IV", "ember", "Song of the Void", "Cinder", "Ashford", "Tyran", "Krag", "Hollow Star",
+//! ]
+//!     .iter()
+//!     .map(|name| name.to_string())
+//!     .collect();
+//! let report = casing::check(&mut names, true).unwrap();
+//! assert_eq!(report.scheme, Casing::Title);
+//! assert_eq!(
+//!     report.outliers,
+//!     [
+//!         ("VEX IV".to_string(), "Vex IV".to_string()),
+//!         ("ember".to_string(), "Ember".to_string()),
+//!     ]
+//! );
+//! assert_eq!(names[..3], ["Silent Dawn", "Vex IV", "Ember"]);
+//!
+//! // Half and half has no majority to follow
+//! let mut split: Vec<String> = ["VEX", "EMBER", "Dawn", "Cinder"].iter().map(|name| name.to_string()).collect();
+//! assert!(casing::check(&mut split, true).is_none());
+//! assert_eq!(casing::classify("iPhone"), None);
+//! ```
+//!
+//! In a run, every block is checked after its names are cleaned up, and the changes land in the
+//! report:
+//!
+//! ```
+//! use stellaris_name_gen::casing::CasingCheck;
+//! use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+//!
+//! let run = |check| {
+//!     futures::executor::block_on(
+//!         Generation::builder()
+//!             .structure("NAME = {\n    # Ship names\n    ships = {\n    }\n}\n")
+//!             .lore("")
+//!             .min_names(1)
+//!             .generator(MockGenerator::new(["Silent Dawn", "EMBER", "Cinder", "Ashford", "Vex"]))
+//!             .consistency(check)
+//!             .cache(InMemoryCache::new())
+//!             .run(),
+//!     )
+//!     .unwrap()
+//! };
+//! let names = |result: &stellaris_name_gen::RunResult| -> Vec<String> {
+//!     result.localisation.iter().map(|(_, name)| name.clone()).collect()
+//! };
+//! let reported = run(CasingCheck::Report);
+//! assert!(names(&reported).contains(&"EMBER".to_string()));
+//! assert!(!reported.report.blocks[0].casing.as_ref().unwrap().fixed);
+//!
+//! let fixed = run(CasingCheck::Fix);
+//! assert!(names(&fixed).contains(&"Ember".to_string()));
+//! assert_eq!(fixed.report.blocks[0].casing.as_ref().unwrap().outliers.len(), 1);
+//! ```
+
+use crate::ordinals::roman;
+use serde::Serialize;
+
+/// Share of the names with a scheme that must follow the same one for it to be enforced
+pub const MAJORITY: f64 = 0.75;
+
+/// Fewest names with a scheme a block needs before a majority means anything
+pub const MIN_NAMES: usize = 4;
+
+/// Words Title Case leaves lowercase past the first word
+const MINOR_WORDS: [&str; 22] = [
+    "a", "an", "and", "at", "by", "da", "de", "del", "der", "di", "du", "for", "in", "la", "le",
+    "of", "on", "or", "the", "to", "van", "von",
+];
+
+/// Highest roman ordinal Title Case keeps uppercase, ie the `IV` of `Vex IV`
+const MAX_ORDINAL: usize = 39;
+
+/// A capitalization scheme
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Casing {
+    /// Every word capitalized but minor ones, ie `Song of the Void`
+    Title,
+    /// `SONG OF THE VOID`
+    Upper,
+    /// `song of the void`
+    Lower,
+}
+
+impl Casing {
+    pub fn as_str(self) -> &'static str {
+        match self {
+            Casing::Title => "Title Case",
+            Casing::Upper => "ALL CAPS",
+            Casing::Lower => "lowercase",
+        }
+    }
+
+    /// The name written in this scheme
+    pub fn apply(self, name: &str) -> String {
+        match self {
+            Casing::Upper => name.to_uppercase(),
+            Casing::Lower => name.to_lowercase(),
+            Casing::Title => {
+                let mut out = String::new();
+                let mut word = String::new();
+                let mut first = true;
+                for c in name.chars().chain(std::iter::once(' ')) {
+                    if c.is_whitespace() || c == '-' {
+                        if !word.is_empty() {
+                            out.push_str(&title_word(&word, first));
+                            first = false;
+                            word.clear();
+                        }
+                        out.push(c);
+                    } else {
+                        word.push(c);
+                    }
+                }
+                out.pop();
+                out
+            }
+        }
+    }
+}
+
+/// Whether to check the capitalization of every block, and whether to fix it
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CasingCheck {
+    #[default]
+    Off,
+    /// Report the outliers and keep them
+    Report,
+    /// Rewrite the outliers in the block's scheme
+    Fix,
+}
+
+/// A block's scheme and the names off it
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct CasingReport {
+    pub scheme: Casing,
+    /// Share of the names with a scheme that follow it
+    pub share: f64,
+    /// Every name off the scheme, with its form in the scheme
+    pub outliers: Vec<(String, String)>,
+    /// Whether the outliers were replaced by their form in the scheme
+    pub fixed: bool,
+}
+
+/// The scheme of a single name, `None` if it fits none or has fewer than two cased letters
+pub fn classify(name: &str) -> Option<Casing> {
+    let letters: Vec<char> = name
+        .chars()
+        .filter(|c| c.is_uppercase() || c.is_lowercase())
+        .collect();
+    if letters.len() < 2 {
+        return None;
+    }
+    if letters.iter().all(|c| c.is_uppercase()) {
+        return Some(Casing::Upper);
+    }
+    if letters.iter().all(|c| c.is_lowercase()) {
+        return Some(Casing::Lower);
+    }
+    let title = name
+        .split(|c: char| c.is_whitespace() || c == '-')
+        .filter(|word| !word.is_empty())
+        .enumerate()
+        .all(
+            |(idx, word)| match word.chars().find(|c| c.is_uppercase() || c.is_lowercase()) {
+                Some(first) if first.is_uppercase() => true,
+                Some(_) => idx > 0 && MINOR_WORDS.contains(&word.to_lowercase().as_str()),
+                None => true,
+            },
+        );
+    title.then_some(Casing::Title)
+}
+
+/// Finds the scheme most of the names follow and the names in another one, rewriting them in
+/// it if `fix`. `None` if no scheme holds the majority or every name follows it
+pub fn check(names: &mut [String], fix: bool) -> Option<CasingReport> {
+    let schemes: Vec<Option<Casing>> = names.iter().map(|name| classify(name)).collect();
+    let classified = schemes.iter().flatten().count();
+    if classified < MIN_NAMES {
+        return None;
+    }
+    let (scheme, count) = [Casing::Title, Casing::Upper, Casing::Lower]
+        .into_iter()
+        .map(|scheme| {
+            let count = schemes.iter().filter(|s| **s == Some(scheme)).count();
+            (scheme, count)
+        })
+        .max_by_key(|(_, count)| *count)?;
+    let share = count as f64 / classified as f64;
+    if share < MAJORITY || count == classified {
+        return None;
+    }
+    let mut outliers = Vec::new();
+    for (name, casing) in names.iter_mut().zip(&schemes) {
+        if casing.is_some_and(|casing| casing != scheme) {
+            let rewritten = scheme.apply(name);
+            outliers.push((name.clone(), rewritten.clone()));
+            if fix {
+                *name = rewritten;
+            }
+        }
+    }
+    Some(CasingReport {
+        scheme,
+        share,
+        outliers,
+        fixed: fix,
+    })
+}
+
+/// A word in Title Case, minor words past the first and roman ordinals kept as they are read
+fn title_word(word: &str, first: bool) -> String {
+    let lower = word.to_lowercase();
+    if !first && MINOR_WORDS.contains(&lower.as_str()) {
+        return lower;
+    }
+    if !first && (1..=MAX_ORDINAL).any(|n| roman(n).eq_ignore_ascii_case(word)) {
+        return word.to_uppercase();
+    }
+    let mut chars = lower.chars();
+    match chars.next() {
+        Some(c) => c.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
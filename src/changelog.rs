@@ -0,0 +1,216 @@
+//! A running history of the name catalog, `--changelog CHANGELOG.md`. Before a run overwrites
+//! its outputs, the names of every block are read back from the previous name list and
+//! localisation, and a dated section with the names each theme gained and lost is appended.
+//! Every line names its block and theme, so `grep` finds when a name came or went:
+//!
+//! ```
+//! use stellaris_name_gen::changelog::{self, previous_names};
+//! use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+//!
+//! let run = |names: &[&str]| {
+//!     futures::executor::block_on(
+//!         Generation::builder()
+//!             .structure("NAME = {\n    # Ship names\n    # Short and proud\n    ships = {\n    }\n}\n")
+//!             .lore("")
+//!             .min_names(1)
+//!             .model("local-model")
+//!             .value_format("§Y{val}§!")
+//!             .generator(MockGenerator::new(names.to_vec()))
+//!             .cache(InMemoryCache::new())
+//!             .run(),
+//!     )
+//!     .unwrap()
+//! };
+//! let before = run(&["Vex", "Ember", "Dawn"]);
+//! let previous = previous_names(&before.name_list, &[before.localisation_yml()], &before.value_format);
+//! assert_eq!(previous["NAME/ships"].len(), 3);
+//!
+//! let after = run(&["Vex", "Ember", "Cinder", "Ash"]);
+//! let changes = changelog::changes(&previous, &after);
+//! assert_eq!(changes[0].added, ["Ash", "Cinder"]);
+//! assert_eq!(changes[0].removed, ["Dawn"]);
+//! assert_eq!(
+//!     changelog::render_section(1_780_000_000, &after, &changes),
+//!     "## 2026-05-28 20:26 UTC, local-model, +2 -1\n\
+//!      \n\
+//!      + NAME/ships (Ship names): Ash, Cinder\n\
+//!      - NAME/ships (Ship names): Dawn\n"
+//! );
+//! // The same names again change nothing
+//! let unchanged = previous_names(&after.name_list, &[after.localisation_yml()], &after.value_format);
+//! assert!(changelog::changes(&unchanged, &after).is_empty());
+//! ```
+
+use crate::pipeline::RunResult;
+use crate::provenance::{date, time_of_day};
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+
+/// Heading of a new changelog file
+const TITLE: &str = "# Name changelog\n";
+
+/// The names a block gained and lost since the previous output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ThemeChange {
+    pub path: String,
+    /// First line of the block's theme, empty for a block the run no longer generates
+    pub theme: String,
+    pub added: Vec<String>,
+    pub removed: Vec<String>,
+}
+
+/// Names per block path of an earlier output, from its name list and `l_english` localisation
+/// documents. Keys without a localised value, ie hand-written names, are left out
+pub fn previous_names(
+    name_list: &str,
+    localisation: &[String],
+    value_format: &str,
+) -> BTreeMap<String, BTreeSet<String>> {
+    let mut values = BTreeMap::new();
+    for yml in localisation {
+        let mut lines = yml.trim_start_matches('\u{feff}').lines();
+        if lines.next().map(str::trim) != Some("l_english:") {
+            continue;
+        }
+        for line in lines {
+            if let Some((key, value)) = entry(line, value_format) {
+                values.insert(key, value);
+            }
+        }
+    }
+    let mut names: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+    let mut path: Vec<&str> = Vec::new();
+    for line in name_list.lines() {
+        let line = line.split('#').next().unwrap_or_default().trim();
+        if let Some(opened) = line.strip_suffix('{') {
+            path.push(opened.trim_end().trim_end_matches('=').trim_end());
+        } else if line == "}" {
+            path.pop();
+        } else if !line.contains('=') && !line.contains('"') {
+            for key in line.split(|c: char| c == ',' || c.is_whitespace()) {
+                if let Some(value) = values.get(key) {
+                    names
+                        .entry(path.join("/"))
+                        .or_default()
+                        .insert(value.clone());
+                }
+            }
+        }
+    }
+    names
+}
+
+/// Key and value of a `KEY:0 "value"` yml entry, the value unwrapped from `value_format`
+fn entry(line: &str, value_format: &str) -> Option<(String, String)> {
+    let (key, rest) = line.trim().split_once(':')?;
+    let quoted = rest
+        .trim_start_matches(|c: char| c.is_ascii_digit())
+        .trim()
+        .strip_prefix('"')?
+        .strip_suffix('"')?;
+    let value = quoted.replace("\\\"", "\"");
+    let format = value_format.replace("{key}", key);
+    let (before, after) = format.split_once("{val}").unwrap_or(("", ""));
+    let value = value
+        .strip_prefix(before)
+        .and_then(|value| value.strip_suffix(after))
+        .unwrap_or(&value);
+    Some((key.to_string(), value.to_string()))
+}
+
+/// The blocks whose names differ between `previous` and the run, in path order
+pub fn changes(
+    previous: &BTreeMap<String, BTreeSet<String>>,
+    result: &RunResult,
+) -> Vec<ThemeChange> {
+    let mut current: BTreeMap<String, (String, BTreeSet<String>)> = BTreeMap::new();
+    for block in &result.report.blocks {
+        let (theme, names) = current.entry(block.path.join("/")).or_default();
+        if theme.is_empty() {
+            *theme = block
+                .theme
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_string();
+        }
+        names.extend(block.entries.iter().map(|(_, name)| name.clone()));
+    }
+    let empty = BTreeSet::new();
+    let paths: BTreeSet<&String> = previous.keys().chain(current.keys()).collect();
+    paths
+        .into_iter()
+        .filter_map(|path| {
+            let before = previous.get(path).unwrap_or(&empty);
+            let (theme, after) = current
+                .get(path)
+                .map(|(theme, names)| (theme.clone(), names))
+                .unwrap_or((String::new(), &empty));
+            let change = ThemeChange {
+                path: path.clone(),
+                theme,
+                added: after.difference(before).cloned().collect(),
+                removed: before.difference(after).cloned().collect(),
+            };
+            (!change.added.is_empty() || !change.removed.is_empty()).then_some(change)
+        })
+        .collect()
+}
+
+/// The section of a run at unix time `at`, headed by its date, models and net change. Every
+/// line holds one side of one block's change
+pub fn render_section(at: u64, result: &RunResult, changes: &[ThemeChange]) -> String {
+    let models: BTreeSet<&str> = result
+        .report
+        .blocks
+        .iter()
+        .map(|block| block.model.as_str())
+        .collect();
+    let added: usize = changes.iter().map(|change| change.added.len()).sum();
+    let removed: usize = changes.iter().map(|change| change.removed.len()).sum();
+    let mut section = format!(
+        "## {} {} UTC, {}, +{} -{}\n\n",
+        date(at),
+        time_of_day(at),
+        models.into_iter().collect::<Vec<_>>().join(", "),
+        added,
+        removed
+    );
+    if changes.is_empty() {
+        section.push_str("No names changed\n");
+    }
+    for change in changes {
+        let label = if change.theme.is_empty() {
+            change.path.clone()
+        } else {
+            format!("{} ({})", change.path, change.theme)
+        };
+        for (sign, names) in [('+', &change.added), ('-', &change.removed)] {
+            if !names.is_empty() {
+                section.push_str(&format!("{} {}: {}\n", sign, label, names.join(", ")));
+            }
+        }
+    }
+    section
+}
+
+/// Appends a section to the changelog, creating it with a title if needed
+pub fn append(path: &Path, section: &str) -> Result<()> {
+    let exists = path.exists();
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .with_context(|| format!("Failed to open {}", path.display()))?;
+    let text = if exists {
+        format!("\n{}", section)
+    } else {
+        format!("{}\n{}", TITLE, section)
+    };
+    file.write_all(text.as_bytes())
+        .with_context(|| format!("Failed to write {}", path.display()))
+}
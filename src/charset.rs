@@ -0,0 +1,145 @@
+//! Checks localisation values against the characters a game accepts, so a stray character never
+//! surfaces as a localisation parse error in game. Control characters are never allowed, they
+//! break the yml line holding them. The `stellaris` profile also rejects the characters its
+//! localisation gives a meaning to, `§` colors, `£` icons, `$` variables, `[` `]` scripted
+//! localisation and `\` escapes, and characters its fonts lack, ie emoji:
+//!
+//! ```
+//! use stellaris_name_gen::charset::LocCharset;
+//!
+//! assert_eq!(LocCharset::Stellaris.disallowed("$Vex$ £ship£"), ['$', '£']);
+//! assert!(LocCharset::Stellaris.disallowed("Vex's Fury, Škoda").is_empty());
+//! assert_eq!(LocCharset::Stellaris.strip("The [Vex] 🚀 Fleet"), "The Vex Fleet");
+//! // The permissive default only rejects control characters
+//! assert!(LocCharset::Permissive.disallowed("$Vex$").is_empty());
+//! assert_eq!(LocCharset::Permissive.disallowed("Em\u{7}ber"), ['\u{7}']);
+//! ```
+//!
+//! Every value of a run is checked, translations included. Violations are logged and kept in the
+//! report, and stripped with [`crate::GenerationBuilder::strip_loc_charset`]. A stripped value is
+//! what the report and name list hold too, and a value nothing is left of is dropped, a
+//! translation falling back to its English value:
+//!
+//! ```
+//! use stellaris_name_gen::charset::LocCharset;
+//! use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+//!
+//! let run = |strip| {
+//!     futures::executor::block_on(
+//!         Generation::builder()
+//!             .structure("NAME = {\n    # Ship names\n    ships = {\n    }\n}\n")
+//!             .lore("")
+//!             .min_names(1)
+//!             .loc_charset(LocCharset::Stellaris)
+//!             .strip_loc_charset(strip)
+//!             .generator(MockGenerator::new(["[Vex]", "Ember", "$$"]))
+//!             .cache(InMemoryCache::new())
+//!             .run(),
+//!     )
+//!     .unwrap()
+//! };
+//! let flagged = run(false);
+//! assert_eq!(flagged.localisation[0].1, "[Vex]");
+//! let violation = &flagged.report.charset_violations[0];
+//! assert_eq!(violation.key, flagged.localisation[0].0);
+//! assert_eq!(violation.disallowed, "[]");
+//! assert!(!violation.stripped);
+//!
+//! let stripped = run(true);
+//! assert_eq!(stripped.localisation[0].1, "Vex");
+//! assert!(stripped.report.charset_violations[0].stripped);
+//! let block = &stripped.report.blocks[0];
+//! assert_eq!(block.entries[0].1, "Vex");
+//! assert_eq!(stripped.localisation.len(), 2);
+//! assert_eq!(block.dropped["charset"], 1);
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+/// Characters the Stellaris localisation reads as markup
+const STELLARIS_MARKUP: [char; 6] = ['§', '£', '$', '[', ']', '\\'];
+
+/// Characters a game accepts in localisation values
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LocCharset {
+    /// Anything but control characters
+    #[default]
+    Permissive,
+    /// No control characters, localisation markup or characters outside the fonts' range
+    Stellaris,
+}
+
+impl LocCharset {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LocCharset::Permissive => "permissive",
+            LocCharset::Stellaris => "stellaris",
+        }
+    }
+
+    /// Whether `c` may appear in a localisation value
+    pub fn allows(&self, c: char) -> bool {
+        if c.is_control() {
+            return false;
+        }
+        match self {
+            LocCharset::Permissive => true,
+            // Outside the basic multilingual plane, ie emoji
+            LocCharset::Stellaris => !STELLARIS_MARKUP.contains(&c) && (c as u32) < 0x10000,
+        }
+    }
+
+    /// The distinct characters of `value` the profile rejects, in order of appearance
+    pub fn disallowed(&self, value: &str) -> Vec<char> {
+        let mut found = Vec::new();
+        for c in value.chars().filter(|c| !self.allows(*c)) {
+            if !found.contains(&c) {
+                found.push(c);
+            }
+        }
+        found
+    }
+
+    /// `value` without the characters the profile rejects, the whitespace left behind collapsed
+    pub fn strip(&self, value: &str) -> String {
+        let kept: String = value.chars().filter(|c| self.allows(*c)).collect();
+        kept.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+}
+
+/// A localisation value holding characters its profile rejects
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CharsetViolation {
+    /// Language of the localisation, ie `english`
+    pub language: String,
+    pub key: String,
+    /// The value as generated
+    pub value: String,
+    /// The rejected characters, in order of appearance
+    pub disallowed: String,
+    /// The characters were removed from the emitted value
+    pub stripped: bool,
+}
+
+/// Checks the `value` of `key`, the violation if it holds characters `charset` rejects.
+/// `strip` only records that the caller strips them
+pub fn check_value(
+    charset: LocCharset,
+    strip: bool,
+    language: &str,
+    key: &str,
+    value: &str,
+) -> Option<CharsetViolation> {
+    let disallowed = charset.disallowed(value);
+    if disallowed.is_empty() {
+        return None;
+    }
+    Some(CharsetViolation {
+        language: language.to_string(),
+        key: key.to_string(),
+        value: value.to_string(),
+        disallowed: disallowed.iter().collect(),
+        stripped: strip,
+    })
+}
@@ -0,0 +1,178 @@
+//! Compares the names of two caches block by block, ie a known good cache against the cache of
+//! a run with another model or prompt. Entries are matched on their cache key:
+//!
+//! ```
+//! use stellaris_name_gen::compare::{compare_caches, render_comparison};
+//! use stellaris_name_gen::{BlockKey, CachedBlock, InMemoryCache, NameCache};
+//!
+//! let mut good = InMemoryCache::new();
+//! let mut fresh = InMemoryCache::new();
+//! let put = |cache: &mut InMemoryCache, key: &str, raw: &str| {
+//!     cache.put(&BlockKey::from(key), CachedBlock::new(raw)).unwrap();
+//! };
+//! put(&mut good, "NAME_ships", r#"{"names": ["Vex", "Ember"]}"#);
+//! put(&mut fresh, "NAME_ships", r#"{"names": ["Ember", "Cinder"]}"#);
+//! put(&mut good, "NAME_fleets", r#"{"names": ["Ash"]}"#);
+//! put(&mut fresh, "NAME_fleets", r#"{"names": ["Ash"]}"#);
+//! put(&mut good, "NAME_old", r#"{"names": ["Dusk"]}"#);
+//! put(&mut fresh, "NAME_new", "not json at all");
+//! put(&mut fresh, "NAME_blank", "  ");
+//!
+//! let comparison = compare_caches(&good, &fresh).unwrap();
+//! assert_eq!(comparison.only_left, ["NAME_old"]);
+//! assert_eq!(comparison.unchanged, 1);
+//! assert_eq!(comparison.changed[0].added, ["Cinder"]);
+//! assert_eq!(comparison.changed[0].removed, ["Vex"]);
+//! // Broken and blank entries are reported, not fatal
+//! assert!(comparison.only_right.is_empty());
+//! assert_eq!(comparison.errors[0], "right NAME_blank: entry is empty or could not be read");
+//! assert!(comparison.errors[1].starts_with("right NAME_new: "));
+//! assert!(render_comparison(&comparison).contains("~ NAME_ships  +1 -1\n    + Cinder\n    - Vex\n"));
+//! ```
+
+use crate::cache::NameCache;
+use crate::json::{parse_names, parse_value};
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Names added and removed between the two sides of a block
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BlockDiff {
+    pub key: String,
+    /// Names only the right cache holds, sorted
+    pub added: Vec<String>,
+    /// Names only the left cache holds, sorted
+    pub removed: Vec<String>,
+}
+
+/// Outcome of [`compare_caches`]
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CacheComparison {
+    /// Keys only the left cache holds
+    pub only_left: Vec<String>,
+    /// Keys only the right cache holds
+    pub only_right: Vec<String>,
+    /// Blocks whose names differ, by key
+    pub changed: Vec<BlockDiff>,
+    /// Blocks holding the same names on both sides
+    pub unchanged: usize,
+    /// Entries that could not be read, as `<side> <key>: <error>`
+    pub errors: Vec<String>,
+}
+
+/// The names of every readable entry of a cache, unreadable ones added to `errors` and
+/// `unreadable`. Translations and scores are left out, inline values count as a single name
+fn read_names(
+    cache: &dyn NameCache,
+    side: &str,
+    errors: &mut Vec<String>,
+    unreadable: &mut BTreeSet<String>,
+) -> Result<BTreeMap<String, BTreeSet<String>>> {
+    let mut entries = BTreeMap::new();
+    for key in cache.keys()? {
+        if key.as_str().contains('~') {
+            continue;
+        }
+        // A listed key `get` can't return is a file that vanished, can't be read or is blank
+        let Some(block) = cache.get(&key) else {
+            errors.push(format!(
+                "{} {}: entry is empty or could not be read",
+                side,
+                key.as_str()
+            ));
+            unreadable.insert(key.as_str().to_string());
+            continue;
+        };
+        let names = match parse_names(&block.raw) {
+            Ok(names) => names,
+            Err(e) => match parse_value(&block.raw) {
+                Ok(value) => vec![value],
+                Err(_) => {
+                    errors.push(format!("{} {}: {:#}", side, key.as_str(), e));
+                    unreadable.insert(key.as_str().to_string());
+                    continue;
+                }
+            },
+        };
+        let names = names
+            .iter()
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty())
+            .collect();
+        entries.insert(key.as_str().to_string(), names);
+    }
+    Ok(entries)
+}
+
+/// Compares every entry of `left` with the entry of the same key in `right`
+pub fn compare_caches(left: &dyn NameCache, right: &dyn NameCache) -> Result<CacheComparison> {
+    let mut comparison = CacheComparison::default();
+    // A key unreadable on either side is only reported as an error
+    let mut unreadable = BTreeSet::new();
+    let left = read_names(left, "left", &mut comparison.errors, &mut unreadable)?;
+    let right = read_names(right, "right", &mut comparison.errors, &mut unreadable)?;
+    comparison.only_left = left
+        .keys()
+        .filter(|key| !right.contains_key(*key) && !unreadable.contains(*key))
+        .cloned()
+        .collect();
+    comparison.only_right = right
+        .keys()
+        .filter(|key| !left.contains_key(*key) && !unreadable.contains(*key))
+        .cloned()
+        .collect();
+    for (key, names) in &left {
+        let Some(other) = right.get(key) else {
+            continue;
+        };
+        if names == other {
+            comparison.unchanged += 1;
+            continue;
+        }
+        comparison.changed.push(BlockDiff {
+            key: key.clone(),
+            added: other.difference(names).cloned().collect(),
+            removed: names.difference(other).cloned().collect(),
+        });
+    }
+    Ok(comparison)
+}
+
+/// Renders a comparison for reading, changed blocks with their names, then the blocks only one
+/// side holds and the unreadable entries
+pub fn render_comparison(comparison: &CacheComparison) -> String {
+    let mut out = String::new();
+    for diff in &comparison.changed {
+        out.push_str(&format!(
+            "~ {}  +{} -{}\n",
+            diff.key,
+            diff.added.len(),
+            diff.removed.len()
+        ));
+        for name in &diff.added {
+            out.push_str(&format!("    + {}\n", name));
+        }
+        for name in &diff.removed {
+            out.push_str(&format!("    - {}\n", name));
+        }
+    }
+    for key in &comparison.only_left {
+        out.push_str(&format!("< {}  only in the left cache\n", key));
+    }
+    for key in &comparison.only_right {
+        out.push_str(&format!("> {}  only in the right cache\n", key));
+    }
+    for error in &comparison.errors {
+        out.push_str(&format!("! {}\n", error));
+    }
+    out.push_str(&format!(
+        "{} changed, {} unchanged, {} only left, {} only right, {} unreadable\n",
+        comparison.changed.len(),
+        comparison.unchanged,
+        comparison.only_left.len(),
+        comparison.only_right.len(),
+        comparison.errors.len()
+    ));
+    out
+}
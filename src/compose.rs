@@ -0,0 +1,148 @@
+//! Compound names drawn from two or more pools, ie `<adjective> <noun>` ship names. A block
+//! with `# compose: adjectives + nouns` emits combinations of the names of its child blocks
+//! `adjectives` and `nouns`, one name of each pool in order. The pools are generated and cached
+//! like any block but only feed their parent, they have no block of their own in the output:
+//!
+//! ```
+//! use futures::future::BoxFuture;
+//! use stellaris_name_gen::{Generation, GenerationRequest, InMemoryCache, NameGenerator};
+//!
+//! struct Pools;
+//!
+//! impl NameGenerator for Pools {
+//!     fn generate<'a>(&'a self, request: &'a GenerationRequest) -> BoxFuture<'a, anyhow::Result<String>> {
+//!         let answer = if request.theme == "Adjectives" {
+//!             r#"{"names": ["Silent", "Iron", "Burning"]}"#
+//!         } else {
+//!             r#"{"names": ["Star", "Oath", "Iron"]}"#
+//!         };
+//!         Box::pin(async move { Ok(answer.to_string()) })
+//!     }
+//! }
+//!
+//! let structure = "NAME = {
+//!     ## Faction ship names
+//!     ## compose: adjectives + nouns
+//!     ## count: 5
+//!     ships = {
+//!         ## Adjectives
+//!         adjectives = {
+//!         }
+//!         ## Nouns
+//!         nouns = {
+//!         }
+//!     }
+//! }
+//! ";
+//! let run = || {
+//!     futures::executor::block_on(
+//!         Generation::builder()
+//!             .structure(structure)
+//!             .lore("")
+//!             .min_names(1)
+//!             .generator(Pools)
+//!             .cache(InMemoryCache::new())
+//!             .run(),
+//!     )
+//!     .unwrap()
+//! };
+//! let result = run();
+//! let names: Vec<&str> = result.localisation.iter().map(|(_, name)| name.as_str()).collect();
+//! assert_eq!(names.len(), 5);
+//! for name in &names {
+//!     let (adjective, noun) = name.split_once(' ').unwrap();
+//!     assert!(["Silent", "Iron", "Burning"].contains(&adjective));
+//!     assert!(["Star", "Oath", "Iron"].contains(&noun));
+//!     // A word never meets itself
+//!     assert_ne!(*name, "Iron Iron");
+//! }
+//! assert!(!result.name_list.contains("adjectives"));
+//! assert_eq!(result.report.blocks.len(), 1);
+//! assert_eq!(result.report.blocks[0].path, ["NAME", "ships"]);
+//! // The same pools give the same combinations
+//! assert_eq!(run().localisation, result.localisation);
+//! ```
+//!
+//! Nothing filters or reshapes the combinations, so the directives doing that to generated
+//! names fail on a composing block. A pool ending up without names fails the run:
+//!
+//! ```
+//! use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+//!
+//! let run = |directive: &str, avoid: &str| {
+//!     let structure = format!(
+//!         "NAME = {{\n    # Ship names\n    # compose: adjectives + nouns\n{}    ships = {{\n        # Adjectives\n        adjectives = {{\n        }}\n        # Nouns\n{}        nouns = {{\n        }}\n    }}\n}}\n",
+//!         directive, avoid
+//!     );
+//!     futures::executor::block_on(
+//!         Generation::builder()
+//!             .structure(structure)
+//!             .lore("")
+//!             .min_names(1)
+//!             .generator(MockGenerator::new(["Silent", "Star"]))
+//!             .cache(InMemoryCache::new())
+//!             .run(),
+//!     )
+//! };
+//! assert!(run("", "").is_ok());
+//! let error = run("    # style: harsh\n", "").unwrap_err();
+//! assert!(format!("{:#}", error).contains("'NAME/ships' composes its names, which style can't apply to"));
+//! let error = run("", "        # avoid-initials: S\n").unwrap_err();
+//! assert!(format!("{:#}", error).contains("'NAME/ships' composes 'nouns', which ended up without names"));
+//! ```
+
+use anyhow::Result;
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use std::collections::HashSet;
+
+/// Most combinations a composed block draws without a `# count:`
+pub const MAX_COMBINATIONS: usize = 500;
+
+/// Parses a `# compose:` value, the keys of the child blocks joined by `+`
+pub(crate) fn parse_compose(value: &str) -> Result<Vec<String>> {
+    let parts: Vec<String> = value
+        .split('+')
+        .map(|part| part.trim().to_string())
+        .collect();
+    if parts.len() < 2 || parts.iter().any(String::is_empty) {
+        anyhow::bail!(
+            "Expected the child blocks to combine, ie adjectives + nouns, got '{}'",
+            value.trim()
+        );
+    }
+    Ok(parts)
+}
+
+/// Up to `cap` distinct combinations of one name of every pool, in pool order, sampled with
+/// `seed`. Combinations repeating a word are left out
+pub fn combine(pools: &[Vec<String>], seed: u64, cap: usize) -> Vec<String> {
+    let total = pools
+        .iter()
+        .try_fold(1usize, |total, pool| total.checked_mul(pool.len()))
+        .unwrap_or(usize::MAX);
+    if total == 0 {
+        return Vec::new();
+    }
+    let mut rng = StdRng::seed_from_u64(seed);
+    // Twice the cap leaves room for the combinations dropped below
+    let drawn = rand::seq::index::sample(&mut rng, total, total.min(cap.saturating_mul(2)));
+    let mut seen = HashSet::new();
+    let mut names = Vec::new();
+    for mut index in drawn.into_iter() {
+        let mut words = Vec::with_capacity(pools.len());
+        for pool in pools {
+            words.push(pool[index % pool.len()].as_str());
+            index /= pool.len();
+        }
+        let distinct: HashSet<String> = words.iter().map(|word| word.to_lowercase()).collect();
+        let name = words.join(" ");
+        if distinct.len() == words.len() && seen.insert(name.to_lowercase()) {
+            names.push(name);
+        }
+        if names.len() == cap {
+            break;
+        }
+    }
+    names
+}
@@ -0,0 +1,155 @@
+use crate::cache::CacheConfig;
+use crate::charset::LocCharset;
+use crate::clusters::ClusterAction;
+use crate::estimate::ModelPrice;
+use crate::keys::KeyStyle;
+use crate::near_forms::NearFormPolicy;
+use crate::providers::ProviderConfig;
+use crate::quoting::QuoteMode;
+use crate::rarity::Rarity;
+use crate::titles::TitlesConfig;
+use crate::translate::Language;
+use crate::unique::UniqueScope;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Optional run configuration read from `namegen.toml`
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Config {
+    /// Model used by blocks without a `# model:` directive
+    pub model: Option<String>,
+    /// Number of blocks generated at once
+    pub concurrency: Option<usize>,
+    /// In-flight cap for models not listed in `model_limits`
+    pub max_concurrent_models: Option<usize>,
+    /// Per-model in-flight caps, ie `"gemini-2.0-flash" = 2`
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub model_limits: HashMap<String, usize>,
+    /// Fewest names a block may end up with after filtering
+    pub min_names: Option<usize>,
+    /// Abbreviations to their spelled-out form, ie `St = "Saint"`, for `#@ abbreviate: yes` blocks
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub abbreviations: HashMap<String, String>,
+    /// Whether stylistic flavors are sampled into prompts (on unless set to false)
+    pub flavor: Option<bool>,
+    /// Flavors added to the built-in pool
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub flavors: Vec<String>,
+    /// Seed of the flavor sampling, random per run if unset
+    pub seed: Option<u64>,
+    /// Species class of every block without a `#@ species-class:`, see [`crate::species`]
+    pub species_class: Option<String>,
+    /// Species classes to their hint, replacing the built-in hint of the same class
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub species_classes: HashMap<String, String>,
+    /// Shuffles the names of blocks without a sort directive, reproducibly
+    pub shuffle_seed: Option<u64>,
+    /// How far name uniqueness reaches (block, category or global)
+    pub unique_scope: Option<UniqueScope>,
+    /// How keys are derived from names, the `[key-style]` table
+    pub key_style: Option<KeyStyle>,
+    /// Most requests a run may send, blocks past it are only read from the cache
+    pub budget_requests: Option<u64>,
+    /// Most tokens a run may spend, counted from provider usage or estimated without it
+    pub budget_tokens: Option<u64>,
+    /// Transliterate displayed names to ASCII, for games that can't render anything else
+    pub ascii_only: Option<bool>,
+    /// What happens to double quotes and backslashes in names (escape, single or strip)
+    pub quotes: Option<QuoteMode>,
+    /// Characters localisation values may hold (permissive or stellaris)
+    pub loc_charset: Option<LocCharset>,
+    /// Remove the characters `loc-charset` rejects instead of only reporting them
+    pub strip_loc_charset: Option<bool>,
+    /// Open every generated block of the name list with a comment on its origin
+    pub provenance_comments: Option<bool>,
+    /// Template of every localisation value between its quotes, holding `{val}` and maybe `{key}`
+    pub value_format: Option<String>,
+    /// Indentation per nesting level of generated lines when the structure shows none
+    pub indent: Option<String>,
+    /// Share of the recent model calls that may fail before the run is aborted
+    pub max_error_rate: Option<f64>,
+    /// Number of recent model calls the error rate is measured over
+    pub error_window: Option<usize>,
+    /// Hours to wait for an exhausted daily quota to reset instead of failing the run
+    pub wait_for_quota: Option<f64>,
+    /// Generate blocks asking a model for the same names once
+    pub coalesce: Option<bool>,
+    /// Continue answers short of their block's `# count:` and fail the blocks still short
+    pub strict_count: Option<bool>,
+    /// Stop a block's stream once its `# count:` names arrived, on by default
+    pub early_stop: Option<bool>,
+    /// Names requested on top of a block's count, so it survives filtering
+    pub early_stop_margin: Option<usize>,
+    /// What to do with near forms of a name in the same block (off, flag, longer or shorter)
+    pub near_forms: Option<NearFormPolicy>,
+    /// Share of a block's names its largest cluster of names sharing a stem may hold
+    pub max_cluster_fraction: Option<f64>,
+    /// What happens to the names trimmed from oversized clusters (trim or regenerate)
+    pub cluster_action: Option<ClusterAction>,
+    /// Drop names meaning the same as a kept name by comparing their embeddings, off by default
+    pub semantic_dedup: Option<bool>,
+    /// Cosine similarity from which `semantic-dedup` drops a name
+    pub semantic_threshold: Option<f64>,
+    /// Model embedding the names for `semantic-dedup`
+    pub embedding_model: Option<String>,
+    /// How unusual the names of blocks without a `# rarity=` should be (normal or high)
+    pub rarity: Option<Rarity>,
+    /// Word list, names made up only of its words are dropped from high rarity blocks
+    pub common_words: Option<PathBuf>,
+    /// Block path prefixes to category names, ie `"NAME/ship_names" = "ships"`.
+    /// Blocks outside any listed prefix use their first path component
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub categories: HashMap<String, String>,
+    /// Files making up the default lore instead of `lore.txt`
+    pub lore: LoreConfig,
+    /// Lore profiles by name, ie `faction_a = "lore/faction_a.txt"`, selected with `# lore:`
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub lore_profiles: HashMap<String, PathBuf>,
+    /// The game's `localisation` folder, generated keys colliding with its keys get renamed
+    pub vanilla_loc_dir: Option<PathBuf>,
+    /// The mod's hand-written localisation, a yml file or folder whose keys are reserved
+    pub existing_loc: Option<PathBuf>,
+    /// Languages `#@ translatable` blocks are translated into, see [`crate::translate`]
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub translate: Vec<Language>,
+    /// Ruler titles and other keyed entries generated next to the name lists, the `[titles]` table
+    pub titles: TitlesConfig,
+    /// Cache backend selection
+    pub cache: CacheConfig,
+    /// Per-provider credentials, keyed by provider name (gemini, openai, anthropic, ollama, ...)
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub providers: HashMap<String, ProviderConfig>,
+    /// Prices per model in USD per million tokens, ie `"gemini-2.0-flash" = { input = 0.1,
+    /// output = 0.4 }`, used by `--estimate`
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub prices: HashMap<String, ModelPrice>,
+}
+
+/// The `[lore]` table
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct LoreConfig {
+    /// Concatenated in order, each under a `## <file name>` header
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub files: Vec<PathBuf>,
+}
+
+impl Config {
+    /// Loads the config, falling back to defaults if the file does not exist
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let config: Self =
+            toml::from_str(&text).with_context(|| format!("Failed to parse {}", path.display()))?;
+        crate::providers::validate(&config.providers)
+            .with_context(|| format!("Invalid {}", path.display()))?;
+        Ok(config)
+    }
+}
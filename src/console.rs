@@ -0,0 +1,298 @@
+//! Renders console output for the terminal it goes to. Colors follow `NO_COLOR`, `CLICOLOR` and
+//! `CLICOLOR_FORCE`, the live progress line is only drawn on an interactive terminal, and text
+//! holding characters the terminal can't encode is spelled out in ASCII:
+//!
+//! ```
+//! use stellaris_name_gen::console::{Console, Tone};
+//!
+//! fn env(vars: &'static [(&str, &str)]) -> impl Fn(&str) -> Option<String> {
+//!     move |name| vars.iter().find(|(var, _)| *var == name).map(|(_, value)| value.to_string())
+//! }
+//! let tty = Console::from_env(true, env(&[("LANG", "en_US.UTF-8")]));
+//! assert!(tty.interactive && tty.color && tty.utf8);
+//! assert!(!Console::from_env(true, env(&[("NO_COLOR", "1")])).color);
+//! assert!(!Console::from_env(true, env(&[("CLICOLOR", "0")])).color);
+//! // Piped to a file, forced colors aside
+//! let piped = Console::from_env(false, env(&[]));
+//! assert!(!piped.interactive && !piped.color);
+//! assert!(Console::from_env(false, env(&[("CLICOLOR_FORCE", "1")])).color);
+//! assert!(!Console::from_env(true, env(&[("TERM", "dumb")])).interactive);
+//!
+//! let latin1 = Console::from_env(false, env(&[("LC_ALL", "de_DE.ISO-8859-1")]));
+//! assert_eq!(latin1.text("Škoda 星 Vex"), "Skoda ? Vex");
+//! // Bidi and zero-width characters never reach the terminal
+//! assert_eq!(tty.text("Em\u{200b}ber \u{202e}Vex\u{1b}[2K"), "Ember Vex[2K");
+//! assert_eq!(tty.paint("+ Vex", Tone::Added), "\u{1b}[32m+ Vex\u{1b}[0m");
+//! assert_eq!(piped.paint("+ Vex", Tone::Added), "+ Vex");
+//! ```
+//!
+//! Off an interactive terminal the progress of the requests in flight is written as plain log
+//! lines, one per finished request:
+//!
+//! ```
+//! use stellaris_name_gen::console::{Console, Progress};
+//!
+//! let progress = Progress::new(Console::plain(), Vec::new());
+//! let ship = progress.start("Ship names\nfor a fallen empire");
+//! let fleet = progress.start("Fleet names");
+//! progress.update(ship, 2, &["Vex".to_string(), "\u{202e}Ember\u{200d}".to_string()]);
+//! progress.finish(ship);
+//! progress.update(fleet, 1, &["Ash".to_string()]);
+//! progress.finish(fleet);
+//! let out = String::from_utf8(progress.into_writer()).unwrap();
+//! assert_eq!(out, "[AI] 'Ship names': 2 names\n[AI] 'Fleet names': 1 names\n");
+//!
+//! // The live line is redrawn in place, its names cleaned up
+//! let console = Console { interactive: true, color: false, utf8: true };
+//! let progress = Progress::new(console, Vec::new());
+//! let ship = progress.start("Ship names");
+//! progress.update(ship, 1, &["\u{202e}Vex\u{2066}".to_string()]);
+//! progress.finish(ship);
+//! let out = String::from_utf8(progress.into_writer()).unwrap();
+//! assert_eq!(
+//!     out,
+//!     "\r\u{1b}[2K[AI] Ship names: 1 (Vex)\r\u{1b}[2K[AI] 'Ship names': 1 names\n"
+//! );
+//! ```
+
+use crate::ascii::to_ascii;
+use crate::normalize::is_invisible;
+use std::collections::BTreeMap;
+use std::io::{self, IsTerminal, Write};
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Clears the current line and returns to its start
+const CLEAR_LINE: &str = "\r\x1b[2K";
+/// Longest label of a request on the progress line, in characters
+const LABEL_LEN: usize = 24;
+
+/// How text is written to the terminal at hand
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Console {
+    /// A terminal that redraws the progress line in place, rather than a pipe or file
+    pub interactive: bool,
+    /// Colors are written as ANSI escapes
+    pub color: bool,
+    /// The terminal encodes any character, otherwise only ASCII is written
+    pub utf8: bool,
+}
+
+impl Default for Console {
+    fn default() -> Self {
+        Self::detect()
+    }
+}
+
+impl Console {
+    /// The console of stdout, from its terminal and the environment
+    pub fn detect() -> Self {
+        Self::from_env(io::stdout().is_terminal(), |name| std::env::var(name).ok())
+    }
+
+    /// A pipe or file taking UTF-8, without colors
+    pub fn plain() -> Self {
+        Self {
+            interactive: false,
+            color: false,
+            utf8: true,
+        }
+    }
+
+    /// The console of a stream that is a terminal or not, reading environment variables
+    /// through `var`
+    pub fn from_env(terminal: bool, var: impl Fn(&str) -> Option<String>) -> Self {
+        let set = |name: &str| var(name).filter(|value| !value.is_empty());
+        let dumb = set("TERM").is_some_and(|term| term == "dumb");
+        let color = if set("NO_COLOR").is_some() {
+            false
+        } else if set("CLICOLOR_FORCE").is_some_and(|value| value != "0") {
+            true
+        } else {
+            terminal && !dumb && set("CLICOLOR").is_none_or(|value| value != "0")
+        };
+        // The first locale variable set wins, as for the C library
+        let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+            .iter()
+            .find_map(|name| set(name));
+        let utf8 = match locale {
+            Some(locale) => {
+                let locale = locale.to_ascii_lowercase();
+                locale.contains("utf-8") || locale.contains("utf8")
+            }
+            // Legacy Windows consoles use a codepage of their own, Windows Terminal takes UTF-8
+            None if cfg!(windows) => set("WT_SESSION").is_some(),
+            None => true,
+        };
+        Self {
+            interactive: terminal && !dumb,
+            color,
+            utf8,
+        }
+    }
+
+    /// `text` as it may be written: invisible and control characters other than line breaks
+    /// and tabs are dropped, and characters the terminal can't encode are spelled out in ASCII
+    /// or replaced by `?`
+    pub fn text(&self, text: &str) -> String {
+        let mut out = String::with_capacity(text.len());
+        for c in text.chars() {
+            if is_invisible(c) || (c.is_control() && c != '\n' && c != '\t') {
+                continue;
+            }
+            if self.utf8 || c.is_ascii() {
+                out.push(c);
+                continue;
+            }
+            match to_ascii(c.encode_utf8(&mut [0; 4])) {
+                ascii if ascii.is_empty() => out.push('?'),
+                ascii => out.push_str(&ascii),
+            }
+        }
+        out
+    }
+
+    /// `text` in the color of `tone`, or as it is without colors
+    pub fn paint(&self, text: &str, tone: Tone) -> String {
+        if !self.color {
+            return text.to_string();
+        }
+        format!("\x1b[{}m{}\x1b[0m", tone.code(), text)
+    }
+
+    /// A rendered cache comparison with added names in green, removed ones in red and
+    /// unreadable entries in yellow, see [`crate::compare::render_comparison`]
+    pub fn diff(&self, rendered: &str) -> String {
+        let mut out = String::with_capacity(rendered.len());
+        for line in self.text(rendered).lines() {
+            let tone = match line.trim_start().chars().next() {
+                Some('+') => Some(Tone::Added),
+                Some('-') => Some(Tone::Removed),
+                Some('!') => Some(Tone::Warning),
+                _ => None,
+            };
+            match tone {
+                Some(tone) => out.push_str(&self.paint(line, tone)),
+                None => out.push_str(line),
+            }
+            out.push('\n');
+        }
+        out
+    }
+}
+
+/// Color of a piece of output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Tone {
+    Added,
+    Removed,
+    Warning,
+}
+
+impl Tone {
+    /// ANSI foreground color
+    fn code(&self) -> u8 {
+        match self {
+            Tone::Added => 32,
+            Tone::Removed => 31,
+            Tone::Warning => 33,
+        }
+    }
+}
+
+/// Label, names so far and the latest names of an in-flight request
+type BlockProgress = (String, usize, Vec<String>);
+
+/// Name counts of the in-flight requests, rendered together on a single console line
+pub struct Progress<W: Write = io::Stdout> {
+    console: Console,
+    out: Mutex<W>,
+    /// By request id
+    blocks: Mutex<BTreeMap<u64, BlockProgress>>,
+    next_id: AtomicU64,
+}
+
+impl Default for Progress {
+    fn default() -> Self {
+        Self::new(Console::detect(), io::stdout())
+    }
+}
+
+impl<W: Write> Progress<W> {
+    pub fn new(console: Console, out: W) -> Self {
+        Self {
+            console,
+            out: Mutex::new(out),
+            blocks: Mutex::new(BTreeMap::new()),
+            next_id: AtomicU64::new(0),
+        }
+    }
+
+    pub fn console(&self) -> Console {
+        self.console
+    }
+
+    /// The writer the progress went to
+    pub fn into_writer(self) -> W {
+        self.out.into_inner().unwrap()
+    }
+
+    /// Adds a request to the line, returning its id
+    pub fn start(&self, theme: &str) -> u64 {
+        let id = self.next_id.fetch_add(1, Ordering::SeqCst);
+        // Multi-line themes are shown by their first line, shortened
+        let label: String = self
+            .console
+            .text(theme.lines().next().unwrap_or_default())
+            .chars()
+            .take(LABEL_LEN)
+            .collect();
+        self.blocks
+            .lock()
+            .unwrap()
+            .insert(id, (label, 0, Vec::new()));
+        id
+    }
+
+    /// Redraws the line with the names a request received so far. Nothing is written off an
+    /// interactive terminal
+    pub fn update(&self, id: u64, count: usize, recent: &[String]) {
+        let mut blocks = self.blocks.lock().unwrap();
+        if let Some(block) = blocks.get_mut(&id) {
+            block.1 = count;
+            // Names are single words on the line, whatever they hold
+            block.2 = recent
+                .iter()
+                .map(|name| {
+                    let name = self.console.text(name);
+                    name.split_whitespace().collect::<Vec<_>>().join(" ")
+                })
+                .collect();
+        }
+        if !self.console.interactive {
+            return;
+        }
+        let line: Vec<String> = blocks
+            .values()
+            .map(|(label, count, recent)| format!("{}: {} ({})", label, count, recent.join(", ")))
+            .collect();
+        let mut out = self.out.lock().unwrap();
+        write!(out, "{}[AI] {}", CLEAR_LINE, line.join(" | ")).ok();
+        out.flush().ok();
+    }
+
+    /// Takes a request off the line, writing its final count on a line of its own
+    pub fn finish(&self, id: u64) {
+        let Some((label, count, _)) = self.blocks.lock().unwrap().remove(&id) else {
+            return;
+        };
+        let clear = if self.console.interactive {
+            CLEAR_LINE
+        } else {
+            ""
+        };
+        let mut out = self.out.lock().unwrap();
+        writeln!(out, "{}[AI] '{}': {} names", clear, label, count).ok();
+        out.flush().ok();
+    }
+}
@@ -0,0 +1,241 @@
+//! Catches output that changes between runs from the same inputs, which breaks reproducible
+//! builds of a mod. [`Generation::check_determinism`](crate::Generation::check_determinism),
+//! `--determinism-check` on the command line, replays every block of a run from the names and
+//! derived entries the run cached, without sending any request, and compares the names each
+//! stage of the pipeline left with the ones it left in the run, then the emitted entries and the
+//! output. The first difference fails the check, naming the block, the stage and the differing
+//! names:
+//!
+//! ```
+//! use stellaris_name_gen::determinism::{Stage, compare_stages};
+//!
+//! let first = vec![
+//!     Stage::new("generated", &["Vex".to_string(), "Ember".to_string()]),
+//!     Stage::new("order", &["Ember".to_string(), "Vex".to_string()]),
+//! ];
+//! let mut second = first.clone();
+//! assert!(compare_stages("NAME/ships", &first, &second).is_none());
+//! second[1] = Stage::new("order", &["Vex".to_string(), "Ember".to_string()]);
+//! let divergence = compare_stages("NAME/ships", &first, &second).unwrap();
+//! assert_eq!(divergence.stage, "order");
+//! assert_eq!(
+//!     divergence.to_string(),
+//!     "'NAME/ships' diverged at order: item 1 is 'Ember' in the run and 'Vex' in the replay"
+//! );
+//! ```
+//!
+//! Every feature of the pipeline is run through the check here, so a filter, sampler or
+//! ordering that stops being reproducible fails this test. It then tampers with each stage of
+//! every block in turn, so a stage the comparison skips fails it too:
+//!
+//! ```
+//! use stellaris_name_gen::determinism::compare;
+//! use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+//!
+//! let structure = "NAME = {
+//!     ## Ship names
+//!     ## style: alliterative
+//!     ships = {
+//!     }
+//!     ## Regnal names
+//!     ## distribution: even
+//!     #@ ordinal-expand: roman:2
+//!     kings = {
+//!     }
+//!     ## Fleet names
+//!     ## compose: adjectives + nouns
+//!     ## count: 6
+//!     fleets = {
+//!         ## Adjectives
+//!         adjectives = {
+//!         }
+//!         ## Nouns
+//!         nouns = {
+//!         }
+//!     }
+//! }
+//! ";
+//! let names = ["Vexmar", "Vexlor", "Vexrin", "Ember", "Cinder", "Ashford", "Tyran", "Tyrans", "Dawn", "Krag"];
+//! let generation = Generation::builder()
+//!     .structure(structure)
+//!     .lore("")
+//!     .min_names(1)
+//!     .shuffle_seed(7)
+//!     .max_cluster_fraction(0.2)
+//!     .generator(MockGenerator::new(names))
+//!     .cache(InMemoryCache::new())
+//!     .build()
+//!     .unwrap();
+//! let result = futures::executor::block_on(generation.run()).unwrap();
+//! futures::executor::block_on(generation.check_determinism(&result)).unwrap();
+//!
+//! for (idx, block) in result.report.blocks.iter().enumerate() {
+//!     assert!(block.stages.iter().any(|stage| stage.name == "order"));
+//!     for (at, stage) in block.stages.iter().enumerate() {
+//!         let mut tampered = result.clone();
+//!         tampered.report.blocks[idx].stages[at].items.push("Intruder".to_string());
+//!         let divergence = compare(&result, &tampered).unwrap();
+//!         assert_eq!(divergence.block, block.path.join("/"));
+//!         assert_eq!(divergence.stage, stage.name);
+//!     }
+//! }
+//! ```
+
+use crate::pipeline::RunResult;
+use anyhow::Result;
+use std::fmt;
+
+/// Names a stage of a block's pipeline left, in order
+#[derive(Debug, Clone, PartialEq)]
+pub struct Stage {
+    pub name: String,
+    pub items: Vec<String>,
+}
+
+impl Stage {
+    pub fn new(name: &str, items: &[String]) -> Self {
+        Self {
+            name: name.to_string(),
+            items: items.to_vec(),
+        }
+    }
+}
+
+/// The first place a run and its replay differ
+#[derive(Debug, Clone, PartialEq)]
+pub struct Divergence {
+    /// Path of the block, or the output file for differences past the blocks
+    pub block: String,
+    pub stage: String,
+    /// What differs, ie the first item in a different place
+    pub detail: String,
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "'{}' diverged at {}: {}",
+            self.block, self.stage, self.detail
+        )
+    }
+}
+
+/// The first stage of a block at which a run and its replay left different names
+pub fn compare_stages(block: &str, run: &[Stage], replay: &[Stage]) -> Option<Divergence> {
+    let divergence = |stage: &str, detail: String| Divergence {
+        block: block.to_string(),
+        stage: stage.to_string(),
+        detail,
+    };
+    for (a, b) in run.iter().zip(replay) {
+        if a.name != b.name {
+            return Some(divergence(
+                &a.name,
+                format!("the replay went through {} instead", b.name),
+            ));
+        }
+        if let Some(detail) = compare_items(&a.items, &b.items) {
+            return Some(divergence(&a.name, detail));
+        }
+    }
+    let names = |stages: &[Stage]| -> Vec<String> {
+        stages.iter().map(|stage| stage.name.clone()).collect()
+    };
+    compare_items(&names(run), &names(replay)).map(|detail| divergence("stages", detail))
+}
+
+/// The first difference between a run and its replay from the same inputs, block by block and
+/// then in the localisation and name list
+pub fn compare(run: &RunResult, replay: &RunResult) -> Option<Divergence> {
+    let paths = |result: &RunResult| -> Vec<String> {
+        result
+            .report
+            .blocks
+            .iter()
+            .map(|block| block.path.join("/"))
+            .collect()
+    };
+    if let Some(detail) = compare_items(&paths(run), &paths(replay)) {
+        return Some(Divergence {
+            block: "run".to_string(),
+            stage: "blocks".to_string(),
+            detail,
+        });
+    }
+    for (a, b) in run.report.blocks.iter().zip(&replay.report.blocks) {
+        let block = a.path.join("/");
+        if let Some(divergence) = compare_stages(&block, &a.stages, &b.stages) {
+            return Some(divergence);
+        }
+        let entries = |entries: &[(String, String)]| -> Vec<String> {
+            entries
+                .iter()
+                .map(|(key, name)| format!("{}: {}", key, name))
+                .collect()
+        };
+        if let Some(detail) = compare_items(&entries(&a.entries), &entries(&b.entries)) {
+            return Some(Divergence {
+                block,
+                stage: "emission".to_string(),
+                detail,
+            });
+        }
+    }
+    let entries = |result: &RunResult| -> Vec<String> {
+        result
+            .localisation
+            .iter()
+            .map(|(key, value)| format!("{}: {}", key, value))
+            .collect()
+    };
+    if let Some(detail) = compare_items(&entries(run), &entries(replay)) {
+        return Some(Divergence {
+            block: "localisation".to_string(),
+            stage: "emission".to_string(),
+            detail,
+        });
+    }
+    let lines = |result: &RunResult| -> Vec<String> {
+        result.name_list.lines().map(str::to_string).collect()
+    };
+    compare_items(&lines(run), &lines(replay)).map(|detail| Divergence {
+        block: "name list".to_string(),
+        stage: "emission".to_string(),
+        detail,
+    })
+}
+
+/// Fails with the first divergence of a run and its replay, if any
+pub(crate) fn check(run: &RunResult, replay: &RunResult) -> Result<()> {
+    match compare(run, replay) {
+        Some(divergence) => anyhow::bail!("The output is not deterministic, {}", divergence),
+        None => Ok(()),
+    }
+}
+
+/// The first difference of two lists, `None` if they are equal
+fn compare_items(run: &[String], replay: &[String]) -> Option<String> {
+    let position = run.iter().zip(replay).position(|(a, b)| a != b);
+    match position {
+        Some(idx) => Some(format!(
+            "item {} is '{}' in the run and '{}' in the replay",
+            idx, run[idx], replay[idx]
+        )),
+        None if run.len() != replay.len() => {
+            let (longer, side) = if run.len() > replay.len() {
+                (run, "run")
+            } else {
+                (replay, "replay")
+            };
+            let extra = &longer[run.len().min(replay.len())..];
+            Some(format!(
+                "only the {} has {} more: {}",
+                side,
+                extra.len(),
+                extra.join(", ")
+            ))
+        }
+        None => None,
+    }
+}
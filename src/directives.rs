@@ -0,0 +1,486 @@
+use crate::hash::fnv1a;
+use crate::provenance::is_block_comment;
+use crate::quoting::{split_all_unquoted, split_unquoted, strip_comment};
+use anyhow::{Context, Result};
+use rand::SeedableRng;
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+
+/// Order in which a block's names are emitted
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum SortMode {
+    /// Keep the order the AI produced them in
+    #[default]
+    Generated,
+    /// Alphabetical, case-insensitive
+    Alpha,
+    /// Shortest first, ties broken alphabetically
+    Length,
+    /// Seeded shuffle, reproducible across runs
+    Shuffle(u64),
+}
+
+impl SortMode {
+    /// Parses the value of a `# sort=` directive, ie `alpha`, `length`, `shuffle` or `shuffle:42`
+    pub(crate) fn parse(value: &str, path: &[String]) -> Result<Self> {
+        let value = value.trim().to_ascii_lowercase();
+        let (mode, seed) = match value.split_once(':') {
+            Some((mode, seed)) => (mode.trim(), Some(seed.trim())),
+            None => (value.as_str(), None),
+        };
+        match mode {
+            "none" | "generated" => Ok(SortMode::Generated),
+            "alpha" => Ok(SortMode::Alpha),
+            "length" => Ok(SortMode::Length),
+            "shuffle" => {
+                let seed = match seed {
+                    Some(seed) => seed
+                        .parse::<u64>()
+                        .with_context(|| format!("Invalid shuffle seed '{}'", seed))?,
+                    // Fall back to a seed derived from the block path so blocks differ, hashed
+                    // the same on every toolchain so the order doesn't churn
+                    None => fnv1a(path.join("/").as_bytes()),
+                };
+                Ok(SortMode::Shuffle(seed))
+            }
+            other => anyhow::bail!(
+                "Unknown sort mode '{}', expected alpha|length|shuffle[:seed]",
+                other
+            ),
+        }
+    }
+
+    /// Reorders the names in place
+    pub(crate) fn apply(&self, names: &mut [String]) {
+        match self {
+            SortMode::Generated => {}
+            SortMode::Alpha => names.sort_by_cached_key(|name| name.to_lowercase()),
+            SortMode::Length => {
+                names.sort_by_cached_key(|name| (name.chars().count(), name.to_lowercase()))
+            }
+            SortMode::Shuffle(seed) => names.shuffle(&mut StdRng::seed_from_u64(*seed)),
+        }
+    }
+}
+
+/// Wraps every emitted name in a template such as `Outpost {name}`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Decoration {
+    template: String,
+}
+
+impl Decoration {
+    /// Parses a `#@ decorate:` value, quoted or not. `{ord}` (1-based position) is only allowed in
+    /// sequential blocks
+    pub(crate) fn parse(value: &str, sequential: bool) -> Result<Self> {
+        let value = value.trim();
+        let template = match value.strip_prefix('"') {
+            Some(quoted) => {
+                let Some((template, rest)) = quoted.split_once('"') else {
+                    anyhow::bail!("Unclosed quote in decoration '{}'", value);
+                };
+                if !rest.trim().is_empty() {
+                    anyhow::bail!(
+                        "Unexpected '{}' after the decoration \"{}\"",
+                        rest.trim(),
+                        template
+                    );
+                }
+                template.to_string()
+            }
+            None => value.to_string(),
+        };
+        if !template.contains("{name}") {
+            anyhow::bail!("Decoration '{}' must contain {{name}}", template);
+        }
+        let mut rest = template.as_str();
+        while let Some(open) = rest.find('{') {
+            let Some(close) = rest[open..].find('}') else {
+                anyhow::bail!("Unclosed placeholder in decoration '{}'", template);
+            };
+            match &rest[open + 1..open + close] {
+                "name" => {}
+                "ord" if sequential => {}
+                "ord" => anyhow::bail!(
+                    "Placeholder {{ord}} in '{}' requires a sequential block (randomized = no)",
+                    template
+                ),
+                other => anyhow::bail!("Unknown placeholder {{{}}} in '{}'", other, template),
+            }
+            rest = &rest[open + close + 1..];
+        }
+        Ok(Self { template })
+    }
+
+    /// Decorates the name at the 0-based position `index`
+    pub(crate) fn apply(&self, name: &str, index: usize) -> String {
+        self.template
+            .replace("{name}", name)
+            .replace("{ord}", &(index + 1).to_string())
+    }
+}
+
+/// Parses a yes/no directive value, an empty value meaning yes
+pub(crate) fn parse_flag(value: &str) -> Result<bool> {
+    match value.trim().to_ascii_lowercase().as_str() {
+        "" | "yes" | "true" | "on" => Ok(true),
+        "no" | "false" | "off" => Ok(false),
+        other => anyhow::bail!("Expected yes or no, got '{}'", other),
+    }
+}
+
+/// Reads an `#@ insert-here` anchor (without the leading `#`), `None` for any other comment.
+/// `Some(true)` when written `#@ insert-here: keep`, which leaves the anchor in the output
+pub(crate) fn parse_anchor(comment: &str) -> Result<Option<bool>> {
+    let Some(directive) = comment.trim().strip_prefix('@') else {
+        return Ok(None);
+    };
+    let (key, value) = directive.split_once(':').unwrap_or((directive, ""));
+    if key.trim() != "insert-here" {
+        return Ok(None);
+    }
+    match value.trim().to_ascii_lowercase().as_str() {
+        "" => Ok(Some(false)),
+        "keep" => Ok(Some(true)),
+        other => anyhow::bail!("Expected insert-here or insert-here: keep, got '{}'", other),
+    }
+}
+
+/// Parses a comma separated letter list such as `X, Q` into uppercase initials
+pub(crate) fn parse_initials(value: &str) -> Result<Vec<char>> {
+    let mut initials = Vec::new();
+    for letter in value.split(',').map(str::trim).filter(|l| !l.is_empty()) {
+        let mut chars = letter.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) if c.is_alphabetic() => initials.extend(c.to_uppercase()),
+            _ => anyhow::bail!("Expected single letters, got '{}'", letter),
+        }
+    }
+    Ok(initials)
+}
+
+/// Parses a comma separated name list such as `"Name A", "Name B"`, quotes optional unless a
+/// name holds a comma
+pub(crate) fn parse_names(value: &str) -> Result<Vec<String>> {
+    let mut names: Vec<String> = Vec::new();
+    let mut rest = value.trim();
+    while !rest.is_empty() {
+        let (name, after) = match rest.strip_prefix('"') {
+            Some(quoted) => {
+                let end = quoted
+                    .find('"')
+                    .with_context(|| format!("Unclosed quote in '{}'", value.trim()))?;
+                let after = quoted[end + 1..].trim_start();
+                if !after.is_empty() && !after.starts_with(',') {
+                    anyhow::bail!("Expected a comma after \"{}\"", &quoted[..end]);
+                }
+                (&quoted[..end], after)
+            }
+            None => rest.split_at(rest.find(',').unwrap_or(rest.len())),
+        };
+        let name = name.trim();
+        if name.is_empty() {
+            anyhow::bail!("Empty name in '{}'", value.trim());
+        }
+        if names.iter().any(|known| known.eq_ignore_ascii_case(name)) {
+            anyhow::bail!("'{}' is listed twice", name);
+        }
+        names.push(name.to_string());
+        rest = after.strip_prefix(',').unwrap_or(after).trim_start();
+    }
+    Ok(names)
+}
+
+/// Parses an `# alias:` path such as `NAME/old_ships` into its keys
+pub(crate) fn parse_alias(value: &str) -> Result<Vec<String>> {
+    let path: Vec<String> = split_all_unquoted(value.trim(), '/')
+        .into_iter()
+        .map(|key| key.trim().to_string())
+        .collect();
+    if path.iter().any(String::is_empty) {
+        anyhow::bail!(
+            "Expected a block path such as NAME/ships, got '{}'",
+            value.trim()
+        );
+    }
+    Ok(path)
+}
+
+/// Second pass that has a model rate every name 1–5 and keeps only the best ones
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct ScoreFilter {
+    /// Lowest score a name may have to be kept
+    pub(crate) min: Option<u8>,
+    /// Keep only this many of the best rated names
+    pub(crate) top: Option<usize>,
+    /// Model doing the rating, defaults to the block's own model
+    pub(crate) model: Option<String>,
+}
+
+impl ScoreFilter {
+    /// Parses a `#@ score-filter:` value such as `min=3 model=gemini-2.0-flash` or `top=40`
+    pub(crate) fn parse(value: &str) -> Result<Self> {
+        let mut filter = Self {
+            min: None,
+            top: None,
+            model: None,
+        };
+        for option in value.split_whitespace() {
+            let (key, value) = option
+                .split_once('=')
+                .with_context(|| format!("Expected key=value, got '{}'", option))?;
+            match key {
+                "min" => {
+                    let min = value
+                        .parse::<u8>()
+                        .ok()
+                        .filter(|min| (1..=5).contains(min))
+                        .with_context(|| format!("Score minimum '{}' must be 1 to 5", value))?;
+                    filter.min = Some(min);
+                }
+                "top" => {
+                    let top = value
+                        .parse::<usize>()
+                        .with_context(|| format!("Invalid top count '{}'", value))?;
+                    filter.top = Some(top);
+                }
+                "model" => filter.model = Some(value.to_string()),
+                other => anyhow::bail!(
+                    "Unknown score-filter option '{}', expected min|top|model",
+                    other
+                ),
+            }
+        }
+        if filter.min.is_none() && filter.top.is_none() {
+            anyhow::bail!("Score filter '{}' needs min=<1-5> or top=<count>", value);
+        }
+        Ok(filter)
+    }
+}
+
+/// Period a block's names belong to, from `# era:`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum Era {
+    Ancient,
+    Industrial,
+    Stellar,
+}
+
+impl Era {
+    pub(crate) fn parse(value: &str) -> Result<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "ancient" => Ok(Era::Ancient),
+            "industrial" => Ok(Era::Industrial),
+            "stellar" => Ok(Era::Stellar),
+            other => anyhow::bail!(
+                "Unknown era '{}', expected ancient|industrial|stellar",
+                other
+            ),
+        }
+    }
+
+    pub(crate) fn as_str(&self) -> &'static str {
+        match self {
+            Era::Ancient => "ancient",
+            Era::Industrial => "industrial",
+            Era::Stellar => "stellar",
+        }
+    }
+
+    /// The period as described in the prompt
+    pub(crate) fn describe(&self) -> &'static str {
+        match self {
+            Era::Ancient => "an ancient era of tribes, city-states and myth, before any industry",
+            Era::Industrial => {
+                "an industrial era of factories, railways and nation states, before spaceflight"
+            }
+            Era::Stellar => "a spacefaring era of starships, colonies and interstellar powers",
+        }
+    }
+}
+
+/// Directives that may be written as a bare `# <name>`
+const FLAGS: [&str; 3] = ["abbreviate", "augment-inline", "translatable"];
+
+/// Directives gathered from the comments preceding a block
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Directives {
+    pub(crate) theme: Option<String>,
+    pub(crate) kv_inserts: Vec<String>,
+    /// `# if-generated: key = value` inserts, emitted only if the block emits names
+    pub(crate) if_generated: Vec<String>,
+    /// Paths the block was cached under before a rename, from every `# alias:`
+    pub(crate) aliases: Vec<String>,
+    pub(crate) prefix: Option<String>,
+    pub(crate) sort: Option<String>,
+    /// Phonetic style the names are filtered toward, see [`crate::euphony`]
+    pub(crate) style: Option<String>,
+    /// Share of names per initial letter, see [`crate::distribution`]
+    pub(crate) distribution: Option<String>,
+    pub(crate) model: Option<String>,
+    pub(crate) decorate: Option<String>,
+    pub(crate) avoid_initials: Option<String>,
+    pub(crate) lore: Option<String>,
+    pub(crate) score_filter: Option<String>,
+    pub(crate) min: Option<String>,
+    /// Names the block needs, from `# count:` or its alias `# max:`
+    pub(crate) count: Option<String>,
+    pub(crate) abbreviate: Option<String>,
+    pub(crate) schema: Option<String>,
+    pub(crate) key_style: Option<String>,
+    pub(crate) augment_inline: Option<String>,
+    pub(crate) translatable: Option<String>,
+    /// Species class whose hint is added to the prompt, see [`crate::species`]
+    pub(crate) species_class: Option<String>,
+    pub(crate) era: Option<String>,
+    /// Names a sequential block starts with, in order
+    pub(crate) first: Option<String>,
+    /// How unusual the names should be, see [`crate::rarity`]
+    pub(crate) rarity: Option<String>,
+    /// Numbered variants every name is emitted as, see [`crate::ordinals`]
+    pub(crate) ordinal_expand: Option<String>,
+    /// Child blocks whose names are combined, see [`crate::compose`]
+    pub(crate) compose: Option<String>,
+    /// Whether the next theme line continues the current theme rather than replacing it
+    theme_open: bool,
+}
+
+impl Directives {
+    /// Records one comment line (without the leading `#`).
+    /// `#@ key: value` lines are always directives, plain `# key: value` comments are directives
+    /// when the key is known and otherwise fall back to kv inserts and themes.
+    /// Consecutive theme lines form one multi-line theme until a blank `#`, any other
+    /// directive or a non-comment line ends it, after which the next theme line replaces it.
+    /// A directive's value ends at a `#` outside quotes, which starts a note on it
+    pub(crate) fn record(&mut self, comment: &str) -> Result<()> {
+        let comment = comment.trim();
+        let continues_theme = std::mem::take(&mut self.theme_open);
+        // Comments a previous output left in the name list are replaced, never read as themes
+        if comment.is_empty() || is_block_comment(comment) {
+            return Ok(());
+        }
+        if let Some(directive) = comment.strip_prefix('@') {
+            let (key, value) = split_unquoted(directive, ':').unwrap_or((directive, ""));
+            if !self.set(key.trim(), strip_comment(value).trim()) {
+                anyhow::bail!("Unknown directive '#@ {}'", key.trim());
+            }
+            return Ok(());
+        }
+        if let Some((key, value)) = split_unquoted(comment, ':')
+            && self.set(key.trim(), strip_comment(value).trim())
+        {
+            return Ok(());
+        }
+        // Flags read naturally without a value, ie `# augment-inline`
+        if FLAGS.contains(&comment) {
+            self.set(comment, "");
+            return Ok(());
+        }
+        // `sort=`, `style=` and `rarity=` read as directives rather than inserts
+        if let Some((key, value)) = split_unquoted(comment, '=')
+            && matches!(key.trim(), "sort" | "style" | "rarity")
+        {
+            self.set(key.trim(), strip_comment(value).trim());
+        } else if let Some((k, v)) = split_unquoted(comment, '=') {
+            self.kv_inserts.push(format!("{} = {}", k.trim(), v.trim()));
+        } else {
+            match &mut self.theme {
+                Some(theme) if continues_theme => {
+                    theme.push('\n');
+                    theme.push_str(comment);
+                }
+                theme => *theme = Some(comment.to_string()),
+            }
+            self.theme_open = true;
+        }
+        Ok(())
+    }
+
+    /// Takes over the directives written inside the block, ahead of its first line. They win
+    /// over the ones preceding the block, inserts of both are kept
+    pub(crate) fn apply_inside(&mut self, inside: Directives) {
+        self.theme = inside.theme.or(self.theme.take());
+        self.kv_inserts.extend(inside.kv_inserts);
+        self.if_generated.extend(inside.if_generated);
+        self.aliases.extend(inside.aliases);
+        self.prefix = inside.prefix.or(self.prefix.take());
+        self.sort = inside.sort.or(self.sort.take());
+        self.style = inside.style.or(self.style.take());
+        self.distribution = inside.distribution.or(self.distribution.take());
+        self.model = inside.model.or(self.model.take());
+        self.decorate = inside.decorate.or(self.decorate.take());
+        self.avoid_initials = inside.avoid_initials.or(self.avoid_initials.take());
+        self.lore = inside.lore.or(self.lore.take());
+        self.score_filter = inside.score_filter.or(self.score_filter.take());
+        self.min = inside.min.or(self.min.take());
+        self.count = inside.count.or(self.count.take());
+        self.abbreviate = inside.abbreviate.or(self.abbreviate.take());
+        self.schema = inside.schema.or(self.schema.take());
+        self.key_style = inside.key_style.or(self.key_style.take());
+        self.augment_inline = inside.augment_inline.or(self.augment_inline.take());
+        self.translatable = inside.translatable.or(self.translatable.take());
+        self.species_class = inside.species_class.or(self.species_class.take());
+        self.era = inside.era.or(self.era.take());
+        self.first = inside.first.or(self.first.take());
+        self.rarity = inside.rarity.or(self.rarity.take());
+        self.ordinal_expand = inside.ordinal_expand.or(self.ordinal_expand.take());
+        self.compose = inside.compose.or(self.compose.take());
+    }
+
+    /// Ends the theme being written, called for every non-comment line
+    pub(crate) fn end_theme(&mut self) {
+        self.theme_open = false;
+    }
+
+    /// Sets a named directive, returning false for unknown names
+    fn set(&mut self, key: &str, value: &str) -> bool {
+        if key == "if-generated" {
+            self.if_generated.push(value.to_string());
+            return true;
+        }
+        // A block renamed more than once lists every old path, on one line or several
+        if key == "alias" {
+            let aliases = split_all_unquoted(value, ',')
+                .into_iter()
+                .map(str::trim)
+                .filter(|alias| !alias.is_empty())
+                .map(str::to_string);
+            self.aliases.extend(aliases);
+            return true;
+        }
+        let value = Some(value.to_string());
+        match key {
+            "prefix" => self.prefix = value,
+            "sort" => self.sort = value,
+            "style" => self.style = value,
+            "distribution" => self.distribution = value,
+            "model" => self.model = value,
+            "decorate" => self.decorate = value,
+            "avoid-initials" => self.avoid_initials = value,
+            "lore" => self.lore = value,
+            "score-filter" => self.score_filter = value,
+            "min" => self.min = value,
+            "count" | "max" => self.count = value,
+            "abbreviate" => self.abbreviate = value,
+            "schema" => self.schema = value,
+            "key-style" => self.key_style = value,
+            "augment-inline" => self.augment_inline = value,
+            "translatable" => self.translatable = value,
+            "species-class" => self.species_class = value,
+            "era" => self.era = value,
+            "first" => self.first = value,
+            "rarity" => self.rarity = value,
+            "ordinal-expand" => self.ordinal_expand = value,
+            "compose" => self.compose = value,
+            _ => return false,
+        }
+        true
+    }
+
+    /// Whether the block keeps its order in game (`randomized = no`), enabling `{ord}`
+    pub(crate) fn is_sequential(&self) -> bool {
+        self.kv_inserts
+            .iter()
+            .any(|kv| kv.replace(' ', "") == "randomized=no")
+    }
+}
@@ -0,0 +1,34 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::{Semaphore, SemaphorePermit};
+
+/// Caps the number of in-flight requests per model, independent of block concurrency
+pub struct ModelLimiter {
+    semaphores: HashMap<String, Arc<Semaphore>>,
+}
+
+impl ModelLimiter {
+    /// Builds one semaphore per model, using `limits` where given and `default_limit` otherwise
+    pub fn new<'a>(
+        models: impl IntoIterator<Item = &'a str>,
+        limits: &HashMap<String, usize>,
+        default_limit: usize,
+    ) -> Self {
+        let semaphores = models
+            .into_iter()
+            .map(|model| {
+                let limit = limits.get(model).copied().unwrap_or(default_limit).max(1);
+                (model.to_string(), Arc::new(Semaphore::new(limit)))
+            })
+            .collect();
+        Self { semaphores }
+    }
+
+    /// Waits for a free slot on `model`, holding it until the permit is dropped
+    pub async fn acquire(&self, model: &str) -> Option<SemaphorePermit<'_>> {
+        match self.semaphores.get(model) {
+            Some(semaphore) => semaphore.acquire().await.ok(),
+            None => None,
+        }
+    }
+}
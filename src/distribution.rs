@@ -0,0 +1,268 @@
+//! Balances a block's names toward a distribution of initial letters, ie an alphabetical roster
+//! with about as many names for every letter. `# distribution: even` weighs A to Z alike,
+//! `# distribution: A=3, B-D=1` weighs the listed letters and leaves the others out. The
+//! block's names, `# count:` of them or else as many as it has, are shared out by weight:
+//!
+//! ```
+//! use stellaris_name_gen::distribution::Distribution;
+//!
+//! let weights = Distribution::parse("A=2, B-C=1").unwrap();
+//! let targets = weights.targets(8);
+//! assert_eq!(targets.into_iter().collect::<Vec<_>>(), [('A', 4), ('B', 2), ('C', 2)]);
+//! assert_eq!(Distribution::parse("even").unwrap().targets(30)[&'A'], 2);
+//! assert_eq!(Distribution::parse("even").unwrap().targets(30)[&'Z'], 1);
+//! assert!(Distribution::parse("A=1, 4=2").is_err());
+//!
+//! // Letters past their share lose their last names, letters short of it are reported
+//! let targets = weights.targets(4);
+//! let mut names: Vec<String> =
+//!     ["Aster", "Arden", "Avel", "Bren", "Élan", "Dusk"].map(String::from).to_vec();
+//! assert_eq!(Distribution::trim(&mut names, &targets), 3);
+//! assert_eq!(names, ["Aster", "Arden", "Bren"]);
+//! assert_eq!(Distribution::missing(&names, &targets).into_iter().collect::<Vec<_>>(), [('C', 1)]);
+//! ```
+//!
+//! Short letters are filled by one more request asking for names starting with them, cached
+//! beside the block. The outcome lands in the block's report:
+//!
+//! ```
+//! use futures::future::BoxFuture;
+//! use stellaris_name_gen::{Generation, GenerationRequest, InMemoryCache, NameGenerator};
+//!
+//! struct Alphabet;
+//!
+//! impl NameGenerator for Alphabet {
+//!     fn generate<'a>(&'a self, request: &'a GenerationRequest) -> BoxFuture<'a, anyhow::Result<String>> {
+//!         let answer = if request.prompt.contains("2 starting with C") {
+//!             r#"{"names": ["Cinder", "Corvus", "Ash", "Crest"]}"#
+//!         } else {
+//!             r#"{"names": ["Aster", "Arden", "Avel", "Bren", "Brisk", "Dusk"]}"#
+//!         };
+//!         Box::pin(async move { Ok(answer.to_string()) })
+//!     }
+//! }
+//!
+//! let structure = "NAME = {\n    # Ship names\n    # distribution: A-C=1\n    # count: 6\n    ships = {\n    }\n}\n";
+//! let result = futures::executor::block_on(
+//!     Generation::builder()
+//!         .structure(structure)
+//!         .lore("")
+//!         .min_names(1)
+//!         .generator(Alphabet)
+//!         .cache(InMemoryCache::new())
+//!         .run(),
+//! )
+//! .unwrap();
+//! let names: Vec<&str> = result.localisation.iter().map(|(_, name)| name.as_str()).collect();
+//! assert_eq!(names, ["Aster", "Arden", "Bren", "Brisk", "Cinder", "Corvus"]);
+//! let balance = result.report.blocks[0].distribution.as_ref().unwrap();
+//! assert_eq!((balance.trimmed, balance.filled), (2, 2));
+//! assert_eq!(balance.achieved, balance.target);
+//! assert_eq!(balance.off_target(), 0);
+//! ```
+//!
+//! The names filling in go through the block's filters like its own, compared with the names it
+//! kept, so a fill can't bring back a near form or a name the block would have dropped:
+//!
+//! ```
+//! use futures::future::BoxFuture;
+//! use stellaris_name_gen::near_forms::NearFormPolicy;
+//! use stellaris_name_gen::{Generation, GenerationRequest, InMemoryCache, NameGenerator};
+//!
+//! struct Plural;
+//!
+//! impl NameGenerator for Plural {
+//!     fn generate<'a>(&'a self, request: &'a GenerationRequest) -> BoxFuture<'a, anyhow::Result<String>> {
+//!         let answer = if request.prompt.contains("1 starting with B") {
+//!             r#"{"names": ["Brens", "Bastion"]}"#
+//!         } else {
+//!             r#"{"names": ["Aster", "Arden", "Avel", "Bren"]}"#
+//!         };
+//!         Box::pin(async move { Ok(answer.to_string()) })
+//!     }
+//! }
+//!
+//! let structure = "NAME = {\n    # Ship names\n    # distribution: A-B=1\n    # count: 4\n    ships = {\n    }\n}\n";
+//! let result = futures::executor::block_on(
+//!     Generation::builder()
+//!         .structure(structure)
+//!         .lore("")
+//!         .min_names(1)
+//!         .generator(Plural)
+//!         .near_forms(NearFormPolicy::Longer)
+//!         .cache(InMemoryCache::new())
+//!         .run(),
+//! )
+//! .unwrap();
+//! let names: Vec<&str> = result.localisation.iter().map(|(_, name)| name.as_str()).collect();
+//! assert_eq!(names, ["Aster", "Arden", "Bren", "Bastion"]);
+//! assert_eq!(result.report.blocks[0].dropped["near-forms"], 1);
+//! ```
+
+use crate::ascii::to_ascii;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Most names the request filling a block's short letters lets arrive
+pub const MAX_FILL: usize = 50;
+
+/// Target shares of initial letters, from `# distribution:`
+#[derive(Debug, Clone, PartialEq)]
+pub struct Distribution {
+    /// Weight of every letter names may start with
+    weights: BTreeMap<char, f64>,
+}
+
+/// How close a block came to its distribution
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct Balance {
+    /// Names wanted per initial letter
+    pub target: BTreeMap<char, usize>,
+    /// Names kept per initial letter
+    pub achieved: BTreeMap<char, usize>,
+    /// Names dropped as their letter had its share or isn't part of the distribution
+    pub trimmed: usize,
+    /// Names added by the request filling short letters
+    pub filled: usize,
+}
+
+impl Balance {
+    /// Names missing from the letters short of their target
+    pub fn off_target(&self) -> usize {
+        self.target
+            .iter()
+            .map(|(letter, target)| {
+                target.saturating_sub(self.achieved.get(letter).copied().unwrap_or(0))
+            })
+            .sum()
+    }
+}
+
+/// The initial letter of `name` as an ASCII capital, `É` counting as `E`
+pub fn initial(name: &str) -> Option<char> {
+    let first = name.chars().find(|c| c.is_alphabetic())?;
+    to_ascii(first.encode_utf8(&mut [0; 4]))
+        .chars()
+        .next()
+        .map(|c| c.to_ascii_uppercase())
+}
+
+/// Parses a letter or a range of letters such as `B-D`
+fn letters(spec: &str) -> Result<Vec<char>> {
+    let spec = spec.trim().to_ascii_uppercase();
+    let (from, to) = spec.split_once('-').unwrap_or((&spec, &spec));
+    let ends = [from.trim(), to.trim()].map(|end| {
+        let mut chars = end.chars();
+        match (chars.next(), chars.next()) {
+            (Some(c), None) if c.is_ascii_uppercase() => Some(c),
+            _ => None,
+        }
+    });
+    let [Some(from), Some(to)] = ends else {
+        anyhow::bail!("Expected a letter or a range such as B-D, got '{}'", spec);
+    };
+    if from > to {
+        anyhow::bail!("Range '{}' runs backwards", spec);
+    }
+    Ok((from..=to).collect())
+}
+
+impl Distribution {
+    /// Parses `even`, or comma separated `LETTER=WEIGHT` pairs whose letter may be a range
+    pub fn parse(value: &str) -> Result<Self> {
+        let value = value.trim();
+        if value.eq_ignore_ascii_case("even") {
+            return Ok(Self {
+                weights: ('A'..='Z').map(|letter| (letter, 1.0)).collect(),
+            });
+        }
+        let mut weights = BTreeMap::new();
+        for pair in value
+            .split(',')
+            .map(str::trim)
+            .filter(|pair| !pair.is_empty())
+        {
+            let (spec, weight) = pair
+                .split_once('=')
+                .with_context(|| format!("Expected LETTER=WEIGHT, got '{}'", pair))?;
+            let weight = weight
+                .trim()
+                .parse::<f64>()
+                .ok()
+                .filter(|weight| weight.is_finite() && *weight >= 0.0)
+                .with_context(|| format!("Invalid weight '{}'", weight.trim()))?;
+            for letter in letters(spec)? {
+                weights.insert(letter, weight);
+            }
+        }
+        if weights.values().all(|weight| *weight == 0.0) {
+            anyhow::bail!(
+                "Distribution '{}' gives no letter a weight, expected even or A=1, B=2...",
+                value
+            );
+        }
+        Ok(Self { weights })
+    }
+
+    /// Names per letter out of `total`, shared by weight. Shares are rounded down and the names
+    /// left over go to the largest remainders, so they add up to `total`
+    pub fn targets(&self, total: usize) -> BTreeMap<char, usize> {
+        let sum: f64 = self.weights.values().sum();
+        let shares: Vec<(char, f64)> = self
+            .weights
+            .iter()
+            .map(|(letter, weight)| (*letter, total as f64 * weight / sum))
+            .collect();
+        let mut targets: BTreeMap<char, usize> = shares
+            .iter()
+            .map(|(letter, share)| (*letter, share.floor() as usize))
+            .collect();
+        let left = total.saturating_sub(targets.values().sum());
+        let mut remainders = shares.clone();
+        // Stable, so ties go to the earlier letter
+        remainders.sort_by(|a, b| (b.1 - b.1.floor()).total_cmp(&(a.1 - a.1.floor())));
+        for (letter, _) in remainders.into_iter().take(left) {
+            *targets.entry(letter).or_default() += 1;
+        }
+        targets.retain(|_, target| *target > 0);
+        targets
+    }
+
+    /// Keeps at most the target of every letter in `names`, in order, and drops names starting
+    /// with other letters. Returns how many were dropped
+    pub fn trim(names: &mut Vec<String>, targets: &BTreeMap<char, usize>) -> usize {
+        let before = names.len();
+        let mut counts: BTreeMap<char, usize> = BTreeMap::new();
+        names.retain(|name| {
+            let Some(letter) = initial(name).filter(|letter| targets.contains_key(letter)) else {
+                return false;
+            };
+            let count = counts.entry(letter).or_default();
+            *count += 1;
+            *count <= targets[&letter]
+        });
+        before - names.len()
+    }
+
+    /// Names every letter short of its target still needs
+    pub fn missing(names: &[String], targets: &BTreeMap<char, usize>) -> BTreeMap<char, usize> {
+        let counts = count(names);
+        targets
+            .iter()
+            .filter_map(|(letter, target)| {
+                let short = target.saturating_sub(counts.get(letter).copied().unwrap_or(0));
+                (short > 0).then_some((*letter, short))
+            })
+            .collect()
+    }
+}
+
+/// Names per initial letter
+pub fn count(names: &[String]) -> BTreeMap<char, usize> {
+    let mut counts = BTreeMap::new();
+    for letter in names.iter().filter_map(|name| initial(name)) {
+        *counts.entry(letter).or_default() += 1;
+    }
+    counts
+}
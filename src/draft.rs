@@ -0,0 +1,132 @@
+//! Drafting themes with a cheap model before paying for the primary one. A run with
+//! `--draft-model` generates every block with the draft model into the `draft` tier of the cache
+//! ([`CacheConfig::tier`](crate::cache::CacheConfig::tier)) and writes only its preview, labelled
+//! as a draft. Once the previews look right, `promote --blocks <glob>` generates the matching
+//! blocks again with their primary model into the main cache, showing the model the draft names
+//! as examples of the style wanted. Runs read one tier only, so draft names never reach the
+//! emitted name list:
+//!
+//! ```
+//! use futures::future::BoxFuture;
+//! use stellaris_name_gen::{FsCache, Generation, GenerationRequest, NameGenerator, RunResult};
+//!
+//! /// Short names from the draft model, longer ones from the primary model after the drafts
+//! struct ByModel;
+//!
+//! impl NameGenerator for ByModel {
+//!     fn generate<'a>(&'a self, request: &'a GenerationRequest) -> BoxFuture<'a, anyhow::Result<String>> {
+//!         let names = if request.model == "cheap-model" {
+//!             ["Vex", "Ember"]
+//!         } else if request.prompt.contains("Vex, Ember") {
+//!             ["Vexaris", "Emberlight"]
+//!         } else {
+//!             ["Dawn", "Cinder"]
+//!         };
+//!         Box::pin(async move { Ok(serde_json::json!({ "names": names }).to_string()) })
+//!     }
+//! }
+//!
+//! let dir = std::env::temp_dir().join(format!("namegen-doc-draft-{}", std::process::id()));
+//! let _ = std::fs::remove_dir_all(&dir);
+//! let generation = |draft_model: Option<&str>, cache: FsCache| {
+//!     let mut builder = Generation::builder()
+//!         .structure("NAME = {\n    # Ship names\n    ships = {\n    }\n    # Fleet names\n    fleets = {\n    }\n}\n")
+//!         .lore("")
+//!         .min_names(1)
+//!         .model("good-model")
+//!         .generator(ByModel)
+//!         .cache(cache);
+//!     if let Some(model) = draft_model {
+//!         builder = builder.draft_model(model);
+//!     }
+//!     builder.build().unwrap()
+//! };
+//! let names = |result: &RunResult| -> Vec<String> {
+//!     result.localisation.iter().map(|(_, name)| name.clone()).collect()
+//! };
+//!
+//! let drafted = futures::executor::block_on(
+//!     generation(Some("cheap-model"), FsCache::new(dir.join("draft"))).run(),
+//! )
+//! .unwrap();
+//! assert!(drafted.report.draft);
+//! assert_eq!(names(&drafted), ["Vex", "Ember", "Vex", "Ember"]);
+//!
+//! let promoted = futures::executor::block_on(
+//!     generation(None, FsCache::new(&dir)).promote("NAME/sh*", &FsCache::new(dir.join("draft"))),
+//! )
+//! .unwrap();
+//! assert_eq!(promoted.len(), 1);
+//! assert_eq!(promoted[0].path, "NAME/ships");
+//! assert_eq!((promoted[0].model.as_str(), promoted[0].draft, promoted[0].names), ("good-model", 2, 2));
+//!
+//! // The final run reads the main cache alone, the fleets never drafted there
+//! let result = futures::executor::block_on(generation(None, FsCache::new(&dir)).run()).unwrap();
+//! assert!(!result.report.draft);
+//! assert_eq!(names(&result), ["Vexaris", "Emberlight", "Dawn", "Cinder"]);
+//! # std::fs::remove_dir_all(&dir).unwrap();
+//! ```
+//!
+//! Blocks are picked by their path, `*` matching within a key and `**` across keys:
+//!
+//! ```
+//! use stellaris_name_gen::draft::matches;
+//!
+//! assert!(matches("NAME/ship_names/*", "NAME/ship_names/military"));
+//! assert!(!matches("NAME/*", "NAME/ship_names/military"));
+//! assert!(matches("NAME/**", "NAME/ship_names/military"));
+//! assert!(matches("**/military", "NAME/ship_names/military"));
+//! ```
+
+use regex::Regex;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// Tier of the cache draft runs generate into
+pub const DRAFT_TIER: &str = "draft";
+
+/// A block generated again from its draft names
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Promotion {
+    pub path: String,
+    /// Primary model the block was generated with
+    pub model: String,
+    /// Draft names shown to the model
+    pub draft: usize,
+    /// Names the block keeps now, 0 if its generation failed
+    pub names: usize,
+}
+
+/// Whether a block path matches a glob, `*` matching any part of a key and `**` any keys
+pub fn matches(pattern: &str, path: &str) -> bool {
+    let mut regex = String::from("^");
+    let mut chars = pattern.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '*' if chars.peek() == Some(&'*') => {
+                chars.next();
+                regex.push_str(".*");
+            }
+            '*' => regex.push_str("[^/]*"),
+            '?' => regex.push_str("[^/]"),
+            c => regex.push_str(&regex::escape(&c.to_string())),
+        }
+    }
+    regex.push('$');
+    Regex::new(&regex).is_ok_and(|regex| regex.is_match(path))
+}
+
+/// Where a draft run writes an output, ie `PREVIEW.draft.md` for `PREVIEW.md`
+pub fn draft_path(path: &Path) -> PathBuf {
+    tier_path(path, DRAFT_TIER)
+}
+
+/// Where a run kept apart in `tier` writes an output, ie `out.short-prompts.txt` for `out.txt`
+pub fn tier_path(path: &Path, tier: &str) -> PathBuf {
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let file = match path.extension() {
+        Some(ext) => format!("{}.{}.{}", stem, tier, ext.to_string_lossy()),
+        None => format!("{}.{}", stem, tier),
+    };
+    path.with_file_name(file)
+}
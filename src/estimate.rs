@@ -0,0 +1,196 @@
+//! A prediction of what a run would spend, from the prompts it would send, without sending any.
+//!
+//! Every block that isn't cached counts one request. Its prompt is assembled as the run would
+//! and measured at 4 bytes per token. Its answer is sized from the block's `# count:`, else from
+//! the cached answers of the same block under other lore or eras, else assumed at
+//! [`ASSUMED_NAMES`] names. Prices come from the `[prices]` table of the config:
+//!
+//! ```
+//! use stellaris_name_gen::estimate::{ModelPrice, OutputBasis, render_estimate};
+//! use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+//!
+//! let structure = "NAME = {\n    # Ship names\n    # count: 40\n    ships = {\n    }\n    # Fleet names\n    fleets = {\n    }\n}\n";
+//! let generation = Generation::builder()
+//!     .structure(structure)
+//!     .lore("An old empire of the deep.")
+//!     .model("local-model")
+//!     .price("local-model", ModelPrice { input: 1.0, output: 4.0 })
+//!     .generator(MockGenerator::new(["Vex"]))
+//!     .cache(InMemoryCache::new())
+//!     .build()
+//!     .unwrap();
+//! let estimate = generation.estimate().unwrap();
+//! assert_eq!(estimate.requests, 2);
+//! // 40 names and the early stop margin of 10
+//! assert_eq!(estimate.blocks[0].basis, OutputBasis::Count(50));
+//! assert_eq!(estimate.blocks[1].basis, OutputBasis::Assumed);
+//! assert_eq!(estimate.confidence, "low");
+//! let cost = estimate.cost.unwrap();
+//! let expected = (estimate.prompt_tokens as f64 + 4.0 * estimate.output_tokens as f64) / 1e6;
+//! assert!((cost - expected).abs() < 1e-12);
+//! assert!(render_estimate(&estimate).contains("[Estimate] Total: 2 requests"));
+//!
+//! // The estimate of a real run is kept in its report, next to what it actually spent
+//! let result = futures::executor::block_on(generation.run()).unwrap();
+//! assert_eq!(result.report.estimate.unwrap().requests, 2);
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+/// Names an answer is assumed to hold when the block has no count and no cache history
+pub const ASSUMED_NAMES: usize = 150;
+/// Bytes of one name in a JSON answer, quotes and separator included
+pub const NAME_BYTES: usize = 16;
+/// Bytes of the answer to an inline placeholder
+pub const VALUE_BYTES: usize = 64;
+
+/// Price of a model in USD per million tokens, an entry of the `[prices]` table
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize, Serialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct ModelPrice {
+    pub input: f64,
+    pub output: f64,
+}
+
+impl ModelPrice {
+    pub fn cost(&self, prompt_tokens: u64, output_tokens: u64) -> f64 {
+        (prompt_tokens as f64 * self.input + output_tokens as f64 * self.output) / 1_000_000.0
+    }
+}
+
+/// Where the size of a block's answer was taken from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum OutputBasis {
+    /// Already cached, no request is sent
+    Cached,
+    /// The names requested by `# count:`, margin included
+    Count(usize),
+    /// The mean size of cached answers of the same block, in bytes
+    History(usize),
+    /// [`ASSUMED_NAMES`] names
+    Assumed,
+    /// A single inline value
+    Value,
+}
+
+impl OutputBasis {
+    fn describe(&self) -> String {
+        match self {
+            OutputBasis::Cached => "cached".to_string(),
+            OutputBasis::Count(names) => format!("{} names by count", names),
+            OutputBasis::History(bytes) => format!("{} bytes by cache history", bytes),
+            OutputBasis::Assumed => format!("{} names assumed", ASSUMED_NAMES),
+            OutputBasis::Value => "one value".to_string(),
+        }
+    }
+}
+
+/// Predicted spending of one block or inline placeholder
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BlockEstimate {
+    /// Block path, inline placeholders end in `line <n>`
+    pub path: Vec<String>,
+    pub model: String,
+    pub prompt_tokens: u64,
+    pub output_tokens: u64,
+    pub basis: OutputBasis,
+    /// `None` without a price for the model
+    pub cost: Option<f64>,
+}
+
+/// Predicted spending of a run
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Estimate {
+    pub blocks: Vec<BlockEstimate>,
+    /// Requests sent, one per uncached block
+    pub requests: usize,
+    pub prompt_tokens: u64,
+    pub output_tokens: u64,
+    /// Cost of the priced models, `None` if none of the requests is priced
+    pub cost: Option<f64>,
+    /// Models with requests but no price, left out of the cost
+    pub unpriced: Vec<String>,
+    /// `high` when every answer is sized by a count, `medium` with cache history, `low` once
+    /// any is assumed
+    pub confidence: String,
+}
+
+impl Estimate {
+    /// Totals the blocks into an estimate
+    pub fn new(blocks: Vec<BlockEstimate>) -> Self {
+        let sent: Vec<&BlockEstimate> = blocks
+            .iter()
+            .filter(|block| block.basis != OutputBasis::Cached)
+            .collect();
+        let mut unpriced: Vec<String> = sent
+            .iter()
+            .filter(|block| block.cost.is_none())
+            .map(|block| block.model.clone())
+            .collect();
+        unpriced.sort();
+        unpriced.dedup();
+        let confidence = if sent.iter().any(|block| block.basis == OutputBasis::Assumed) {
+            "low"
+        } else if sent
+            .iter()
+            .any(|block| matches!(block.basis, OutputBasis::History(_)))
+        {
+            "medium"
+        } else {
+            "high"
+        };
+        Self {
+            requests: sent.len(),
+            prompt_tokens: sent.iter().map(|block| block.prompt_tokens).sum(),
+            output_tokens: sent.iter().map(|block| block.output_tokens).sum(),
+            cost: sent
+                .iter()
+                .filter_map(|block| block.cost)
+                .fold(None, |total, cost| Some(total.unwrap_or(0.0) + cost)),
+            unpriced,
+            confidence: confidence.to_string(),
+            blocks,
+        }
+    }
+}
+
+/// The estimate as console lines, one per block then the totals
+pub fn render_estimate(estimate: &Estimate) -> String {
+    let money =
+        |cost: Option<f64>| cost.map_or("unpriced".to_string(), |cost| format!("${:.4}", cost));
+    let mut out = String::new();
+    for block in &estimate.blocks {
+        if block.basis == OutputBasis::Cached {
+            out.push_str(&format!("[Estimate] {}  cached\n", block.path.join("/")));
+            continue;
+        }
+        out.push_str(&format!(
+            "[Estimate] {}  model={} prompt~{} output~{} ({})  {}\n",
+            block.path.join("/"),
+            block.model,
+            block.prompt_tokens,
+            block.output_tokens,
+            block.basis.describe(),
+            money(block.cost)
+        ));
+    }
+    out.push_str(&format!(
+        "[Estimate] Total: {} requests, ~{} prompt and ~{} output tokens, {}\n",
+        estimate.requests,
+        estimate.prompt_tokens,
+        estimate.output_tokens,
+        money(estimate.cost)
+    ));
+    if !estimate.unpriced.is_empty() {
+        out.push_str(&format!(
+            "[Estimate] No [prices] entry for {}, left out of the cost\n",
+            estimate.unpriced.join(", ")
+        ));
+    }
+    out.push_str(&format!(
+        "[Estimate] Confidence: {}. Retries, salvage and scoring requests, translations and [titles] are not included\n",
+        estimate.confidence
+    ));
+    out
+}
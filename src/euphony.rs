@@ -0,0 +1,237 @@
+//! Phonetic styles a block's names are reshaped toward after generation, set with
+//! `# style=alliterative`. Every name is scored by a heuristic and the best ones are kept,
+//! rather than trusting the prompt alone:
+//!
+//! ```
+//! use stellaris_name_gen::euphony::{NameStyle, score, select};
+//!
+//! let style = NameStyle::Alliterative;
+//! // Words sharing their first sound
+//! assert_eq!(score(style, "Silent Sorrow"), 2);
+//! assert_eq!(score(style, "Chrome Chariot"), 2);
+//! // Words sharing their ending
+//! assert_eq!(score(style, "Helter Skelter"), 2);
+//! // Both, over three words
+//! assert_eq!(score(style, "Boundless Bold Fold"), 4);
+//! // A single word repeating its sounds
+//! assert_eq!(score(style, "Zamzam"), 2);
+//! assert_eq!(score(style, "Vex"), 0);
+//!
+//! let mut names: Vec<String> = ["Vex", "Silent Sorrow", "Ember", "Helter Skelter", "Ash"]
+//!     .map(String::from)
+//!     .to_vec();
+//! // The best two are kept, in their generated order
+//! let scores = select(&mut names, style, Some(2), 0);
+//! assert_eq!(names, ["Silent Sorrow", "Helter Skelter"]);
+//! assert_eq!(scores[0], ("Vex".to_string(), 0));
+//! ```
+//!
+//! Without a `# count:`, names that score nothing are dropped, though never below the block's
+//! minimum:
+//!
+//! ```
+//! use stellaris_name_gen::euphony::{NameStyle, select};
+//!
+//! let mut names: Vec<String> = ["Vex", "Silent Sorrow", "Ember"].map(String::from).to_vec();
+//! select(&mut names, NameStyle::Alliterative, None, 2);
+//! assert_eq!(names, ["Vex", "Silent Sorrow"]);
+//! ```
+//!
+//! The scores of every candidate are kept in the block's report and printed with `--verbose`:
+//!
+//! ```
+//! use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+//!
+//! let result = futures::executor::block_on(
+//!     Generation::builder()
+//!         .structure("NAME = {\n    # Fleet names\n    # style=alliterative\n    # count: 1\n    fleets = {\n    }\n}\n")
+//!         .lore("")
+//!         .min_names(1)
+//!         .generator(MockGenerator::new(["Vex", "Dread Drifter"]))
+//!         .cache(InMemoryCache::new())
+//!         .run(),
+//! )
+//! .unwrap();
+//! let block = &result.report.blocks[0];
+//! assert_eq!(block.entries[0].1, "Dread Drifter");
+//! assert_eq!(block.style_scores, [("Vex".to_string(), 0), ("Dread Drifter".to_string(), 2)]);
+//! assert_eq!(block.dropped["style"], 1);
+//! ```
+
+use anyhow::Result;
+use serde::Serialize;
+
+/// Sounds spelled with two letters, compared as one
+const DIGRAPHS: [&str; 6] = ["ch", "sh", "th", "ph", "kh", "zh"];
+
+/// A phonetic aesthetic names are scored against
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum NameStyle {
+    /// Alliteration and internal rhyme, ie `Silent Sorrow` or `Helter Skelter`
+    Alliterative,
+}
+
+impl NameStyle {
+    /// Parses the value of a `# style=` directive
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "alliterative" => Ok(NameStyle::Alliterative),
+            other => anyhow::bail!("Unknown style '{}', expected alliterative", other),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NameStyle::Alliterative => "alliterative",
+        }
+    }
+}
+
+/// How well a name fits the style, 0 when not at all
+pub fn score(style: NameStyle, name: &str) -> u8 {
+    match style {
+        NameStyle::Alliterative => alliteration(name),
+    }
+}
+
+/// Keeps the `keep` best scoring names, or without a count every name scoring above 0 but no
+/// fewer than `min`. Kept names stay in their order, ties go to the earlier name. Returns the
+/// score of every candidate, in the order given
+pub fn select(
+    names: &mut Vec<String>,
+    style: NameStyle,
+    keep: Option<usize>,
+    min: usize,
+) -> Vec<(String, u8)> {
+    let scores: Vec<(String, u8)> = names
+        .iter()
+        .map(|name| (name.clone(), score(style, name)))
+        .collect();
+    let mut ranked: Vec<usize> = (0..scores.len()).collect();
+    ranked.sort_by_key(|idx| std::cmp::Reverse(scores[*idx].1));
+    let keep = keep.unwrap_or_else(|| {
+        scores
+            .iter()
+            .filter(|(_, score)| *score > 0)
+            .count()
+            .max(min)
+    });
+    let mut kept = vec![false; scores.len()];
+    for idx in ranked.into_iter().take(keep) {
+        kept[idx] = true;
+    }
+    let mut kept = kept.into_iter();
+    names.retain(|_| kept.next().unwrap_or(false));
+    scores
+}
+
+/// Two points per pair of neighbouring words sharing their first sound, two per pair of words
+/// sharing their ending. Single words score a point per repeated onset and for a repeated
+/// syllable
+fn alliteration(name: &str) -> u8 {
+    let words: Vec<String> = name
+        .split(|c: char| c.is_whitespace() || c == '-')
+        .map(|word| {
+            word.chars()
+                .filter(|c| c.is_alphabetic())
+                .flat_map(char::to_lowercase)
+                .collect::<String>()
+        })
+        .filter(|word| !word.is_empty())
+        .collect();
+    let mut score = 0u8;
+    if let [word] = words.as_slice() {
+        let onsets = syllable_onsets(word);
+        if let Some((first, rest)) = onsets.split_first()
+            && !first.is_empty()
+        {
+            score += rest.iter().filter(|onset| *onset == first).count() as u8;
+        }
+        let chars: Vec<char> = word.chars().collect();
+        let half = chars.len() / 2;
+        if half >= 2 && chars[..half] == chars[chars.len() - half..] {
+            score += 1;
+        }
+        return score;
+    }
+    for pair in words.windows(2) {
+        if onset(&pair[0]) == onset(&pair[1]) {
+            score = score.saturating_add(2);
+        }
+    }
+    for (idx, word) in words.iter().enumerate() {
+        for other in &words[idx + 1..] {
+            if word != other && rime(word).is_some_and(|ending| Some(ending) == rime(other)) {
+                score = score.saturating_add(2);
+            }
+        }
+    }
+    score
+}
+
+fn is_vowel(c: char) -> bool {
+    matches!(c, 'a' | 'e' | 'i' | 'o' | 'u' | 'y')
+}
+
+/// First sound of a word, vowels all alliterating with each other
+fn onset(word: &str) -> &str {
+    match word.chars().next() {
+        Some(c) if is_vowel(c) => "",
+        Some(c) => DIGRAPHS
+            .iter()
+            .find(|digraph| word.starts_with(**digraph))
+            .map_or(&word[..c.len_utf8()], |digraph| &word[..digraph.len()]),
+        None => "",
+    }
+}
+
+/// Consonant opening each syllable of a word, empty for a leading vowel. Between two vowels
+/// the syllable takes the last consonant, or digraph, of the cluster
+fn syllable_onsets(word: &str) -> Vec<&str> {
+    let chars: Vec<(usize, char)> = word.char_indices().collect();
+    let mut onsets = vec![onset(word)];
+    let mut idx = 0;
+    while idx < chars.len() && !is_vowel(chars[idx].1) {
+        idx += 1;
+    }
+    loop {
+        while idx < chars.len() && is_vowel(chars[idx].1) {
+            idx += 1;
+        }
+        let start = idx;
+        while idx < chars.len() && !is_vowel(chars[idx].1) {
+            idx += 1;
+        }
+        // A cluster ending the word opens no syllable
+        if idx >= chars.len() {
+            break;
+        }
+        let end = chars[idx].0;
+        let from = match idx - start {
+            2.. if DIGRAPHS.contains(&&word[chars[idx - 2].0..end]) => chars[idx - 2].0,
+            _ => chars[idx - 1].0,
+        };
+        onsets.push(&word[from..end]);
+    }
+    onsets
+}
+
+/// Ending of a word from its second to last vowel group, or its only one, at least 3 letters
+fn rime(word: &str) -> Option<&str> {
+    let mut groups = Vec::new();
+    let mut in_vowels = false;
+    for (idx, c) in word.char_indices() {
+        if is_vowel(c) && !in_vowels {
+            groups.push(idx);
+        }
+        in_vowels = is_vowel(c);
+    }
+    let start = match groups.len() {
+        0 => return None,
+        1 => groups[0],
+        n => groups[n - 2],
+    };
+    let rime = &word[start..];
+    (rime.len() >= 3).then_some(rime)
+}
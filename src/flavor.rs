@@ -0,0 +1,72 @@
+//! Stylistic modifiers sampled per block to keep repeated generations from converging.
+//!
+//! ```
+//! use stellaris_name_gen::flavor::{builtin_flavors, sample_flavors};
+//!
+//! let pool = builtin_flavors();
+//! let path = vec!["NAME".to_string(), "ships".to_string()];
+//! let picked = sample_flavors(&pool, 42, &path);
+//! assert!((1..=2).contains(&picked.len()));
+//! assert!(picked.iter().all(|flavor| pool.contains(flavor)));
+//! // The same seed and block always pick the same flavors
+//! assert_eq!(picked, sample_flavors(&pool, 42, &path));
+//! assert!(sample_flavors(&[], 42, &path).is_empty());
+//! ```
+//!
+//! The flavors a block was generated with are recorded in its cache metadata:
+//!
+//! ```
+//! use stellaris_name_gen::flavor::{builtin_flavors, sample_flavors};
+//! use stellaris_name_gen::{BlockKey, FsCache, Generation, MockGenerator, NameCache};
+//!
+//! let dir = std::env::temp_dir().join("namegen-flavor-doctest");
+//! let _ = std::fs::remove_dir_all(&dir);
+//! futures::executor::block_on(
+//!     Generation::builder()
+//!         .structure("NAME = {\n    ## Ship names\n    ships = {\n    }\n}\n")
+//!         .lore("")
+//!         .generator(MockGenerator::new(["Vex"]))
+//!         .cache(FsCache::new(&dir))
+//!         .seed(7)
+//!         .run(),
+//! )
+//! .unwrap();
+//! let block = FsCache::new(&dir).get(&BlockKey::from("NAME_ships")).unwrap();
+//! let path = vec!["NAME".to_string(), "ships".to_string()];
+//! assert_eq!(block.flavors, sample_flavors(&builtin_flavors(), 7, &path));
+//! ```
+
+use crate::hash::fnv1a;
+use rand::rngs::StdRng;
+use rand::seq::IndexedRandom;
+use rand::{Rng, SeedableRng};
+
+/// Flavors available without any configuration
+pub const BUILTIN_FLAVORS: &[&str] = &[
+    "favor hard consonants",
+    "favor soft, flowing vowels",
+    "favor nautical terminology",
+    "favor two-word compound names",
+    "favor short, punchy single words",
+    "favor names drawn from old myths and legends",
+    "favor names that sound like titles or epithets",
+    "favor names inspired by natural phenomena",
+];
+
+/// The built-in flavors as owned strings, ready to be extended from the config
+pub fn builtin_flavors() -> Vec<String> {
+    BUILTIN_FLAVORS
+        .iter()
+        .map(|flavor| flavor.to_string())
+        .collect()
+}
+
+/// Picks one or two flavors for the block at `path`, reproducible from the run's seed
+pub fn sample_flavors(pool: &[String], seed: u64, path: &[String]) -> Vec<String> {
+    if pool.is_empty() {
+        return Vec::new();
+    }
+    let mut rng = StdRng::seed_from_u64(seed ^ fnv1a(path.join("/").as_bytes()));
+    let count = rng.random_range(1..=2);
+    pool.choose_multiple(&mut rng, count).cloned().collect()
+}
@@ -0,0 +1,713 @@
+use crate::budget::estimate_tokens;
+use crate::console::Progress;
+use crate::providers::{self, ProviderConfig};
+use crate::stream::NameStream;
+use crate::warning;
+use anyhow::Result;
+use futures::StreamExt;
+use futures::future::BoxFuture;
+use genai::Client as GenAiClient;
+use genai::chat::{
+    ChatMessage, ChatOptions, ChatRequest, ChatResponseFormat, ChatStream, ChatStreamResponse,
+    JsonSpec,
+};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+
+/// Sampling temperature of every request [`GenAiGenerator`] sends
+pub const TEMPERATURE: f64 = 0.5;
+
+/// `max_tokens` of every request, until the provider rejects it for a model. The run then halves
+/// it, down to [`MIN_MAX_TOKENS`], and keeps the limit that worked for the model's later requests:
+///
+/// ```
+/// use futures::future::BoxFuture;
+/// use std::sync::{Arc, Mutex};
+/// use stellaris_name_gen::{FsCache, Generation, GenerationRequest, NameGenerator};
+///
+/// /// Rejects any limit above 8k like some providers do, recording every limit it was sent
+/// struct Capped(Arc<Mutex<Vec<u32>>>);
+///
+/// impl NameGenerator for Capped {
+///     fn generate<'a>(&'a self, request: &'a GenerationRequest) -> BoxFuture<'a, anyhow::Result<String>> {
+///         let limit = request.max_tokens.unwrap();
+///         self.0.lock().unwrap().push(limit);
+///         Box::pin(async move {
+///             if limit > 8192 {
+///                 anyhow::bail!("400 Bad Request: max_tokens: {} > 8192, the maximum allowed", limit);
+///             }
+///             Ok(r#"{"names": ["Vex"]}"#.to_string())
+///         })
+///     }
+/// }
+///
+/// let dir = std::env::temp_dir().join(format!("namegen-doc-tokens-{}", std::process::id()));
+/// let _ = std::fs::remove_dir_all(&dir);
+/// let sent = Arc::new(Mutex::new(Vec::new()));
+/// let generation = Generation::builder()
+///     .structure("NAME = {\n    # Ship names\n    ships = {\n    }\n    # Fleet names\n    fleets = {\n    }\n}\n")
+///     .lore("")
+///     .generator(Capped(sent.clone()))
+///     .concurrency(1)
+///     .cache(FsCache::new(&dir))
+///     .build()
+///     .unwrap();
+/// futures::executor::block_on(generation.run()).unwrap();
+/// // Discovered once, the second block starts at the limit that worked
+/// assert_eq!(*sent.lock().unwrap(), [65536, 32768, 16384, 8192, 8192]);
+/// let meta = std::fs::read_to_string(dir.join("NAME_fleets.meta.json")).unwrap();
+/// assert!(meta.contains("\"max_tokens\": 8192"));
+/// # std::fs::remove_dir_all(&dir).unwrap();
+/// ```
+pub const DEFAULT_MAX_TOKENS: u32 = 65536;
+/// Lowest `max_tokens` a rejected request is retried with, a rejection below fails the request
+pub const MIN_MAX_TOKENS: u32 = 4096;
+
+/// A single request for a block's names
+#[derive(Debug, Clone)]
+pub struct GenerationRequest {
+    /// Model the block resolved to
+    pub model: String,
+    /// Theme of the block, for logging
+    pub theme: String,
+    /// Fully assembled prompt
+    pub prompt: String,
+    /// Shape of the expected answer
+    pub format: ResponseFormat,
+    /// Block's own JSON schema, replacing the built-in one of [`ResponseFormat::Names`]
+    pub schema: Option<serde_json::Value>,
+    /// Names needed, overshoot included, the stream is stopped early once this many have arrived
+    pub max_names: Option<usize>,
+    /// Output token limit, [`DEFAULT_MAX_TOKENS`] if unset
+    pub max_tokens: Option<u32>,
+    /// Sampling temperature replacing the generator's own, ie one replayed by
+    /// [`crate::replay`]
+    pub temperature: Option<f64>,
+}
+
+/// Structured output requested from the model
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ResponseFormat {
+    /// `{"names": [...]}`, enforced with a JSON schema
+    #[default]
+    Names,
+    /// `{"names": [...]}` in plain JSON mode, for models that choke on the schema
+    JsonNames,
+    /// One name per line, the last resort for models that cannot produce JSON
+    Lines,
+    /// `{"scores": {"<name>": <1-5>, ...}}`
+    Scores,
+    /// `{"value": "..."}`, a single value for an inline placeholder
+    Value,
+    /// `{"translations": {"<key>": "...", ...}}`, the request's schema lists the keys
+    Translations,
+}
+
+impl ResponseFormat {
+    /// Short description for logs
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ResponseFormat::Names => "json schema",
+            ResponseFormat::JsonNames => "json",
+            ResponseFormat::Lines => "plain lines",
+            ResponseFormat::Scores => "score schema",
+            ResponseFormat::Value => "value schema",
+            ResponseFormat::Translations => "translation schema",
+        }
+    }
+
+    fn response_format(&self, schema: Option<&serde_json::Value>) -> Option<ChatResponseFormat> {
+        let spec = match self {
+            ResponseFormat::Names if let Some(schema) = schema => {
+                JsonSpec::new("custom_names", schema.clone())
+            }
+            ResponseFormat::Names => JsonSpec::new(
+                "names",
+                serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "names": {
+                        "type": "array",
+                        "items": {
+                            "type": "string"
+                        }
+                        }
+                    }
+                }),
+            ),
+            ResponseFormat::Scores => JsonSpec::new(
+                "scores",
+                serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "scores": {
+                        "type": "object",
+                        "additionalProperties": {
+                            "type": "integer"
+                        }
+                        }
+                    }
+                }),
+            ),
+            ResponseFormat::Value => JsonSpec::new(
+                "value",
+                serde_json::json!({
+                    "type": "object",
+                    "properties": {
+                        "value": {
+                            "type": "string"
+                        }
+                    }
+                }),
+            ),
+            ResponseFormat::Translations => JsonSpec::new(
+                "translations",
+                schema.cloned().unwrap_or_else(|| {
+                    serde_json::json!({
+                        "type": "object",
+                        "properties": {
+                            "translations": {
+                            "type": "object",
+                            "additionalProperties": {
+                                "type": "string"
+                            }
+                            }
+                        }
+                    })
+                }),
+            ),
+            ResponseFormat::JsonNames => return Some(ChatResponseFormat::JsonMode),
+            ResponseFormat::Lines => return None,
+        };
+        Some(ChatResponseFormat::JsonSpec(spec))
+    }
+}
+
+/// Tokens spent over every request, as reported by the providers. Streams stopped early never
+/// get a report, their tokens are estimated from what was sent and received
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct TokenUsage {
+    pub requests: u64,
+    pub prompt_tokens: u64,
+    pub completion_tokens: u64,
+}
+
+/// How a stream ended, from its Start, End and error events:
+///
+/// ```
+/// use stellaris_name_gen::{StreamError, StreamEvents, StreamStatus};
+///
+/// // The End event's captured content is the answer, over the chunks put together by hand
+/// let mut events = StreamEvents::default();
+/// events.start();
+/// events.end(Some(r#"{"names": ["Vex"]}"#));
+/// assert_eq!(events.status(), StreamStatus::Complete);
+/// assert_eq!(events.finish(r#"{"names": ["Vex"]}{"#.to_string()).unwrap(), r#"{"names": ["Vex"]}"#);
+///
+/// // Without captured content the chunks stand
+/// let mut events = StreamEvents::default();
+/// events.start();
+/// events.end(None);
+/// assert_eq!(events.finish("Vex".to_string()).unwrap(), "Vex");
+///
+/// // An error ends the stream for good, what arrived before it is discarded
+/// let mut events = StreamEvents::default();
+/// events.start();
+/// events.fail("connection reset");
+/// assert_eq!(events.status(), StreamStatus::Interrupted);
+/// let error = events.finish(r#"{"names": ["Ve"#.to_string()).unwrap_err();
+/// assert_eq!(error.to_string(), "stream interrupted: connection reset");
+///
+/// // A stream closing without its End event may have been cut off, its text is kept for salvage
+/// let mut events = StreamEvents::default();
+/// events.start();
+/// assert_eq!(events.status(), StreamStatus::Truncated);
+/// let error = events.finish(r#"{"names": ["Vex", "Ember""#.to_string()).unwrap_err();
+/// assert_eq!(error, StreamError::Truncated(r#"{"names": ["Vex", "Ember""#.to_string()));
+/// assert_eq!(StreamEvents::default().status(), StreamStatus::Truncated);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum StreamStatus {
+    /// Closed by the provider's End event
+    Complete,
+    /// Broke off with an error
+    Interrupted,
+    /// Closed without an End event
+    Truncated,
+}
+
+/// The Start, End and error events of a stream, reduced to how it ended
+#[derive(Debug, Clone, Default)]
+pub struct StreamEvents {
+    started: bool,
+    ended: bool,
+    /// Text of the End event, requested with `capture_content`
+    captured: Option<String>,
+    error: Option<String>,
+}
+
+impl StreamEvents {
+    pub fn start(&mut self) {
+        if self.started {
+            warning!("[AI Warning] Stream started twice");
+        }
+        self.started = true;
+    }
+
+    pub fn end(&mut self, captured: Option<&str>) {
+        if !self.started {
+            warning!("[AI Warning] Stream ended without starting");
+        }
+        self.ended = true;
+        self.captured = captured.map(str::to_string);
+    }
+
+    pub fn fail(&mut self, error: impl fmt::Display) {
+        self.error = Some(error.to_string());
+    }
+
+    pub fn status(&self) -> StreamStatus {
+        match (&self.error, self.ended) {
+            (Some(_), _) => StreamStatus::Interrupted,
+            (None, true) => StreamStatus::Complete,
+            (None, false) => StreamStatus::Truncated,
+        }
+    }
+
+    /// The answer of a complete stream, its captured content or else `chunks`, the text put
+    /// together from the chunks
+    pub fn finish(self, chunks: String) -> Result<String, StreamError> {
+        match (self.error, self.ended) {
+            (Some(error), _) => Err(StreamError::Interrupted(error)),
+            (None, true) => Ok(self.captured.unwrap_or(chunks)),
+            (None, false) => Err(StreamError::Truncated(chunks)),
+        }
+    }
+}
+
+/// A stream that did not end with its End event. Interrupted requests are repeated as they
+/// were and never cached, truncated answers are salvaged like answers cut off by the token
+/// limit, their block reported as `truncated`:
+///
+/// ```
+/// use futures::future::BoxFuture;
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use stellaris_name_gen::{Generation, GenerationRequest, InMemoryCache, NameGenerator, StreamError};
+///
+/// /// Interrupted on the first call, closed early on the second, whole after that
+/// #[derive(Default)]
+/// struct Flaky(AtomicUsize);
+///
+/// impl NameGenerator for Flaky {
+///     fn generate<'a>(&'a self, request: &'a GenerationRequest) -> BoxFuture<'a, anyhow::Result<String>> {
+///         let call = self.0.fetch_add(1, Ordering::SeqCst);
+///         let avoided = request.prompt.contains("Vex");
+///         Box::pin(async move {
+///             match call {
+///                 0 => Err(StreamError::Interrupted("connection reset".to_string()).into()),
+///                 1 => Err(StreamError::Truncated(r#"{"names": ["Vex", "#.to_string()).into()),
+///                 _ if avoided => Ok(r#"{"names": ["Ember"]}"#.to_string()),
+///                 _ => Ok(r#"{"names": ["Vex"]}"#.to_string()),
+///             }
+///         })
+///     }
+/// }
+///
+/// let result = futures::executor::block_on(
+///     Generation::builder()
+///         .structure("NAME = {\n    # Ship names\n    ships = {\n    }\n}\n")
+///         .lore("")
+///         .generator(Flaky::default())
+///         .cache(InMemoryCache::new())
+///         .run(),
+/// )
+/// .unwrap();
+/// let block = &result.report.blocks[0];
+/// assert!(block.truncated);
+/// assert_eq!(block.salvaged, 1);
+/// let names: Vec<&str> = block.entries.iter().map(|(_, name)| name.as_str()).collect();
+/// assert_eq!(names, ["Vex", "Ember"]);
+/// assert_eq!(result.report.errors["stream error"], 1);
+/// assert_eq!(result.report.errors["truncated stream"], 1);
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StreamError {
+    /// Broke off with this error
+    Interrupted(String),
+    /// Closed without an End event after this text
+    Truncated(String),
+}
+
+impl fmt::Display for StreamError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StreamError::Interrupted(error) => write!(f, "stream interrupted: {}", error),
+            StreamError::Truncated(text) => write!(
+                f,
+                "stream ended without an End event after {} bytes",
+                text.len()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for StreamError {}
+
+/// Produces the raw (ideally JSON) text of a block's names
+pub trait NameGenerator: Send + Sync {
+    fn generate<'a>(&'a self, request: &'a GenerationRequest) -> BoxFuture<'a, Result<String>>;
+
+    /// Sampling temperature of the requests, `None` if not applicable
+    fn temperature(&self) -> Option<f64> {
+        None
+    }
+
+    /// Tokens spent so far, `None` if the generator does not track them
+    fn usage(&self) -> Option<TokenUsage> {
+        None
+    }
+
+    /// One embedding of each name from `model`, in order, see [`crate::semantic`]
+    fn embed<'a>(
+        &'a self,
+        model: &'a str,
+        _names: &'a [String],
+    ) -> BoxFuture<'a, Result<Vec<Vec<f32>>>> {
+        Box::pin(async move { anyhow::bail!("The generator can't embed names with '{}'", model) })
+    }
+}
+
+/// Generates names through a genai client, showing a live count of the names streamed so far
+#[derive(Default)]
+pub struct GenAiGenerator {
+    client: GenAiClient,
+    usage: Mutex<TokenUsage>,
+    /// Echo the raw streamed chunks instead of the live count
+    verbose: bool,
+    progress: Progress,
+    /// Credentials and endpoints of the embedding requests, which bypass the client
+    providers: HashMap<String, ProviderConfig>,
+    /// Sends the embedding requests, reusing connections
+    http: reqwest::Client,
+}
+
+impl GenAiGenerator {
+    pub fn new(client: GenAiClient) -> Self {
+        Self {
+            client,
+            ..Self::default()
+        }
+    }
+
+    /// Echoes the raw streamed chunks instead of a live count of the names
+    pub fn verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    /// Per-provider credentials of the embedding requests, like the client's
+    pub fn providers(mut self, providers: HashMap<String, ProviderConfig>) -> Self {
+        self.providers = providers;
+        self
+    }
+
+    /// Embeds through the provider's OpenAI compatible endpoint, as configured under
+    /// `[providers.<name>]`. The client only chats
+    async fn embed_names(&self, model: &str, names: &[String]) -> Result<Vec<Vec<f32>>> {
+        let credentials =
+            providers::resolve_embedding(model, &self.providers, |var| std::env::var(var).ok())?;
+        let endpoint = providers::embeddings_url(credentials.provider, &self.providers)?;
+        println!("[AI] Embedding {} names with '{}'", names.len(), model);
+        let mut request = self
+            .http
+            .post(&endpoint)
+            .json(&serde_json::json!({ "model": model, "input": names }));
+        if let Some(var) = &credentials.key_env {
+            request = request.bearer_auth(std::env::var(var)?);
+        }
+        let mut answer: Embeddings = request.send().await?.error_for_status()?.json().await?;
+        if answer.data.len() != names.len() {
+            anyhow::bail!(
+                "Asked to embed {} names, got {} embeddings",
+                names.len(),
+                answer.data.len()
+            );
+        }
+        answer.data.sort_by_key(|embedding| embedding.index);
+        Ok(answer
+            .data
+            .into_iter()
+            .map(|embedding| embedding.embedding)
+            .collect())
+    }
+
+    fn record_usage(&self, captured: Option<&genai::chat::Usage>) {
+        let mut usage = self.usage.lock().unwrap();
+        usage.requests += 1;
+        if let Some(captured) = captured {
+            usage.prompt_tokens += captured.prompt_tokens.unwrap_or(0).max(0) as u64;
+            usage.completion_tokens += captured.completion_tokens.unwrap_or(0).max(0) as u64;
+        }
+    }
+
+    /// Counts a stream dropped before the provider reported its usage, estimating the prompt
+    /// and the part received at 4 bytes per token
+    fn record_dropped(&self, prompt: &str, received: usize) {
+        let mut usage = self.usage.lock().unwrap();
+        usage.requests += 1;
+        usage.prompt_tokens += estimate_tokens(prompt.len());
+        usage.completion_tokens += estimate_tokens(received);
+    }
+
+    async fn stream(&self, request: &GenerationRequest) -> Result<String> {
+        match request.format {
+            ResponseFormat::Names | ResponseFormat::JsonNames | ResponseFormat::Lines => {
+                println!("[AI] Streaming generation for theme '{}'", request.theme)
+            }
+            ResponseFormat::Scores => {
+                println!("[AI] Streaming scores for theme '{}'", request.theme)
+            }
+            ResponseFormat::Value => println!("[AI] Streaming value for '{}'", request.theme),
+            ResponseFormat::Translations => {
+                println!("[AI] Streaming translations for '{}'", request.theme)
+            }
+        }
+        let user_msg = ChatMessage::user(request.prompt.clone());
+        let chat_req = ChatRequest::new(vec![user_msg]);
+        let mut chat_opts = ChatOptions::default()
+            .with_temperature(request.temperature.unwrap_or(TEMPERATURE))
+            .with_max_tokens(request.max_tokens.unwrap_or(DEFAULT_MAX_TOKENS))
+            .with_capture_content(true)
+            .with_capture_usage(true);
+        if let Some(format) = request.format.response_format(request.schema.as_ref()) {
+            chat_opts = chat_opts.with_response_format(format);
+        }
+
+        // Stream the chat
+        let stream_response: ChatStreamResponse = self
+            .client
+            .exec_chat_stream(&request.model, chat_req, Some(&chat_opts))
+            .await?;
+        let mut stream: ChatStream = stream_response.stream;
+
+        let mut names = NameStream::new(request.format, request.max_names);
+        let progress =
+            (!self.verbose && names.tracks_names()).then(|| self.progress.start(&request.theme));
+        let mut stopped_early = false;
+        let mut events = StreamEvents::default();
+        if self.verbose {
+            println!();
+        }
+        while let Some(chunk) = stream.next().await {
+            match chunk {
+                Ok(genai::chat::ChatStreamEvent::Start) => events.start(),
+                Ok(genai::chat::ChatStreamEvent::Chunk(stream_chunk)) => {
+                    let before = names.names().len();
+                    let added = names.push(&stream_chunk.content);
+                    if self.verbose {
+                        print!("{}", self.progress.console().text(added));
+                    }
+                    if names.names().len() == before {
+                        continue;
+                    }
+                    if let Some(id) = progress {
+                        self.progress
+                            .update(id, names.names().len(), names.recent(3));
+                    }
+                    if names.is_complete() {
+                        // Dropping the stream closes the connection, no further tokens are spent
+                        stopped_early = true;
+                        self.record_dropped(&request.prompt, names.accumulator().received());
+                        break;
+                    }
+                }
+                Ok(genai::chat::ChatStreamEvent::ReasoningChunk(stream_chunk)) => {
+                    if self.verbose {
+                        print!("{}", self.progress.console().text(&stream_chunk.content));
+                    }
+                }
+                Ok(genai::chat::ChatStreamEvent::End(end)) => {
+                    if self.verbose {
+                        println!("Final out: {:?}", end.captured_content);
+                    }
+                    self.record_usage(end.captured_usage.as_ref());
+                    let captured = end.captured_content.as_ref().and_then(|c| c.text_as_str());
+                    if let Some(captured) = captured
+                        && let Some((received, reported)) =
+                            names.accumulator().total_mismatch(captured.len())
+                    {
+                        warning!(
+                            "[AI Warning] Received {} bytes but the provider reported {}, using the provider's",
+                            received,
+                            reported
+                        );
+                    }
+                    events.end(captured);
+                    break;
+                }
+                Err(e) => {
+                    warning!("[AI Warning] Streaming error: {}", e);
+                    events.fail(e);
+                    break;
+                }
+            }
+        }
+        if !stopped_early && events.status() != StreamStatus::Complete {
+            self.record_dropped(&request.prompt, names.accumulator().received());
+        }
+        if let Some(id) = progress {
+            self.progress.finish(id);
+        } else if self.verbose {
+            println!();
+        }
+        if stopped_early && let Some(answer) = names.early_answer() {
+            println!(
+                "[AI] Stopped '{}' early with {} names after {} bytes",
+                request.theme,
+                request.max_names.unwrap_or_default(),
+                names.accumulator().received()
+            );
+            // Whatever the format, a complete answer of the names collected so far
+            return Ok(answer);
+        }
+        let accumulator = names.into_accumulator();
+        if accumulator.dropped_whitespace() > 0 || accumulator.trimmed_overlap() > 0 {
+            println!(
+                "[AI] Dropped {} whitespace chunks and {} bytes of re-sent content",
+                accumulator.dropped_whitespace(),
+                accumulator.trimmed_overlap()
+            );
+        }
+        let mut combined = events.finish(accumulator.into_text())?;
+
+        // gracefully close off the json if not complete
+        // remove trailing ,
+        if request.format != ResponseFormat::Lines
+            && let Some(last_quote_pos) = combined.rfind('"')
+        {
+            let mut idx = last_quote_pos + 1;
+            // Skip whitespace
+            while idx < combined.len() && combined.as_bytes()[idx].is_ascii_whitespace() {
+                idx += 1;
+            }
+            // If next character is a comma, remove it
+            if idx < combined.len() && combined.as_bytes()[idx] == b',' {
+                combined.remove(idx);
+            }
+        }
+        Ok(combined)
+    }
+}
+
+impl NameGenerator for GenAiGenerator {
+    fn generate<'a>(&'a self, request: &'a GenerationRequest) -> BoxFuture<'a, Result<String>> {
+        Box::pin(self.stream(request))
+    }
+
+    fn temperature(&self) -> Option<f64> {
+        Some(TEMPERATURE)
+    }
+
+    fn usage(&self) -> Option<TokenUsage> {
+        Some(*self.usage.lock().unwrap())
+    }
+
+    fn embed<'a>(
+        &'a self,
+        model: &'a str,
+        names: &'a [String],
+    ) -> BoxFuture<'a, Result<Vec<Vec<f32>>>> {
+        Box::pin(self.embed_names(model, names))
+    }
+}
+
+/// Answer of an OpenAI compatible embeddings endpoint
+#[derive(Deserialize)]
+struct Embeddings {
+    data: Vec<Embedding>,
+}
+
+#[derive(Deserialize)]
+struct Embedding {
+    index: usize,
+    embedding: Vec<f32>,
+}
+
+/// Returns the same names for every block without calling any model, for tests and previews
+#[derive(Debug, Clone, Default)]
+pub struct MockGenerator {
+    names: Vec<String>,
+    /// Prompt and completion tokens reported per call, `None` reports no usage
+    usage_per_call: Option<(u64, u64)>,
+    /// Shared between clones
+    spent: Arc<Mutex<TokenUsage>>,
+}
+
+impl MockGenerator {
+    pub fn new<S: Into<String>>(names: impl IntoIterator<Item = S>) -> Self {
+        Self {
+            names: names.into_iter().map(Into::into).collect(),
+            ..Self::default()
+        }
+    }
+
+    /// Reports `prompt` and `completion` tokens for every call, like a provider would
+    pub fn with_usage(mut self, prompt: u64, completion: u64) -> Self {
+        self.usage_per_call = Some((prompt, completion));
+        self
+    }
+}
+
+impl NameGenerator for MockGenerator {
+    fn generate<'a>(&'a self, request: &'a GenerationRequest) -> BoxFuture<'a, Result<String>> {
+        // Stops early like a stream would
+        let names = &self.names[..self
+            .names
+            .len()
+            .min(request.max_names.unwrap_or(usize::MAX))];
+        let raw = match request.format {
+            ResponseFormat::Names | ResponseFormat::JsonNames => {
+                serde_json::json!({ "names": names }).to_string()
+            }
+            ResponseFormat::Lines => names.join("\n"),
+            ResponseFormat::Value => {
+                serde_json::json!({ "value": self.names.first().cloned().unwrap_or_default() })
+                    .to_string()
+            }
+            // Echoes the English value every key of the schema is described with
+            ResponseFormat::Translations => {
+                let keys = request.schema.as_ref().and_then(|schema| {
+                    schema["properties"]["translations"]["properties"].as_object()
+                });
+                let translations: serde_json::Map<String, serde_json::Value> = keys
+                    .into_iter()
+                    .flatten()
+                    .map(|(key, spec)| (key.clone(), spec["description"].clone()))
+                    .collect();
+                serde_json::json!({ "translations": translations }).to_string()
+            }
+            // Rates its own names a middling 3
+            ResponseFormat::Scores => {
+                let scores: serde_json::Map<String, serde_json::Value> = self
+                    .names
+                    .iter()
+                    .map(|name| (name.clone(), 3.into()))
+                    .collect();
+                serde_json::json!({ "scores": scores }).to_string()
+            }
+        };
+        if let Some((prompt, completion)) = self.usage_per_call {
+            let mut spent = self.spent.lock().unwrap();
+            spent.requests += 1;
+            spent.prompt_tokens += prompt;
+            spent.completion_tokens += completion;
+        }
+        Box::pin(async move { Ok(raw) })
+    }
+
+    fn usage(&self) -> Option<TokenUsage> {
+        self.usage_per_call.map(|_| *self.spent.lock().unwrap())
+    }
+}
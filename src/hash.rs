@@ -0,0 +1,14 @@
+/// 64-bit FNV-1a, stable across platforms and Rust versions unlike `DefaultHasher`
+pub(crate) fn fnv1a(bytes: &[u8]) -> u64 {
+    let mut hash: u64 = 0xcbf29ce484222325;
+    for byte in bytes {
+        hash ^= *byte as u64;
+        hash = hash.wrapping_mul(0x100000001b3);
+    }
+    hash
+}
+
+/// Hex digest of `text`, used to detect changed prompts and inputs
+pub(crate) fn digest(text: &str) -> String {
+    format!("{:016x}", fnv1a(text.as_bytes()))
+}
@@ -0,0 +1,266 @@
+//! A circuit breaker over the run's model calls, aborting once too many of the recent ones failed.
+//!
+//! Failed calls, whether the request errored or its answer was unusable, are retried like any
+//! unusable answer. During an outage every retry fails too, so the run is aborted with a
+//! breakdown of the errors instead of crawling through every block:
+//!
+//! ```
+//! use futures::future::BoxFuture;
+//! use stellaris_name_gen::{Generation, GenerationRequest, InMemoryCache, NameGenerator};
+//!
+//! struct Down;
+//!
+//! impl NameGenerator for Down {
+//!     fn generate<'a>(&'a self, _: &'a GenerationRequest) -> BoxFuture<'a, anyhow::Result<String>> {
+//!         Box::pin(async { anyhow::bail!("HTTP 503 Service Unavailable") })
+//!     }
+//! }
+//!
+//! let error = futures::executor::block_on(
+//!     Generation::builder()
+//!         .structure("NAME = {\n    # Ship names\n    ships = {\n    }\n}\n")
+//!         .lore("")
+//!         .generator(Down)
+//!         .max_error_rate(0.5)
+//!         .error_window(4)
+//!         .cache(InMemoryCache::new())
+//!         .run(),
+//! )
+//! .unwrap_err();
+//! assert_eq!(
+//!     error.to_string(),
+//!     "Aborting, 3 of the last 3 model calls failed, above the 50% limit. Failures: 3 server error in 3 calls"
+//! );
+//! ```
+//!
+//! Failures below the limit are retried and counted in the report:
+//!
+//! ```
+//! use futures::future::BoxFuture;
+//! use std::sync::atomic::{AtomicUsize, Ordering};
+//! use stellaris_name_gen::{Generation, GenerationRequest, InMemoryCache, NameGenerator};
+//!
+//! /// Rate limited on every other call
+//! #[derive(Default)]
+//! struct Throttled(AtomicUsize);
+//!
+//! impl NameGenerator for Throttled {
+//!     fn generate<'a>(&'a self, _: &'a GenerationRequest) -> BoxFuture<'a, anyhow::Result<String>> {
+//!         let call = self.0.fetch_add(1, Ordering::SeqCst);
+//!         Box::pin(async move {
+//!             if call % 2 == 0 {
+//!                 anyhow::bail!("429 Too Many Requests");
+//!             }
+//!             Ok(r#"{"names": ["Vex"]}"#.to_string())
+//!         })
+//!     }
+//! }
+//!
+//! let result = futures::executor::block_on(
+//!     Generation::builder()
+//!         .structure("NAME = {\n    # Ship names\n    ships = {\n    }\n    # Fleet names\n    fleets = {\n    }\n}\n")
+//!         .lore("")
+//!         .generator(Throttled::default())
+//!         .cache(InMemoryCache::new())
+//!         .run(),
+//! )
+//! .unwrap();
+//! assert_eq!(result.report.blocks[1].names, 1);
+//! assert_eq!(result.report.errors["rate limit"], 2);
+//! ```
+
+use crate::generator::StreamError;
+use crate::quota::quota_exhausted;
+use std::collections::{BTreeMap, VecDeque};
+use std::fmt;
+use std::sync::Mutex;
+
+/// Share of failed calls in the window above which the run aborts, unless configured otherwise
+pub const DEFAULT_MAX_ERROR_RATE: f64 = 0.5;
+/// Number of recent calls the error rate is measured over, unless configured otherwise
+pub const DEFAULT_ERROR_WINDOW: usize = 20;
+
+/// Tracks the outcome of recent calls and trips once too many of them failed
+///
+/// ```
+/// use stellaris_name_gen::health::HealthMonitor;
+///
+/// // A limit of 1.0 can't be exceeded, it trips once the whole window failed
+/// let health = HealthMonitor::new(1.0, 3);
+/// health.success();
+/// assert!(health.failure("server error").is_ok());
+/// assert!(health.failure("server error").is_ok());
+/// let unhealthy = health.failure("server error").unwrap_err();
+/// assert!(unhealthy.to_string().starts_with("Aborting, 3 of the last 3 model calls failed, reaching the 100% limit"));
+/// ```
+#[derive(Debug)]
+pub struct HealthMonitor {
+    max_error_rate: f64,
+    window: usize,
+    state: Mutex<HealthState>,
+}
+
+#[derive(Debug, Default)]
+struct HealthState {
+    /// Outcome of the latest calls, `true` for a failure
+    recent: VecDeque<bool>,
+    calls: usize,
+    /// Failures over the whole run by kind
+    failures: BTreeMap<String, usize>,
+    /// The diagnostic of the trip, every later call is refused with it
+    tripped: Option<Unhealthy>,
+}
+
+/// The run was aborted by the circuit breaker
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Unhealthy {
+    pub failed: usize,
+    pub window: usize,
+    pub max_error_rate_percent: u32,
+    /// Failures over the whole run by kind
+    pub failures: BTreeMap<String, usize>,
+    pub calls: usize,
+}
+
+impl fmt::Display for Unhealthy {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let breakdown: Vec<String> = self
+            .failures
+            .iter()
+            .map(|(kind, count)| format!("{} {}", count, kind))
+            .collect();
+        // A 100% limit can't be exceeded, it trips once every call in the window failed
+        let reached = if self.max_error_rate_percent >= 100 {
+            "reaching"
+        } else {
+            "above"
+        };
+        write!(
+            f,
+            "Aborting, {} of the last {} model calls failed, {} the {}% limit. Failures: {} in {} calls",
+            self.failed,
+            self.window,
+            reached,
+            self.max_error_rate_percent,
+            breakdown.join(", "),
+            self.calls
+        )
+    }
+}
+
+impl std::error::Error for Unhealthy {}
+
+impl HealthMonitor {
+    pub fn new(max_error_rate: f64, window: usize) -> Self {
+        Self {
+            max_error_rate: max_error_rate.clamp(0.0, 1.0),
+            window: window.max(1),
+            state: Mutex::default(),
+        }
+    }
+
+    /// Refuses further calls once tripped
+    pub fn check(&self) -> Result<(), Unhealthy> {
+        match &self.state.lock().unwrap().tripped {
+            Some(unhealthy) => Err(unhealthy.clone()),
+            None => Ok(()),
+        }
+    }
+
+    /// Records a successful call
+    pub fn success(&self) {
+        self.record(None);
+    }
+
+    /// Records a failed call of `kind`, see [`classify`], tripping when the window holds more
+    /// failures than the limit allows, or is full of failures with a limit of 1.0
+    pub fn failure(&self, kind: &str) -> Result<(), Unhealthy> {
+        self.record(Some(kind));
+        self.check()
+    }
+
+    fn record(&self, failure: Option<&str>) {
+        let mut state = self.state.lock().unwrap();
+        state.calls += 1;
+        state.recent.push_back(failure.is_some());
+        if state.recent.len() > self.window {
+            state.recent.pop_front();
+        }
+        let Some(kind) = failure else {
+            return;
+        };
+        *state.failures.entry(kind.to_string()).or_default() += 1;
+        let failed = state.recent.iter().filter(|failed| **failed).count();
+        let limit = self.max_error_rate * self.window as f64;
+        if state.tripped.is_none() && (failed as f64 > limit || failed >= self.window) {
+            state.tripped = Some(Unhealthy {
+                failed,
+                window: state.recent.len(),
+                max_error_rate_percent: (self.max_error_rate * 100.0).round() as u32,
+                failures: state.failures.clone(),
+                calls: state.calls,
+            });
+        }
+    }
+
+    /// Failures over the run so far by kind
+    pub fn failures(&self) -> BTreeMap<String, usize> {
+        self.state.lock().unwrap().failures.clone()
+    }
+}
+
+/// Kind of a failed request, from its error message: `quota`, `rate limit`, `server error`,
+/// `timeout`, `auth` or `other`, or `stream error` and `truncated stream` for a [`StreamError`].
+/// `quota` is a used up daily quota, see [`crate::quota`]
+pub fn classify(error: &anyhow::Error) -> &'static str {
+    match error.downcast_ref::<StreamError>() {
+        Some(StreamError::Interrupted(_)) => return "stream error",
+        Some(StreamError::Truncated(_)) => return "truncated stream",
+        None => {}
+    }
+    let message = format!("{:#}", error).to_lowercase();
+    let has = |needles: &[&str]| needles.iter().any(|needle| message.contains(needle));
+    if quota_exhausted(error) {
+        "quota"
+    } else if has(&[
+        "429",
+        "rate limit",
+        "rate_limit",
+        "too many requests",
+        "quota",
+    ]) {
+        "rate limit"
+    } else if has(&["timeout", "timed out"]) {
+        "timeout"
+    } else if has(&["401", "403", "unauthorized", "forbidden", "api key"]) {
+        "auth"
+    } else if has(&[
+        "500",
+        "502",
+        "503",
+        "504",
+        "529",
+        "unavailable",
+        "overloaded",
+    ]) {
+        "server error"
+    } else {
+        "other"
+    }
+}
+
+/// Whether a request failed because the provider refused its `max_tokens`, rather than for a
+/// reason a smaller limit wouldn't fix
+pub fn rejects_max_tokens(error: &anyhow::Error) -> bool {
+    let message = format!("{:#}", error).to_lowercase();
+    [
+        "max_tokens",
+        "max tokens",
+        "max_output_tokens",
+        "maxoutputtokens",
+        "max_completion_tokens",
+        "maximum number of tokens",
+    ]
+    .iter()
+    .any(|needle| message.contains(needle))
+}
@@ -0,0 +1,236 @@
+//! Edits an existing name list in place, replacing only the generated sections and leaving every
+//! other line, manual additions included, as it is. With
+//! [`crate::GenerationBuilder::markers`] each generated block's lines are wrapped in a pair of
+//! marker comments holding the block's path:
+//!
+//! ```text
+//!     ships = {
+//!         # namegen:begin NAME/ships
+//!         VEX,
+//!         # namegen:end NAME/ships
+//!         MY_OWN_SHIP,
+//!     }
+//! ```
+//!
+//! The next run swaps what is between the markers of the file for what is between the same
+//! markers of its output:
+//!
+//! ```
+//! use stellaris_name_gen::in_place::splice_generated;
+//! use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+//!
+//! let structure = "NAME = {\n    # Ship names\n    ships = {\n    }\n    # Fleet names\n    fleets = {\n    }\n}\n";
+//! let result = futures::executor::block_on(
+//!     Generation::builder()
+//!         .structure(structure)
+//!         .lore("")
+//!         .min_names(1)
+//!         .markers(true)
+//!         .generator(MockGenerator::new(["Vex", "Ember"]))
+//!         .cache(InMemoryCache::new())
+//!         .run(),
+//! )
+//! .unwrap();
+//! assert!(result.name_list.contains(
+//!     "    ships = {\n        # namegen:begin NAME/ships\n        VEX,\n        EMBER,\n        # namegen:end NAME/ships\n    }"
+//! ));
+//!
+//! let existing = "\
+//! ## Tuned by hand
+//! NAME = {
+//!     ships = {
+//!         ## namegen:begin NAME/ships
+//!         OLD_NAME,
+//!         ## namegen:end NAME/ships
+//!         MY_OWN_SHIP,
+//!     }
+//!     armies = {
+//!         ## namegen:begin NAME/armies
+//!         LEGION,
+//!         ## namegen:end NAME/armies
+//!     }
+//! }
+//! ";
+//! let spliced = splice_generated(existing, &result.name_list).unwrap();
+//! assert_eq!(
+//!     spliced.text,
+//!     "# Tuned by hand\nNAME = {\n    ships = {\n        # namegen:begin NAME/ships\n        VEX,\n        EMBER,\n        # namegen:end NAME/ships\n        MY_OWN_SHIP,\n    }\n    armies = {\n        # namegen:begin NAME/armies\n        LEGION,\n        # namegen:end NAME/armies\n    }\n}\n"
+//! );
+//! assert_eq!(spliced.replaced, ["NAME/ships"]);
+//! // Generated without a place in the file, and in the file without being generated
+//! assert_eq!(spliced.missing, ["NAME/fleets"]);
+//! assert_eq!(spliced.stale, ["NAME/armies"]);
+//!
+//! // A file without any marker was never written with them, and is left alone
+//! assert!(splice_generated("NAME = {\n}\n", &result.name_list).is_err());
+//! ```
+
+use crate::warning;
+use anyhow::{Context, Result};
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+/// Opens a generated section, followed by the block's path
+pub const BEGIN_MARKER: &str = "# namegen:begin ";
+/// Closes a generated section, followed by the block's path
+pub const END_MARKER: &str = "# namegen:end ";
+
+/// The lines between a pair of markers, by line index
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Section {
+    path: String,
+    /// Index of the begin marker's line
+    begin: usize,
+    /// Index of the end marker's line
+    end: usize,
+}
+
+/// Outcome of [`splice_generated`]
+#[derive(Debug, Clone, Default)]
+pub struct Spliced {
+    /// The existing file with its generated sections replaced
+    pub text: String,
+    /// Sections that were replaced, in file order
+    pub replaced: Vec<String>,
+    /// Generated sections the file has no markers for, left out
+    pub missing: Vec<String>,
+    /// Sections of the file the run no longer generates, left as they are
+    pub stale: Vec<String>,
+    /// Markers that could not be paired, ie a begin without its end
+    pub warnings: Vec<String>,
+}
+
+/// Pairs the markers of `text`. Unpaired markers are skipped with a warning
+fn sections(lines: &[&str], warnings: &mut Vec<String>) -> Vec<Section> {
+    let mut sections = Vec::new();
+    let mut open: Option<(String, usize)> = None;
+    for (idx, line) in lines.iter().enumerate() {
+        let line = line.trim();
+        if let Some(path) = line.strip_prefix(BEGIN_MARKER) {
+            if let Some((path, begin)) = open.take() {
+                warnings.push(format!(
+                    "line {}: '{}' has no end marker, skipped",
+                    begin + 1,
+                    path
+                ));
+            }
+            open = Some((path.trim().to_string(), idx));
+        } else if let Some(path) = line.strip_prefix(END_MARKER) {
+            let path = path.trim();
+            match open.take() {
+                Some((open_path, begin)) if open_path == path => sections.push(Section {
+                    path: open_path,
+                    begin,
+                    end: idx,
+                }),
+                other => {
+                    warnings.push(format!(
+                        "line {}: end marker of '{}' without its begin, skipped",
+                        idx + 1,
+                        path
+                    ));
+                    open = other;
+                }
+            }
+        }
+    }
+    if let Some((path, begin)) = open {
+        warnings.push(format!(
+            "line {}: '{}' has no end marker, skipped",
+            begin + 1,
+            path
+        ));
+    }
+    sections
+}
+
+/// Replaces every generated section of `existing` with the section of the same path in
+/// `generated`, a name list written with markers. Everything outside the markers, the marker
+/// lines included, is kept byte for byte. Fails if `existing` holds no marker at all
+pub fn splice_generated(existing: &str, generated: &str) -> Result<Spliced> {
+    let mut spliced = Spliced::default();
+    let generated_lines: Vec<&str> = generated.lines().collect();
+    let mut ignored = Vec::new();
+    let mut fresh: HashMap<String, &[&str]> = HashMap::new();
+    let mut order = Vec::new();
+    for section in sections(&generated_lines, &mut ignored) {
+        order.push(section.path.clone());
+        fresh.insert(
+            section.path,
+            &generated_lines[section.begin + 1..section.end],
+        );
+    }
+
+    let lines: Vec<&str> = existing.lines().collect();
+    let found = sections(&lines, &mut spliced.warnings);
+    if found.is_empty() && spliced.warnings.is_empty() {
+        anyhow::bail!(
+            "The name list holds no '{}' markers, write it once without editing in place",
+            BEGIN_MARKER.trim()
+        );
+    }
+    let mut out: Vec<&str> = Vec::with_capacity(lines.len());
+    let mut next = 0;
+    for section in &found {
+        let Some(names) = fresh.get(&section.path) else {
+            spliced.stale.push(section.path.clone());
+            continue;
+        };
+        if spliced.replaced.contains(&section.path) {
+            spliced.warnings.push(format!(
+                "line {}: '{}' appears twice, only the first is replaced",
+                section.begin + 1,
+                section.path
+            ));
+            continue;
+        }
+        out.extend(&lines[next..=section.begin]);
+        out.extend(names.iter());
+        next = section.end;
+        spliced.replaced.push(section.path.clone());
+    }
+    out.extend(&lines[next..]);
+    spliced.missing = order
+        .into_iter()
+        .filter(|path| !found.iter().any(|section| section.path == *path))
+        .collect();
+
+    spliced.text = out.join("\n");
+    if existing.ends_with('\n') {
+        spliced.text.push('\n');
+    }
+    Ok(spliced)
+}
+
+/// Splices `generated` into the name list at `path` with [`splice_generated`], returning the
+/// text to write back. Fails if a generated block has no markers in the file, as its names
+/// would be left out while its localisation keys are still written
+pub fn splice_file(path: &Path, generated: &str) -> Result<String> {
+    let existing =
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))?;
+    let spliced = splice_generated(&existing, generated)
+        .with_context(|| format!("Failed to edit {} in place", path.display()))?;
+    for warning in &spliced.warnings {
+        warning!("[In Place] {}: {}", path.display(), warning);
+    }
+    if !spliced.missing.is_empty() {
+        anyhow::bail!(
+            "{} has no markers for '{}', add them where the names belong",
+            path.display(),
+            spliced.missing.join("', '")
+        );
+    }
+    for section in &spliced.stale {
+        println!(
+            "[In Place] '{}' is no longer generated, left as it is",
+            section
+        );
+    }
+    println!(
+        "[In Place] Replaced {} generated sections of {}",
+        spliced.replaced.len(),
+        path.display()
+    );
+    Ok(spliced.text)
+}
@@ -0,0 +1,88 @@
+use crate::cache::BlockKey;
+use crate::lore::LoreProfiles;
+use anyhow::{Context, Result};
+
+/// A `{{generate: <theme>}}` placeholder inside an ordinary structure line
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Placeholder {
+    /// Byte range of the placeholder, braces included
+    start: usize,
+    end: usize,
+    pub(crate) theme: String,
+    /// Wrapped in quotes, so the value is substituted literally instead of as a localisation key
+    pub(crate) literal: bool,
+}
+
+/// A pass-through line holding placeholders, each filled by a single generated value
+#[derive(Debug, Clone)]
+pub(crate) struct InlineLine {
+    pub(crate) line: String,
+    /// 1-based line number in the structure file
+    pub(crate) line_no: usize,
+    pub(crate) path: Vec<String>,
+    pub(crate) prefix: String,
+    pub(crate) model: String,
+    pub(crate) lore: Option<String>,
+    /// `#@ key-style:` options of the enclosing block
+    pub(crate) key_style: Option<String>,
+    pub(crate) placeholders: Vec<Placeholder>,
+}
+
+impl InlineLine {
+    /// Key of the cached value of the `index`th placeholder, from the enclosing path and the position
+    pub(crate) fn cache_key(&self, index: usize, lore: &LoreProfiles) -> Result<BlockKey> {
+        let mut key = BlockKey::from_path(&self.path).as_str().to_string();
+        if !key.is_empty() {
+            key.push('_');
+        }
+        key.push_str(&format!("line{}_{}", self.line_no, index));
+        let key = BlockKey::from(key.as_str());
+        match &self.lore {
+            Some(profile) => lore
+                .cache_key(key, profile)
+                .with_context(|| format!("Invalid lore for file_structure.txt:{}", self.line_no)),
+            None => Ok(lore.default_key(key)),
+        }
+    }
+
+    /// The line with every placeholder replaced by its substitution, in placeholder order
+    pub(crate) fn render(&self, substitutions: &[String]) -> String {
+        let mut line = self.line.clone();
+        for (placeholder, value) in self.placeholders.iter().zip(substitutions).rev() {
+            line.replace_range(placeholder.start..placeholder.end, value);
+        }
+        line
+    }
+}
+
+/// Finds every `{{generate: <theme>}}` placeholder in a line
+pub(crate) fn parse_placeholders(line: &str) -> Result<Vec<Placeholder>> {
+    let mut placeholders = Vec::new();
+    let mut offset = 0;
+    while let Some(open) = line[offset..].find("{{") {
+        let start = offset + open;
+        let Some(close) = line[start..].find("}}") else {
+            anyhow::bail!("Unclosed placeholder '{}'", &line[start..]);
+        };
+        let end = start + close + 2;
+        let inner = line[start + 2..end - 2].trim();
+        let Some(theme) = inner.strip_prefix("generate:") else {
+            anyhow::bail!(
+                "Unknown placeholder '{}', expected {{{{generate: <theme>}}}}",
+                &line[start..end]
+            );
+        };
+        let theme = theme.trim();
+        if theme.is_empty() {
+            anyhow::bail!("Placeholder '{}' has no theme", &line[start..end]);
+        }
+        placeholders.push(Placeholder {
+            start,
+            end,
+            theme: theme.to_string(),
+            literal: line[..start].ends_with('"') && line[end..].starts_with('"'),
+        });
+        offset = end;
+    }
+    Ok(placeholders)
+}
@@ -0,0 +1,243 @@
+//! Startup check of the files a run reads. Every required input is checked at once, before the
+//! cache is opened, and the missing ones are listed together with what each is for, the least
+//! it must hold and the `init` command writing a starter for it:
+//!
+//! ```
+//! use stellaris_name_gen::inputs::{self, Input};
+//!
+//! let dir = std::env::temp_dir().join(format!("namegen-doc-inputs-{}", std::process::id()));
+//! let _ = std::fs::remove_dir_all(&dir);
+//! std::fs::create_dir_all(&dir).unwrap();
+//! let required = [
+//!     (Input::Structure, dir.join("file_structure.txt")),
+//!     (Input::Lore, dir.join("lore.txt")),
+//! ];
+//!
+//! // A fresh folder gets the plain init command
+//! let missing = inputs::preflight(&dir, &required).unwrap_err();
+//! assert_eq!(missing.missing.len(), 2);
+//! let message = missing.to_string();
+//! assert!(message.starts_with("[Inputs] 2 required files are missing"));
+//! assert!(message.contains("The structure of the name list"));
+//! assert!(message.contains("cargo run -- init` writes"));
+//!
+//! // With the lore already written, init goes to its own folder and only the structure is taken
+//! std::fs::write(dir.join("lore.txt"), "A proud seafaring people").unwrap();
+//! let missing = inputs::preflight(&dir, &required).unwrap_err();
+//! assert_eq!(missing.missing[0].input, Input::Structure);
+//! let message = missing.to_string();
+//! assert!(message.starts_with("[Inputs] A required file is missing"));
+//! assert!(!message.contains("The lore the model reads"));
+//! assert!(message.contains("would refuse to overwrite"));
+//! let starter = dir.join("starter").join("file_structure.txt");
+//! assert!(message.contains(&format!("{} -> ", starter.display())));
+//!
+//! // A missing lore profile has no starter to copy
+//! let profile = (Input::LoreProfile("rebels".to_string()), dir.join("rebels.txt"));
+//! let missing = inputs::preflight(&dir, &[profile]).unwrap_err();
+//! assert!(missing.to_string().contains("lore profile 'rebels'"));
+//! assert!(!missing.to_string().contains("init"));
+//!
+//! std::fs::write(dir.join("file_structure.txt"), "NAME = {\n}\n").unwrap();
+//! assert!(inputs::preflight(&dir, &required).is_ok());
+//!
+//! // Missing inputs exit apart from failed runs (1) and structures with nothing to generate (3)
+//! assert_eq!(inputs::EXIT_MISSING_INPUTS, 4);
+//! # std::fs::remove_dir_all(&dir).unwrap();
+//! ```
+
+use crate::scaffold::{self, Template};
+use std::fmt;
+use std::path::{Path, PathBuf};
+
+/// Exit code of a run stopped by missing inputs
+pub const EXIT_MISSING_INPUTS: i32 = 4;
+
+/// Folder `init` is pointed at when the project folder already holds some of its files
+const STARTER_DIR: &str = "starter";
+
+/// A file a run reads
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Input {
+    Manifest,
+    Structure,
+    /// `lore.txt` or a `--lore` file
+    Lore,
+    /// The file of a named `--lore-profile`
+    LoreProfile(String),
+}
+
+impl Input {
+    /// What the run reads the file for
+    pub fn purpose(&self) -> String {
+        match self {
+            Input::Manifest => {
+                "The whole project, blocks and settings, replacing the structure file".to_string()
+            }
+            Input::Structure => "The structure of the name list, the model names every block \
+                                 under a theme comment"
+                .to_string(),
+            Input::Lore => "The lore the model reads before naming anything".to_string(),
+            Input::LoreProfile(name) => {
+                format!("The lore of blocks with `# lore: {}`", name)
+            }
+        }
+    }
+
+    /// The least the file must hold
+    pub fn example(&self) -> &'static str {
+        match self {
+            Input::Manifest => {
+                "[blocks.NAME.children.ship_names.children.generic]\ntheme = \"Ship names of a proud seafaring empire\""
+            }
+            Input::Structure => {
+                "NAME = {\n    ship_names = {\n        # Ship names of a proud seafaring empire\n        generic = {\n        }\n    }\n}"
+            }
+            Input::Lore | Input::LoreProfile(_) => {
+                "A proud seafaring people who took to the stars, fond of short and sharp words."
+            }
+        }
+    }
+
+    /// File of the starter project `init` writes for it
+    fn starter(&self) -> Option<&'static str> {
+        match self {
+            Input::Structure => Some("file_structure.txt"),
+            Input::Lore => Some("lore.txt"),
+            Input::Manifest | Input::LoreProfile(_) => None,
+        }
+    }
+
+    fn label(&self) -> String {
+        match self {
+            Input::Manifest => "manifest".to_string(),
+            Input::Structure => "structure file".to_string(),
+            Input::Lore => "lore".to_string(),
+            Input::LoreProfile(name) => format!("lore profile '{}'", name),
+        }
+    }
+}
+
+/// A required input that doesn't exist
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingInput {
+    pub input: Input,
+    pub path: PathBuf,
+}
+
+/// Every missing input of a run, displayed as one message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MissingInputs {
+    pub missing: Vec<MissingInput>,
+    /// How `init` writes starters for them, `None` when it has none
+    pub hint: Option<String>,
+}
+
+impl fmt::Display for MissingInputs {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.missing.len() == 1 {
+            writeln!(f, "[Inputs] A required file is missing:")?;
+        } else {
+            writeln!(
+                f,
+                "[Inputs] {} required files are missing:",
+                self.missing.len()
+            )?;
+        }
+        for missing in &self.missing {
+            writeln!(f)?;
+            writeln!(
+                f,
+                "  {} ({})",
+                missing.path.display(),
+                missing.input.label()
+            )?;
+            writeln!(f, "    {}. At least:", missing.input.purpose())?;
+            for line in missing.input.example().lines() {
+                writeln!(f, "      {}", line)?;
+            }
+        }
+        if let Some(hint) = &self.hint {
+            writeln!(f)?;
+            writeln!(f, "{}", hint)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for MissingInputs {}
+
+/// Checks that every required input exists, `dir` being the project folder `init` would write
+/// into. Fails with all the missing ones at once
+pub fn preflight(dir: &Path, required: &[(Input, PathBuf)]) -> Result<(), MissingInputs> {
+    let missing: Vec<MissingInput> = required
+        .iter()
+        .filter(|(_, path)| !path.is_file())
+        .map(|(input, path)| MissingInput {
+            input: input.clone(),
+            path: path.clone(),
+        })
+        .collect();
+    if missing.is_empty() {
+        return Ok(());
+    }
+    let hint = init_hint(dir, &missing);
+    Err(MissingInputs { missing, hint })
+}
+
+/// The init command writing the missing starters, straight into `dir` when none of the files of
+/// the starter project exists there yet, since init never overwrites any
+fn init_hint(dir: &Path, missing: &[MissingInput]) -> Option<String> {
+    let starters: Vec<(&'static str, &Path)> = missing
+        .iter()
+        .filter_map(|missing| Some((missing.input.starter()?, missing.path.as_path())))
+        .collect();
+    if starters.is_empty() {
+        return None;
+    }
+    let untouched = scaffold::files(Template::Minimal)
+        .iter()
+        .all(|(file, _)| !dir.join(file).exists());
+    let in_place = starters
+        .iter()
+        .all(|(file, path)| in_dir(path, dir) && path.file_name() == Some(file.as_ref()));
+    if untouched && in_place {
+        return Some(format!(
+            "`cargo run -- init` writes a commented starter project{}, with every file above",
+            if dir == Path::new(".") {
+                String::new()
+            } else {
+                format!(" into {}", dir.display())
+            }
+        ));
+    }
+    let starter = if dir == Path::new(".") {
+        PathBuf::from(STARTER_DIR)
+    } else {
+        dir.join(STARTER_DIR)
+    };
+    let copies: Vec<String> = starters
+        .iter()
+        .map(|(file, path)| format!("  {} -> {}", starter.join(file).display(), path.display()))
+        .collect();
+    let reason = if untouched {
+        ""
+    } else {
+        "`cargo run -- init` would refuse to overwrite the files already there. "
+    };
+    Some(format!(
+        "{}Run `cargo run -- init --dir {}` and copy over:\n{}",
+        reason,
+        starter.display(),
+        copies.join("\n")
+    ))
+}
+
+/// Whether `path` sits right in `dir`, a bare file name sitting in `.`
+fn in_dir(path: &Path, dir: &Path) -> bool {
+    let parent = path
+        .parent()
+        .filter(|parent| !parent.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    parent == dir
+}
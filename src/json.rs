@@ -0,0 +1,233 @@
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+/// Basic struct of gen ai output
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub(crate) struct GenerativeAIOutput {
+    pub(crate) names: Vec<NameItem>,
+}
+
+/// A generated name, a bare string or an object shaped by a block's custom schema
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(untagged)]
+pub(crate) enum NameItem {
+    Plain(String),
+    Entry {
+        name: String,
+        /// Key stem used instead of the one derived from the name
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        key: Option<String>,
+        /// Any other schema field, ie a rarity tag, kept in the cache as generated
+        #[serde(flatten)]
+        extras: BTreeMap<String, serde_json::Value>,
+    },
+}
+
+impl NameItem {
+    pub(crate) fn name(&self) -> &str {
+        match self {
+            NameItem::Plain(name) | NameItem::Entry { name, .. } => name,
+        }
+    }
+
+    pub(crate) fn key(&self) -> Option<&str> {
+        match self {
+            NameItem::Plain(_) => None,
+            NameItem::Entry { key, .. } => key.as_deref(),
+        }
+    }
+}
+
+/// Checks that a custom schema still yields names: an object whose `names` property is an
+/// array, of strings or of objects with a `name`
+pub(crate) fn validate_schema(schema: &serde_json::Value) -> Result<()> {
+    let names = &schema["properties"]["names"];
+    if names["type"] != "array" {
+        anyhow::bail!("The schema must have a `names` array property");
+    }
+    let items = &names["items"];
+    if items["type"] == "object" && items["properties"]["name"].is_null() {
+        anyhow::bail!("Objects in `names` must have a `name` property");
+    }
+    Ok(())
+}
+
+/// Fixes common JSON issues of a truncated or sloppy stream, ie unbalanced quotes and brackets
+pub(crate) fn repair_json(raw: &str) -> String {
+    let mut fixed = raw.to_string();
+    // Keep content starting at first '{'
+    if let Some(pos) = fixed.find('{') {
+        fixed = fixed[pos..].to_string();
+    }
+    // Ensure quotes are balanced
+    if !fixed.matches('"').count().is_multiple_of(2) {
+        fixed.push('"');
+    }
+    // Remove empty trailing string entries (incomplete " element)
+    {
+        let trimmed = fixed.trim_end();
+        // if ends with two quotes indicating an empty string
+        if trimmed.ends_with("\"\"") {
+            // drop the empty "" and any leading comma
+            if let Some(pos) = fixed.rfind(",\"\"") {
+                fixed.replace_range(pos..pos + 3, "");
+            }
+        }
+    }
+    // Remove trailing comma after last quoted string
+    if let Some(last_q) = fixed.rfind('"') {
+        let mut idx = last_q + 1;
+        while idx < fixed.len() && fixed.as_bytes()[idx].is_ascii_whitespace() {
+            idx += 1;
+        }
+        if idx < fixed.len() && fixed.as_bytes()[idx] == b',' {
+            fixed.remove(idx);
+        }
+    }
+    // Balance brackets and braces
+    let ob = fixed.matches('[').count();
+    let cb = fixed.matches(']').count();
+    if cb < ob {
+        fixed.push_str(&"]".repeat(ob - cb));
+    }
+    let obc = fixed.matches('{').count();
+    let cbc = fixed.matches('}').count();
+    if cbc < obc {
+        fixed.push_str(&"}".repeat(obc - cbc));
+    }
+    fixed
+}
+
+/// Whether the JSON object `raw` opens ends before it is closed, ie an answer stopped by the
+/// token limit. Sloppy but complete answers, ie with a trailing comma or prose around the object,
+/// are not cut off
+pub(crate) fn is_cut_off(raw: &str) -> bool {
+    let Some(start) = raw.find('{') else {
+        return false;
+    };
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    for c in raw[start..].chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_string => escaped = true,
+            '"' => in_string = !in_string,
+            _ if in_string => {}
+            '{' | '[' => depth += 1,
+            '}' | ']' => {
+                depth = depth.saturating_sub(1);
+                if depth == 0 {
+                    return false;
+                }
+            }
+            _ => {}
+        }
+    }
+    true
+}
+
+/// A line of a one-name-per-line answer without list markers, numbering or quotes
+pub(crate) fn clean_line(line: &str) -> String {
+    let line = line.trim().trim_start_matches(['-', '*', '•']);
+    // Drop numbering such as `1.` or `12)`
+    let digits = line.len() - line.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+    let line = match line[digits..].chars().next() {
+        Some('.' | ')') if digits > 0 => &line[digits + 1..],
+        _ => line,
+    };
+    line.trim().trim_matches(['"', ',']).trim().to_string()
+}
+
+/// Parses a one-name-per-line answer, tolerating list markers and quotes.
+/// Models that answer in JSON anyway are parsed as such
+pub(crate) fn parse_lines(raw: &str) -> Result<Vec<String>> {
+    if let Ok(names) = parse_names(raw) {
+        return Ok(names);
+    }
+    let names: Vec<String> = raw
+        .lines()
+        .map(clean_line)
+        .filter(|line| !line.is_empty())
+        .collect();
+    if names.is_empty() {
+        anyhow::bail!("Answer holds no names");
+    }
+    Ok(names)
+}
+
+/// Gen ai output of an inline placeholder
+#[derive(Debug, Clone, Deserialize)]
+struct ValueOutput {
+    value: String,
+}
+
+/// Parses a single value answer with the same leniency as names, rejecting empty values
+pub(crate) fn parse_value(raw: &str) -> Result<String> {
+    let out: ValueOutput = serde_json::from_str(raw)
+        .or_else(|_| serde_json::from_str(&repair_json(raw)))
+        .context("Value is not valid JSON, even after repair")?;
+    let value = out.value.trim();
+    if value.is_empty() {
+        anyhow::bail!("Value is empty");
+    }
+    Ok(value.to_string())
+}
+
+/// Gen ai output of a scoring pass
+#[derive(Debug, Clone, Deserialize)]
+struct ScoreOutput {
+    scores: BTreeMap<String, f64>,
+}
+
+/// Parses a scoring answer with the same leniency as names, rounding and clamping scores to 1–5
+pub(crate) fn parse_scores(raw: &str) -> Result<BTreeMap<String, u8>> {
+    let out: ScoreOutput = serde_json::from_str(raw)
+        .or_else(|_| serde_json::from_str(&repair_json(raw)))
+        .context("Scores are not valid JSON, even after repair")?;
+    Ok(out
+        .scores
+        .into_iter()
+        .map(|(name, score)| (name.trim().to_string(), score.round().clamp(1.0, 5.0) as u8))
+        .collect())
+}
+
+/// Gen ai output of a translation request
+#[derive(Debug, Clone, Deserialize)]
+struct TranslationOutput {
+    translations: BTreeMap<String, String>,
+}
+
+/// Parses a translation answer with the same leniency as names, dropping empty values
+pub(crate) fn parse_translations(raw: &str) -> Result<BTreeMap<String, String>> {
+    let out: TranslationOutput = serde_json::from_str(raw)
+        .or_else(|_| serde_json::from_str(&repair_json(raw)))
+        .context("Translations are not valid JSON, even after repair")?;
+    Ok(out
+        .translations
+        .into_iter()
+        .map(|(key, value)| (key.trim().to_string(), value.trim().to_string()))
+        .filter(|(_, value)| !value.is_empty())
+        .collect())
+}
+
+/// Parses cached output as-is, falling back to the repaired form
+pub(crate) fn parse_names(raw: &str) -> Result<Vec<String>> {
+    let out: GenerativeAIOutput = serde_json::from_str(raw)
+        .or_else(|_| serde_json::from_str(&repair_json(raw)))
+        .context("Cache is not valid JSON, even after repair")?;
+    Ok(out
+        .names
+        .iter()
+        .map(|item| item.name().to_string())
+        .collect())
+}
+
+/// Parses names with the fields of a custom schema, same leniency as [`parse_names`]
+pub(crate) fn parse_items(raw: &str) -> Result<Vec<NameItem>> {
+    let out: GenerativeAIOutput = serde_json::from_str(raw)
+        .or_else(|_| serde_json::from_str(&repair_json(raw)))
+        .context("Cache is not valid JSON, even after repair")?;
+    Ok(out.names)
+}
@@ -0,0 +1,215 @@
+//! Keys that survive regeneration. Save games refer to leader names by key, so a name that is
+//! emitted again must keep the key it had, even where new neighbours would now number or hash
+//! it differently. The map, `keymap.json`, records the key of every name per block. Names it
+//! holds keep their key, new names get keys no other name holds, and the keys of removed names
+//! are retired, never handed to another name unless `--reuse-retired-keys`:
+//!
+//! ```
+//! use stellaris_name_gen::keymap::KeyMap;
+//! use stellaris_name_gen::keys::HashKeys;
+//! use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator, RunResult};
+//!
+//! let run = |names: &[&str], keymap: &KeyMap, reuse: bool| {
+//!     futures::executor::block_on(
+//!         Generation::builder()
+//!             .structure("NAME = {\n    # Leader names\n    leaders = {\n    }\n}\n")
+//!             .lore("")
+//!             .min_names(1)
+//!             .generator(MockGenerator::new(names.to_vec()))
+//!             .hash_keys(HashKeys::Fallback)
+//!             .key_map(keymap.clone())
+//!             .reuse_retired_keys(reuse)
+//!             .cache(InMemoryCache::new())
+//!             .run(),
+//!     )
+//!     .unwrap()
+//! };
+//! let key = |result: &RunResult, name: &str| -> String {
+//!     let (key, _) = result.localisation.iter().find(|(_, value)| value == name).unwrap();
+//!     key.clone()
+//! };
+//!
+//! let mut keymap = KeyMap::default();
+//! let first = run(&["Vex?", "Ember", "Dawn"], &keymap, false);
+//! assert_eq!(key(&first, "Vex?"), "VEX_");
+//! let changes = keymap.update(&first);
+//! assert_eq!((changes.kept, changes.added, changes.retired), (0, 3, 0));
+//!
+//! // Without the map the new `Vex!` would take `VEX_` and push `Vex?` onto a hash
+//! let second = run(&["Vex!", "Vex?", "Ember", "Cinder"], &keymap, false);
+//! assert_eq!(key(&second, "Vex?"), "VEX_");
+//! assert_eq!(key(&second, "Ember"), "EMBER");
+//! assert_ne!(key(&second, "Vex!"), "VEX_");
+//! let changes = keymap.update(&second);
+//! assert_eq!((changes.kept, changes.added, changes.retired), (2, 2, 1));
+//! assert!(keymap.is_retired("DAWN"));
+//!
+//! // `Dawn` is gone, so another name spelled the same way keys elsewhere, unless reuse is asked
+//! let third = run(&["Vex?", "Ember", "DAWN"], &keymap, false);
+//! assert_ne!(key(&third, "DAWN"), "DAWN");
+//! let third = run(&["Vex?", "Ember", "DAWN"], &keymap, true);
+//! assert_eq!(key(&third, "DAWN"), "DAWN");
+//! // A removed name coming back gets its own key again
+//! let back = run(&["Vex?", "Ember", "Dawn"], &keymap, false);
+//! assert_eq!(key(&back, "Dawn"), "DAWN");
+//!
+//! let reloaded = KeyMap::parse(&keymap.to_json().unwrap()).unwrap();
+//! assert_eq!(reloaded.key_for("NAME/leaders", "Vex?"), Some("VEX_"));
+//! assert!(reloaded.claimed("EMBER", "Embers", false));
+//! assert!(!reloaded.claimed("EMBER", "Ember", false));
+//! ```
+
+use crate::pipeline::RunResult;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// The name a retired key belonged to
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Retired {
+    pub block: String,
+    pub name: String,
+}
+
+/// Key of every emitted name per block path, and the keys of names no longer emitted
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct KeyMap {
+    #[serde(default)]
+    blocks: BTreeMap<String, BTreeMap<String, String>>,
+    #[serde(default)]
+    retired: BTreeMap<String, Retired>,
+    /// Name holding each key, in any block
+    #[serde(skip)]
+    owners: HashMap<String, String>,
+}
+
+/// What [`KeyMap::update`] changed, in names
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct KeyMapChanges {
+    /// Names emitted under the key the map held for them
+    pub kept: usize,
+    /// Names new to their block, or keyed anew
+    pub added: usize,
+    /// Keys no block emits any more
+    pub retired: usize,
+}
+
+impl KeyMap {
+    /// Reads a map written by [`save`](Self::save), empty if the file doesn't exist yet
+    pub fn load(path: &Path) -> Result<Self> {
+        if !path.exists() {
+            return Ok(Self::default());
+        }
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read key map {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("Failed to parse key map {}", path.display()))
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        let mut map: Self = serde_json::from_str(text)?;
+        map.index();
+        Ok(map)
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self).context("Failed to serialize the key map")
+    }
+
+    /// Writes the map through a temporary file, so it is replaced whole or not at all
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let mut temp = path.as_os_str().to_owned();
+        temp.push(".tmp");
+        let temp = PathBuf::from(temp);
+        fs::write(&temp, self.to_json()?)
+            .with_context(|| format!("Failed to write {}", temp.display()))?;
+        fs::rename(&temp, path).with_context(|| format!("Failed to replace {}", path.display()))
+    }
+
+    /// Names the map holds a key for
+    pub fn len(&self) -> usize {
+        self.blocks.values().map(BTreeMap::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.blocks.values().all(BTreeMap::is_empty)
+    }
+
+    /// The key a name of a block was emitted under, or had before it was removed
+    pub fn key_for(&self, block: &str, name: &str) -> Option<&str> {
+        self.blocks
+            .get(block)
+            .and_then(|names| names.get(name))
+            .or_else(|| {
+                self.retired
+                    .iter()
+                    .find(|(_, retired)| retired.block == block && retired.name == name)
+                    .map(|(key, _)| key)
+            })
+            .map(String::as_str)
+    }
+
+    /// Whether `key` belongs to another name than `name`, or to a removed name unless
+    /// `reuse_retired`. The same name shares its key across blocks
+    pub fn claimed(&self, key: &str, name: &str, reuse_retired: bool) -> bool {
+        self.owners.get(key).is_some_and(|owner| owner != name)
+            || (!reuse_retired && self.is_retired(key))
+    }
+
+    pub fn is_retired(&self, key: &str) -> bool {
+        self.retired.contains_key(key)
+    }
+
+    /// Records the keys the blocks of a run emitted. Names a block no longer emits, or emits
+    /// under another key, retire their old key once no block emits it. Blocks the run didn't
+    /// generate keep theirs
+    pub fn update(&mut self, result: &RunResult) -> KeyMapChanges {
+        let mut changes = KeyMapChanges::default();
+        let mut removed = Vec::new();
+        for block in &result.report.blocks {
+            let path = block.path.join("/");
+            let names: BTreeMap<String, String> = block
+                .entries
+                .iter()
+                .map(|(key, name)| (name.clone(), key.clone()))
+                .collect();
+            let previous = self
+                .blocks
+                .insert(path.clone(), names.clone())
+                .unwrap_or_default();
+            for (name, key) in &names {
+                if previous.get(name) == Some(key) {
+                    changes.kept += 1;
+                } else {
+                    changes.added += 1;
+                }
+                self.retired.remove(key);
+            }
+            for (name, key) in previous {
+                if names.get(&name) != Some(&key) {
+                    removed.push((key, path.clone(), name));
+                }
+            }
+        }
+        self.index();
+        for (key, block, name) in removed {
+            if !self.owners.contains_key(&key) && !self.retired.contains_key(&key) {
+                self.retired.insert(key, Retired { block, name });
+                changes.retired += 1;
+            }
+        }
+        changes
+    }
+
+    fn index(&mut self) {
+        self.owners.clear();
+        for names in self.blocks.values() {
+            for (name, key) in names {
+                self.owners
+                    .entry(key.clone())
+                    .or_insert_with(|| name.clone());
+            }
+        }
+    }
+}
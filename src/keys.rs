@@ -0,0 +1,538 @@
+//! Localisation key derivation, configurable to match the conventions of an existing mod.
+//!
+//! ```
+//! use stellaris_name_gen::keys::KeyStyle;
+//!
+//! // The default style, ie `ARK_SAINT_VEX`
+//! assert_eq!(KeyStyle::default().key("ARK_", "Saint Vex"), "ARK_SAINT_VEX");
+//!
+//! let dotted = KeyStyle::default()
+//!     .with_options("separator=. case=lower prefix=none")
+//!     .unwrap();
+//! assert_eq!(dotted.key("ARK_", "Saint Vex"), "saint.vex");
+//! let squashed = dotted.with_options("spaces=remove prefix=end").unwrap();
+//! assert_eq!(squashed.key("ark", "Saint Vex"), "saintvex.ark");
+//! // The prefix follows the case too, only `case=preserve` keeps it as written
+//! let upper = dotted.with_options("case=upper prefix=start").unwrap();
+//! assert_eq!(upper.key("ark", "Saint Vex"), "ARK.SAINT.VEX");
+//! let preserved = dotted.with_options("case=preserve prefix=start").unwrap();
+//! assert_eq!(preserved.key("Ark", "Saint Vex"), "Ark.Saint.Vex");
+//!
+//! // Names without a single usable character fall back to a stable hash of the name
+//! let fallback = KeyStyle::default().with_options("hash=fallback").unwrap();
+//! assert_eq!(fallback.key("ARK_", "Saint Vex"), "ARK_SAINT_VEX");
+//! let hashed = fallback.key("ARK_", "★★★");
+//! assert_eq!(hashed.len(), "ARK_".len() + 8);
+//! assert_eq!(hashed, fallback.key("ARK_", "★★★"));
+//! assert_ne!(hashed, fallback.key("ARK_", "☆☆☆"));
+//! // Or every key is one, for those who don't read them
+//! let always = KeyStyle::default().with_options("hash=always case=lower").unwrap();
+//! assert_eq!(always.key("ARK_", "Saint Vex"), format!("ark_{}", &always.key("", "Saint Vex")));
+//! assert!(always.key("", "Saint Vex").chars().all(|c| c.is_ascii_hexdigit() && !c.is_ascii_uppercase()));
+//!
+//! // Keys longer than `max-len` bytes are cut and end in a hash of the whole key instead
+//! let capped = KeyStyle::default().with_options("max-len=24").unwrap();
+//! assert_eq!(capped.key("ARK_", "Saint Vex"), "ARK_SAINT_VEX");
+//! let long = capped.key("DEEP_SPACE_RESEARCH_STATION_", "Saint Vex of the Burning Choir");
+//! assert_eq!(long.len(), 24);
+//! assert!(long.starts_with("DEEP_SPACE_RESE_"));
+//! assert_eq!(long, capped.key("DEEP_SPACE_RESEARCH_STATION_", "Saint Vex of the Burning Choir"));
+//! assert_ne!(long, capped.key("DEEP_SPACE_RESEARCH_STATION_", "Saint Vex of the Burning Chord"));
+//! // Numbered keys stay within the limit too
+//! assert_eq!(capped.numbered(&long, 2).len(), 24);
+//! assert!(KeyStyle::default().with_options("max-len=6").is_err());
+//! ```
+//!
+//! Keys of a previously published mod are reused for names whose value matches exactly, so
+//! saves referencing them keep working:
+//!
+//! ```
+//! use stellaris_name_gen::keys::KeyCompatMap;
+//! use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+//!
+//! let old = KeyCompatMap::parse("l_english:\n leader.vex:0 \"Vex\"\n leader.old:0 \"Retired\"\n");
+//! assert_eq!(old.key_for("Vex"), Some("leader.vex"));
+//! assert_eq!(old.key_for("vex"), None);
+//!
+//! let result = futures::executor::block_on(
+//!     Generation::builder()
+//!         .structure("NAME = {\n    ## Leader names\n    leaders = {\n    }\n}\n")
+//!         .lore("")
+//!         .generator(MockGenerator::new(["Vex", "Ember"]))
+//!         .key_compat(old)
+//!         .cache(InMemoryCache::new())
+//!         .run(),
+//! )
+//! .unwrap();
+//! assert_eq!(
+//!     result.localisation,
+//!     vec![
+//!         // A hit keeps the old key, a miss derives a new one
+//!         ("leader.vex".to_string(), "Vex".to_string()),
+//!         ("EMBER".to_string(), "Ember".to_string()),
+//!     ]
+//! );
+//! ```
+
+use crate::hash::digest;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::Path;
+
+/// Hex digits of a name's digest in a hashed key, and ending a shortened one
+const HASH_LEN: usize = 8;
+
+/// Sanitizes name into a valid localization key fragment
+pub fn sanitize_key(name: &str) -> String {
+    name.chars()
+        .map(|c| match c {
+            ' ' | '-' | '\'' | '!' | '"' => '_',
+            c if c.is_ascii_alphanumeric() => c.to_ascii_uppercase(),
+            _ => '_',
+        })
+        .collect()
+}
+
+/// Key prefix derived from a block's path, used by `# prefix: auto`. Keys that hold no letter,
+/// ie weighted groups such as `3`, are joined to their parent's key so the prefix stays a
+/// valid key fragment. Quotes around a key are dropped:
+///
+/// ```
+/// use stellaris_name_gen::keys::block_prefix;
+///
+/// let prefix = |path: &[&str]| {
+///     let path: Vec<String> = path.iter().map(|key| key.to_string()).collect();
+///     block_prefix(&path)
+/// };
+/// assert_eq!(prefix(&["NAME", "pc_desert"]), "PC_DESERT_");
+/// assert_eq!(prefix(&["NAME", "ship_names", "3"]), "SHIP_NAMES_3_");
+/// assert_eq!(prefix(&["NAME", "my.mod.block"]), "MY_MOD_BLOCK_");
+/// assert_eq!(prefix(&["NAME", "\"deep space\""]), "DEEP_SPACE_");
+/// ```
+///
+/// The name list keeps every key exactly as written:
+///
+/// ```
+/// use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+///
+/// let structure = "NAME = {\n    # prefix: auto\n    ship_names = {\n        # Ship names\n        3 = {\n        }\n    }\n    # Station names\n    # prefix: auto\n    \"deep space\" = {\n    }\n}\n";
+/// let result = futures::executor::block_on(
+///     Generation::builder()
+///         .structure(structure)
+///         .lore("")
+///         .generator(MockGenerator::new(["Vex"]))
+///         .cache(InMemoryCache::new())
+///         .run(),
+/// )
+/// .unwrap();
+/// assert!(result.name_list.contains("\n        3 = {\n            SHIP_NAMES_VEX,\n"));
+/// assert!(result.name_list.contains("\n    \"deep space\" = {\n        DEEP_SPACE_VEX,\n"));
+/// ```
+pub fn block_prefix(path: &[String]) -> String {
+    let mut parts = Vec::new();
+    for key in path.iter().rev() {
+        let part = sanitize_key(key.trim_matches('"'));
+        let part = part.trim_matches('_').to_string();
+        let lettered = part.chars().any(|c| c.is_ascii_alphabetic());
+        if !part.is_empty() {
+            parts.push(part);
+        }
+        if lettered {
+            break;
+        }
+    }
+    parts.reverse();
+    format!("{}_", parts.join("_"))
+}
+
+/// Letter case of derived keys
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum KeyCase {
+    #[default]
+    Upper,
+    Lower,
+    /// Keep the case of the name
+    Preserve,
+}
+
+/// Where a block's prefix goes in its keys
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PrefixPlacement {
+    #[default]
+    Start,
+    End,
+    /// Drop the prefix
+    None,
+}
+
+/// What spaces in a name turn into
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum SpaceMode {
+    #[default]
+    Separator,
+    Remove,
+}
+
+/// When keys are a hash of the name rather than spelled out
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum HashKeys {
+    /// Keys are always spelled out, distinct names with the same key are numbered or dropped
+    #[default]
+    Off,
+    /// Names leaving nothing to spell out, and distinct names of a block sharing a key, are hashed
+    Fallback,
+    /// Every key is hashed
+    Always,
+}
+
+/// How keys are derived from names, the `[key-style]` config table or a `#@ key-style:` directive
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct KeyStyle {
+    /// Joins the prefix and words, and replaces any other character that is not a letter or digit
+    pub separator: String,
+    pub case: KeyCase,
+    pub prefix: PrefixPlacement,
+    pub spaces: SpaceMode,
+    pub hash: HashKeys,
+    /// Most bytes a key may have, longer keys are cut and end in a hash of the whole key
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_len: Option<usize>,
+}
+
+impl Default for KeyStyle {
+    fn default() -> Self {
+        Self {
+            separator: "_".to_string(),
+            case: KeyCase::default(),
+            prefix: PrefixPlacement::default(),
+            spaces: SpaceMode::default(),
+            hash: HashKeys::default(),
+            max_len: None,
+        }
+    }
+}
+
+impl KeyStyle {
+    /// Applies the options of a `#@ key-style:` directive on top of this style, ie
+    /// `separator=. case=lower prefix=none spaces=remove`
+    pub fn with_options(&self, options: &str) -> Result<Self> {
+        let mut style = self.clone();
+        for option in options.split_whitespace() {
+            let (key, value) = option
+                .split_once('=')
+                .with_context(|| format!("Expected key=value, got '{}'", option))?;
+            match key {
+                "separator" => style.separator = value.trim_matches('"').to_string(),
+                "case" => {
+                    style.case = match value {
+                        "upper" => KeyCase::Upper,
+                        "lower" => KeyCase::Lower,
+                        "preserve" => KeyCase::Preserve,
+                        other => {
+                            anyhow::bail!("Unknown case '{}', expected upper|lower|preserve", other)
+                        }
+                    }
+                }
+                "prefix" => {
+                    style.prefix = match value {
+                        "start" => PrefixPlacement::Start,
+                        "end" => PrefixPlacement::End,
+                        "none" => PrefixPlacement::None,
+                        other => anyhow::bail!(
+                            "Unknown prefix placement '{}', expected start|end|none",
+                            other
+                        ),
+                    }
+                }
+                "spaces" => {
+                    style.spaces = match value {
+                        "separator" => SpaceMode::Separator,
+                        "remove" => SpaceMode::Remove,
+                        other => anyhow::bail!(
+                            "Unknown spaces mode '{}', expected separator|remove",
+                            other
+                        ),
+                    }
+                }
+                "hash" => {
+                    style.hash = match value {
+                        "off" => HashKeys::Off,
+                        "fallback" => HashKeys::Fallback,
+                        "always" => HashKeys::Always,
+                        other => anyhow::bail!(
+                            "Unknown hash mode '{}', expected off|fallback|always",
+                            other
+                        ),
+                    }
+                }
+                "max-len" => {
+                    let max = value
+                        .parse()
+                        .with_context(|| format!("Invalid max-len '{}'", value))?;
+                    style.max_len = Some(max);
+                }
+                other => anyhow::bail!(
+                    "Unknown key-style option '{}', expected separator|case|prefix|spaces|hash|max-len",
+                    other
+                ),
+            }
+        }
+        style.check()?;
+        Ok(style)
+    }
+
+    /// Fails when `max_len` leaves no room for the hash ending a shortened key
+    pub fn check(&self) -> Result<()> {
+        if let Some(max) = self.max_len
+            && max < HASH_LEN
+        {
+            anyhow::bail!(
+                "max-len {} is shorter than the {} digit hash ending a shortened key",
+                max,
+                HASH_LEN
+            );
+        }
+        Ok(())
+    }
+
+    /// Derives the key of `name` in a block with `prefix`. The prefix loses its trailing
+    /// separator and takes the style's case, it is only used as written under `case=preserve`
+    pub fn key(&self, prefix: &str, name: &str) -> String {
+        let stem = self.fragment(name);
+        let spelled_out = stem.chars().any(|c| c.is_ascii_alphanumeric());
+        match self.hash {
+            HashKeys::Always => self.hashed(prefix, name),
+            HashKeys::Fallback if !spelled_out => self.hashed(prefix, name),
+            _ => self.bounded(self.join(prefix, stem)),
+        }
+    }
+
+    /// The key of `name` made of the first 8 hex digits of its digest, stable across runs
+    pub fn hashed(&self, prefix: &str, name: &str) -> String {
+        self.bounded(self.join(prefix, self.hash_of(name)))
+    }
+
+    /// The first 8 hex digits of the digest of `text` in the style's case
+    fn hash_of(&self, text: &str) -> String {
+        let hash = &digest(text)[..HASH_LEN];
+        match self.case {
+            KeyCase::Upper => hash.to_ascii_uppercase(),
+            KeyCase::Lower | KeyCase::Preserve => hash.to_string(),
+        }
+    }
+
+    /// Cuts a key longer than `max_len` bytes and ends it in a hash of the whole key, so keys
+    /// that differ anywhere stay distinct once shortened
+    fn bounded(&self, key: String) -> String {
+        let Some(max) = self.max_len else {
+            return key;
+        };
+        if key.len() <= max {
+            return key;
+        }
+        let hash = self.hash_of(&key);
+        let mut end = max.saturating_sub(HASH_LEN + self.separator.len());
+        while !key.is_char_boundary(end) {
+            end -= 1;
+        }
+        let mut head = &key[..end];
+        if !self.separator.is_empty() {
+            head = head.trim_end_matches(self.separator.as_str());
+        }
+        if head.is_empty() {
+            hash
+        } else {
+            format!("{}{}{}", head, self.separator, hash)
+        }
+    }
+
+    /// Places the prefix around a key's stem
+    fn join(&self, prefix: &str, stem: String) -> String {
+        let prefix = prefix
+            .trim_end_matches('_')
+            .trim_end_matches(self.separator.as_str());
+        // A prefix written in another case than the keys would leave them mixed case
+        let prefix = match self.case {
+            KeyCase::Lower => prefix.to_ascii_lowercase(),
+            KeyCase::Upper => prefix.to_ascii_uppercase(),
+            KeyCase::Preserve => prefix.to_string(),
+        };
+        if prefix.is_empty() {
+            return stem;
+        }
+        match self.prefix {
+            PrefixPlacement::Start => format!("{}{}{}", prefix, self.separator, stem),
+            PrefixPlacement::End => format!("{}{}{}", stem, self.separator, prefix),
+            PrefixPlacement::None => stem,
+        }
+    }
+
+    /// Appends the `n`th numbered suffix to a key
+    pub fn numbered(&self, key: &str, n: usize) -> String {
+        self.bounded(format!("{}{}{}", key, self.separator, n))
+    }
+
+    /// A name or prefix with every character mapped to the style. Quotes and backslashes are
+    /// left out, so `The "Unbroken" Oath` keys like `The Unbroken Oath`
+    fn fragment(&self, text: &str) -> String {
+        let mut out = String::new();
+        for c in text.chars() {
+            match c {
+                ' ' if self.spaces == SpaceMode::Remove => {}
+                '"' | '\\' => {}
+                c if c.is_ascii_alphanumeric() => match self.case {
+                    KeyCase::Upper => out.push(c.to_ascii_uppercase()),
+                    KeyCase::Lower => out.push(c.to_ascii_lowercase()),
+                    KeyCase::Preserve => out.push(c),
+                },
+                _ => out.push_str(&self.separator),
+            }
+        }
+        out
+    }
+}
+
+/// Keys of a previous version of a mod by their exact value, reused instead of deriving new ones
+#[derive(Debug, Clone, Default)]
+pub struct KeyCompatMap {
+    keys: HashMap<String, String>,
+}
+
+impl KeyCompatMap {
+    /// Reads a key list in localisation form, ie the previous mod's `_l_english.yml`
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read key list {}", path.display()))?;
+        Ok(Self::parse(&text))
+    }
+
+    /// Parses `key:0 "value"` lines, the first key of a value wins
+    pub fn parse(text: &str) -> Self {
+        let mut keys = HashMap::new();
+        for line in text.lines() {
+            let line = line.trim().trim_start_matches('\u{feff}');
+            if line.starts_with('#') {
+                continue;
+            }
+            let Some((key, rest)) = line.split_once(':') else {
+                continue;
+            };
+            let rest = rest.trim_start_matches(|c: char| c.is_ascii_digit()).trim();
+            let (Some(open), Some(close)) = (rest.find('"'), rest.rfind('"')) else {
+                continue;
+            };
+            if close > open {
+                keys.entry(rest[open + 1..close].to_string())
+                    .or_insert_with(|| key.trim().to_string());
+            }
+        }
+        Self { keys }
+    }
+
+    /// The previous key of a value, matched exactly
+    pub fn key_for(&self, value: &str) -> Option<&str> {
+        self.keys.get(value).map(String::as_str)
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+}
+
+/// Names pinned to keys per block, emitted verbatim ahead of the block's generated names. Read
+/// from a TOML file with a table per block path:
+///
+/// ```toml
+/// ["NAME/ship_names/military"]
+/// VOID_FLAGSHIP = "Unyielding \"Iron\" Crown"
+/// ```
+///
+/// Pinned keys are used as written, never derived or renamed away from vanilla keys. A
+/// generated name landing on a pinned key is dropped in favor of the pin:
+///
+/// ```
+/// use stellaris_name_gen::keys::Pins;
+/// use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+///
+/// let pins = Pins::parse("[\"NAME/leaders\"]\nlegacy_vex = 'Vex \"the Elder\"'\nEMBER = 'Ember of Old'\n").unwrap();
+/// assert_eq!(pins.len(), 2);
+/// let result = futures::executor::block_on(
+///     Generation::builder()
+///         .structure("NAME = {\n    ## Leader names\n    leaders = {\n    }\n}\n")
+///         .lore("")
+///         .generator(MockGenerator::new(["Ember", "Ash"]))
+///         .pins(pins)
+///         .cache(InMemoryCache::new())
+///         .run(),
+/// )
+/// .unwrap();
+/// assert_eq!(
+///     result.name_list,
+///     "NAME = {\n    leaders = {\n        EMBER,\n        legacy_vex,\n        ASH,\n    }\n}"
+/// );
+/// // Values are still escaped
+/// assert!(result.localisation_yml().contains("    legacy_vex:0 \"Vex \\\"the Elder\\\"\"\n"));
+/// assert!(result.localisation_yml().contains("    EMBER:0 \"Ember of Old\"\n"));
+/// assert_eq!(result.report.blocks[0].dropped["pinned"], 1);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Pins {
+    /// Pinned (key, name) pairs by block path, ie `NAME/leaders`
+    blocks: BTreeMap<String, Vec<(String, String)>>,
+}
+
+impl Pins {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read pins {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("Invalid pins {}", path.display()))
+    }
+
+    /// Parses a table of `key = "name"` pairs per block path
+    pub fn parse(text: &str) -> Result<Self> {
+        let blocks: BTreeMap<String, BTreeMap<String, String>> = toml::from_str(text)?;
+        Ok(Self {
+            blocks: blocks
+                .into_iter()
+                .map(|(path, pins)| (path, pins.into_iter().collect()))
+                .collect(),
+        })
+    }
+
+    /// Pinned (key, name) pairs of the block at `path`, sorted by key
+    pub fn for_block(&self, path: &str) -> &[(String, String)] {
+        self.blocks.get(path).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// Whether any block pins `key`
+    pub fn pins_key(&self, key: &str) -> bool {
+        self.blocks
+            .values()
+            .any(|pins| pins.iter().any(|(pinned, _)| pinned == key))
+    }
+
+    /// Block paths holding pins
+    pub fn paths(&self) -> impl Iterator<Item = &str> {
+        self.blocks.keys().map(String::as_str)
+    }
+
+    /// Number of pinned names over every block
+    pub fn len(&self) -> usize {
+        self.blocks.values().map(Vec::len).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
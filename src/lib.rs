@@ -0,0 +1,612 @@
+//! Generates Stellaris name lists and their localisation from a structure file and some lore.
+//!
+//! The binary reads `file_structure.txt` and `lore.txt` from disk, but the whole pipeline also
+//! runs from in-memory strings with any [`NameGenerator`] and [`NameCache`]:
+//!
+//! ```
+//! use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+//!
+//! let structure = r#"
+//! NAME = {
+//!     ## prefix: VOID_
+//!     ship_names = {
+//!         ## Warship names
+//!         ## weight = 50
+//!         military = {
+//!         }
+//!     }
+//! }
+//! "#;
+//! let result = futures::executor::block_on(
+//!     Generation::builder()
+//!         .structure(structure)
+//!         .lore("An ancient empire adrift in the void.")
+//!         .generator(MockGenerator::new(["Silent Dawn", "Ember"]))
+//!         .cache(InMemoryCache::new())
+//!         .run(),
+//! )
+//! .unwrap();
+//!
+//! // Prefixes propagate down to the generated keys, kv inserts and names share the block's indent
+//! assert!(result.name_list.contains(
+//!     "        military = {\n            weight = 50\n            VOID_SILENT_DAWN,\n"
+//! ));
+//! assert_eq!(
+//!     result.localisation,
+//!     vec![
+//!         ("VOID_SILENT_DAWN".to_string(), "Silent Dawn".to_string()),
+//!         ("VOID_EMBER".to_string(), "Ember".to_string()),
+//!     ]
+//! );
+//! ```
+//!
+//! Blocks sharing a prefix produce the same keys for the same names. The first block wins the
+//! localisation and the collision is reported:
+//!
+//! ```
+//! use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+//!
+//! let structure = r#"
+//! NAME = {
+//!     ## prefix: SHARED_
+//!     ## Admiral names
+//!     admirals = {
+//!     }
+//!     ## prefix: SHARED_
+//!     ## General names
+//!     generals = {
+//!     }
+//! }
+//! "#;
+//! let result = futures::executor::block_on(
+//!     Generation::builder()
+//!         .structure(structure)
+//!         .lore("")
+//!         .generator(MockGenerator::new(["Vex"]))
+//!         .cache(InMemoryCache::new())
+//!         .run(),
+//! )
+//! .unwrap();
+//!
+//! assert_eq!(result.localisation.len(), 1);
+//! assert_eq!(result.report.collisions, vec!["SHARED_VEX".to_string()]);
+//! ```
+//!
+//! Consecutive theme comments form a single multi-line theme, a blank `#` starts over:
+//!
+//! ```
+//! use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+//!
+//! let structure = r#"
+//! NAME = {
+//!     ## Old note about this block
+//!     ##
+//!     ## Names of deep space survey vessels,
+//!     ## evoking patience and distance.
+//!     ## weight = 50
+//!     survey = {
+//!     }
+//! }
+//! "#;
+//! let result = futures::executor::block_on(
+//!     Generation::builder()
+//!         .structure(structure)
+//!         .lore("")
+//!         .generator(MockGenerator::new(["Vex"]))
+//!         .cache(InMemoryCache::new())
+//!         .run(),
+//! )
+//! .unwrap();
+//!
+//! assert_eq!(
+//!     result.report.blocks[0].theme,
+//!     "Names of deep space survey vessels,\nevoking patience and distance."
+//! );
+//! ```
+//!
+//! Comments may also open the block they describe. They win over the comments preceding it,
+//! unless a child block follows them, which they then describe as usual:
+//!
+//! ```
+//! use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+//!
+//! let structure = r#"
+//! NAME = {
+//!     ## Warship names
+//!     military = {
+//!         ## prefix: WAR_
+//!     }
+//!     ## count: 5
+//!     ## Civilian ship names
+//!     civilian = {
+//!         ## Trade ship names
+//!         ## count: 1
+//!         ## weight = 10
+//!     }
+//! }
+//! "#;
+//! let result = futures::executor::block_on(
+//!     Generation::builder()
+//!         .structure(structure)
+//!         .lore("")
+//!         .min_names(1)
+//!         .generator(MockGenerator::new(["Vex", "Ember"]))
+//!         .cache(InMemoryCache::new())
+//!         .run(),
+//! )
+//! .unwrap();
+//!
+//! let blocks = &result.report.blocks;
+//! assert_eq!((blocks[0].theme.as_str(), blocks[0].entries[0].0.as_str()), ("Warship names", "WAR_VEX"));
+//! assert_eq!((blocks[1].theme.as_str(), blocks[1].names), ("Trade ship names", 1));
+//! assert!(result.name_list.contains("    civilian = {\n        weight = 10\n        VEX,\n    }"));
+//! ```
+//!
+//! A directive may carry a note of its own after a `#`, which is not part of its value:
+//!
+//! ```
+//! use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+//!
+//! let structure = "NAME = {\n    # Ship names\n    # avoid-initials: X, Q # steer away from over-used letters\n    # sort=alpha # alpha, length or shuffle\n    ships = {\n    }\n}\n";
+//! let result = futures::executor::block_on(
+//!     Generation::builder()
+//!         .structure(structure)
+//!         .lore("")
+//!         .min_names(1)
+//!         .generator(MockGenerator::new(["Vex", "Quill", "Ember", "Ash"]))
+//!         .cache(InMemoryCache::new())
+//!         .run(),
+//! )
+//! .unwrap();
+//!
+//! // Quill is dropped for its initial, the rest sorted
+//! let values: Vec<&str> = result.localisation.iter().map(|(_, value)| value.as_str()).collect();
+//! assert_eq!(values, ["Ash", "Ember", "Vex"]);
+//! ```
+//!
+//! A `# prefix:` replaces the inherited prefix, `+PART` appends `PART_` to it and `-` clears it
+//! for the block and its descendants. The prefix a block was cached under is stored with its
+//! names, so changing it generates them again:
+//!
+//! ```
+//! use stellaris_name_gen::{BlockKey, FsCache, Generation, MockGenerator, NameCache};
+//!
+//! let dir = std::env::temp_dir().join(format!("namegen-doc-prefix-{}", std::process::id()));
+//! let _ = std::fs::remove_dir_all(&dir);
+//! let structure = r#"
+//! NAME = {
+//!     ## prefix: MYMOD_
+//!     ships = {
+//!         ## prefix: +SHIP
+//!         fleet = {
+//!             ## Military ship names
+//!             ## prefix: +MILITARY
+//!             military = {
+//!             }
+//!             ## Civilian ship names
+//!             ## prefix: CIV_
+//!             civilian = {
+//!             }
+//!             ## prefix: -
+//!             science = {
+//!                 ## Survey ship names
+//!                 ## prefix: +SURVEY
+//!                 survey = {
+//!                 }
+//!             }
+//!         }
+//!     }
+//! }
+//! "#;
+//! let run = |structure: &str| {
+//!     futures::executor::block_on(
+//!         Generation::builder()
+//!             .structure(structure)
+//!             .lore("")
+//!             .min_names(1)
+//!             .generator(MockGenerator::new(["Vex"]))
+//!             .cache(FsCache::new(&dir))
+//!             .run(),
+//!     )
+//!     .unwrap()
+//! };
+//! let result = run(structure);
+//! let keys: Vec<&str> = result.localisation.iter().map(|(key, _)| key.as_str()).collect();
+//! assert_eq!(keys, ["MYMOD_SHIP_MILITARY_VEX", "CIV_VEX", "SURVEY_VEX"]);
+//! let block = FsCache::new(&dir).get(&BlockKey::from("NAME_ships_fleet_military")).unwrap();
+//! assert_eq!(block.prefix.as_deref(), Some("MYMOD_SHIP_MILITARY_"));
+//!
+//! let result = run(&structure.replace("+MILITARY", "+NAVY"));
+//! let blocks = &result.report.blocks;
+//! assert_eq!((blocks[0].prefix.as_str(), blocks[0].cached), ("MYMOD_SHIP_NAVY_", false));
+//! assert!(blocks[1].cached && blocks[2].cached);
+//! # std::fs::remove_dir_all(&dir).unwrap();
+//! ```
+//!
+//! Generated lines and inserts follow the indentation of the structure, whether it uses two
+//! spaces, four or tabs, and runs of blank lines around the generated names collapse into one.
+//! Hand-written lines, blank ones included, are written as they are:
+//!
+//! ```
+//! use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+//!
+//! let fixture = |i: &str| {
+//!     format!("NAME = {{\n{i}randomized = yes\n{i}# Ship names\n{i}# weight = 10\n{i}ships = {{\n\n\n{i}{i}randomized = no\n\n{i}{i}\n{i}}}\n {i}odd_line = yes\n}}\n")
+//! };
+//! let snapshot = |i: &str| {
+//!     format!("NAME = {{\n{i}randomized = yes\n{i}ships = {{\n{i}{i}weight = 10\n\n\n{i}{i}randomized = no\n\n{i}{i}VEX,\n{i}{i}EMBER,\n{i}}}\n {i}odd_line = yes\n}}")
+//! };
+//! for unit in ["  ", "    ", "\t"] {
+//!     let result = futures::executor::block_on(
+//!         Generation::builder()
+//!             .structure(fixture(unit))
+//!             .lore("")
+//!             .min_names(1)
+//!             .generator(MockGenerator::new(["Vex", "Ember"]))
+//!             .cache(InMemoryCache::new())
+//!             .run(),
+//!     )
+//!     .unwrap();
+//!     assert_eq!(result.name_list, snapshot(unit));
+//! }
+//! ```
+//!
+//! Blocks holding hand-written entries only generate with `# augment-inline`, which treats the
+//! entries as seeds and appends new names after them without repeating any. Entries are listed
+//! one per line or separated by `,` or `;`:
+//!
+//! ```
+//! use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+//!
+//! let structure = r#"
+//! NAME = {
+//!     ## Admiral names
+//!     ## augment-inline
+//!     admirals = {
+//!         VEX; "Ember"
+//!         ASH
+//!     }
+//! }
+//! "#;
+//! let result = futures::executor::block_on(
+//!     Generation::builder()
+//!         .structure(structure)
+//!         .lore("")
+//!         .generator(MockGenerator::new(["Vex", "ember", "Ash", "Cinder"]))
+//!         .cache(InMemoryCache::new())
+//!         .run(),
+//! )
+//! .unwrap();
+//!
+//! assert!(result.name_list.contains(
+//!     "        VEX; \"Ember\"\n        ASH\n        CINDER,\n    }"
+//! ));
+//! assert_eq!(result.report.blocks[0].dropped["seeds"], 3);
+//! ```
+//!
+//! An `#@ insert-here` line puts the new names at its place rather than after the entries.
+//! The anchor is removed, or kept after the names with `#@ insert-here: keep`:
+//!
+//! ```
+//! use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+//!
+//! let run = |structure: &str| {
+//!     futures::executor::block_on(
+//!         Generation::builder()
+//!             .structure(structure)
+//!             .lore("")
+//!             .min_names(1)
+//!             .generator(MockGenerator::new(["Cinder", "Ash"]))
+//!             .cache(InMemoryCache::new())
+//!             .run(),
+//!     )
+//! };
+//! let structure = r#"
+//! NAME = {
+//!     ## Admiral names
+//!     ## augment-inline
+//!     admirals = {
+//!         VEX,
+//!         #@ insert-here
+//!         "Ember",
+//!         weight = 5
+//!     }
+//! }
+//! "#;
+//! let result = run(structure).unwrap();
+//! assert!(result.name_list.contains(
+//!     "    admirals = {\n        VEX,\n        CINDER,\n        ASH,\n        \"Ember\",\n        weight = 5\n    }"
+//! ));
+//!
+//! let kept = run(&structure.replace("#@ insert-here", "#@ insert-here: keep")).unwrap();
+//! assert!(kept.name_list.contains("        ASH,\n        #@ insert-here: keep\n        \"Ember\","));
+//!
+//! // One anchor per block
+//! let twice = structure.replace("\"Ember\",", "#@ insert-here\n        \"Ember\",");
+//! let error = format!("{:#}", run(&twice).err().unwrap());
+//! assert!(error.contains("file_structure.txt:8: 'NAME/admirals' already has an insert-here on line 7"));
+//! ```
+//!
+//! An answer cut off mid-stream keeps the names it holds. One more request asks for names
+//! around them, and the two are merged:
+//!
+//! ```
+//! use futures::future::BoxFuture;
+//! use std::sync::atomic::{AtomicUsize, Ordering};
+//! use stellaris_name_gen::{Generation, GenerationRequest, InMemoryCache, NameGenerator};
+//!
+//! #[derive(Default)]
+//! struct CutOff(AtomicUsize);
+//! impl NameGenerator for CutOff {
+//!     fn generate<'a>(&'a self, request: &'a GenerationRequest) -> BoxFuture<'a, anyhow::Result<String>> {
+//!         let answer = match self.0.fetch_add(1, Ordering::SeqCst) {
+//!             0 => r#"{"names": ["Vex", "Ember", "Cin"#.to_string(),
+//!             _ => {
+//!                 assert!(request.prompt.contains("already taken, come up with different ones: Vex, Ember, Cin"));
+//!                 r#"{"names": ["ember", "Dusk"]}"#.to_string()
+//!             }
+//!         };
+//!         Box::pin(async move { Ok(answer) })
+//!     }
+//! }
+//!
+//! let result = futures::executor::block_on(
+//!     Generation::builder()
+//!         .structure("NAME = {\n    ## Ship names\n    ships = {\n    }\n}\n")
+//!         .lore("")
+//!         .generator(CutOff::default())
+//!         .cache(InMemoryCache::new())
+//!         .run(),
+//! )
+//! .unwrap();
+//! let block = &result.report.blocks[0];
+//! assert_eq!(block.salvaged, 3);
+//! let names: Vec<&str> = block.entries.iter().map(|(_, name)| name.as_str()).collect();
+//! assert_eq!(names, ["Vex", "Ember", "Cin", "Dusk"]);
+//!
+//! // A complete answer needing repair for other reasons, ie a trailing comma, is not cut off
+//! struct Sloppy;
+//! impl NameGenerator for Sloppy {
+//!     fn generate<'a>(&'a self, _: &'a GenerationRequest) -> BoxFuture<'a, anyhow::Result<String>> {
+//!         Box::pin(async { Ok(r#"Sure! {"names": ["Vex", "Ember",]}"#.to_string()) })
+//!     }
+//! }
+//! let result = futures::executor::block_on(
+//!     Generation::builder()
+//!         .structure("NAME = {\n    # Ship names\n    ships = {\n    }\n}\n")
+//!         .lore("")
+//!         .min_names(1)
+//!         .generator(Sloppy)
+//!         .cache(InMemoryCache::new())
+//!         .run(),
+//! )
+//! .unwrap();
+//! assert_eq!(result.report.blocks[0].salvaged, 0);
+//! assert_eq!(result.report.blocks[0].names, 2);
+//! ```
+//!
+//! `#@ decorate: "Outpost {name}"` wraps every name of a block, its key following the decorated
+//! form. `{ord}` numbers the names of a `randomized = no` block by their position:
+//!
+//! ```
+//! use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+//!
+//! let run = |structure: &str| {
+//!     futures::executor::block_on(
+//!         Generation::builder()
+//!             .structure(structure)
+//!             .lore("")
+//!             .min_names(1)
+//!             .generator(MockGenerator::new(["Vex", "Ember"]))
+//!             .cache(InMemoryCache::new())
+//!             .run(),
+//!     )
+//! };
+//! let result = run("NAME = {\n    # Outpost names\n    #@ decorate: \"Outpost {name}\" # the key follows\n    outposts = {\n    }\n}\n").unwrap();
+//! assert_eq!(result.localisation[0], ("OUTPOST_VEX".to_string(), "Outpost Vex".to_string()));
+//! assert!(result.name_list.contains("        OUTPOST_VEX,\n        OUTPOST_EMBER,\n"));
+//!
+//! let result = run("NAME = {\n    # Patrol names\n    #@ decorate: \"{name} Patrol {ord}\"\n    patrols = {\n        randomized = no\n    }\n}\n").unwrap();
+//! let values: Vec<&str> = result.localisation.iter().map(|(_, value)| value.as_str()).collect();
+//! assert_eq!(values, ["Vex Patrol 1", "Ember Patrol 2"]);
+//!
+//! // Only a sequential block has positions to number
+//! let error = run("NAME = {\n    # Patrol names\n    #@ decorate: \"{name} {ord}\"\n    patrols = {\n    }\n}\n").unwrap_err();
+//! assert!(format!("{:#}", error).contains("requires a sequential block"));
+//!
+//! // Text after the closing quote is a mistake, not part of the template
+//! let error = run("NAME = {\n    # Outpost names\n    #@ decorate: \"Outpost {name}\" station\n    outposts = {\n    }\n}\n").unwrap_err();
+//! assert!(format!("{:#}", error).contains("Unexpected 'station' after the decoration"));
+//! ```
+//!
+//! `# era: ancient|industrial|stellar` asks for names of that period, on top of the theme. Each
+//! era of a block is cached on its own, so switching eras back and forth never regenerates:
+//!
+//! ```
+//! use futures::future::BoxFuture;
+//! use stellaris_name_gen::{Generation, GenerationRequest, InMemoryCache, NameGenerator};
+//!
+//! struct Periods;
+//!
+//! impl NameGenerator for Periods {
+//!     fn generate<'a>(&'a self, request: &'a GenerationRequest) -> BoxFuture<'a, anyhow::Result<String>> {
+//!         let name = if request.prompt.contains("industrial era") { "Foundry" } else { "Vex" };
+//!         Box::pin(async move { Ok(format!(r#"{{"names": ["{}"]}}"#, name)) })
+//!     }
+//! }
+//!
+//! let result = futures::executor::block_on(
+//!     Generation::builder()
+//!         .structure("NAME = {\n    # Ship names\n    ships = {\n    }\n    # Ship names\n    # era: industrial\n    old_ships = {\n    }\n}\n")
+//!         .lore("")
+//!         .generator(Periods)
+//!         .cache(InMemoryCache::new())
+//!         .run(),
+//! )
+//! .unwrap();
+//! assert_eq!(result.report.blocks[0].era, None);
+//! assert_eq!(result.report.blocks[1].era.as_deref(), Some("industrial"));
+//! let names: Vec<&str> = result.localisation.iter().map(|(_, name)| name.as_str()).collect();
+//! assert_eq!(names, ["Vex", "Foundry"]);
+//! ```
+//!
+//! The game consumes the names of `randomized = no` blocks in order, so `#@ first:` puts names
+//! at the front of such a block, whether or not the model came up with them. The rest follow in
+//! the block's sort order, and the report keeps the first ten for a check:
+//!
+//! ```
+//! use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+//!
+//! let run = |structure: &str| {
+//!     futures::executor::block_on(
+//!         Generation::builder()
+//!             .structure(structure)
+//!             .lore("")
+//!             .generator(MockGenerator::new(["Ember", "vex", "Ash"]))
+//!             .cache(InMemoryCache::new())
+//!             .run(),
+//!     )
+//! };
+//! let result = run("NAME = {\n    # Fleet names\n    #@ first: \"Home Fleet\", \"Vex\"\n    # sort: alpha\n    fleets = {\n        randomized = no\n    }\n}\n").unwrap();
+//! assert_eq!(result.report.blocks[0].order, ["Home Fleet", "Vex", "Ash", "Ember"]);
+//! assert!(result.name_list.contains("randomized = no\n        HOME_FLEET,\n        VEX,\n        ASH,"));
+//!
+//! // Listing names first in a block the game shuffles anyway is a mistake
+//! let error = run("NAME = {\n    # Fleet names\n    #@ first: Vex\n    fleets = {\n    }\n}\n").unwrap_err();
+//! assert!(error.to_string().contains("add randomized = no"));
+//! ```
+//!
+//! `# if-generated: key = value` inserts a line like `# key = value` does, but only into blocks
+//! that end up with names, so a block left empty carries no `weight`:
+//!
+//! ```
+//! use futures::future::BoxFuture;
+//! use stellaris_name_gen::{Generation, GenerationRequest, InMemoryCache, NameGenerator};
+//!
+//! struct Picky;
+//!
+//! impl NameGenerator for Picky {
+//!     fn generate<'a>(&'a self, request: &'a GenerationRequest) -> BoxFuture<'a, anyhow::Result<String>> {
+//!         let answer = if request.theme == "Ship names" { r#"{"names": ["Vex"]}"# } else { r#"{"names": []}"# };
+//!         Box::pin(async move { Ok(answer.to_string()) })
+//!     }
+//! }
+//!
+//! let result = futures::executor::block_on(
+//!     Generation::builder()
+//!         .structure("NAME = {\n    # Ship names\n    # randomized = no\n    # if-generated: weight=10\n    ships = {\n    }\n    # Fleet names\n    # randomized = no\n    # if-generated: weight = 10\n    fleets = {\n    }\n}\n")
+//!         .lore("")
+//!         .min_names(0)
+//!         .generator(Picky)
+//!         .cache(InMemoryCache::new())
+//!         .run(),
+//! )
+//! .unwrap();
+//! assert_eq!(
+//!     result.name_list,
+//!     "NAME = {\n    ships = {\n        randomized = no\n        weight = 10\n        VEX,\n    }\n    fleets = {\n        randomized = no\n    }\n}"
+//! );
+//! ```
+//!
+//! `# alias:` moves the names cached under a block's old path to its new one, so renaming a
+//! block, or one of its ancestors, costs no generation:
+//!
+//! ```
+//! use stellaris_name_gen::{BlockKey, CachedBlock, Generation, InMemoryCache, MockGenerator, NameCache};
+//!
+//! let mut cache = InMemoryCache::new();
+//! let mut put = |key: &str, raw: &str| cache.put(&BlockKey::from(key), CachedBlock::new(raw)).unwrap();
+//! put("NAME_old_ships", r#"{"names": ["Vex"]}"#);
+//! put("OLD_fleets", r#"{"names": ["Ember"]}"#);
+//! let structure = "NAME = {\n    # Ship names\n    # alias: NAME/older_ships, NAME/old_ships\n    ships = {\n    }\n}\n# alias: OLD\nNEW = {\n    # Fleet names\n    fleets = {\n    }\n}\n";
+//! let result = futures::executor::block_on(
+//!     Generation::builder()
+//!         .structure(structure)
+//!         .lore("")
+//!         .min_names(1)
+//!         .generator(MockGenerator::new(["Cinder"]))
+//!         .cache(cache)
+//!         .run(),
+//! )
+//! .unwrap();
+//! let names: Vec<&str> = result.localisation.iter().map(|(_, name)| name.as_str()).collect();
+//! assert_eq!(names, ["Vex", "Ember"]);
+//! assert_eq!(result.report.aliased["NAME/ships"], "NAME/old_ships");
+//! assert_eq!(result.report.aliased["NEW/fleets"], "OLD/fleets");
+//! ```
+
+pub mod abbreviations;
+pub mod ascii;
+pub mod budget;
+pub mod cache;
+pub mod casing;
+pub mod changelog;
+pub mod charset;
+pub mod clusters;
+pub mod compare;
+pub mod compose;
+pub mod config;
+pub mod console;
+pub mod determinism;
+mod directives;
+mod dispatch;
+pub mod distribution;
+pub mod draft;
+pub mod estimate;
+pub mod euphony;
+pub mod flavor;
+pub mod generator;
+mod hash;
+pub mod health;
+pub mod in_place;
+mod inline;
+pub mod inputs;
+mod json;
+pub mod keymap;
+pub mod keys;
+pub mod loc_split;
+pub mod lore;
+pub mod manifest;
+pub mod merge;
+pub mod near_forms;
+pub mod normalize;
+pub mod ordinals;
+pub mod output;
+pub mod package;
+pub mod pipeline;
+pub mod plan;
+mod prompt;
+pub mod provenance;
+pub mod providers;
+pub mod prune;
+pub mod quota;
+pub mod quoting;
+pub mod rarity;
+pub mod replay;
+pub mod scaffold;
+pub mod semantic;
+pub mod snapshot;
+pub mod species;
+pub mod stream;
+mod structure;
+pub mod titles;
+pub mod translate;
+pub mod unique;
+pub mod vanilla;
+pub mod warnings;
+
+pub use abbreviations::Abbreviations;
+pub use cache::{BlockKey, CachedBlock, FsCache, InMemoryCache, NameCache, SqliteCache};
+pub use config::Config;
+pub use generator::{
+    GenAiGenerator, GenerationRequest, MockGenerator, NameGenerator, ResponseFormat, StreamError,
+    StreamEvents, StreamStatus, TokenUsage,
+};
+pub use lore::LoreProfiles;
+pub use output::{
+    LocalisationWriter, MarkdownWriter, ModWriter, NameListWriter, OutputWriter, ReportWriter,
+    ReverseLookupWriter, SqliteWriter,
+};
+pub use pipeline::{BlockReport, Generation, GenerationBuilder, KeyConflict, Report, RunResult};
+pub use unique::UniqueScope;
+pub use vanilla::VanillaKeys;
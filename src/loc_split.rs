@@ -0,0 +1,120 @@
+//! Splits the localisation over several yml files, so a name list of thousands of keys stays
+//! reviewable. `--loc-split category` writes a file per top-level block of the name list, ie
+//! `name_list_ships_l_english.yml`, and `block` a file per generated block. Every key goes to the
+//! file of the first block that localised it, keys of no block (inline placeholders) stay in the
+//! unsplit file. Together the files hold exactly the keys of the single file:
+//!
+//! ```
+//! use std::collections::HashSet;
+//! use stellaris_name_gen::loc_split::{LocSplit, split_entries};
+//! use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+//!
+//! let structure = "NAME = {\n    ships = {\n        # Warship names\n        # prefix: WAR_\n        military = {\n        }\n        # Trade ship names\n        # prefix: TRADE_\n        civilian = {\n        }\n    }\n    # Admiral names\n    # prefix: ADM_\n    characters = {\n    }\n}\n";
+//! let result = futures::executor::block_on(
+//!     Generation::builder()
+//!         .structure(structure)
+//!         .lore("")
+//!         .min_names(1)
+//!         .generator(MockGenerator::new(["Vex", "Ember"]))
+//!         .cache(InMemoryCache::new())
+//!         .run(),
+//! )
+//! .unwrap();
+//!
+//! let categories = split_entries(&result, LocSplit::Category, &result.localisation);
+//! let parts: Vec<&str> = categories.keys().map(String::as_str).collect();
+//! assert_eq!(parts, ["characters", "ships"]);
+//! let blocks = split_entries(&result, LocSplit::Block, &result.localisation);
+//! let parts: Vec<&str> = blocks.keys().map(String::as_str).collect();
+//! assert_eq!(parts, ["characters", "ships_civilian", "ships_military"]);
+//!
+//! // No key is in two files, and none is lost
+//! for split in [categories, blocks] {
+//!     let keys: Vec<&String> = split.values().flatten().map(|(key, _)| key).collect();
+//!     assert_eq!(keys.iter().collect::<HashSet<_>>().len(), keys.len());
+//!     assert_eq!(keys.len(), result.localisation.len());
+//! }
+//! let single = split_entries(&result, LocSplit::Single, &result.localisation);
+//! assert_eq!(single[""], result.localisation);
+//! ```
+
+use crate::keys::sanitize_key;
+use crate::pipeline::RunResult;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+
+/// How the localisation is spread over files
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum LocSplit {
+    /// Every key in one file
+    #[default]
+    Single,
+    /// A file per top-level block below the name list's root
+    Category,
+    /// A file per generated block
+    Block,
+}
+
+impl LocSplit {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            LocSplit::Single => "single",
+            LocSplit::Category => "category",
+            LocSplit::Block => "block",
+        }
+    }
+
+    /// Part of the file name holding the keys of the block at `path`, lowercased. Titles from
+    /// the `[titles]` table share a `titles` file
+    fn part(&self, path: &[String]) -> String {
+        let part = match (self, path) {
+            (LocSplit::Single, _) | (_, []) => return String::new(),
+            (_, [root, ..]) if root == "titles" => root.clone(),
+            (_, [root]) => root.clone(),
+            (LocSplit::Category, [_, top, ..]) => top.clone(),
+            (LocSplit::Block, [_, rest @ ..]) => rest.join("_"),
+        };
+        sanitize_key(part.trim_matches('"')).to_lowercase()
+    }
+}
+
+/// File name stem of a part, ie `name_list_ships` for the part `ships` of `name_list`
+pub fn part_stem(stem: &str, part: &str) -> String {
+    if part.is_empty() {
+        stem.to_string()
+    } else {
+        format!("{}_{}", stem, part)
+    }
+}
+
+/// Spreads `entries`, the English localisation or a translation of it, over the files of
+/// `split` by part, the unsplit file under `""`. Entries keep their order within a file. Empty
+/// files are left out, but the single file is always there
+pub fn split_entries(
+    result: &RunResult,
+    split: LocSplit,
+    entries: &[(String, String)],
+) -> BTreeMap<String, Vec<(String, String)>> {
+    // The first block emitting a key localised it, later ones collided
+    let mut parts: HashMap<&str, String> = HashMap::new();
+    for block in &result.report.blocks {
+        for (key, _) in &block.entries {
+            parts
+                .entry(key.as_str())
+                .or_insert_with(|| split.part(&block.path));
+        }
+    }
+    let mut files: BTreeMap<String, Vec<(String, String)>> = BTreeMap::new();
+    for (key, value) in entries {
+        let part = parts.get(key.as_str()).cloned().unwrap_or_default();
+        files
+            .entry(part)
+            .or_default()
+            .push((key.clone(), value.clone()));
+    }
+    if split == LocSplit::Single || files.is_empty() {
+        files.entry(String::new()).or_default();
+    }
+    files
+}
@@ -0,0 +1,170 @@
+//! Lore documents: the run's default lore, optionally assembled from several files, and named
+//! profiles selected per block.
+//!
+//! ```
+//! use stellaris_name_gen::lore;
+//!
+//! let dir = std::env::temp_dir().join("namegen-lore-doctest");
+//! std::fs::create_dir_all(&dir).unwrap();
+//! std::fs::write(dir.join("history.md"), "The Ark left a dying world.\n").unwrap();
+//! std::fs::write(dir.join("navy.md"), "Its fleet is small but old.").unwrap();
+//!
+//! let files = [dir.join("navy.md"), dir.join("history.md")];
+//! let combined = lore::concat_files(&files).unwrap();
+//! // Files keep the given order, each under a header naming it
+//! assert_eq!(
+//!     combined,
+//!     "## navy.md\nIts fleet is small but old.\n\n## history.md\nThe Ark left a dying world.\n"
+//! );
+//! assert_eq!(combined, lore::concat_files(&files).unwrap());
+//!
+//! // A missing file fails before anything is read
+//! let err = lore::concat_files(&[dir.join("history.md"), dir.join("religion.md")]).unwrap_err();
+//! assert!(err.to_string().contains("religion.md"));
+//! ```
+//!
+//! Empty lore makes for names without any grounding, so it is refused unless allowed. Allowed
+//! empty lore drops the lore from the prompts, which ask the model to invent freely instead:
+//!
+//! ```
+//! use stellaris_name_gen::lore;
+//!
+//! assert!(lore::check_lore("The Ark left a dying world.", false).is_ok());
+//! let err = lore::check_lore(" \n\t\n", false).unwrap_err();
+//! assert!(err.to_string().contains("--allow-empty-lore"));
+//! assert!(lore::check_lore("", true).is_ok());
+//! ```
+
+use crate::cache::BlockKey;
+use crate::hash::digest;
+use anyhow::{Context, Result};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Named lore documents that blocks select with a `# lore: <profile>` directive
+#[derive(Debug, Clone, Default)]
+pub struct LoreProfiles {
+    profiles: BTreeMap<String, String>,
+    /// Digest of the default lore when it takes part in cache keys
+    default_digest: Option<String>,
+}
+
+impl LoreProfiles {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds or replaces a profile
+    pub fn insert(&mut self, name: impl Into<String>, lore: impl Into<String>) {
+        self.profiles.insert(name.into(), lore.into());
+    }
+
+    /// Reads every profile from disk, later entries replace earlier ones of the same name
+    pub fn load<'a>(paths: impl IntoIterator<Item = (&'a String, &'a PathBuf)>) -> Result<Self> {
+        let mut profiles = Self::new();
+        for (name, path) in paths {
+            let lore = fs::read_to_string(path).with_context(|| {
+                format!(
+                    "Failed to read lore profile '{}' ({})",
+                    name,
+                    path.display()
+                )
+            })?;
+            profiles.insert(name.clone(), lore);
+        }
+        Ok(profiles)
+    }
+
+    /// Lore of a profile, erroring on names that were never registered
+    pub fn get(&self, name: &str) -> Result<&str> {
+        self.profiles
+            .get(name)
+            .map(String::as_str)
+            .with_context(|| {
+                let known: Vec<&str> = self.profiles.keys().map(String::as_str).collect();
+                format!(
+                    "Unknown lore profile '{}', known profiles: [{}]",
+                    name,
+                    known.join(", ")
+                )
+            })
+    }
+
+    /// Suffixes the cache keys of blocks on the default lore with its digest, so editing the
+    /// lore regenerates them. Used for lore assembled with [`concat_files`]
+    pub fn key_default_lore(&mut self, lore: &str) {
+        self.default_digest = Some(digest(lore));
+    }
+
+    /// Digest of every profile's lore, by profile name
+    pub fn digests(&self) -> BTreeMap<String, String> {
+        self.profiles
+            .iter()
+            .map(|(name, lore)| (name.clone(), digest(lore)))
+            .collect()
+    }
+
+    /// Cache key of a block on the default lore
+    pub(crate) fn default_key(&self, key: BlockKey) -> BlockKey {
+        match &self.default_digest {
+            Some(digest) => {
+                BlockKey::from(format!("{}@lore-{}", key.as_str(), &digest[..8]).as_str())
+            }
+            None => key,
+        }
+    }
+
+    /// Cache key of a block on `profile`, suffixed with the profile's digest so editing
+    /// the profile regenerates exactly the blocks that use it
+    pub(crate) fn cache_key(&self, key: BlockKey, profile: &str) -> Result<BlockKey> {
+        let lore = self.get(profile)?;
+        Ok(BlockKey::from(
+            format!("{}@{}-{}", key.as_str(), profile, &digest(lore)[..8]).as_str(),
+        ))
+    }
+}
+
+/// Parses a `--lore-profile name=path` argument
+pub fn parse_profile_arg(arg: &str) -> Result<(String, PathBuf)> {
+    let (name, path) = arg
+        .split_once('=')
+        .with_context(|| format!("Expected name=path, got '{}'", arg))?;
+    let name = name.trim();
+    if name.is_empty() {
+        anyhow::bail!("Lore profile name is empty in '{}'", arg);
+    }
+    Ok((name.to_string(), Path::new(path.trim()).to_path_buf()))
+}
+
+/// Fails on empty or whitespace-only lore unless `allow_empty`
+pub fn check_lore(lore: &str, allow_empty: bool) -> Result<()> {
+    if lore.trim().is_empty() && !allow_empty {
+        anyhow::bail!(
+            "The lore is empty, write some lore or pass --allow-empty-lore to invent names freely"
+        );
+    }
+    Ok(())
+}
+
+/// Concatenates lore files in the given order, each under a `## <file name>` header.
+/// Every file is checked before any is read, so a missing one fails up front
+pub fn concat_files(paths: &[PathBuf]) -> Result<String> {
+    if let Some(missing) = paths.iter().find(|path| !path.is_file()) {
+        anyhow::bail!("Lore file '{}' not found", missing.display());
+    }
+    let mut lore = String::new();
+    for path in paths {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read lore file {}", path.display()))?;
+        if !lore.is_empty() {
+            lore.push('\n');
+        }
+        let name = path.file_name().unwrap_or(path.as_os_str());
+        lore.push_str(&format!("## {}\n{}", name.to_string_lossy(), text));
+        if !lore.ends_with('\n') {
+            lore.push('\n');
+        }
+    }
+    Ok(lore)
+}
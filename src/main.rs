@@ -1,349 +1,1418 @@
 use anyhow::{Context, Result};
+use clap::{Parser, Subcommand, ValueEnum};
 use dotenv::dotenv;
-use futures::StreamExt;
-use genai::Client as GenAiClient;
-use genai::chat::{
-    ChatMessage, ChatOptions, ChatRequest, ChatResponseFormat, ChatStream, ChatStreamResponse,
-    JsonSpec,
-};
-use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{BTreeMap, BTreeSet};
 use std::fs;
-use std::path::Path;
-use std::time::Instant;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+use stellaris_name_gen::casing::CasingCheck;
+use stellaris_name_gen::changelog;
+use stellaris_name_gen::charset::LocCharset;
+use stellaris_name_gen::clusters::ClusterAction;
+use stellaris_name_gen::console::Console;
+use stellaris_name_gen::draft::{DRAFT_TIER, draft_path, tier_path};
+use stellaris_name_gen::inputs::{self, Input};
+use stellaris_name_gen::keymap::KeyMap;
+use stellaris_name_gen::keys::{HashKeys, KeyCase, KeyCompatMap, Pins};
+use stellaris_name_gen::loc_split::LocSplit;
+use stellaris_name_gen::manifest::{Manifest, ModInfo, OutputLayout};
+use stellaris_name_gen::near_forms::NearFormPolicy;
+use stellaris_name_gen::package::{self, Prune};
+use stellaris_name_gen::quota::{Interrupt, format_wait};
+use stellaris_name_gen::quoting::QuoteMode;
+use stellaris_name_gen::rarity::{CommonWords, Rarity};
+use stellaris_name_gen::scaffold::{self, Template};
+use stellaris_name_gen::translate::Language;
+use stellaris_name_gen::vanilla::yml_files;
+use stellaris_name_gen::warnings;
+use stellaris_name_gen::{
+    Config, FsCache, GenAiGenerator, Generation, LocalisationWriter, LoreProfiles, MarkdownWriter,
+    ModWriter, NameCache, NameListWriter, OutputWriter, ReportWriter, ReverseLookupWriter,
+    SqliteCache, SqliteWriter, UniqueScope, VanillaKeys, cache, compare, estimate, lore, merge,
+    plan, providers, prune, snapshot,
+};
 
-//const AI_MODEL: &'static str = "gemma3:27b-it-qat";
-const AI_MODEL: &'static str = "gemini-2.5-flash-preview-04-17";
-//const AI_MODEL: &'statiuc str = "gemini-2.0-flash";
+/// Exit code of a run whose structure has nothing to generate, apart from failures (1)
+const EXIT_NOTHING_TO_GENERATE: i32 = 3;
 
-/// Basic struct of gen ai output
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct GenerativeAIOutput {
-    names: Vec<String>,
+/// Command line arguments, overriding values from the config file
+#[derive(Debug, Parser)]
+#[command(
+    version,
+    about = "Generates Stellaris name lists and localisation using an LLM"
+)]
+struct Args {
+    /// The structure file, `-` reads it from stdin
+    #[arg(long, default_value = "file_structure.txt")]
+    structure: PathBuf,
+    /// Path to the optional config file
+    #[arg(long, default_value = "namegen.toml")]
+    config: PathBuf,
+    /// Manifest declaring the blocks, settings, mod and outputs in one file, replacing the
+    /// structure and config files
+    #[arg(long, value_name = "PATH", conflicts_with_all = ["structure", "config"])]
+    manifest: Option<PathBuf>,
+    /// Number of blocks generated at once
+    #[arg(long)]
+    concurrency: Option<usize>,
+    /// Maximum in-flight requests to any single model without its own limit in the config
+    #[arg(long)]
+    max_concurrent_models: Option<usize>,
+    /// How far name uniqueness reaches: within a block, a category of blocks, or the whole run
+    #[arg(long, value_enum)]
+    unique_scope: Option<UniqueScope>,
+    /// The previous mod's localisation, names matching one of its values exactly keep its key
+    #[arg(long, value_name = "PATH")]
+    key_compat_map: Option<PathBuf>,
+    /// Where the key of every emitted name is kept between runs, names emitted again keep it
+    #[arg(long, default_value = "keymap.json")]
+    keymap: PathBuf,
+    /// Let new names take the keys of names no longer emitted, retired by the key map
+    #[arg(long)]
+    reuse_retired_keys: bool,
+    /// TOML file of names pinned to keys per block path, emitted verbatim over generated names
+    #[arg(long, value_name = "PATH")]
+    pin: Option<PathBuf>,
+    /// What to do with near forms of a name in the same block, ie `Tyran` and `Tyrans`
+    #[arg(long, value_enum)]
+    near_forms: Option<NearFormPolicy>,
+    /// Trim blocks whose largest cluster of names sharing a stem holds more than this share of
+    /// them, ie 0.15, keeping one name per oversized cluster
+    #[arg(long, value_name = "FRACTION")]
+    max_cluster_fraction: Option<f64>,
+    /// What happens to the names trimmed from oversized clusters
+    #[arg(long, value_enum)]
+    cluster_action: Option<ClusterAction>,
+    /// Embed every block's names and drop the ones meaning the same as a kept name, at the cost
+    /// of a call to the embedding model per block
+    #[arg(long)]
+    semantic_dedup: bool,
+    /// Cosine similarity from which --semantic-dedup drops a name (default 0.9)
+    #[arg(long, value_name = "SIMILARITY")]
+    semantic_threshold: Option<f64>,
+    /// Model embedding the names for --semantic-dedup (default text-embedding-3-small)
+    #[arg(long, value_name = "MODEL")]
+    embedding_model: Option<String>,
+    /// How unusual the names of blocks without a `# rarity=` should be
+    #[arg(long, value_enum)]
+    rarity: Option<Rarity>,
+    /// Word list, one word per line. Names made up only of its words are dropped from high
+    /// rarity blocks
+    #[arg(long, value_name = "PATH")]
+    common_words: Option<PathBuf>,
+    /// Hash keys of names that can't be spelled out or collide in their block, or every key
+    #[arg(long, value_enum)]
+    hash_keys: Option<HashKeys>,
+    /// Letter case of derived keys, uppercase unless the game wants lowercase identifiers
+    #[arg(long, value_enum)]
+    key_case: Option<KeyCase>,
+    /// Most bytes a key may have, longer keys are cut and end in a hash of the whole key
+    #[arg(long, value_name = "N")]
+    max_key_len: Option<usize>,
+    /// Most requests the run may send, blocks past it are only read from the cache
+    #[arg(long, value_name = "N")]
+    budget_requests: Option<u64>,
+    /// Most tokens the run may spend, counted from provider usage or estimated without it
+    #[arg(long, value_name = "N")]
+    budget_tokens: Option<u64>,
+    /// Send no request, every block is read from the cache. Needs no API key
+    #[arg(long, conflicts_with = "budget_requests")]
+    offline: bool,
+    /// Transliterate displayed names and values to ASCII, warning about every changed value
+    #[arg(long)]
+    ascii_only: bool,
+    /// What happens to double quotes and backslashes in names: escaped where the output allows
+    /// it (names it can't hold are dropped and reported), turned into single quotes, or stripped
+    #[arg(long, value_enum, value_name = "MODE")]
+    quotes: Option<QuoteMode>,
+    /// Characters the game accepts in localisation values, values holding others are reported
+    #[arg(long, value_enum, value_name = "PROFILE")]
+    loc_charset: Option<LocCharset>,
+    /// Remove the characters --loc-charset rejects from the values instead of only reporting them
+    #[arg(long)]
+    strip_loc_charset: bool,
+    /// Open every generated block of out.txt with a comment on its date, theme and model
+    #[arg(long)]
+    provenance_comments: bool,
+    /// Edit an existing out.txt in place, replacing only the sections between the markers a run
+    /// with this flag writes around every generated block. Applies to the mod's name list too
+    #[arg(long, conflicts_with = "prune_output")]
+    in_place: bool,
+    /// Generate blocks asking a model for the same names once, the later blocks reusing the
+    /// answer of the first
+    #[arg(long)]
+    coalesce: bool,
+    /// Abort once more than this share of the recent model calls failed, ie `0.5`. At `1`, the
+    /// run aborts once every one of them failed
+    #[arg(long, value_name = "RATE", value_parser = parse_error_rate)]
+    max_error_rate: Option<f64>,
+    /// Number of recent model calls the error rate is measured over
+    #[arg(long, value_name = "N")]
+    error_window: Option<usize>,
+    /// On an exhausted daily quota, wait up to this many hours for it to reset, checking every
+    /// 15 minutes, instead of failing the run
+    #[arg(long, value_name = "MAX_HOURS")]
+    wait_for_quota: Option<f64>,
+    /// Translate `#@ translatable` blocks into these languages, ie `german,russian`. Entries
+    /// that are not translated keep their English value
+    #[arg(long, value_enum, value_delimiter = ',', value_name = "LANGS")]
+    translate: Vec<Language>,
+    /// Stream every answer to its end, even for blocks with a `# count:`
+    #[arg(long)]
+    no_early_stop: bool,
+    /// Names requested on top of a block's `# count:`, so it survives filtering
+    #[arg(long, value_name = "N")]
+    early_stop_margin: Option<usize>,
+    /// Lore file replacing lore.txt, repeatable. Files are concatenated in order under
+    /// `## <file name>` headers, a lone `-` reads the lore from stdin
+    #[arg(long = "lore", value_name = "PATH")]
+    lore_files: Vec<PathBuf>,
+    /// Extra lore document selectable per block with `# lore: <name>`, repeatable
+    #[arg(long = "lore-profile", value_name = "NAME=PATH", value_parser = parse_lore_profile)]
+    lore_profiles: Vec<(String, PathBuf)>,
+    /// The game's `localisation` folder, generated keys colliding with its keys get renamed
+    #[arg(long)]
+    vanilla_loc_dir: Option<PathBuf>,
+    /// The mod's hand-written localisation, a yml file or folder, generated keys colliding with
+    /// its keys get renamed
+    #[arg(long, value_name = "PATH")]
+    existing_loc: Option<PathBuf>,
+    /// Extra outputs written next to out.txt and localisation.txt, repeatable
+    #[arg(long, value_enum)]
+    emit: Vec<Emit>,
+    /// Also upsert every emitted name into this SQLite database, keyed on its localisation key
+    #[arg(long, value_name = "PATH")]
+    sqlite: Option<PathBuf>,
+    /// Also write every emitted name with the keys it is localised under as JSON
+    #[arg(long, value_name = "PATH")]
+    export_reverse: Option<PathBuf>,
+    /// Spread the localisation over a file per top-level block (category) or per generated block
+    #[arg(long, value_enum)]
+    loc_split: Option<LocSplit>,
+    /// Also package the output as a ready-to-zip mod with this name
+    #[arg(long)]
+    mod_name: Option<String>,
+    /// Folder the packaged mod is written into
+    #[arg(long, default_value = "mod")]
+    mod_dir: PathBuf,
+    /// Merge into the mod's existing name list, removing top-level blocks the run no longer
+    /// generates, instead of overwriting it
+    #[arg(long, requires = "mod_name")]
+    prune_output: bool,
+    /// With --prune-output, keep blocks whose keys the mod never localised, ie hand-added ones
+    #[arg(long, requires = "prune_output")]
+    keep_unknown: bool,
+    /// Fewest names a block may keep after filtering before it is marked failed (default 10)
+    #[arg(long)]
+    min_names: Option<usize>,
+    /// Seed of the per-block flavor sampling, reuse a printed seed to reproduce a run
+    #[arg(long)]
+    seed: Option<u64>,
+    /// Shuffle the names of every non-sequential block without a sort directive with this seed
+    #[arg(long)]
+    shuffle_seed: Option<u64>,
+    /// Don't append stylistic flavors to the prompts
+    #[arg(long)]
+    no_flavor: bool,
+    /// Add the naming hint of a species class to every block's prompt, ie avian or machine
+    #[arg(long)]
+    species_class: Option<String>,
+    /// Exit with an error, before writing any output, if a block kept fewer than N names
+    #[arg(long, value_name = "N")]
+    fail_on_thin: Option<usize>,
+    /// Exit with an error, before writing any output, if a name appears in more than one block
+    /// of this category
+    #[arg(long, value_name = "CATEGORY")]
+    fail_on_cross_duplicates: Option<String>,
+    /// Check every model with a trivial request before the real run
+    #[arg(long)]
+    preflight: bool,
+    /// Replay the pipeline from the names the run cached and fail on the first difference from
+    /// the run, after writing the run log but before any other output
+    #[arg(long)]
+    determinism_check: bool,
+    /// Fail instead of warning when two blocks emit the same key with different values, or a
+    /// block keeps fewer names than its minimum
+    #[arg(long)]
+    strict: bool,
+    /// Continue answers with fewer names than their block's `# count:` and fail the blocks
+    /// still short, instead of keeping fewer names
+    #[arg(long)]
+    strict_count: bool,
+    /// Report the names of every block off the capitalization most of its names follow
+    #[arg(long)]
+    consistency_check: bool,
+    /// Rewrite the names of every block off the capitalization most of its names follow in it
+    #[arg(long)]
+    consistency_fix: bool,
+    /// Where the run's provenance log is written, also after a failed run
+    #[arg(long, default_value = "run.json")]
+    run_log: PathBuf,
+    /// Markdown file a section with the names each theme gained and lost since the previous
+    /// output is appended to every run
+    #[arg(long, value_name = "PATH")]
+    changelog: Option<PathBuf>,
+    /// Print every warning as it comes, rather than summing up those repeated more than 3 times
+    #[arg(long)]
+    show_all_warnings: bool,
+    /// Write every warning of the run in full to warnings.log in this folder
+    #[arg(long, value_name = "DIR")]
+    debug_dir: Option<PathBuf>,
+    /// Echo the raw streamed model output instead of a live count of the names
+    #[arg(long)]
+    verbose: bool,
+    /// Print the planned generation as a tree, with each block's settings and cache status,
+    /// then exit without sending any request or writing any output
+    #[arg(long)]
+    dry_run: bool,
+    /// Run on an empty lore, the prompts then ask the model to invent names freely
+    #[arg(long)]
+    allow_empty_lore: bool,
+    /// Print the predicted tokens and cost of each block from the `[prices]` table, then exit
+    /// without sending any request or writing any output
+    #[arg(long)]
+    estimate: bool,
+    /// Also keep the unrepaired model output in a `.raw` sidecar next to each cache file
+    #[arg(long)]
+    keep_raw: bool,
+    /// Generate blocks missing from the cache with the model, flavors and temperature their
+    /// newest cached names were generated with. --seed and --no-flavor still win
+    #[arg(long)]
+    match_existing: bool,
+    /// Generate every block with this cheaper model into the draft cache, writing only a preview
+    /// labelled as a draft. `promote` then generates the blocks worth keeping with their model
+    #[arg(long, value_name = "MODEL")]
+    draft_model: Option<String>,
+    /// Keep the run's cache in a namespace of its own beside the main cache, ie for an
+    /// experiment with another prompt or model that can be discarded whole
+    #[arg(long, value_name = "NAME")]
+    cache_namespace: Option<String>,
+    #[command(subcommand)]
+    command: Option<Command>,
 }
 
-/// Holds parsing context for each block in the structure file
-struct ContextEntry {
-    key: String,
-    indent: usize,
-    theme: Option<String>,
-    kv_inserts: Vec<String>,
-    prefix: Option<String>,
-    has_data: bool,
-    child_count: usize,
-    path: Vec<String>,
+/// Outputs beyond the name list and localisation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+enum Emit {
+    /// `PREVIEW.md`, names grouped per block for sharing
+    Markdown,
+    /// `report.json`, the full run report
+    Report,
 }
 
-/// Sanitizes name into a valid localization key fragment
-fn sanitize_key(name: &str) -> String {
-    name.chars()
-        .map(|c| match c {
-            ' ' | '-' | '\'' | '!' | '"' => '_',
-            c if c.is_ascii_alphanumeric() => c.to_ascii_uppercase(),
-            _ => '_',
-        })
-        .collect()
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// Writes the parsed, sorted names of every cached block into one diffable file
+    Snapshot {
+        /// Where to write the snapshot
+        #[arg(long, short, default_value = "cache-snapshot.txt")]
+        output: PathBuf,
+    },
+    /// Writes the structure file, config and command line settings as one manifest
+    Convert {
+        /// Where to write the manifest
+        #[arg(long, short, default_value = "namegen.manifest.toml")]
+        output: PathBuf,
+    },
+    /// Writes a commented starter project: structure file, lore, config and .env.example.
+    /// Existing files are never overwritten
+    Init {
+        /// Which starter project to write
+        #[arg(long, value_enum, default_value = "minimal")]
+        template: Template,
+        /// Folder the project is written into
+        #[arg(long, default_value = ".")]
+        dir: PathBuf,
+    },
+    /// Reports the blocks whose names differ between two cache directories, and the blocks only
+    /// one of them holds
+    CompareCaches {
+        /// The reference cache, ie a known good one
+        left: PathBuf,
+        /// The cache compared against it
+        right: PathBuf,
+        /// Print the comparison as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Merges the names of one cache directory into another block by block, so a run with
+    /// `--offline` emits the union of both
+    MergeCaches {
+        /// Cache directory whose names are added
+        #[arg(long)]
+        from: PathBuf,
+        /// Cache directory the names are merged into
+        #[arg(long)]
+        into: PathBuf,
+        /// Also merge blocks whose two sides were generated from different prompts
+        #[arg(long)]
+        force_mismatched: bool,
+        /// Print the outcome as JSON
+        #[arg(long)]
+        json: bool,
+    },
+    /// Generates the blocks matching a glob of block paths again with their own model into the
+    /// cache, showing it the names a `--draft-model` run drafted for them
+    Promote {
+        /// Block paths to promote, ie `NAME/ship_names/*`. `*` matches within a key, `**`
+        /// across keys
+        #[arg(long)]
+        blocks: String,
+    },
+    /// Lists the cache entries no block of the structure maps to anymore, like `cache prune`
+    PruneCache {
+        /// Delete the listed entries instead of only listing them
+        #[arg(long)]
+        apply: bool,
+    },
+    /// Cache maintenance
+    Cache {
+        #[command(subcommand)]
+        action: CacheCommand,
+    },
 }
 
-/// Helper to call AI and write raw CSV to cache, showing streamed chunks
-async fn generate_and_cache(
-    client: &GenAiClient,
-    cache_path: &Path,
-    lore: &str,
-    theme: &str,
-) -> Result<String> {
-    println!("[AI] Streaming generation for theme '{}'", theme);
-    let prompt_text = format!(
-        r#"
-- Prefer to use Latinization of languages (a-z alphabet) and **do not under accents**
-- Come up with **as many** possible names
-- Avoid duplicates
-Come up with as many {} names as possible using the lore:
-{}
-"#,
-        theme, lore
-    );
-    let user_msg = ChatMessage::user(prompt_text);
-    let chat_req = ChatRequest::new(vec![user_msg]);
-    let chat_opts = ChatOptions::default()
-        .with_temperature(0.5)
-        .with_max_tokens(65536)
-        .with_response_format(ChatResponseFormat::JsonSpec(JsonSpec::new(
-            "names",
-            serde_json::json!({
-                "type": "object",
-                "properties": {
-                    "names": {
-                    "type": "array",
-                    "items": {
-                        "type": "string"
-                    }
-                    }
-                }
-            }),
-        )))
-        .with_capture_content(true);
-
-    // Stream the chat
-    let stream_response: ChatStreamResponse = client
-        .exec_chat_stream(AI_MODEL, chat_req, Some(&chat_opts))
-        .await?;
-    let mut stream: ChatStream = stream_response.stream;
+#[derive(Debug, Subcommand)]
+enum CacheCommand {
+    /// Copies a flat-file cache directory into a SQLite cache
+    Migrate {
+        /// Flat-file cache directory to read, the configured cache `dir` by default
+        #[arg(long)]
+        from: Option<PathBuf>,
+        /// SQLite database to write, created if missing, the configured cache `path` by default
+        #[arg(long)]
+        to: Option<PathBuf>,
+    },
+    /// Lists the cache entries no block of the structure maps to anymore
+    Prune {
+        /// Delete the listed entries instead of only listing them
+        #[arg(long)]
+        apply: bool,
+    },
+}
 
-    let mut combined = String::new();
-    println!();
-    while let Some(chunk) = stream.next().await {
-        match chunk {
-            Ok(genai::chat::ChatStreamEvent::Start) => {}
-            Ok(genai::chat::ChatStreamEvent::Chunk(stream_chunk)) => {
-                print!("{}", stream_chunk.content);
-                combined.push_str(&stream_chunk.content);
-            }
-            Ok(genai::chat::ChatStreamEvent::ReasoningChunk(stream_chunk)) => {
-                print!("{}", stream_chunk.content);
-            }
-            Ok(genai::chat::ChatStreamEvent::End(end)) => {
-                println!("Final out: {:?}", end.captured_content);
-                break;
-            }
-            Err(e) => {
-                eprintln!("[AI Warning] Streaming error: {}", e);
-                break;
-            }
-        }
+/// Reads an input file, or stdin when the path is `-`
+fn read_input(path: &Path) -> Result<String> {
+    if path == Path::new("-") {
+        let mut text = String::new();
+        io::stdin()
+            .read_to_string(&mut text)
+            .context("Failed to read stdin")?;
+        return Ok(text);
     }
-    println!();
+    fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))
+}
 
-    // gracefully close off the json if not complete
-    // remove trailing ,
-    if let Some(last_quote_pos) = combined.rfind('"') {
-        let mut idx = last_quote_pos + 1;
-        // Skip whitespace
-        while idx < combined.len() && combined.as_bytes()[idx].is_ascii_whitespace() {
-            idx += 1;
-        }
-        // If next character is a comma, remove it
-        if idx < combined.len() && combined.as_bytes()[idx] == b',' {
-            combined.remove(idx);
-        }
-    }
+fn parse_lore_profile(arg: &str) -> Result<(String, PathBuf)> {
+    lore::parse_profile_arg(arg)
+}
 
-    // Fix common JSON issues in-place
-    let mut fixed = combined.clone();
-    // Keep content starting at first '{'
-    if let Some(pos) = fixed.find('{') {
-        fixed = fixed[pos..].to_string();
+fn parse_error_rate(arg: &str) -> Result<f64> {
+    let rate: f64 = arg.parse()?;
+    anyhow::ensure!(
+        rate > 0.0 && rate <= 1.0,
+        "expected a share above 0 and up to 1, ie 0.5"
+    );
+    Ok(rate)
+}
+
+/// The config with the command line settings applied, as `convert` writes them to a manifest
+fn flag_settings(args: &Args, mut config: Config) -> Config {
+    config.concurrency = args.concurrency.or(config.concurrency);
+    config.max_concurrent_models = args.max_concurrent_models.or(config.max_concurrent_models);
+    config.unique_scope = args.unique_scope.or(config.unique_scope);
+    config.near_forms = args.near_forms.or(config.near_forms);
+    config.max_cluster_fraction = args.max_cluster_fraction.or(config.max_cluster_fraction);
+    config.cluster_action = args.cluster_action.or(config.cluster_action);
+    if args.semantic_dedup {
+        config.semantic_dedup = Some(true);
     }
-    // Ensure quotes are balanced
-    if fixed.matches('"').count() % 2 != 0 {
-        fixed.push('"');
+    config.semantic_threshold = args.semantic_threshold.or(config.semantic_threshold);
+    config.embedding_model = args.embedding_model.clone().or(config.embedding_model);
+    config.rarity = args.rarity.or(config.rarity);
+    config.common_words = args.common_words.clone().or(config.common_words);
+    if let Some(mode) = args.hash_keys {
+        config.key_style.get_or_insert_with(Default::default).hash = mode;
     }
-    // Remove empty trailing string entries (incomplete " element)
-    {
-        let trimmed = fixed.trim_end();
-        // if ends with two quotes indicating an empty string
-        if trimmed.ends_with("\"\"") {
-            // drop the empty "" and any leading comma
-            if let Some(pos) = fixed.rfind(",\"\"") {
-                fixed.replace_range(pos..pos + 3, "");
-            }
-        }
+    if let Some(case) = args.key_case {
+        config.key_style.get_or_insert_with(Default::default).case = case;
     }
-    // Remove trailing comma after last quoted string
-    if let Some(last_q) = fixed.rfind('"') {
-        let mut idx = last_q + 1;
-        while idx < fixed.len() && fixed.as_bytes()[idx].is_ascii_whitespace() {
-            idx += 1;
-        }
-        if idx < fixed.len() && fixed.as_bytes()[idx] == b',' {
-            fixed.remove(idx);
-        }
+    if let Some(max) = args.max_key_len {
+        config
+            .key_style
+            .get_or_insert_with(Default::default)
+            .max_len = Some(max);
+    }
+    config.budget_requests = args.budget_requests.or(config.budget_requests);
+    config.budget_tokens = args.budget_tokens.or(config.budget_tokens);
+    if args.offline {
+        config.budget_requests = Some(0);
+    }
+    if args.ascii_only {
+        config.ascii_only = Some(true);
+    }
+    config.quotes = args.quotes.or(config.quotes);
+    config.loc_charset = args.loc_charset.or(config.loc_charset);
+    if args.strip_loc_charset {
+        config.strip_loc_charset = Some(true);
     }
-    // Balance brackets and braces
-    let ob = fixed.matches('[').count();
-    let cb = fixed.matches(']').count();
-    if cb < ob {
-        fixed.push_str(&"]".repeat(ob - cb));
+    if args.provenance_comments {
+        config.provenance_comments = Some(true);
     }
-    let obc = fixed.matches('{').count();
-    let cbc = fixed.matches('}').count();
-    if cbc < obc {
-        fixed.push_str(&"}".repeat(obc - cbc));
+    if args.coalesce {
+        config.coalesce = Some(true);
     }
+    if args.strict_count {
+        config.strict_count = Some(true);
+    }
+    config.max_error_rate = args.max_error_rate.or(config.max_error_rate);
+    config.error_window = args.error_window.or(config.error_window);
+    config.wait_for_quota = args.wait_for_quota.or(config.wait_for_quota);
+    if !args.translate.is_empty() {
+        config.translate = args.translate.clone();
+    }
+    if args.no_early_stop {
+        config.early_stop = Some(false);
+    }
+    config.early_stop_margin = args.early_stop_margin.or(config.early_stop_margin);
+    if !args.lore_files.is_empty() {
+        config.lore.files = args.lore_files.clone();
+    }
+    config
+        .lore_profiles
+        .extend(args.lore_profiles.iter().cloned());
+    config.vanilla_loc_dir = args.vanilla_loc_dir.clone().or(config.vanilla_loc_dir);
+    config.existing_loc = args.existing_loc.clone().or(config.existing_loc);
+    config.min_names = args.min_names.or(config.min_names);
+    config.seed = args.seed.or(config.seed);
+    config.shuffle_seed = args.shuffle_seed.or(config.shuffle_seed);
+    if args.no_flavor {
+        config.flavor = Some(false);
+    }
+    config.species_class = args.species_class.clone().or(config.species_class);
+    config
+}
 
-    // Write cache
-    if let Some(parent) = cache_path.parent() {
-        fs::create_dir_all(parent).ok();
-    }
-    fs::write(&cache_path, &combined).context("Failed to write cache file")?;
-    println!(
-        "[AI] Cached {} bytes to '{}'",
-        combined.len(),
-        cache_path.display()
-    );
-    Ok(combined)
+/// Whether `file` is one of the localisation files a run writes to `written`, its split parts
+/// and translations included
+fn is_written_localisation(file: &Path, written: &Path) -> bool {
+    let dir = |path: &Path| {
+        let dir = path.parent().filter(|dir| !dir.as_os_str().is_empty());
+        fs::canonicalize(dir.unwrap_or(Path::new("."))).ok()
+    };
+    let stem = written.file_stem().unwrap_or_default().to_string_lossy();
+    dir(file) == dir(written)
+        && file
+            .file_stem()
+            .is_some_and(|own| own.to_string_lossy().starts_with(&*stem))
 }
 
-/// Generates or reads cached raw CSV of names, then applies prefix formatting.
-async fn generate_localized_entries(
-    client: &GenAiClient,
-    cache_path: &Path,
-    lore: &str,
-    theme: &str,
-    prefix: &str,
-) -> Result<Vec<(String, String)>> {
-    let raw = if let Ok(string) = fs::read_to_string(&cache_path) {
-        if !string.trim().is_empty() {
-            println!(
-                "[Cache] '{}' exists—using cached names",
-                cache_path.display()
-            );
-            string
-        } else {
-            generate_and_cache(client, cache_path, lore, theme).await?
+/// The mod the output is packaged into, from `--mod-name` or the manifest's `[mod]`. A run in
+/// a cache namespace packages it beside the real one, ie into `mod.short-prompts`
+fn mod_info(args: &Args, manifest: Option<&Manifest>, namespace: Option<&str>) -> Option<ModInfo> {
+    let info = match &args.mod_name {
+        Some(name) => ModInfo {
+            name: name.clone(),
+            dir: Some(args.mod_dir.clone()),
+        },
+        None => manifest.and_then(|manifest| manifest.mod_info.clone())?,
+    };
+    let dir = info.dir.unwrap_or_else(|| PathBuf::from("mod"));
+    Some(ModInfo {
+        dir: Some(match namespace {
+            Some(namespace) => tier_path(&dir, namespace),
+            None => dir,
+        }),
+        ..info
+    })
+}
+
+/// Names per block path of the name list and localisation a previous run wrote, empty before
+/// the first run
+fn previous_names(
+    layout: &OutputLayout,
+    value_format: &str,
+) -> Result<BTreeMap<String, BTreeSet<String>>> {
+    let read = |path: &Path| {
+        fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))
+    };
+    if !layout.name_list.exists() {
+        return Ok(BTreeMap::new());
+    }
+    let name_list = read(&layout.name_list)?;
+    let dir = layout
+        .localisation
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or(Path::new("."));
+    let mut localisation = Vec::new();
+    for entry in fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))? {
+        let file = entry?.path();
+        if file.is_file() && is_written_localisation(&file, &layout.localisation) {
+            localisation.push(read(&file)?);
         }
-    } else {
-        generate_and_cache(client, cache_path, lore, theme).await?
+    }
+    Ok(changelog::previous_names(
+        &name_list,
+        &localisation,
+        value_format,
+    ))
+}
+
+/// The folder of the `--cache-namespace` within the cache directory `dir`, `dir` itself
+/// without one
+fn namespaced_dir(args: &Args, dir: &Path) -> Result<PathBuf> {
+    let config = cache::CacheConfig {
+        dir: dir.to_path_buf(),
+        namespace: args.cache_namespace.clone(),
+        ..cache::CacheConfig::default()
     };
-    let mut json_out: Option<GenerativeAIOutput> = serde_json::from_str(&raw)
-        .map_err(|e| println!("[Gen AI Error]: {}", e))
-        .ok();
-    // keep trying over and over
-    while json_out.is_none() {
-        json_out =
-            serde_json::from_str(&generate_and_cache(client, cache_path, lore, theme).await?)
-                .map_err(|e| println!("[Gen AI Error]: {}", e))
-                .ok();
-    }
-    let json_out: GenerativeAIOutput = json_out.unwrap();
-    let prefix_clean = prefix.trim_end_matches('_');
-    let mut entries = Vec::new();
-    for nm in json_out.names {
-        let name = nm.trim();
-        if name.is_empty() {
-            continue;
+    Ok(config.namespaced()?.dir)
+}
+
+/// The outputs of the run. A run in a cache namespace writes every one of them beside the real
+/// one, so an experiment never replaces the names a mod ships
+fn run_layout(args: &Args, manifest: Option<&Manifest>, namespace: Option<&str>) -> OutputLayout {
+    let layout = output_layout(
+        args,
+        manifest
+            .map(|manifest| manifest.output.clone())
+            .unwrap_or_default(),
+    );
+    let Some(namespace) = namespace else {
+        return layout;
+    };
+    let namespaced = |path: Option<PathBuf>| path.map(|path| tier_path(&path, namespace));
+    OutputLayout {
+        name_list: tier_path(&layout.name_list, namespace),
+        localisation: tier_path(&layout.localisation, namespace),
+        markdown: namespaced(layout.markdown),
+        report: namespaced(layout.report),
+        sqlite: namespaced(layout.sqlite),
+        reverse: namespaced(layout.reverse),
+        loc_split: layout.loc_split,
+    }
+}
+
+/// The output layout with the extra outputs asked for on the command line
+fn output_layout(args: &Args, mut layout: OutputLayout) -> OutputLayout {
+    for emit in &args.emit {
+        match emit {
+            Emit::Markdown => layout.markdown = Some(MarkdownWriter::default().path),
+            Emit::Report => layout.report = Some(ReportWriter::default().path),
         }
-        let nm_san = sanitize_key(name);
-        let key = if prefix_clean.is_empty() {
-            nm_san.clone()
-        } else {
-            format!("{}_{}", prefix_clean, nm_san)
-        };
-        entries.push((key, name.to_string()));
     }
-    Ok(entries)
+    layout.sqlite = args.sqlite.clone().or(layout.sqlite);
+    layout.reverse = args.export_reverse.clone().or(layout.reverse);
+    layout.loc_split = args.loc_split.or(layout.loc_split);
+    layout
 }
 
 #[tokio::main]
 async fn main() -> Result<()> {
     let start = Instant::now();
     dotenv().ok();
+    let args = Args::parse();
+    warnings::show_all(args.show_all_warnings);
+    if let Some(Command::Init { template, dir }) = &args.command {
+        for path in scaffold::init(dir, *template)? {
+            println!("[Init] Wrote '{}'", path.display());
+        }
+        return Ok(());
+    }
+    if let Some(Command::CompareCaches { left, right, json }) = &args.command {
+        let (left, right) = (namespaced_dir(&args, left)?, namespaced_dir(&args, right)?);
+        for dir in [&left, &right] {
+            if !dir.is_dir() {
+                anyhow::bail!("'{}' is not a cache directory", dir.display());
+            }
+        }
+        let comparison = compare::compare_caches(&FsCache::new(&left), &FsCache::new(&right))?;
+        if *json {
+            println!("{}", serde_json::to_string_pretty(&comparison)?);
+        } else {
+            let console = Console::detect();
+            print!("{}", console.diff(&compare::render_comparison(&comparison)));
+        }
+        return Ok(());
+    }
+    if let Some(Command::MergeCaches {
+        from,
+        into,
+        force_mismatched,
+        json,
+    }) = &args.command
+    {
+        let (from, into) = (namespaced_dir(&args, from)?, namespaced_dir(&args, into)?);
+        if !from.is_dir() {
+            anyhow::bail!("'{}' is not a cache directory", from.display());
+        }
+        let label = |dir: &Path| dir.display().to_string();
+        let merge = merge::merge_caches(
+            &FsCache::new(&from),
+            &label(&from),
+            &mut FsCache::new(&into),
+            &label(&into),
+            *force_mismatched,
+        )?;
+        if *json {
+            println!("{}", serde_json::to_string_pretty(&merge)?);
+        } else {
+            print!("{}", Console::detect().diff(&merge::render_merge(&merge)));
+        }
+        return Ok(());
+    }
+    // A missing manifest is reported with the other missing inputs below
+    let manifest = args
+        .manifest
+        .as_deref()
+        .filter(|path| path.is_file())
+        .map(Manifest::load)
+        .transpose()?;
+    let mut config = match &manifest {
+        Some(manifest) => manifest.settings.clone(),
+        None => Config::load(&args.config)?,
+    };
     println!("[Start] Initializing generation process");
-    
-    fs::create_dir_all("cache").context("Failed to create cache dir")?;
-
-    let lore = fs::read_to_string("lore.txt").context("Failed to read lore.txt")?;
-    let structure =
-        fs::read_to_string("file_structure.txt").context("Failed to read file_structure.txt")?;
+    // Kept before any command touches the cache, the outputs follow it below
+    let namespace = args
+        .cache_namespace
+        .clone()
+        .or(config.cache.namespace.clone());
+    if let Some(namespace) = &namespace {
+        println!("[Cache] Using the namespace '{}'", namespace);
+    }
+    config.cache.namespace = namespace.clone();
 
-    let client = GenAiClient::default();
-    let mut stack: Vec<ContextEntry> = Vec::new();
-    let mut pending_theme: Option<String> = None;
-    let mut pending_kvs: Vec<String> = Vec::new();
-    let mut pending_prefix: Option<String> = None;
-    let mut output: Vec<String> = Vec::new();
-    let mut localisations: HashMap<String, String> = HashMap::new();
+    if let Some(Command::Cache {
+        action: CacheCommand::Migrate { from, to },
+    }) = &args.command
+    {
+        let migrated = cache::CacheConfig {
+            dir: from.clone().unwrap_or_else(|| config.cache.dir.clone()),
+            path: to.clone().unwrap_or_else(|| config.cache.path.clone()),
+            ..config.cache.clone()
+        }
+        .namespaced()?;
+        let copied = cache::migrate(
+            &FsCache::new(&migrated.dir),
+            &mut SqliteCache::open(&migrated.path)?,
+        )?;
+        println!(
+            "[Cache] Migrated {} blocks into '{}'",
+            copied,
+            migrated.path.display()
+        );
+        return Ok(());
+    }
+    config.cache = config.cache.namespaced()?;
 
-    for raw_line in structure.lines() {
-        let indent = raw_line.chars().take_while(|c| c.is_whitespace()).count();
-        let trimmed = raw_line.trim();
+    let generating = matches!(args.command, None | Some(Command::Promote { .. }));
+    let lore_files = if args.lore_files.is_empty() {
+        &config.lore.files
+    } else {
+        &args.lore_files
+    };
+    let mut required = Vec::new();
+    match &args.manifest {
+        Some(path) => required.push((Input::Manifest, path.clone())),
+        None if args.structure != Path::new("-") => {
+            required.push((Input::Structure, args.structure.clone()))
+        }
+        None => {}
+    }
+    if lore_files.is_empty() && generating {
+        required.push((Input::Lore, PathBuf::from("lore.txt")));
+    }
+    for path in lore_files.iter().filter(|path| *path != Path::new("-")) {
+        required.push((Input::Lore, path.clone()));
+    }
+    let profiles = config
+        .lore_profiles
+        .iter()
+        .chain(args.lore_profiles.iter().map(|(name, path)| (name, path)));
+    for (name, path) in profiles {
+        required.push((Input::LoreProfile(name.clone()), path.clone()));
+    }
+    if let Err(missing) = inputs::preflight(Path::new("."), &required) {
+        eprint!("{}", missing);
+        std::process::exit(inputs::EXIT_MISSING_INPUTS);
+    }
 
-        if trimmed.starts_with('#') {
-            let comment = &trimmed[1..].trim();
-            if let Some((k, v)) = comment.split_once('=') {
-                pending_kvs.push(format!("{} = {}", k.trim(), v.trim()));
-            } else if let Some(pref) = comment.strip_prefix("prefix:") {
-                pending_prefix = Some(pref.trim().to_string());
+    let stdin_lore = args.lore_files.iter().any(|path| path == Path::new("-"));
+    if stdin_lore && args.lore_files.len() > 1 {
+        anyhow::bail!("--lore - reads all of the lore from stdin and can't be combined with files");
+    }
+    if stdin_lore && args.structure == Path::new("-") {
+        anyhow::bail!("Only one of --structure and --lore can read from stdin");
+    }
+    let structure = match &manifest {
+        Some(manifest) => manifest.structure()?,
+        None => read_input(&args.structure)?,
+    };
+    if let Some(Command::Convert { output }) = &args.command {
+        if manifest.is_some() {
+            anyhow::bail!("convert reads a structure file, not a manifest");
+        }
+        let mut converted = Manifest::from_structure(&structure, flag_settings(&args, config))?;
+        converted.mod_info = args.mod_name.clone().map(|name| ModInfo {
+            name,
+            dir: Some(args.mod_dir.clone()),
+        });
+        converted.output = output_layout(&args, OutputLayout::default());
+        fs::write(output, converted.to_toml()?)
+            .with_context(|| format!("Failed to write {}", output.display()))?;
+        println!("[Manifest] Wrote '{}'", output.display());
+        return Ok(());
+    }
+    // Inputs are checked before the cache is opened or a client is set up
+    if generating && plan::generated_blocks(&structure)? == 0 {
+        eprintln!(
+            "[Structure] Nothing to generate, no leaf block has a theme and no line has a placeholder"
+        );
+        std::process::exit(EXIT_NOTHING_TO_GENERATE);
+    }
+    // Command line profiles replace config profiles of the same name
+    let mut lore_profiles = LoreProfiles::load(
+        config
+            .lore_profiles
+            .iter()
+            .chain(args.lore_profiles.iter().map(|(name, path)| (name, path))),
+    )?;
+    // Assembled or piped lore takes part in the cache keys, so editing it regenerates
+    let assembled_lore = if lore_files.is_empty() {
+        None
+    } else {
+        let lore = if stdin_lore {
+            read_input(Path::new("-"))?
+        } else {
+            lore::concat_files(lore_files)?
+        };
+        lore_profiles.key_default_lore(&lore);
+        Some(lore)
+    };
+    let lore = match assembled_lore {
+        Some(lore) => Some(lore),
+        None if generating => {
+            Some(fs::read_to_string("lore.txt").context("Failed to read lore.txt")?)
+        }
+        None => None,
+    };
+    if let Some(lore) = &lore {
+        lore::check_lore(lore, args.allow_empty_lore)?;
+    }
+    // Draft runs never read or write the main cache
+    let cache_config = match &args.draft_model {
+        Some(_) => config.cache.tier(DRAFT_TIER),
+        None => config.cache.clone(),
+    };
+    let mut name_cache = cache_config.open()?;
+    if let Some(Command::Snapshot { output }) = &args.command {
+        return snapshot::write_snapshot(&structure, &name_cache, &lore_profiles, output);
+    }
+    if let Some(
+        Command::PruneCache { apply }
+        | Command::Cache {
+            action: CacheCommand::Prune { apply },
+        },
+    ) = &args.command
+    {
+        let stale = prune::stale_cache_keys(&structure, &name_cache, &lore_profiles)?;
+        for key in &stale {
+            if *apply {
+                name_cache.remove(key)?;
+                println!("[Cache] Removed '{}'", key.as_str());
             } else {
-                pending_theme = Some(comment.to_string());
+                println!("[Cache] Stale '{}'", key.as_str());
             }
-            continue;
         }
+        if *apply {
+            println!("[Cache] Removed {} stale entries", stale.len());
+        } else {
+            println!(
+                "[Cache] {} stale entries, rerun with --apply to delete them",
+                stale.len()
+            );
+        }
+        return Ok(());
+    }
 
-        if trimmed.ends_with('{') {
-            let key = trimmed
-                .split_once('=')
-                .map(|(a, _)| a.trim())
-                .unwrap_or(trimmed)
-                .to_string();
-            let mut path = if let Some(parent) = stack.last() {
-                parent.path.clone()
-            } else {
-                Vec::new()
-            };
-            path.push(key.clone());
-            let cur_prefix = pending_prefix
-                .take()
-                .or_else(|| stack.last().and_then(|p| p.prefix.clone()));
-            let ctx = ContextEntry {
-                key: key.clone(),
-                indent,
-                theme: pending_theme.take(),
-                kv_inserts: pending_kvs.clone(),
-                prefix: cur_prefix,
-                has_data: false,
-                child_count: 0,
-                path,
-            };
-            pending_kvs.clear();
+    let lore = lore.context("No lore was read")?;
+    let mut builder = Generation::builder()
+        .structure(structure)
+        .lore(lore)
+        .lore_profiles(lore_profiles)
+        .config(&config)
+        .keep_raw(args.keep_raw)
+        .match_existing(args.match_existing)
+        .explicit_flavors(args.seed.is_some() || args.no_flavor)
+        .strict(args.strict)
+        .interrupt(Interrupt::ctrl_c())
+        .generator(
+            GenAiGenerator::new(providers::client(&config.providers))
+                .verbose(args.verbose)
+                .providers(config.providers.clone()),
+        )
+        .cache(name_cache);
+    if let Some(model) = &args.draft_model {
+        builder = builder.draft_model(model);
+    }
+    if let Some(concurrency) = args.concurrency {
+        builder = builder.concurrency(concurrency);
+    }
+    if let Some(limit) = args.max_concurrent_models {
+        builder = builder.max_concurrent_models(limit);
+    }
+    if let Some(dir) = args
+        .vanilla_loc_dir
+        .as_ref()
+        .or(config.vanilla_loc_dir.as_ref())
+    {
+        // The parsed key set is cached next to the name cache, keyed by the folder's listing
+        let keys = VanillaKeys::load(dir, Some(config.cache.dir.as_path()))?;
+        println!("[Vanilla] Loaded {} localisation keys", keys.len());
+        builder = builder.vanilla_keys(keys);
+    }
+    if let Some(path) = &config.existing_loc {
+        // The localisation this run writes holds its own keys, they are never reserved
+        let layout = run_layout(&args, manifest.as_ref(), namespace.as_deref());
+        // And so does the localisation it packaged into the mod last time, split parts included
+        let info = mod_info(&args, manifest.as_ref(), namespace.as_deref());
+        let packaged: Vec<PathBuf> = match info {
+            Some(info) => package::generated_localisation(
+                &info.dir.unwrap_or_else(|| PathBuf::from("mod")),
+                &info.name,
+            )?
+            .iter()
+            .filter_map(|file| fs::canonicalize(file).ok())
+            .collect(),
+            None => Vec::new(),
+        };
+        let files: Vec<PathBuf> = yml_files(path)?
+            .into_iter()
+            .filter(|file| {
+                !is_written_localisation(file, &layout.localisation)
+                    && !fs::canonicalize(file).is_ok_and(|file| packaged.contains(&file))
+            })
+            .collect();
+        let keys = VanillaKeys::from_files(&files)?;
+        println!(
+            "[Existing Loc] Loaded {} keys from {} files",
+            keys.len(),
+            files.len()
+        );
+        builder = builder.existing_keys(keys);
+    }
+    if let Some(seed) = args.seed {
+        builder = builder.seed(seed);
+    }
+    if args.no_flavor {
+        builder = builder.flavor(false);
+    }
+    if let Some(class) = &args.species_class {
+        builder = builder.species_class(class);
+    }
+    if let Some(seed) = args.shuffle_seed {
+        builder = builder.shuffle_seed(seed);
+    }
+    if let Some(min_names) = args.min_names {
+        builder = builder.min_names(min_names);
+    }
+    if let Some(scope) = args.unique_scope {
+        builder = builder.unique_scope(scope);
+    }
+    if let Some(path) = &args.key_compat_map {
+        let keys = KeyCompatMap::load(path)?;
+        println!("[Keys] Loaded {} previous keys", keys.len());
+        builder = builder.key_compat(keys);
+    }
+    let mut key_map = KeyMap::load(&args.keymap)?;
+    if !key_map.is_empty() {
+        println!("[Keymap] Loaded the keys of {} names", key_map.len());
+    }
+    builder = builder
+        .key_map(key_map.clone())
+        .reuse_retired_keys(args.reuse_retired_keys);
+    if let Some(path) = &args.pin {
+        let pins = Pins::load(path)?;
+        println!("[Pin] Loaded {} pinned names", pins.len());
+        builder = builder.pins(pins);
+    }
+    if let Some(requests) = args.budget_requests {
+        builder = builder.budget_requests(requests);
+    }
+    if let Some(tokens) = args.budget_tokens {
+        builder = builder.budget_tokens(tokens);
+    }
+    if args.offline {
+        builder = builder.budget_requests(0);
+    }
+    if args.ascii_only {
+        builder = builder.ascii_only(true);
+    }
+    if let Some(mode) = args.quotes {
+        builder = builder.quotes(mode);
+    }
+    if let Some(charset) = args.loc_charset {
+        builder = builder.loc_charset(charset);
+    }
+    if args.strip_loc_charset {
+        builder = builder.strip_loc_charset(true);
+    }
+    if args.provenance_comments {
+        builder = builder.provenance_comments(true);
+    }
+    if args.in_place {
+        builder = builder.markers(true);
+    }
+    if args.coalesce {
+        builder = builder.coalesce(true);
+    }
+    if args.strict_count {
+        builder = builder.strict_count(true);
+    }
+    if args.consistency_fix {
+        builder = builder.consistency(CasingCheck::Fix);
+    } else if args.consistency_check {
+        builder = builder.consistency(CasingCheck::Report);
+    }
+    if let Some(rate) = args.max_error_rate {
+        builder = builder.max_error_rate(rate);
+    }
+    if let Some(calls) = args.error_window {
+        builder = builder.error_window(calls);
+    }
+    if let Some(hours) = args.wait_for_quota {
+        builder = builder.wait_for_quota(Duration::from_secs_f64(hours.max(0.0) * 3600.0));
+    }
+    if args.no_early_stop {
+        builder = builder.early_stop(false);
+    }
+    if let Some(margin) = args.early_stop_margin {
+        builder = builder.early_stop_margin(margin);
+    }
+    if let Some(policy) = args.near_forms {
+        builder = builder.near_forms(policy);
+    }
+    if let Some(fraction) = args.max_cluster_fraction {
+        builder = builder.max_cluster_fraction(fraction);
+    }
+    if let Some(action) = args.cluster_action {
+        builder = builder.cluster_action(action);
+    }
+    if args.semantic_dedup {
+        builder = builder.semantic_dedup(true);
+    }
+    if let Some(threshold) = args.semantic_threshold {
+        builder = builder.semantic_threshold(threshold);
+    }
+    if let Some(model) = &args.embedding_model {
+        builder = builder.embedding_model(model);
+    }
+    if let Some(rarity) = args.rarity {
+        builder = builder.rarity(rarity);
+    }
+    if let Some(path) = args.common_words.as_ref().or(config.common_words.as_ref()) {
+        let words = CommonWords::load(path)?;
+        println!("[Rarity] Loaded {} common words", words.len());
+        builder = builder.common_words(words);
+    }
+    if let Some(mode) = args.hash_keys {
+        builder = builder.hash_keys(mode);
+    }
+    if let Some(case) = args.key_case {
+        builder = builder.key_case(case);
+    }
+    if let Some(max) = args.max_key_len {
+        builder = builder.max_key_len(max);
+    }
+    let generation = builder.build()?;
+    if args.dry_run {
+        print!("{}", plan::render_tree(&generation.plan()?));
+        return Ok(());
+    }
+    if args.estimate {
+        print!("{}", estimate::render_estimate(&generation.estimate()?));
+        return Ok(());
+    }
+    if !args.no_flavor {
+        println!("[Flavor] Seed {}", generation.seed());
+    }
+    let title_tasks = config.titles.tasks()?;
+    let mut models = generation.models()?;
+    models.extend(title_tasks.iter().filter_map(|task| task.model.clone()));
+    models.sort();
+    models.dedup();
+    // Offline runs read the cache only, no provider needs a key
+    for model in models.into_iter().filter(|_| !args.offline) {
+        let credentials =
+            providers::resolve(&model, &config.providers, |var| std::env::var(var).ok())?;
+        println!("[Providers] {}", credentials);
+    }
+    if args.preflight && !args.offline {
+        generation.preflight().await?;
+    }
+    if let Some(Command::Promote { blocks }) = &args.command {
+        let draft = config.cache.tier(DRAFT_TIER).open()?;
+        let promotions = generation.promote(blocks, &*draft).await?;
+        println!(
+            "[Promote] Generated {} blocks from their drafts, the next run emits them",
+            promotions.len()
+        );
+        return Ok(());
+    }
+    if let Some(model) = &args.draft_model {
+        println!("[Draft] Generating every block with '{}'", model);
+    }
+    let started = SystemTime::now();
+    let mut run = generation.run().await;
+    // Replayed as the pipeline left it, before titles and translations join it
+    let untouched = match &run {
+        Ok(result) if args.determinism_check => Some(result.clone()),
+        _ => None,
+    };
+    if let Ok(result) = &mut run
+        && !title_tasks.is_empty()
+    {
+        println!("[Titles] Generating {} keys", title_tasks.len());
+        match generation.run_tasks(&title_tasks).await {
+            Ok(titles) => result.merge(titles),
+            Err(e) => run = Err(e),
+        }
+    }
+    let languages = if args.translate.is_empty() {
+        &config.translate
+    } else {
+        &args.translate
+    };
+    if let Ok(result) = &mut run
+        && !languages.is_empty()
+        && let Err(e) = generation.translate(result, languages).await
+    {
+        run = Err(e);
+    }
+    // Written before the error surfaces, so failed runs leave their log behind too
+    generation
+        .provenance(started, run.as_ref())
+        .write(&args.run_log)?;
+    println!("[Provenance] Wrote {}", args.run_log.display());
+    if let Some(dir) = &args.debug_dir {
+        let path = dir.join("warnings.log");
+        warnings::write_log(&path)?;
+        println!("[Debug] Wrote every warning to {}", path.display());
+    }
+    let result = run?;
+    if let Some(untouched) = &untouched {
+        generation.check_determinism(untouched).await?;
+        println!("[Determinism] A replay from the cache produced the same output as the run");
+    }
 
-            output.push(raw_line.to_string());
-            for kv in &ctx.kv_inserts {
-                let kv_indent = " ".repeat(indent + 4);
-                output.push(format!("{}{}", kv_indent, kv));
+    for (key, renamed) in &result.report.vanilla_collisions {
+        println!(
+            "[Vanilla] '{}' is a vanilla key, renamed to '{}'",
+            key, renamed
+        );
+    }
+    for (key, renamed) in &result.report.existing_collisions {
+        println!(
+            "[Existing Loc] '{}' is a key of the mod's localisation, renamed to '{}'",
+            key, renamed
+        );
+    }
+    println!("[Summary] generated -> kept per block");
+    for block in &result.report.blocks {
+        println!(
+            "[Summary] {:>5} -> {:<5} {}{}{}",
+            block.generated,
+            block.names,
+            block.path.join("/"),
+            block
+                .era
+                .as_ref()
+                .map(|era| format!(" ({} era)", era))
+                .unwrap_or_default(),
+            if block.budget_exhausted {
+                "  FAILED (budget)"
+            } else if block.failed {
+                "  FAILED"
+            } else {
+                ""
             }
-            stack.push(ctx);
+        );
+    }
+    for line in &result.report.budget_skipped {
+        println!("[Summary] {} left out, FAILED (budget)", line);
+    }
+    // Names may hold characters the terminal can't show
+    let console = Console::detect();
+    for block in result
+        .report
+        .blocks
+        .iter()
+        .filter(|block| !block.order.is_empty())
+    {
+        println!(
+            "[Order] {}: {}",
+            block.path.join("/"),
+            console.text(&block.order.join(", "))
+        );
+    }
+    for block in &result.report.blocks {
+        let Some(balance) = &block.distribution else {
             continue;
-        }
-
-        if trimmed == "}" {
-            if let Some(ctx) = stack.pop() {
-                if ctx.child_count == 0 && !ctx.has_data && ctx.theme.is_some() {
-                    let theme = ctx.theme.unwrap();
-                    let prefix = ctx.prefix.clone().unwrap_or_default();
-                    let filename = ctx.path.join("_");
-                    let cache_file = Path::new("cache").join(format!("{}.txt", filename));
-                    let entries =
-                        generate_localized_entries(&client, &cache_file, &lore, &theme, &prefix)
-                            .await?;
-                    for (key, val) in entries {
-                        output.push(format!("{}{},", " ".repeat(ctx.indent + 4), key));
-                        localisations.entry(key.clone()).or_insert(val);
-                    }
-                }
+        };
+        // Achieved against target per letter
+        let letters: Vec<String> = balance
+            .target
+            .iter()
+            .map(|(letter, target)| {
+                let achieved = balance.achieved.get(letter).copied().unwrap_or(0);
+                format!("{} {}/{}", letter, achieved, target)
+            })
+            .collect();
+        println!(
+            "[Distribution] {}: {}{}",
+            block.path.join("/"),
+            letters.join(", "),
+            match balance.off_target() {
+                0 => String::new(),
+                off => format!(" ({} short)", off),
             }
-            output.push(raw_line.to_string());
-            if let Some(parent) = stack.last_mut() {
-                parent.child_count += 1;
-                parent.has_data = true;
+        );
+    }
+    if args.verbose {
+        for block in result
+            .report
+            .blocks
+            .iter()
+            .filter(|block| !block.style_scores.is_empty())
+        {
+            let scores: Vec<String> = block
+                .style_scores
+                .iter()
+                .map(|(name, score)| format!("{} {}", name, score))
+                .collect();
+            let scores = console.text(&scores.join(", "));
+            println!("[Style] {}: {}", block.path.join("/"), scores);
+        }
+        for block in result
+            .report
+            .blocks
+            .iter()
+            .filter(|block| !block.diversity.clusters.is_empty())
+        {
+            let clusters: Vec<String> = block
+                .diversity
+                .clusters
+                .iter()
+                .map(|cluster| {
+                    format!(
+                        "{} {} ({})",
+                        cluster.label(),
+                        cluster.size,
+                        cluster.examples.join(", ")
+                    )
+                })
+                .collect();
+            println!(
+                "[Clusters] {}: diversity {:.2}, {}",
+                block.path.join("/"),
+                block.diversity.score,
+                console.text(&clusters.join("; "))
+            );
+        }
+    }
+    for block in result
+        .report
+        .blocks
+        .iter()
+        .filter(|block| block.salvaged > 0)
+    {
+        println!(
+            "[Partial] {}: {} of {} names salvaged from a cut-off answer",
+            block.path.join("/"),
+            block.salvaged,
+            block.generated
+        );
+    }
+    let common: usize = result
+        .report
+        .blocks
+        .iter()
+        .filter_map(|block| block.dropped.get("common"))
+        .sum();
+    if common > 0 {
+        println!("[Rarity] Filtered {} common-sounding names", common);
+    }
+    if let Some(budget) = &result.report.budget {
+        let limit = |max: Option<u64>| max.map_or("unlimited".to_string(), |max| max.to_string());
+        println!(
+            "[Budget] {} of {} requests, {} of {} tokens{}",
+            budget.requests,
+            limit(budget.max_requests),
+            budget.tokens,
+            limit(budget.max_tokens),
+            if budget.tokens_estimated {
+                " (estimated)"
+            } else {
+                ""
             }
-            continue;
+        );
+    }
+    if !result.report.errors.is_empty() {
+        let failures: Vec<String> = result
+            .report
+            .errors
+            .iter()
+            .map(|(kind, count)| format!("{} {}", count, kind))
+            .collect();
+        println!("[Health] Failed model calls: {}", failures.join(", "));
+    }
+    if !result.report.coalesced.is_empty() {
+        println!(
+            "[Coalesce] Saved {} calls on blocks asking for the same names",
+            result.report.coalesced.len()
+        );
+    }
+    if !result.report.aliased.is_empty() {
+        println!(
+            "[Alias] Moved the cache of {} renamed blocks",
+            result.report.aliased.len()
+        );
+    }
+    let violations = &result.report.charset_violations;
+    if !violations.is_empty() {
+        let stripped = violations.iter().filter(|v| v.stripped).count();
+        println!(
+            "[Charset] {} values hold characters the game can't show, {} of them stripped",
+            violations.len(),
+            stripped
+        );
+    }
+    if result.report.quota_wait_ms > 0 {
+        println!(
+            "[Quota] Waited {} for the quota to reset, left out of the block durations",
+            format_wait(Duration::from_millis(result.report.quota_wait_ms))
+        );
+    }
+    if !result.report.cross_duplicates.is_empty() {
+        println!("[Duplicates] name -> blocks");
+        for (name, blocks) in &result.report.cross_duplicates {
+            println!(
+                "[Duplicates] {:<24} {}",
+                console.text(name),
+                blocks.join(", ")
+            );
         }
-
-        output.push(raw_line.to_string());
-        if let Some(ctx) = stack.last_mut() {
-            if trimmed.contains('=') || trimmed.contains(',') {
-                ctx.has_data = true;
+    }
+    if let Some(min) = args.fail_on_thin {
+        result.report.check_thin(min)?;
+    }
+    if let Some(category) = &args.fail_on_cross_duplicates {
+        result.report.check_cross_duplicates(category)?;
+    }
+    let layout = run_layout(&args, manifest.as_ref(), namespace.as_deref());
+    // Drafts only preview their names, beside the final outputs
+    if args.draft_model.is_some() {
+        let preview = draft_path(
+            layout
+                .markdown
+                .as_deref()
+                .unwrap_or(&MarkdownWriter::default().path),
+        );
+        MarkdownWriter {
+            path: preview.clone(),
+            ..MarkdownWriter::default()
+        }
+        .write(&result)?;
+        if let Some(path) = &layout.report {
+            ReportWriter {
+                path: draft_path(path),
             }
+            .write(&result)?;
         }
+        println!(
+            "[Draft] Wrote {}, promote the blocks worth keeping with `promote --blocks <glob>`",
+            preview.display()
+        );
+        println!("Completed in {:.2?}", start.elapsed());
+        return Ok(());
     }
-
-    fs::write("out.txt", output.join("\n")).context("Failed to write out.txt")?;
-    let mut loc_out = String::from("l_english:\n");
-    for (key, val) in &localisations {
-        loc_out.push_str(&format!("    {}:0 \"{}\"\n", key, val));
+    // Read before the writers overwrite the previous output
+    let changelog_path = args.changelog.as_ref().map(|path| match &namespace {
+        Some(namespace) => tier_path(path, namespace),
+        None => path.clone(),
+    });
+    let previous = match &changelog_path {
+        Some(_) => Some(previous_names(&layout, &result.value_format)?),
+        None => None,
+    };
+    let split = layout.loc_split.unwrap_or_default();
+    let mut writers: Vec<Box<dyn OutputWriter>> = vec![
+        Box::new(NameListWriter {
+            path: layout.name_list,
+            in_place: args.in_place,
+        }),
+        Box::new(LocalisationWriter {
+            path: layout.localisation,
+            split,
+        }),
+    ];
+    if let Some(path) = layout.markdown {
+        writers.push(Box::new(MarkdownWriter {
+            path,
+            ..MarkdownWriter::default()
+        }));
+    }
+    if let Some(path) = layout.report {
+        writers.push(Box::new(ReportWriter { path }));
+    }
+    if let Some(path) = layout.sqlite {
+        writers.push(Box::new(SqliteWriter { path }));
+    }
+    if let Some(path) = layout.reverse {
+        writers.push(Box::new(ReverseLookupWriter { path }));
+    }
+    if let Some(mod_info) = mod_info(&args, manifest.as_ref(), namespace.as_deref()) {
+        writers.push(Box::new(ModWriter {
+            name: mod_info.name,
+            dir: mod_info.dir.unwrap_or_else(|| PathBuf::from("mod")),
+            prune: args.prune_output.then_some(Prune {
+                keep_unknown: args.keep_unknown,
+            }),
+            in_place: args.in_place,
+            split,
+        }));
+    }
+    for writer in &writers {
+        writer.write(&result)?;
+    }
+    // Saved once every output is written, so the map never gets ahead of them. Experiments in a
+    // namespace read the keys but never retire or add any
+    if namespace.is_some() {
+        println!("[Keymap] Left as is, the run is in a cache namespace");
+    } else {
+        let changes = key_map.update(&result);
+        key_map.save(&args.keymap)?;
+        println!(
+            "[Keymap] {} names kept their keys, {} keyed anew, {} keys retired",
+            changes.kept, changes.added, changes.retired
+        );
+    }
+    if let (Some(path), Some(previous)) = (&changelog_path, previous) {
+        let changes = changelog::changes(&previous, &result);
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs())
+            .unwrap_or_default();
+        changelog::append(path, &changelog::render_section(now, &result, &changes))?;
+        println!(
+            "[Changelog] {} themes changed, appended to {}",
+            changes.len(),
+            path.display()
+        );
     }
-    fs::write("localisation.txt", loc_out).context("Failed to write localisation.txt")?;
 
     println!("Completed in {:.2?}", start.elapsed());
     Ok(())
@@ -0,0 +1,514 @@
+//! `namegen.manifest.toml`, a whole project in one file: the block tree, the settings of
+//! `namegen.toml`, the mod and the output layout.
+//!
+//! Blocks are tables under `[blocks]`, with their directives as fields and their child blocks
+//! under `children`, so a child can be called like a directive. The tree is read into the same
+//! structure a `file_structure.txt` is, so both forms of a project produce the same output:
+//!
+//! ```
+//! use stellaris_name_gen::manifest::Manifest;
+//! use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+//!
+//! let structure = r#"NAME = {
+//!     motto = "{{generate: imperial motto}}"
+//!     #@ prefix: VOID_
+//!     ship_names = {
+//!         ## weight = 50
+//!         ## Warship names
+//!         ## Short and menacing
+//!         military = {
+//!         }
+//!         #@ count: 2
+//!         #@ translatable
+//!         ## Civilian ship names
+//!         civilian = {
+//!         }
+//!     }
+//! }
+//! "#;
+//! let manifest = Manifest::parse(r#"
+//! model = "local-model"
+//! min-names = 1
+//!
+//! [mod]
+//! name = "Void Names"
+//!
+//! [blocks.NAME]
+//! lines = ['motto = "{{generate: imperial motto}}"']
+//!
+//! [blocks.NAME.children.ship_names]
+//! prefix = "VOID_"
+//!
+//! [blocks.NAME.children.ship_names.children.military]
+//! theme = """
+//! Warship names
+//! Short and menacing"""
+//! inserts = ["weight = 50"]
+//!
+//! [blocks.NAME.children.ship_names.children.civilian]
+//! theme = "Civilian ship names"
+//! count = 2
+//! translatable = true
+//! "#).unwrap();
+//! assert_eq!(manifest.mod_info.as_ref().unwrap().name, "Void Names");
+//!
+//! let run = |structure: String| {
+//!     futures::executor::block_on(
+//!         Generation::builder()
+//!             .structure(structure)
+//!             .lore("")
+//!             .config(&manifest.settings)
+//!             .generator(MockGenerator::new(["Vex", "Ember", "Ash"]))
+//!             .cache(InMemoryCache::new())
+//!             .run(),
+//!     )
+//!     .unwrap()
+//! };
+//! let from_structure = run(structure.to_string());
+//! let from_manifest = run(manifest.structure().unwrap());
+//! assert_eq!(from_manifest.name_list, from_structure.name_list);
+//! assert_eq!(from_manifest.localisation, from_structure.localisation);
+//! assert_eq!(from_manifest.report.blocks[1].names, 2);
+//!
+//! // An existing structure file converts into an equivalent manifest
+//! let converted = Manifest::from_structure(structure, manifest.settings.clone()).unwrap();
+//! let reread = Manifest::parse(&converted.to_toml().unwrap()).unwrap();
+//! assert_eq!(run(reread.structure().unwrap()).name_list, from_structure.name_list);
+//!
+//! // A child named like a directive is still a block, a misspelled directive fails
+//! let named = Manifest::parse("[blocks.NAME.children.model]\ntheme = \"Model names\"").unwrap();
+//! assert!(named.structure().unwrap().contains("    # Model names\n    model = {\n"));
+//! let misspelled = Manifest::parse("[blocks.NAME]\nthemes = \"Names\"").unwrap();
+//! assert!(misspelled.structure().is_err());
+//! ```
+
+use crate::config::Config;
+use crate::directives::{Directives, parse_anchor, parse_flag};
+use crate::loc_split::LocSplit;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A project declared in one file
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct Manifest {
+    /// Every `namegen.toml` setting, at the top level of the manifest
+    #[serde(flatten)]
+    pub settings: Config,
+    /// The `[mod]` table, packaging the output as a mod when present
+    #[serde(rename = "mod", skip_serializing_if = "Option::is_none")]
+    pub mod_info: Option<ModInfo>,
+    /// Where the outputs are written
+    pub output: OutputLayout,
+    /// The block tree, each top-level table is a top-level block of the name list
+    pub blocks: toml::Table,
+}
+
+/// The `[mod]` table
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct ModInfo {
+    pub name: String,
+    /// Folder the mod is written into, `mod` by default
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dir: Option<PathBuf>,
+}
+
+/// The `[output]` table
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct OutputLayout {
+    pub name_list: PathBuf,
+    pub localisation: PathBuf,
+    /// Also write the markdown preview here
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub markdown: Option<PathBuf>,
+    /// Also write the run report here
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub report: Option<PathBuf>,
+    /// Also upsert the names into this SQLite database
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sqlite: Option<PathBuf>,
+    /// Also write the name to key lookup here
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub reverse: Option<PathBuf>,
+    /// Spread the localisation over several files, see [`crate::loc_split`]
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub loc_split: Option<LocSplit>,
+}
+
+impl Default for OutputLayout {
+    fn default() -> Self {
+        Self {
+            name_list: PathBuf::from("out.txt"),
+            localisation: PathBuf::from("localisation.txt"),
+            markdown: None,
+            report: None,
+            sqlite: None,
+            reverse: None,
+            loc_split: None,
+        }
+    }
+}
+
+/// A block of the tree, its directives as fields and its child blocks under `children`
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, deny_unknown_fields, rename_all = "kebab-case")]
+pub struct ManifestBlock {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub theme: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prefix: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub lore: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub count: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub sort: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub style: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub distribution: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub decorate: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub avoid_initials: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub score_filter: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schema: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub key_style: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub abbreviate: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub augment_inline: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub translatable: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub species_class: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub era: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub rarity: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ordinal_expand: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compose: Option<String>,
+    /// `# key = value` inserts, ie `weight = 50` or `randomized = no`
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub inserts: Vec<String>,
+    /// `# if-generated: key = value` inserts, written only if the block emits names
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub if_generated: Vec<String>,
+    /// Paths the block had before it was renamed, its `# alias:` lines
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+    /// Lines of the block written as is, ahead of its child blocks
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub lines: Vec<String>,
+    /// Child blocks by key, in order
+    #[serde(skip_serializing_if = "toml::Table::is_empty")]
+    pub children: toml::Table,
+}
+
+impl ManifestBlock {
+    /// Directives as `#@` lines, in the order they are written to the structure
+    fn directives(&self) -> Vec<String> {
+        let flag = |enabled: bool| if enabled { "" } else { ": no" };
+        let mut lines = Vec::new();
+        let mut push = |name: &str, value: Option<String>| {
+            if let Some(value) = value {
+                lines.push(format!("#@ {}{}", name, value));
+            }
+        };
+        push("prefix", self.prefix.as_ref().map(|v| format!(": {}", v)));
+        push("model", self.model.as_ref().map(|v| format!(": {}", v)));
+        push("lore", self.lore.as_ref().map(|v| format!(": {}", v)));
+        push("min", self.min.map(|v| format!(": {}", v)));
+        push("count", self.count.map(|v| format!(": {}", v)));
+        push("sort", self.sort.as_ref().map(|v| format!(": {}", v)));
+        push("style", self.style.as_ref().map(|v| format!(": {}", v)));
+        push(
+            "distribution",
+            self.distribution.as_ref().map(|v| format!(": {}", v)),
+        );
+        push(
+            "decorate",
+            self.decorate.as_ref().map(|v| format!(": {}", v)),
+        );
+        push(
+            "avoid-initials",
+            self.avoid_initials.as_ref().map(|v| format!(": {}", v)),
+        );
+        push(
+            "score-filter",
+            self.score_filter.as_ref().map(|v| format!(": {}", v)),
+        );
+        push("schema", self.schema.as_ref().map(|v| format!(": {}", v)));
+        push(
+            "key-style",
+            self.key_style.as_ref().map(|v| format!(": {}", v)),
+        );
+        push("abbreviate", self.abbreviate.map(|v| flag(v).to_string()));
+        push(
+            "augment-inline",
+            self.augment_inline.map(|v| flag(v).to_string()),
+        );
+        push(
+            "translatable",
+            self.translatable.map(|v| flag(v).to_string()),
+        );
+        push(
+            "species-class",
+            self.species_class.as_ref().map(|v| format!(": {}", v)),
+        );
+        push("era", self.era.as_ref().map(|v| format!(": {}", v)));
+        push("rarity", self.rarity.as_ref().map(|v| format!(": {}", v)));
+        push(
+            "ordinal-expand",
+            self.ordinal_expand.as_ref().map(|v| format!(": {}", v)),
+        );
+        push("compose", self.compose.as_ref().map(|v| format!(": {}", v)));
+        lines
+    }
+
+    /// Child blocks, failing on children that are not a table
+    fn child_blocks(&self, path: &str) -> Result<Vec<(&String, ManifestBlock)>> {
+        self.children
+            .iter()
+            .map(|(key, value)| {
+                if !value.is_table() {
+                    anyhow::bail!("Child '{}' of block '{}' is not a table", key, path);
+                }
+                let block = value
+                    .clone()
+                    .try_into()
+                    .with_context(|| format!("Invalid block '{}/{}'", path, key))?;
+                Ok((key, block))
+            })
+            .collect()
+    }
+
+    /// Writes the block and its children as structure lines
+    fn render(&self, key: &str, path: &str, depth: usize, out: &mut String) -> Result<()> {
+        let indent = "    ".repeat(depth);
+        for directive in self.directives() {
+            out.push_str(&format!("{}{}\n", indent, directive));
+        }
+        for insert in &self.inserts {
+            out.push_str(&format!("{}# {}\n", indent, insert));
+        }
+        for insert in &self.if_generated {
+            out.push_str(&format!("{}#@ if-generated: {}\n", indent, insert));
+        }
+        for alias in &self.aliases {
+            out.push_str(&format!("{}#@ alias: {}\n", indent, alias));
+        }
+        if let Some(theme) = &self.theme {
+            let lines: Vec<&str> = theme
+                .lines()
+                .map(str::trim)
+                .filter(|line| !line.is_empty())
+                .collect();
+            // Theme lines reading as directives or inserts would change the block
+            let mut check = Directives::default();
+            for line in &lines {
+                check.record(line)?;
+            }
+            if check.theme.as_deref() != Some(lines.join("\n").as_str()) {
+                anyhow::bail!(
+                    "The theme of '{}' has a line that reads as a directive or insert",
+                    path
+                );
+            }
+            for line in lines {
+                out.push_str(&format!("{}# {}\n", indent, line));
+            }
+        }
+        out.push_str(&format!("{}{} = {{\n", indent, key));
+        for line in &self.lines {
+            out.push_str(&format!("{}    {}\n", indent, line));
+        }
+        for (child_key, child) in self.child_blocks(path)? {
+            child.render(
+                child_key,
+                &format!("{}/{}", path, child_key),
+                depth + 1,
+                out,
+            )?;
+        }
+        out.push_str(&format!("{}}}\n", indent));
+        Ok(())
+    }
+
+    /// A block from the directives preceding it in a structure file
+    fn from_directives(directives: Directives, path: &str) -> Result<Self> {
+        let flag = |value: Option<String>, name: &str| {
+            value
+                .map(|value| {
+                    parse_flag(&value).with_context(|| format!("Invalid {} for '{}'", name, path))
+                })
+                .transpose()
+        };
+        let number = |value: Option<String>, name: &str| {
+            value
+                .map(|value| {
+                    value
+                        .parse::<usize>()
+                        .with_context(|| format!("Invalid {} '{}' for '{}'", name, value, path))
+                })
+                .transpose()
+        };
+        Ok(Self {
+            theme: directives.theme,
+            prefix: directives.prefix,
+            model: directives.model,
+            lore: directives.lore,
+            min: number(directives.min, "min")?,
+            count: number(directives.count, "count")?,
+            sort: directives.sort,
+            style: directives.style,
+            distribution: directives.distribution,
+            decorate: directives.decorate,
+            avoid_initials: directives.avoid_initials,
+            score_filter: directives.score_filter,
+            schema: directives.schema,
+            key_style: directives.key_style,
+            abbreviate: flag(directives.abbreviate, "abbreviate")?,
+            augment_inline: flag(directives.augment_inline, "augment-inline")?,
+            translatable: flag(directives.translatable, "translatable")?,
+            species_class: directives.species_class,
+            era: directives.era,
+            rarity: directives.rarity,
+            ordinal_expand: directives.ordinal_expand,
+            compose: directives.compose,
+            inserts: directives.kv_inserts,
+            if_generated: directives.if_generated,
+            aliases: directives.aliases,
+            lines: Vec::new(),
+            children: toml::Table::new(),
+        })
+    }
+}
+
+impl Manifest {
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let manifest =
+            Self::parse(&text).with_context(|| format!("Failed to parse {}", path.display()))?;
+        crate::providers::validate(&manifest.settings.providers)
+            .with_context(|| format!("Invalid {}", path.display()))?;
+        Ok(manifest)
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        Ok(toml::from_str(text)?)
+    }
+
+    /// The manifest as TOML
+    pub fn to_toml(&self) -> Result<String> {
+        Ok(toml::to_string(self)?)
+    }
+
+    /// The block tree written as a structure file
+    pub fn structure(&self) -> Result<String> {
+        let root = ManifestBlock {
+            children: self.blocks.clone(),
+            ..ManifestBlock::default()
+        };
+        let mut out = String::new();
+        for (key, block) in root.child_blocks("blocks")? {
+            block.render(key, key, 0, &mut out)?;
+        }
+        Ok(out)
+    }
+
+    /// A manifest declaring the blocks of a structure file and `settings`. Comments other than
+    /// directives and themes, and blank lines, are not carried over
+    pub fn from_structure(structure: &str, settings: Config) -> Result<Self> {
+        // Blocks keep their directives while only comments follow their opening line, the
+        // comments may still be theirs
+        let mut stack: Vec<(String, String, ManifestBlock, Option<Directives>)> = Vec::new();
+        let mut pending = Directives::default();
+        let mut blocks = toml::Table::new();
+        for (line_no, raw_line) in structure.lines().enumerate() {
+            let context = || format!("file_structure.txt:{}", line_no + 1);
+            let trimmed = raw_line.trim();
+            // Anchors stay where they are, kept as lines of their block
+            if let Some(comment) = trimmed.strip_prefix('#')
+                && parse_anchor(comment).with_context(context)?.is_none()
+            {
+                pending.record(comment).with_context(context)?;
+                continue;
+            }
+            pending.end_theme();
+            if trimmed.ends_with('{') {
+                let key = trimmed
+                    .split_once('=')
+                    .map(|(a, _)| a.trim())
+                    .unwrap_or(trimmed)
+                    .to_string();
+                let path = match stack.last_mut() {
+                    Some((_, parent, _, fresh)) => {
+                        *fresh = None;
+                        format!("{}/{}", parent, key)
+                    }
+                    None => key.clone(),
+                };
+                let directives = std::mem::take(&mut pending);
+                let block = ManifestBlock::from_directives(directives.clone(), &path)?;
+                stack.push((key, path, block, Some(directives)));
+                continue;
+            }
+            // Comments written first inside a block are its own, see `structure::settle`
+            if !trimmed.is_empty()
+                && let Some((_, path, block, fresh)) = stack.last_mut()
+                && let Some(mut directives) = fresh.take()
+            {
+                directives.apply_inside(std::mem::take(&mut pending));
+                *block = ManifestBlock::from_directives(directives, path)?;
+            }
+            if trimmed == "}" {
+                let Some((key, _, block, _)) = stack.pop() else {
+                    anyhow::bail!("{}: unmatched '}}'", context());
+                };
+                let value = toml::Value::try_from(block)?;
+                match stack.last_mut() {
+                    Some((_, _, parent, _)) => parent.children.insert(key, value),
+                    None => blocks.insert(key, value),
+                };
+                continue;
+            }
+            if trimmed.is_empty() {
+                continue;
+            }
+            let Some((_, path, block, _)) = stack.last_mut() else {
+                anyhow::bail!("{}: manifests only hold lines inside blocks", context());
+            };
+            if !block.children.is_empty() {
+                anyhow::bail!(
+                    "{}: '{}' has a line after a child block, which a manifest can't keep in order",
+                    context(),
+                    path
+                );
+            }
+            block.lines.push(trimmed.to_string());
+        }
+        if let Some((_, path, _, _)) = stack.last() {
+            anyhow::bail!("'{}' is never closed", path);
+        }
+        Ok(Self {
+            settings,
+            blocks,
+            ..Self::default()
+        })
+    }
+}
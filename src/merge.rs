@@ -0,0 +1,258 @@
+//! Merges the cache of one run into another block by block, ie a run with a hosted model into
+//! a run with a local one, so the next run emits the union of both. Names are combined without
+//! case-insensitive repeats, the target's first, and the entry records both sources. Blocks
+//! generated from different prompts are left alone unless forced:
+//!
+//! ```
+//! use stellaris_name_gen::merge::merge_caches;
+//! use stellaris_name_gen::{BlockKey, CachedBlock, InMemoryCache, NameCache};
+//!
+//! let block = |names: &str, model: &str, prompt: &str| CachedBlock {
+//!     raw: format!(r#"{{"names": [{}]}}"#, names),
+//!     model: Some(model.to_string()),
+//!     prompt_hash: Some(prompt.to_string()),
+//!     ..CachedBlock::default()
+//! };
+//! let mut local = InMemoryCache::new();
+//! let mut hosted = InMemoryCache::new();
+//! local.put(&BlockKey::from("NAME_ships"), block(r#""Vex", "Ember""#, "llama3", "a1")).unwrap();
+//! hosted.put(&BlockKey::from("NAME_ships"), block(r#""ember", "Cinder""#, "gemini", "a1")).unwrap();
+//! hosted.put(&BlockKey::from("NAME_fleets"), block(r#""Ash""#, "gemini", "b1")).unwrap();
+//! local.put(&BlockKey::from("NAME_armies"), block(r#""Legion""#, "llama3", "c1")).unwrap();
+//! hosted.put(&BlockKey::from("NAME_armies"), block(r#""Cohort""#, "gemini", "c2")).unwrap();
+//!
+//! let merge = merge_caches(&hosted, "cache-gemini", &mut local, "cache", false).unwrap();
+//! // Overlapping names are kept once, in the target's spelling
+//! assert_eq!(merge.merged[0].key, "NAME_ships");
+//! assert_eq!(merge.merged[0].added, ["Cinder"]);
+//! let ships = local.get(&BlockKey::from("NAME_ships")).unwrap();
+//! assert_eq!(ships.raw, r#"{"names":["Vex","Ember","Cinder"]}"#);
+//! assert_eq!(ships.sources, ["cache: llama3", "cache-gemini: gemini"]);
+//! // Blocks only the source holds are copied as they are
+//! assert_eq!(merge.copied, ["NAME_fleets"]);
+//! assert!(local.get(&BlockKey::from("NAME_fleets")).is_some());
+//! // Another prompt means another theme, the target keeps its names
+//! assert_eq!(merge.mismatched, ["NAME_armies"]);
+//! assert!(!local.get(&BlockKey::from("NAME_armies")).unwrap().raw.contains("Cohort"));
+//!
+//! let forced = merge_caches(&hosted, "cache-gemini", &mut local, "cache", true).unwrap();
+//! assert_eq!(forced.merged[0].key, "NAME_armies");
+//! assert_eq!(forced.unchanged, 2);
+//! assert!(local.get(&BlockKey::from("NAME_armies")).unwrap().raw.contains("Cohort"));
+//! ```
+//!
+//! Runs sample their own flavors, which change the prompt. Entries recording the prompt without
+//! them compare that instead, older entries of other flavors are taken for another theme:
+//!
+//! ```
+//! use stellaris_name_gen::merge::merge_caches;
+//! use stellaris_name_gen::{BlockKey, CachedBlock, InMemoryCache, NameCache};
+//!
+//! let block = |name: &str, flavor: &str, theme: Option<&str>| CachedBlock {
+//!     raw: format!(r#"{{"names": ["{}"]}}"#, name),
+//!     prompt_hash: Some(format!("{}-{}", theme.unwrap_or("old"), flavor)),
+//!     theme_hash: theme.map(String::from),
+//!     flavors: vec![flavor.to_string()],
+//!     ..CachedBlock::default()
+//! };
+//! let mut local = InMemoryCache::new();
+//! let mut hosted = InMemoryCache::new();
+//! local.put(&BlockKey::from("NAME_ships"), block("Vex", "harsh", Some("t1"))).unwrap();
+//! hosted.put(&BlockKey::from("NAME_ships"), block("Cinder", "soft", Some("t1"))).unwrap();
+//! local.put(&BlockKey::from("NAME_fleets"), block("Ash", "harsh", Some("t1"))).unwrap();
+//! hosted.put(&BlockKey::from("NAME_fleets"), block("Dawn", "harsh", Some("t2"))).unwrap();
+//! local.put(&BlockKey::from("NAME_armies"), block("Legion", "harsh", None)).unwrap();
+//! hosted.put(&BlockKey::from("NAME_armies"), block("Cohort", "soft", None)).unwrap();
+//!
+//! let merge = merge_caches(&hosted, "cache-gemini", &mut local, "cache", false).unwrap();
+//! assert_eq!(merge.merged.len(), 1);
+//! assert_eq!(merge.merged[0].key, "NAME_ships");
+//! assert_eq!(merge.mismatched, ["NAME_armies", "NAME_fleets"]);
+//! ```
+//!
+//! A run that sends no request then reads the combined lists:
+//!
+//! ```
+//! use stellaris_name_gen::merge::merge_caches;
+//! use stellaris_name_gen::{BlockKey, CachedBlock, Generation, InMemoryCache, MockGenerator, NameCache};
+//!
+//! let mut local = InMemoryCache::new();
+//! let mut hosted = InMemoryCache::new();
+//! local.put(&BlockKey::from("NAME_ships"), CachedBlock::new(r#"{"names": ["Vex", "Ember"]}"#)).unwrap();
+//! hosted.put(&BlockKey::from("NAME_ships"), CachedBlock::new(r#"{"names": ["Cinder", "VEX"]}"#)).unwrap();
+//! merge_caches(&hosted, "cache-gemini", &mut local, "cache", false).unwrap();
+//!
+//! let result = futures::executor::block_on(
+//!     Generation::builder()
+//!         .structure("NAME = {\n    # Ship names\n    ships = {\n    }\n}\n")
+//!         .lore("")
+//!         .min_names(1)
+//!         .budget_requests(0)
+//!         .generator(MockGenerator::new(["Never"]))
+//!         .cache(local)
+//!         .run(),
+//! )
+//! .unwrap();
+//! let names: Vec<&str> = result.localisation.iter().map(|(_, name)| name.as_str()).collect();
+//! assert_eq!(names, ["Vex", "Ember", "Cinder"]);
+//! ```
+
+use crate::cache::{CachedBlock, NameCache};
+use crate::json::{GenerativeAIOutput, parse_items, parse_value};
+use anyhow::Result;
+use serde::Serialize;
+use std::collections::HashSet;
+
+/// Names a block gained from the source
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MergedBlock {
+    pub key: String,
+    /// Names only the source held, in its order
+    pub added: Vec<String>,
+}
+
+/// Outcome of [`merge_caches`]
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CacheMerge {
+    /// Keys the target had no entry for, copied from the source
+    pub copied: Vec<String>,
+    /// Blocks that gained names
+    pub merged: Vec<MergedBlock>,
+    /// Entries of both caches the source added nothing to, inline values, scores and
+    /// translations included
+    pub unchanged: usize,
+    /// Blocks generated from different prompts on either side, left as the target had them
+    pub mismatched: Vec<String>,
+    /// Entries that could not be read, as `<key>: <error>`
+    pub errors: Vec<String>,
+}
+
+/// Where the names of an entry came from, its recorded sources or else its cache and model
+fn sources(block: &CachedBlock, label: &str) -> Vec<String> {
+    if !block.sources.is_empty() {
+        return block.sources.clone();
+    }
+    vec![match &block.model {
+        Some(model) => format!("{}: {}", label, model),
+        None => label.to_string(),
+    }]
+}
+
+/// Merges every entry of `from` into `into`. Entries `into` lacks are copied, blocks both hold
+/// get the names only `from` has appended, unless they were generated for different themes and
+/// `force_mismatched` is off. Themes are told apart by [`CachedBlock::theme_hash`], or for
+/// entries without one by their prompts, where differing flavors count as a mismatch too.
+/// Derived entries, ie scores, and inline values are only copied.
+/// The labels name the two caches in the merged entries' sources
+pub fn merge_caches(
+    from: &dyn NameCache,
+    from_label: &str,
+    into: &mut dyn NameCache,
+    into_label: &str,
+    force_mismatched: bool,
+) -> Result<CacheMerge> {
+    let mut merge = CacheMerge::default();
+    for key in from.keys()? {
+        let Some(incoming) = from.get(&key) else {
+            continue;
+        };
+        let Some(mut existing) = into.get(&key) else {
+            into.put(&key, incoming)?;
+            merge.copied.push(key.as_str().to_string());
+            continue;
+        };
+        if key.as_str().contains('~') {
+            merge.unchanged += 1;
+            continue;
+        }
+        let (names, others) = match (parse_items(&existing.raw), parse_items(&incoming.raw)) {
+            (Ok(names), Ok(others)) => (names, others),
+            (Err(e), _) | (_, Err(e)) => {
+                // Inline values hold a single value, there is nothing to combine
+                if parse_value(&existing.raw).is_ok() && parse_value(&incoming.raw).is_ok() {
+                    merge.unchanged += 1;
+                } else {
+                    merge.errors.push(format!("{}: {:#}", key.as_str(), e));
+                }
+                continue;
+            }
+        };
+        let mismatched = match (&existing.theme_hash, &incoming.theme_hash) {
+            (Some(ours), Some(theirs)) => ours != theirs,
+            // Sampled flavors change the prompt too, prompts of other flavors can't tell a
+            // theme apart and are taken for another one
+            _ => {
+                existing.flavors != incoming.flavors
+                    || matches!(
+                        (&existing.prompt_hash, &incoming.prompt_hash),
+                        (Some(ours), Some(theirs)) if ours != theirs
+                    )
+            }
+        };
+        if mismatched && !force_mismatched {
+            merge.mismatched.push(key.as_str().to_string());
+            continue;
+        }
+        let mut known: HashSet<String> = names
+            .iter()
+            .map(|item| item.name().trim().to_lowercase())
+            .collect();
+        let added: Vec<_> = others
+            .into_iter()
+            .filter(|item| known.insert(item.name().trim().to_lowercase()))
+            .collect();
+        if added.is_empty() {
+            merge.unchanged += 1;
+            continue;
+        }
+        merge.merged.push(MergedBlock {
+            key: key.as_str().to_string(),
+            added: added.iter().map(|item| item.name().to_string()).collect(),
+        });
+        let mut combined = sources(&existing, into_label);
+        for source in sources(&incoming, from_label) {
+            if !combined.contains(&source) {
+                combined.push(source);
+            }
+        }
+        existing.raw = serde_json::to_string(&GenerativeAIOutput {
+            names: names.into_iter().chain(added).collect(),
+        })?;
+        existing.sources = combined;
+        into.put(&key, existing)?;
+    }
+    Ok(merge)
+}
+
+/// Renders a merge for reading, the blocks that gained names with their new names, then the
+/// blocks left alone
+pub fn render_merge(merge: &CacheMerge) -> String {
+    let mut out = String::new();
+    for block in &merge.merged {
+        out.push_str(&format!("~ {}  +{}\n", block.key, block.added.len()));
+        for name in &block.added {
+            out.push_str(&format!("    + {}\n", name));
+        }
+    }
+    for key in &merge.copied {
+        out.push_str(&format!("> {}  copied\n", key));
+    }
+    for key in &merge.mismatched {
+        out.push_str(&format!(
+            "! {}  generated from another prompt, skipped (--force-mismatched merges it)\n",
+            key
+        ));
+    }
+    for error in &merge.errors {
+        out.push_str(&format!("! {}\n", error));
+    }
+    out.push_str(&format!(
+        "{} merged, {} copied, {} unchanged, {} mismatched, {} unreadable\n",
+        merge.merged.len(),
+        merge.copied.len(),
+        merge.unchanged,
+        merge.mismatched.len(),
+        merge.errors.len()
+    ));
+    out
+}
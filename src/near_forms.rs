@@ -0,0 +1,135 @@
+//! Detects near forms of a name within a block, ie `Tyran` and `Tyrans` or `Korrath` and
+//! `Korrath's Fury`, whose keys read as accidental duplicates in game.
+//!
+//! ```
+//! use stellaris_name_gen::near_forms::{NearFormPolicy, is_near_form, resolve};
+//!
+//! assert!(is_near_form("Tyran", "Tyrans"));
+//! assert!(is_near_form("Korrath", "Korrath's Fury"));
+//! // A shared prefix alone is not enough
+//! assert!(!is_near_form("Mars", "Marsh"));
+//! assert!(!is_near_form("Korr", "Korrath's Fury"));
+//!
+//! let mut names = vec!["Tyran".to_string(), "Marsh".to_string(), "Tyrans".to_string(), "Mars".to_string()];
+//! let pairs = resolve(&mut names, NearFormPolicy::Longer);
+//! assert_eq!(names, vec!["Marsh", "Tyrans", "Mars"]);
+//! assert_eq!(pairs.len(), 1);
+//! assert_eq!(pairs[0].dropped.as_deref(), Some("Tyran"));
+//! ```
+//!
+//! Pairs found in a run and the decision taken land in the block's report:
+//!
+//! ```
+//! use stellaris_name_gen::near_forms::NearFormPolicy;
+//! use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+//!
+//! let result = futures::executor::block_on(
+//!     Generation::builder()
+//!         .structure("NAME = {\n    ## Leader names\n    leaders = {\n    }\n}\n")
+//!         .lore("")
+//!         .generator(MockGenerator::new(["Korrath", "Korrath's Fury", "Vex"]))
+//!         .near_forms(NearFormPolicy::Flag)
+//!         .cache(InMemoryCache::new())
+//!         .run(),
+//! )
+//! .unwrap();
+//! let block = &result.report.blocks[0];
+//! assert_eq!(block.names, 3);
+//! assert_eq!(block.near_forms.len(), 1);
+//! assert_eq!(block.near_forms[0].longer, "Korrath's Fury");
+//! assert_eq!(block.near_forms[0].dropped, None);
+//! ```
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+
+/// What happens to the two names of a near form pair
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum NearFormPolicy {
+    /// Near forms are not looked for
+    #[default]
+    Off,
+    /// Both names are kept, the pair is only reported
+    Flag,
+    /// The longer name is kept
+    Longer,
+    /// The shorter name is kept
+    Shorter,
+}
+
+impl NearFormPolicy {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            NearFormPolicy::Off => "off",
+            NearFormPolicy::Flag => "flag",
+            NearFormPolicy::Longer => "longer",
+            NearFormPolicy::Shorter => "shorter",
+        }
+    }
+}
+
+/// A flagged pair and the decision taken on it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct NearForm {
+    pub shorter: String,
+    pub longer: String,
+    /// The name removed, `None` if both were kept
+    pub dropped: Option<String>,
+}
+
+/// Whether `longer` is `shorter` with a trailing `s` or `'s`, or starts with `shorter` (or its
+/// possessive) as a whole leading word. Case-insensitive
+pub fn is_near_form(shorter: &str, longer: &str) -> bool {
+    let (shorter, longer) = (shorter.trim().to_lowercase(), longer.trim().to_lowercase());
+    if shorter.is_empty() {
+        return false;
+    }
+    let Some(rest) = longer.strip_prefix(&shorter) else {
+        return false;
+    };
+    let rest = rest.replace('’', "'");
+    matches!(rest.as_str(), "s" | "'s")
+        || ["'s ", " "].iter().any(|lead| {
+            rest.strip_prefix(lead)
+                .is_some_and(|tail| !tail.trim().is_empty())
+        })
+}
+
+/// Finds the near form pairs among `names` and applies `policy`, keeping the order of the
+/// remaining names. A name dropped by one pair takes part in no further pair
+pub fn resolve(names: &mut Vec<String>, policy: NearFormPolicy) -> Vec<NearForm> {
+    let mut pairs = Vec::new();
+    if policy == NearFormPolicy::Off {
+        return pairs;
+    }
+    let mut dropped = HashSet::new();
+    for shorter in 0..names.len() {
+        for longer in 0..names.len() {
+            if shorter == longer
+                || dropped.contains(&shorter)
+                || dropped.contains(&longer)
+                || !is_near_form(&names[shorter], &names[longer])
+            {
+                continue;
+            }
+            let removed = match policy {
+                NearFormPolicy::Longer => Some(shorter),
+                NearFormPolicy::Shorter => Some(longer),
+                NearFormPolicy::Off | NearFormPolicy::Flag => None,
+            };
+            dropped.extend(removed);
+            pairs.push(NearForm {
+                shorter: names[shorter].clone(),
+                longer: names[longer].clone(),
+                dropped: removed.map(|idx| names[idx].clone()),
+            });
+        }
+    }
+    let mut idx = 0;
+    names.retain(|_| {
+        idx += 1;
+        !dropped.contains(&(idx - 1))
+    });
+    pairs
+}
@@ -0,0 +1,60 @@
+//! Cleans up the names a model returns before they are keyed and escaped. Every kind of
+//! whitespace, ie non-breaking spaces, becomes a single ASCII space, and zero-width, bidi and
+//! control characters are dropped, as they would end up in keys and in game unseen:
+//!
+//! ```
+//! use stellaris_name_gen::normalize::normalize_name;
+//!
+//! assert_eq!(normalize_name("Silent\u{a0}Sorrow"), "Silent Sorrow");
+//! assert_eq!(normalize_name("Em\u{200d}ber\u{feff}"), "Ember");
+//! assert_eq!(normalize_name(" Vex \u{2003}\t Prime\u{7}\u{0}"), "Vex Prime");
+//! assert_eq!(normalize_name("\u{202e}\u{200b}"), "");
+//! ```
+//!
+//! Every generated name goes through it, cached ones included:
+//!
+//! ```
+//! use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+//!
+//! let result = futures::executor::block_on(
+//!     Generation::builder()
+//!         .structure("NAME = {\n    # Ship names\n    ships = {\n    }\n}\n")
+//!         .lore("")
+//!         .min_names(1)
+//!         .generator(MockGenerator::new([
+//!             "Silent\u{a0}Sorrow",
+//!             "Em\u{200d}ber",
+//!             "Vex\u{7}\u{1b}",
+//!             "\u{200b}\u{2060}",
+//!         ]))
+//!         .cache(InMemoryCache::new())
+//!         .run(),
+//! )
+//! .unwrap();
+//! let names: Vec<&str> = result.localisation.iter().map(|(_, name)| name.as_str()).collect();
+//! assert_eq!(names, ["Silent Sorrow", "Ember", "Vex"]);
+//! assert!(result.localisation.iter().all(|(key, _)| key.is_ascii() && !key.contains(' ')));
+//! ```
+
+/// Characters that are invisible or reorder the text around them
+pub(crate) fn is_invisible(c: char) -> bool {
+    matches!(
+        c,
+        '\u{00ad}'
+            | '\u{200b}'..='\u{200f}'
+            | '\u{202a}'..='\u{202e}'
+            | '\u{2060}'..='\u{2069}'
+            | '\u{061c}'
+            | '\u{feff}'
+    )
+}
+
+/// `name` with its whitespace collapsed to single ASCII spaces and trimmed, and its invisible
+/// and control characters dropped
+pub fn normalize_name(name: &str) -> String {
+    let visible: String = name
+        .chars()
+        .filter(|c| !is_invisible(*c) && (c.is_whitespace() || !c.is_control()))
+        .collect();
+    visible.split_whitespace().collect::<Vec<_>>().join(" ")
+}
@@ -0,0 +1,190 @@
+//! Ordinal variants of regnal and dynasty names, ie `Karth III` or `Dynasty Gamma`. A block with
+//! `#@ ordinal-expand: roman:5` emits every generated name five times, numbered `I` to `V`, with
+//! `greek:8` numbered `Alpha` to `Theta` and `numeric:10` numbered `1` to `10`:
+//!
+//! ```
+//! use stellaris_name_gen::ordinals::{OrdinalExpand, greek, roman};
+//!
+//! assert_eq!(roman(4), "IV");
+//! assert_eq!(roman(9), "IX");
+//! assert_eq!(roman(14), "XIV");
+//! assert_eq!(roman(1994), "MCMXCIV");
+//! assert_eq!(greek(3), Some("Gamma"));
+//! assert_eq!(greek(24), Some("Omega"));
+//! assert_eq!(greek(25), None);
+//!
+//! let regnal = OrdinalExpand::parse("roman:3").unwrap();
+//! assert_eq!(regnal.expand("Karth"), ["Karth I", "Karth II", "Karth III"]);
+//! assert_eq!(OrdinalExpand::parse("greek:2").unwrap().expand("Dynasty"), ["Dynasty Alpha", "Dynasty Beta"]);
+//! assert_eq!(OrdinalExpand::parse("numeric:2").unwrap().to_string(), "numeric:2");
+//! // Greek letters run out at Omega, Roman numerals at 3999
+//! assert!(OrdinalExpand::parse("greek:25").is_err());
+//! assert!(OrdinalExpand::parse("roman").is_err());
+//! assert!(OrdinalExpand::parse("runic:3").is_err());
+//! ```
+//!
+//! Keys derive from the variants, so `Karth III` is keyed `..._KARTH_III`. A block's `# count:`
+//! counts the variants, the model is asked for as many base names as fit in it. Variants of
+//! the same base are distinct names to deduplication, and the expansion is noted in the run log:
+//!
+//! ```
+//! use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+//!
+//! let generation = Generation::builder()
+//!     .structure("NAME = {\n    # Regnal names\n    #@ ordinal-expand: roman:3\n    # count: 7\n    kings = {\n    }\n}\n")
+//!     .lore("")
+//!     .min_names(1)
+//!     .generator(MockGenerator::new(["Karth", "Vex", "Ember"]))
+//!     .cache(InMemoryCache::new())
+//!     .build()
+//!     .unwrap();
+//! let result = futures::executor::block_on(generation.run()).unwrap();
+//! let names: Vec<&str> = result.localisation.iter().map(|(_, name)| name.as_str()).collect();
+//! // Two base names fit in 7 entries
+//! assert_eq!(names, ["Karth I", "Karth II", "Karth III", "Vex I", "Vex II", "Vex III"]);
+//! assert!(result.localisation[2].0.ends_with("KARTH_III"));
+//! let block = &result.report.blocks[0];
+//! assert_eq!(block.ordinal_expand.as_deref(), Some("roman:3"));
+//! let log = generation.provenance(std::time::SystemTime::now(), Ok(&result));
+//! assert_eq!(log.blocks[0].ordinal_expand.as_deref(), Some("roman:3"));
+//! ```
+
+use anyhow::{Context, Result};
+use std::fmt;
+
+/// Greek letter names in order, `Alpha` being the first
+const GREEK: [&str; 24] = [
+    "Alpha", "Beta", "Gamma", "Delta", "Epsilon", "Zeta", "Eta", "Theta", "Iota", "Kappa",
+    "Lambda", "Mu", "Nu", "Xi", "Omicron", "Pi", "Rho", "Sigma", "Tau", "Upsilon", "Phi", "Chi",
+    "Psi", "Omega",
+];
+
+/// Largest number written in Roman numerals without overlines
+const MAX_ROMAN: usize = 3999;
+
+/// Roman numeral values with their subtractive forms, largest first
+const NUMERALS: [(usize, &str); 13] = [
+    (1000, "M"),
+    (900, "CM"),
+    (500, "D"),
+    (400, "CD"),
+    (100, "C"),
+    (90, "XC"),
+    (50, "L"),
+    (40, "XL"),
+    (10, "X"),
+    (9, "IX"),
+    (5, "V"),
+    (4, "IV"),
+    (1, "I"),
+];
+
+/// `n` in Roman numerals, ie `XIV`. Empty for 0
+pub fn roman(mut n: usize) -> String {
+    let mut out = String::new();
+    for (value, numeral) in NUMERALS {
+        while n >= value {
+            out.push_str(numeral);
+            n -= value;
+        }
+    }
+    out
+}
+
+/// The name of the `n`th Greek letter, 1-based, `None` past Omega
+pub fn greek(n: usize) -> Option<&'static str> {
+    n.checked_sub(1).and_then(|index| GREEK.get(index)).copied()
+}
+
+/// How ordinals are written
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OrdinalStyle {
+    /// `I`, `II`, `III`
+    Roman,
+    /// `Alpha`, `Beta`, `Gamma`
+    Greek,
+    /// `1`, `2`, `3`
+    Numeric,
+}
+
+impl OrdinalStyle {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            OrdinalStyle::Roman => "roman",
+            OrdinalStyle::Greek => "greek",
+            OrdinalStyle::Numeric => "numeric",
+        }
+    }
+
+    /// Most ordinals the style can write
+    fn max(&self) -> usize {
+        match self {
+            OrdinalStyle::Roman => MAX_ROMAN,
+            OrdinalStyle::Greek => GREEK.len(),
+            OrdinalStyle::Numeric => usize::MAX,
+        }
+    }
+
+    /// The `n`th ordinal, 1-based
+    fn label(&self, n: usize) -> String {
+        match self {
+            OrdinalStyle::Roman => roman(n),
+            OrdinalStyle::Greek => greek(n).unwrap_or_default().to_string(),
+            OrdinalStyle::Numeric => n.to_string(),
+        }
+    }
+}
+
+/// A block's `#@ ordinal-expand:`, every name becoming `count` numbered variants
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OrdinalExpand {
+    pub style: OrdinalStyle,
+    pub count: usize,
+}
+
+impl OrdinalExpand {
+    /// Parses `STYLE:COUNT`, ie `roman:5`
+    pub fn parse(value: &str) -> Result<Self> {
+        let (style, count) = value
+            .trim()
+            .split_once(':')
+            .with_context(|| format!("Expected STYLE:COUNT, ie roman:5, got '{}'", value.trim()))?;
+        let style = match style.trim().to_ascii_lowercase().as_str() {
+            "roman" => OrdinalStyle::Roman,
+            "greek" => OrdinalStyle::Greek,
+            "numeric" => OrdinalStyle::Numeric,
+            other => anyhow::bail!(
+                "Unknown ordinal style '{}', expected roman, greek or numeric",
+                other
+            ),
+        };
+        let count = count
+            .trim()
+            .parse::<usize>()
+            .ok()
+            .filter(|count| *count > 0)
+            .with_context(|| format!("Invalid ordinal count '{}'", count.trim()))?;
+        if count > style.max() {
+            anyhow::bail!(
+                "{} ordinals only go up to {}, got {}",
+                style.as_str(),
+                style.max(),
+                count
+            );
+        }
+        Ok(Self { style, count })
+    }
+
+    /// The variants of `name`, in order
+    pub fn expand(&self, name: &str) -> Vec<String> {
+        (1..=self.count)
+            .map(|n| format!("{} {}", name, self.style.label(n)))
+            .collect()
+    }
+}
+
+impl fmt::Display for OrdinalExpand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:{}", self.style.as_str(), self.count)
+    }
+}
@@ -0,0 +1,392 @@
+//! Writers turning a [`RunResult`] into files. Any number of them can run on the same result.
+
+use crate::in_place::splice_file;
+use crate::loc_split::{LocSplit, part_stem};
+use crate::package;
+use crate::pipeline::RunResult;
+use crate::translate::Language;
+use anyhow::{Context, Result};
+use rusqlite::{Connection, params};
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::PathBuf;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// Writes some form of a run's output
+pub trait OutputWriter {
+    fn write(&self, result: &RunResult) -> Result<()>;
+}
+
+/// The Paradox name list, ie `out.txt`
+#[derive(Debug, Clone)]
+pub struct NameListWriter {
+    pub path: PathBuf,
+    /// Replace only the generated sections of an existing file, see [`crate::in_place`]. The
+    /// run must be generated with markers
+    pub in_place: bool,
+}
+
+impl Default for NameListWriter {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("out.txt"),
+            in_place: false,
+        }
+    }
+}
+
+impl OutputWriter for NameListWriter {
+    fn write(&self, result: &RunResult) -> Result<()> {
+        let text = if self.in_place && self.path.exists() {
+            splice_file(&self.path, &result.name_list)?
+        } else {
+            result.name_list.clone()
+        };
+        fs::write(&self.path, text)
+            .with_context(|| format!("Failed to write {}", self.path.display()))
+    }
+}
+
+/// The `l_english` localisation, ie `localisation.txt`, and a file per translated language
+/// next to it, ie `localisation_german.txt`. Split by `split`, every file is written per part,
+/// ie `localisation_ships.txt` and `localisation_ships_german.txt`
+#[derive(Debug, Clone)]
+pub struct LocalisationWriter {
+    pub path: PathBuf,
+    pub split: LocSplit,
+}
+
+impl Default for LocalisationWriter {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("localisation.txt"),
+            split: LocSplit::Single,
+        }
+    }
+}
+
+impl LocalisationWriter {
+    /// Path of a part's file, `language` appended to the stem for a translation
+    fn part_path(&self, part: &str, language: Option<Language>) -> PathBuf {
+        let stem = self
+            .path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy())
+            .unwrap_or_default();
+        let mut stem = part_stem(&stem, part);
+        if let Some(language) = language {
+            stem = format!("{}_{}", stem, language.id());
+        }
+        let name = match self.path.extension() {
+            Some(ext) => format!("{}.{}", stem, ext.to_string_lossy()),
+            None => stem,
+        };
+        self.path.with_file_name(name)
+    }
+}
+
+impl OutputWriter for LocalisationWriter {
+    fn write(&self, result: &RunResult) -> Result<()> {
+        for (part, yml) in result.localisation_parts(self.split) {
+            let path = self.part_path(&part, None);
+            fs::write(&path, yml).with_context(|| format!("Failed to write {}", path.display()))?;
+        }
+        for &language in result.translations.keys() {
+            for (part, yml) in result.translated_parts(language, self.split) {
+                let path = self.part_path(&part, Some(language));
+                fs::write(&path, yml)
+                    .with_context(|| format!("Failed to write {}", path.display()))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A human-readable preview grouping names per block, ie `PREVIEW.md`
+#[derive(Debug, Clone)]
+pub struct MarkdownWriter {
+    pub path: PathBuf,
+    /// Names per table row
+    pub columns: usize,
+}
+
+impl Default for MarkdownWriter {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("PREVIEW.md"),
+            columns: 4,
+        }
+    }
+}
+
+impl MarkdownWriter {
+    /// Renders the preview document
+    pub fn render(&self, result: &RunResult) -> String {
+        let columns = self.columns.max(1);
+        let mut out = String::from(if result.report.draft {
+            "# Name list preview (draft)\n"
+        } else {
+            "# Name list preview\n"
+        });
+        for block in &result.report.blocks {
+            out.push_str(&format!("\n## {}\n\n", block.path.join(" / ")));
+            out.push_str(&format!("> {}\n\n", block.theme.replace('\n', "\n> ")));
+            if let Some(era) = &block.era {
+                out.push_str(&format!("Era: {}\n\n", era));
+            }
+            out.push_str(&format!(
+                "{} names ({} generated",
+                block.names, block.generated
+            ));
+            for (filter, count) in &block.dropped {
+                out.push_str(&format!(", {} dropped by {}", count, filter));
+            }
+            out.push_str(")\n\n");
+            if !block.diversity.clusters.is_empty() {
+                let clusters: Vec<String> = block
+                    .diversity
+                    .clusters
+                    .iter()
+                    .map(|cluster| {
+                        format!(
+                            "`{}` {} ({})",
+                            cluster.label(),
+                            cluster.size,
+                            cluster.examples.join(", ")
+                        )
+                    })
+                    .collect();
+                out.push_str(&format!(
+                    "Diversity {:.2}, largest clusters: {}\n\n",
+                    block.diversity.score,
+                    clusters.join("; ")
+                ));
+            }
+            if block.failed {
+                out.push_str("**Too few names survived filtering**\n\n");
+            }
+            if block.entries.is_empty() {
+                continue;
+            }
+            out.push_str(&format!("|{}\n", " |".repeat(columns)));
+            out.push_str(&format!("|{}\n", "---|".repeat(columns)));
+            for row in block.entries.chunks(columns) {
+                let cells: Vec<String> = (0..columns)
+                    .map(|idx| {
+                        row.get(idx)
+                            .map(|(_, name)| name.replace('|', "\\|"))
+                            .unwrap_or_default()
+                    })
+                    .collect();
+                out.push_str(&format!("| {} |\n", cells.join(" | ")));
+            }
+        }
+        out
+    }
+}
+
+impl OutputWriter for MarkdownWriter {
+    fn write(&self, result: &RunResult) -> Result<()> {
+        fs::write(&self.path, self.render(result))
+            .with_context(|| format!("Failed to write {}", self.path.display()))
+    }
+}
+
+/// The full run report as JSON, ie `report.json`
+#[derive(Debug, Clone)]
+pub struct ReportWriter {
+    pub path: PathBuf,
+}
+
+impl Default for ReportWriter {
+    fn default() -> Self {
+        Self {
+            path: PathBuf::from("report.json"),
+        }
+    }
+}
+
+impl OutputWriter for ReportWriter {
+    fn write(&self, result: &RunResult) -> Result<()> {
+        let json = serde_json::to_string_pretty(&result.report)?;
+        fs::write(&self.path, json)
+            .with_context(|| format!("Failed to write {}", self.path.display()))
+    }
+}
+
+/// Every emitted name with the keys it is localised under as JSON, for tooling that has a
+/// display name and needs its key. Keys are arrays since the same name may be emitted by
+/// several blocks:
+///
+/// ```
+/// use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator, ReverseLookupWriter};
+///
+/// let result = futures::executor::block_on(
+///     Generation::builder()
+///         .structure("NAME = {\n    # prefix: ARK_\n    # Ship names\n    ships = {\n    }\n    # prefix: WAR_\n    # Fleet names\n    fleets = {\n    }\n}\n")
+///         .lore("")
+///         .generator(MockGenerator::new(["Vex", "Ember"]))
+///         .cache(InMemoryCache::new())
+///         .run(),
+/// )
+/// .unwrap();
+/// let lookup = ReverseLookupWriter::render(&result);
+/// assert_eq!(lookup["Vex"], ["ARK_VEX", "WAR_VEX"]);
+/// assert_eq!(lookup["Ember"], ["ARK_EMBER", "WAR_EMBER"]);
+/// ```
+#[derive(Debug, Clone)]
+pub struct ReverseLookupWriter {
+    pub path: PathBuf,
+}
+
+impl ReverseLookupWriter {
+    /// Maps every localised name to its keys, in emission order
+    pub fn render(result: &RunResult) -> BTreeMap<String, Vec<String>> {
+        let mut lookup: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for (key, name) in &result.localisation {
+            let keys = lookup.entry(name.clone()).or_default();
+            if !keys.contains(key) {
+                keys.push(key.clone());
+            }
+        }
+        lookup
+    }
+}
+
+impl OutputWriter for ReverseLookupWriter {
+    fn write(&self, result: &RunResult) -> Result<()> {
+        let json = serde_json::to_string_pretty(&Self::render(result))?;
+        fs::write(&self.path, json)
+            .with_context(|| format!("Failed to write {}", self.path.display()))
+    }
+}
+
+/// A ready-to-zip mod, see [`package::write_mod`]
+#[derive(Debug, Clone)]
+pub struct ModWriter {
+    pub name: String,
+    pub dir: PathBuf,
+    /// Merge into an existing name list, dropping stale blocks, rather than overwrite it
+    pub prune: Option<package::Prune>,
+    /// Replace only the generated sections of the mod's existing name list, like
+    /// [`NameListWriter::in_place`]
+    pub in_place: bool,
+    /// How the localisation is spread over files
+    pub split: LocSplit,
+}
+
+impl OutputWriter for ModWriter {
+    fn write(&self, result: &RunResult) -> Result<()> {
+        package::write_mod(
+            result,
+            &self.name,
+            &self.dir,
+            self.prune,
+            self.in_place,
+            self.split,
+        )
+        .map(|_| ())
+    }
+}
+
+/// Every emitted name as a row of a SQLite database, upserted on its key so catalogs of several
+/// runs and projects can be queried together. The `names` table is created if missing:
+///
+/// ```
+/// use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator, OutputWriter, SqliteWriter};
+///
+/// let path = std::env::temp_dir().join(format!("namegen-doc-{}.sqlite", std::process::id()));
+/// let _ = std::fs::remove_file(&path);
+/// let run = |names: [&str; 2]| {
+///     futures::executor::block_on(
+///         Generation::builder()
+///             .structure("NAME = {\n    # prefix: ARK_\n    ships = {\n        # Ship names\n        military = {\n        }\n    }\n}\n")
+///             .lore("")
+///             .model("local-model")
+///             .generator(MockGenerator::new(names))
+///             .cache(InMemoryCache::new())
+///             .run(),
+///     )
+///     .unwrap()
+/// };
+/// let writer = SqliteWriter { path: path.clone() };
+/// writer.write(&run(["Vex", "Ember"])).unwrap();
+/// writer.write(&run(["Vex", "Ash"])).unwrap();
+///
+/// let db = rusqlite::Connection::open(&path).unwrap();
+/// let row: (String, String, String, String, String) = db
+///     .query_row(
+///         "SELECT name, theme, prefix, path, model FROM names WHERE key = 'ARK_VEX'",
+///         [],
+///         |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?, row.get(3)?, row.get(4)?)),
+///     )
+///     .unwrap();
+/// assert_eq!(
+///     row,
+///     ("Vex".into(), "Ship names".into(), "ARK_".into(), "NAME/ships/military".into(), "local-model".into())
+/// );
+/// // Keys are upserted, names of earlier runs stay
+/// let count: usize = db.query_row("SELECT COUNT(*) FROM names", [], |row| row.get(0)).unwrap();
+/// assert_eq!(count, 3);
+/// # std::fs::remove_file(&path).unwrap();
+/// ```
+#[derive(Debug, Clone)]
+pub struct SqliteWriter {
+    pub path: PathBuf,
+}
+
+impl OutputWriter for SqliteWriter {
+    fn write(&self, result: &RunResult) -> Result<()> {
+        let mut conn = Connection::open(&self.path)
+            .with_context(|| format!("Failed to open {}", self.path.display()))?;
+        // Other runs may be writing to the same catalog, wait for them instead of failing
+        conn.busy_timeout(Duration::from_secs(30))?;
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.execute_batch(
+            "CREATE TABLE IF NOT EXISTS names (
+                key TEXT PRIMARY KEY,
+                name TEXT NOT NULL,
+                theme TEXT NOT NULL,
+                prefix TEXT NOT NULL,
+                path TEXT NOT NULL,
+                model TEXT NOT NULL,
+                run_timestamp INTEGER NOT NULL
+            );",
+        )
+        .context("Failed to create names schema")?;
+        let run_timestamp = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|elapsed| elapsed.as_secs() as i64)
+            .unwrap_or_default();
+        let tx = conn.transaction_with_behavior(rusqlite::TransactionBehavior::Immediate)?;
+        {
+            let mut upsert = tx.prepare(
+                "INSERT INTO names (key, name, theme, prefix, path, model, run_timestamp)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)
+                 ON CONFLICT(key) DO UPDATE SET
+                    name = excluded.name,
+                    theme = excluded.theme,
+                    prefix = excluded.prefix,
+                    path = excluded.path,
+                    model = excluded.model,
+                    run_timestamp = excluded.run_timestamp",
+            )?;
+            for block in &result.report.blocks {
+                let path = block.path.join("/");
+                for (key, name) in &block.entries {
+                    upsert.execute(params![
+                        key,
+                        name,
+                        block.theme,
+                        block.prefix,
+                        path,
+                        block.model,
+                        run_timestamp
+                    ])?;
+                }
+            }
+        }
+        tx.commit()
+            .with_context(|| format!("Failed to write {}", self.path.display()))
+    }
+}
@@ -0,0 +1,290 @@
+//! Packages a run's output into the folder layout a Stellaris mod expects.
+//!
+//! ```
+//! use stellaris_name_gen::loc_split::LocSplit;
+//! use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator, package};
+//!
+//! let result = futures::executor::block_on(
+//!     Generation::builder()
+//!         .structure("NAME = {\n    # Ship names\n    ships = {\n    }\n}\n")
+//!         .lore("")
+//!         .generator(MockGenerator::new(["Vex"]))
+//!         .cache(InMemoryCache::new())
+//!         .run(),
+//! )
+//! .unwrap();
+//! let dir = std::env::temp_dir().join("namegen-package-doctest");
+//! let root = package::write_mod(&result, "Ark Names", &dir, None, false, LocSplit::Single).unwrap();
+//! assert!(root.join("common/name_lists/ark_names.txt").exists());
+//! assert!(root.join("localisation/english/ark_names_l_english.yml").exists());
+//! assert!(dir.join("ark_names.mod").exists());
+//!
+//!
+//! // Split per category, the single file of the previous layout goes away, while a
+//! // hand-maintained file next to it is left alone
+//! let events = root.join("localisation/english/ark_names_events_l_english.yml");
+//! std::fs::write(&events, "l_english:\n ARK_EVENT:0 \"Event\"\n").unwrap();
+//! package::write_mod(&result, "Ark Names", &dir, None, false, LocSplit::Category).unwrap();
+//! assert!(root.join("localisation/english/ark_names_ships_l_english.yml").exists());
+//! assert!(!root.join("localisation/english/ark_names_l_english.yml").exists());
+//! assert!(events.exists());
+//! # std::fs::remove_dir_all(&dir).unwrap();
+//! ```
+
+use crate::in_place::splice_file;
+use crate::keys::sanitize_key;
+use crate::loc_split::{LocSplit, part_stem};
+use crate::pipeline::RunResult;
+use crate::prune::prune_name_list;
+use crate::vanilla::VanillaKeys;
+use anyhow::{Context, Result};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs;
+use std::io::BufReader;
+use std::path::{Path, PathBuf};
+
+/// Lists the localisation files a run wrote, relative to the mod's root, so the next run only
+/// removes files it generated itself
+const GENERATED_LIST: &str = ".namegen-localisation";
+
+/// Merges the new name list into the one already in the mod instead of overwriting it,
+/// see [`prune_name_list`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Prune {
+    /// Keep blocks missing from the new output whose keys the mod never localised
+    pub keep_unknown: bool,
+}
+
+/// Writes a ready-to-zip mod named `mod_name` into `dir`, returning the mod's root folder.
+///
+/// ```text
+/// <dir>/<id>.mod                                    launcher descriptor
+/// <dir>/<id>/descriptor.mod
+/// <dir>/<id>/common/name_lists/<id>.txt             the name list
+/// <dir>/<id>/localisation/english/<id>_l_english.yml
+/// <dir>/<id>/localisation/<language>/<id>_l_<language>.yml   per translated language
+/// ```
+/// where `<id>` is the lowercased, sanitized mod name. Split by `split`, each localisation file
+/// is written as `<id>_<part>_l_<language>.yml` instead. Files an earlier run wrote and this one
+/// didn't, ie the single file after switching to a split, are removed. Other files are never
+/// touched. With `in_place` an existing name list only has its generated sections replaced, see
+/// [`crate::in_place::splice_file`]
+pub fn write_mod(
+    result: &RunResult,
+    mod_name: &str,
+    dir: &Path,
+    prune: Option<Prune>,
+    in_place: bool,
+    split: LocSplit,
+) -> Result<PathBuf> {
+    let id = sanitize_key(mod_name).to_lowercase();
+    if id.trim_matches('_').is_empty() {
+        anyhow::bail!("Mod name '{}' has no usable characters", mod_name);
+    }
+    let root = dir.join(&id);
+    let name_lists = root.join("common").join("name_lists");
+    let localisation = root.join("localisation").join("english");
+    for folder in [&name_lists, &localisation] {
+        fs::create_dir_all(folder)
+            .with_context(|| format!("Failed to create {}", folder.display()))?;
+    }
+
+    let name_list_path = name_lists.join(format!("{}.txt", id));
+    let name_list = match prune {
+        _ if in_place && name_list_path.exists() => {
+            splice_file(&name_list_path, &result.name_list)?
+        }
+        Some(prune) if name_list_path.exists() => {
+            let existing = fs::read_to_string(&name_list_path)
+                .with_context(|| format!("Failed to read {}", name_list_path.display()))?;
+            // The previous localisation, all of its files, tells generated blocks from
+            // hand-added ones
+            let previous = localisation_files(&localisation, &id, "english")?;
+            let localised = if prune.keep_unknown && !previous.is_empty() {
+                let mut keys = VanillaKeys::new();
+                for path in &previous {
+                    let file = fs::File::open(path)
+                        .with_context(|| format!("Failed to read {}", path.display()))?;
+                    keys.extend_from_reader(BufReader::new(file))?;
+                }
+                Some(keys)
+            } else {
+                None
+            };
+            let pruned = prune_name_list(&existing, &result.name_list, localised.as_ref());
+            for block in &pruned.removed {
+                println!("[Prune] Removed stale block '{}'", block);
+            }
+            for block in &pruned.kept {
+                println!("[Prune] Kept hand-added block '{}'", block);
+            }
+            pruned.text
+        }
+        _ => result.name_list.clone(),
+    };
+    write(&name_list_path, &name_list)?;
+    let generated = generated_files(&root)?;
+    let mut written = write_localisation(
+        &localisation,
+        &id,
+        "english",
+        result.localisation_parts(split),
+    )?;
+    for &language in result.translations.keys() {
+        let folder = root.join("localisation").join(language.id());
+        fs::create_dir_all(&folder)
+            .with_context(|| format!("Failed to create {}", folder.display()))?;
+        written.extend(write_localisation(
+            &folder,
+            &id,
+            language.id(),
+            result.translated_parts(language, split),
+        )?);
+    }
+    for path in generated.iter().filter(|path| !written.contains(*path)) {
+        let path = root.join(path);
+        if path.exists() {
+            fs::remove_file(&path)
+                .with_context(|| format!("Failed to remove {}", path.display()))?;
+            println!("[Mod] Removed stale localisation file '{}'", path.display());
+        }
+    }
+    let list: Vec<String> = written
+        .iter()
+        .map(|path| path.to_string_lossy().replace('\\', "/"))
+        .collect();
+    write(
+        &root.join(GENERATED_LIST),
+        &format!("{}\n", list.join("\n")),
+    )?;
+    let descriptor = format!(
+        "name=\"{}\"\nversion=\"1.0\"\ntags={{\n\t\"Species\"\n}}\nsupported_version=\"*\"\n",
+        mod_name.replace('"', "")
+    );
+    write(&root.join("descriptor.mod"), &descriptor)?;
+    write(
+        &dir.join(format!("{}.mod", id)),
+        &format!("{}path=\"mod/{}\"\n", descriptor, id),
+    )?;
+    println!("[Mod] Wrote '{}'", root.display());
+    Ok(root)
+}
+
+/// The localisation files the last run wrote into the mod `mod_name` in `dir`, so their keys are
+/// told from hand-maintained ones. A mod packaged before the list was kept counts the unsplit
+/// file of every language
+///
+/// ```
+/// use stellaris_name_gen::loc_split::LocSplit;
+/// use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator, package};
+///
+/// let result = futures::executor::block_on(
+///     Generation::builder()
+///         .structure("NAME = {\n    # Ship names\n    ships = {\n    }\n}\n")
+///         .lore("")
+///         .generator(MockGenerator::new(["Vex"]))
+///         .cache(InMemoryCache::new())
+///         .run(),
+/// )
+/// .unwrap();
+/// let dir = std::env::temp_dir().join(format!("namegen-doc-generated-{}", std::process::id()));
+/// let root = package::write_mod(&result, "Ark Names", &dir, None, false, LocSplit::Category).unwrap();
+/// assert_eq!(
+///     package::generated_localisation(&dir, "Ark Names").unwrap(),
+///     [root.join("localisation/english/ark_names_ships_l_english.yml")]
+/// );
+/// # std::fs::remove_dir_all(&dir).unwrap();
+/// ```
+pub fn generated_localisation(dir: &Path, mod_name: &str) -> Result<Vec<PathBuf>> {
+    let id = sanitize_key(mod_name).to_lowercase();
+    let root = dir.join(&id);
+    if root.join(GENERATED_LIST).exists() {
+        return Ok(generated_files(&root)?
+            .into_iter()
+            .map(|path| root.join(path))
+            .collect());
+    }
+    let folder = root.join("localisation");
+    if !folder.exists() {
+        return Ok(Vec::new());
+    }
+    let mut files = Vec::new();
+    for entry in
+        fs::read_dir(&folder).with_context(|| format!("Failed to read {}", folder.display()))?
+    {
+        let language = entry?.file_name().to_string_lossy().to_string();
+        let file = folder
+            .join(&language)
+            .join(format!("{}_l_{}.yml", id, language));
+        if file.exists() {
+            files.push(file);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// The mod's localisation files of a language in `folder`, `<id>_l_<language>.yml` and every
+/// `<id>_<part>_l_<language>.yml`, sorted
+fn localisation_files(folder: &Path, id: &str, language: &str) -> Result<Vec<PathBuf>> {
+    if !folder.exists() {
+        return Ok(Vec::new());
+    }
+    let suffix = format!("_l_{}.yml", language);
+    let mut files = Vec::new();
+    for entry in
+        fs::read_dir(folder).with_context(|| format!("Failed to read {}", folder.display()))?
+    {
+        let path = entry?.path();
+        let Some(name) = path.file_name().and_then(|name| name.to_str()) else {
+            continue;
+        };
+        let Some(stem) = name.strip_suffix(&suffix) else {
+            continue;
+        };
+        if stem == id || stem.starts_with(&format!("{}_", id)) {
+            files.push(path);
+        }
+    }
+    files.sort();
+    Ok(files)
+}
+
+/// The localisation files an earlier run wrote into the mod at `root`, relative to it, empty
+/// before the first run
+fn generated_files(root: &Path) -> Result<BTreeSet<PathBuf>> {
+    let path = root.join(GENERATED_LIST);
+    if !path.exists() {
+        return Ok(BTreeSet::new());
+    }
+    let text =
+        fs::read_to_string(&path).with_context(|| format!("Failed to read {}", path.display()))?;
+    Ok(text
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(PathBuf::from)
+        .collect())
+}
+
+/// Writes the localisation files of a language, one per part, returning their paths relative to
+/// the mod's root, which `folder` is two levels below
+fn write_localisation(
+    folder: &Path,
+    id: &str,
+    language: &str,
+    parts: BTreeMap<String, String>,
+) -> Result<BTreeSet<PathBuf>> {
+    let mut written = BTreeSet::new();
+    for (part, yml) in parts {
+        let file = format!("{}_l_{}.yml", part_stem(id, &part), language);
+        // The game only reads localisation files saved as UTF-8 with a BOM
+        write(&folder.join(&file), &format!("\u{feff}{}", yml))?;
+        written.insert(Path::new("localisation").join(language).join(file));
+    }
+    Ok(written)
+}
+
+fn write(path: &Path, contents: &str) -> Result<()> {
+    fs::write(path, contents).with_context(|| format!("Failed to write {}", path.display()))
+}
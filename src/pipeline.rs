@@ -0,0 +1,4990 @@
+use crate::abbreviations::Abbreviations;
+use crate::ascii::to_ascii;
+use crate::budget::{Budget, BudgetUsage, estimate_tokens};
+use crate::cache::{BlockKey, CachedBlock, DERIVED_SUFFIXES, FsCache, NameCache};
+use crate::casing::{self, CasingCheck, CasingReport};
+use crate::charset::{CharsetViolation, LocCharset, check_value};
+use crate::clusters::{self, Cluster, ClusterAction, Diversity};
+use crate::compose;
+use crate::config::Config;
+use crate::determinism::{self, Stage};
+use crate::directives::{Era, ScoreFilter, SortMode};
+use crate::dispatch::ModelLimiter;
+use crate::distribution::{self, Balance, Distribution, MAX_FILL};
+use crate::draft::{self, Promotion};
+use crate::estimate::{
+    ASSUMED_NAMES, BlockEstimate, Estimate, ModelPrice, NAME_BYTES, OutputBasis, VALUE_BYTES,
+};
+use crate::euphony;
+use crate::flavor::{builtin_flavors, sample_flavors};
+use crate::generator::{
+    DEFAULT_MAX_TOKENS, GenAiGenerator, GenerationRequest, MIN_MAX_TOKENS, NameGenerator,
+    ResponseFormat, StreamError, TokenUsage,
+};
+use crate::hash::{digest, fnv1a};
+use crate::health::{
+    DEFAULT_ERROR_WINDOW, DEFAULT_MAX_ERROR_RATE, HealthMonitor, Unhealthy, classify,
+    rejects_max_tokens,
+};
+use crate::in_place::{BEGIN_MARKER, END_MARKER};
+use crate::inline::InlineLine;
+use crate::json::{
+    NameItem, is_cut_off, parse_items, parse_lines, parse_names, parse_scores, parse_translations,
+    parse_value, validate_schema,
+};
+use crate::keymap::KeyMap;
+use crate::keys::{HashKeys, KeyCase, KeyCompatMap, KeyStyle, Pins};
+use crate::loc_split::{LocSplit, split_entries};
+use crate::lore::LoreProfiles;
+use crate::near_forms::{self, NearForm, NearFormPolicy};
+use crate::normalize::normalize_name;
+use crate::plan::PlannedBlock;
+use crate::prompt::{
+    build_balance_prompt, build_diversify_prompt, build_prompt, build_score_prompt,
+    build_translation_prompt, build_value_prompt,
+};
+use crate::provenance::{
+    BlockProvenance, CacheStats, LoreDigests, PROVENANCE_VERSION, Provenance, RunSettings,
+    RunStatus, block_comment,
+};
+use crate::providers::{self, ProviderConfig};
+use crate::quota::{DEFAULT_CHECK_INTERVAL, Interrupt, QuotaExhausted, QuotaWait, quota_exhausted};
+use crate::quoting::{self, QuoteMode, Target};
+use crate::rarity::{CommonWords, Rarity};
+use crate::replay::{PROMPT_VERSION, Replayed, replay};
+use crate::semantic::{self, SemanticCluster, SemanticDedup};
+use crate::species::SpeciesClasses;
+use crate::structure::{GenerationJob, Segment, parse_structure};
+use crate::titles::LocalisationTask;
+use crate::translate::{Language, TranslationReport};
+use crate::unique::{UniqueNames, UniqueScope};
+use crate::vanilla::VanillaKeys;
+use crate::warning;
+use crate::warnings::{self, RepeatedWarning};
+use anyhow::{Context, Result};
+use futures::StreamExt;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs;
+use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+//const AI_MODEL: &str = "gemma3:27b-it-qat";
+pub const AI_MODEL: &str = "gemini-2.5-flash-preview-04-17";
+//const AI_MODEL: &str = "gemini-2.0-flash";
+
+/// Fewest names a block may end up with unless configured otherwise
+pub const DEFAULT_MIN_NAMES: usize = 10;
+
+/// Names requested on top of a block's `# count:`, so it survives filtering after an early stop
+pub const DEFAULT_EARLY_STOP_MARGIN: usize = 10;
+
+/// Template of localisation values unless configured otherwise, the plain value
+pub const VALUE_FORMAT: &str = "{val}";
+
+/// Indentation per nesting level of a structure that shows none, see [`GenerationBuilder::indent`]
+pub const INDENT: &str = "    ";
+
+/// Requests continuing a block left short under [`GenerationBuilder::strict_count`]
+pub const MAX_CONTINUATIONS: usize = 3;
+
+/// Most names a prompt lists as taken by the blocks ahead in its unique scope, the nearest kept
+const MAX_TAKEN: usize = 200;
+
+/// Most requests a block sends before it is left empty, its output failing to parse or its
+/// stream breaking every time. The block is reported failed:
+///
+/// ```
+/// use futures::future::BoxFuture;
+/// use std::sync::Arc;
+/// use std::sync::atomic::{AtomicUsize, Ordering};
+/// use stellaris_name_gen::pipeline::MAX_ATTEMPTS;
+/// use stellaris_name_gen::{Generation, GenerationRequest, InMemoryCache, NameGenerator};
+///
+/// /// Never answers with names
+/// struct Rambling(Arc<AtomicUsize>);
+///
+/// impl NameGenerator for Rambling {
+///     fn generate<'a>(&'a self, _: &'a GenerationRequest) -> BoxFuture<'a, anyhow::Result<String>> {
+///         self.0.fetch_add(1, Ordering::SeqCst);
+///         Box::pin(async { Ok(String::new()) })
+///     }
+/// }
+///
+/// let calls = Arc::new(AtomicUsize::new(0));
+/// let result = futures::executor::block_on(
+///     Generation::builder()
+///         .structure("NAME = {\n    # Ship names\n    ships = {\n    }\n}\n")
+///         .lore("")
+///         .generator(Rambling(calls.clone()))
+///         .cache(InMemoryCache::new())
+///         .run(),
+/// )
+/// .unwrap();
+/// assert_eq!(calls.load(Ordering::SeqCst), MAX_ATTEMPTS);
+/// assert!(result.report.blocks[0].failed);
+/// ```
+pub const MAX_ATTEMPTS: usize = 6;
+
+/// Formats tried in turn while a block's output fails to parse, the last one repeats
+const RETRY_FORMATS: [ResponseFormat; 3] = [
+    ResponseFormat::Names,
+    ResponseFormat::JsonNames,
+    ResponseFormat::Lines,
+];
+
+/// Outcome of a single generated block
+#[derive(Debug, Clone, Serialize)]
+pub struct BlockReport {
+    pub path: Vec<String>,
+    /// Category of the block, see [`GenerationBuilder::category`]
+    pub category: String,
+    pub theme: String,
+    /// Period the names belong to, from `# era:`
+    pub era: Option<String>,
+    /// Prefix of the block's keys, as resolved from `# prefix:`
+    pub prefix: String,
+    pub model: String,
+    /// Number of names the generator returned, before any filtering
+    pub generated: usize,
+    /// Number of entries emitted
+    pub names: usize,
+    /// Names removed per filter, ie `avoid-initials`
+    pub dropped: BTreeMap<String, usize>,
+    /// Number of candidates per score (1–5) of a `score-filter` block, empty if unscored
+    pub scores: BTreeMap<u8, usize>,
+    /// Every candidate of a `# style=` block with its style score, see [`crate::euphony`]
+    pub style_scores: Vec<(String, u8)>,
+    /// Near form pairs found among the names and what was done with them
+    pub near_forms: Vec<NearForm>,
+    /// Names dropped as meaning the same as a kept name, see [`crate::semantic`]
+    pub semantic: Vec<SemanticCluster>,
+    /// Names off the block's capitalization scheme, if checked, see [`crate::casing`]
+    pub casing: Option<CasingReport>,
+    /// Names dropped as no localisation value can hold them in the run's quote mode, see
+    /// [`crate::quoting`]
+    pub unsafe_names: Vec<String>,
+    /// How varied the names are and their largest clusters, see [`crate::clusters`]
+    pub diversity: Diversity,
+    /// Names per initial letter against the block's `# distribution:`, if it has one
+    pub distribution: Option<Balance>,
+    /// Whether the names came from the cache rather than the generator
+    pub cached: bool,
+    /// Emitted entries as (key, name), in output order
+    pub entries: Vec<(String, String)>,
+    /// Fewer names survived filtering than the block's minimum, or than its `# count:` under
+    /// [`GenerationBuilder::strict_count`]
+    pub failed: bool,
+    /// The run's budget ran out before the block could be generated, see [`crate::budget`]
+    pub budget_exhausted: bool,
+    /// Names salvaged from a cut-off answer, completed by a retry avoiding them
+    pub salvaged: usize,
+    /// The answer's stream closed without its End event, so its names were salvaged as cut off
+    pub truncated: bool,
+    /// Seed the names were shuffled with, by a `sort: shuffle` directive or the run's shuffle seed
+    pub shuffle_seed: Option<u64>,
+    /// Time spent generating or loading the block
+    pub elapsed_ms: u64,
+    /// Marked `#@ translatable`, so [`Generation::translate`] translates its entries
+    pub translatable: bool,
+    /// First ten names of a sequential (`randomized = no`) block as the game consumes them,
+    /// empty for other blocks
+    pub order: Vec<String>,
+    /// Stored parameters the block was generated again with, see [`crate::replay`]
+    pub replayed: Option<Replayed>,
+    /// Numbered variants every name was emitted as, ie `roman:5`, see [`crate::ordinals`]
+    pub ordinal_expand: Option<String>,
+    /// Names left after each stage of the block's pipeline, see [`crate::determinism`]
+    #[serde(skip)]
+    pub stages: Vec<Stage>,
+}
+
+/// Summary of a run
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct Report {
+    pub blocks: Vec<BlockReport>,
+    /// Keys emitted by more than one block, only the first value is localised
+    pub collisions: Vec<String>,
+    /// Generated keys that matched a vanilla key, as (generated, renamed)
+    pub vanilla_collisions: Vec<(String, String)>,
+    /// Generated keys that matched a key of the mod's own localisation, as (generated, renamed)
+    pub existing_collisions: Vec<(String, String)>,
+    /// Colliding keys whose values differ, the later values are lost
+    pub conflicts: Vec<KeyConflict>,
+    /// Displayed names emitted by more than one block, to the paths of those blocks
+    pub cross_duplicates: BTreeMap<String, Vec<String>>,
+    /// Warnings repeated past [`warnings::THRESHOLD`], every message in full, see
+    /// [`crate::warnings`]
+    pub warnings: Vec<RepeatedWarning>,
+    /// Spending against the run's budget, `None` without one
+    pub budget: Option<BudgetUsage>,
+    /// One per translation pass and language, see [`Generation::translate`]
+    pub translations: Vec<TranslationReport>,
+    /// Failed model calls by kind, ie `rate limit`, see [`crate::health`]
+    pub errors: BTreeMap<String, usize>,
+    /// What the run was predicted to spend before it started, see [`Generation::estimate`]
+    pub estimate: Option<Estimate>,
+    /// Tokens the run actually spent, `None` when the generator doesn't count them
+    pub usage: Option<TokenUsage>,
+    /// Time the run was parked waiting for an exhausted quota, see [`crate::quota`]
+    pub quota_wait_ms: u64,
+    /// Blocks that reused the answer of a block asking for the same names, to that block's
+    /// path, see [`GenerationBuilder::coalesce`]. Each saved a model call
+    pub coalesced: BTreeMap<String, String>,
+    /// Renamed blocks whose cache was moved from one of their `# alias:` paths, to that path
+    pub aliased: BTreeMap<String, String>,
+    /// Localisation values holding characters the run's charset rejects, see [`crate::charset`]
+    pub charset_violations: Vec<CharsetViolation>,
+    /// Inline values no localisation value can hold in the run's quote mode, as (structure
+    /// line, value), emitted with their quotes and backslashes stripped, see [`crate::quoting`]
+    pub unsafe_values: Vec<(String, String)>,
+    /// Inline lines left out as the budget ran out before their values were generated, as
+    /// `file_structure.txt:<line>`, see [`crate::budget`]
+    pub budget_skipped: Vec<String>,
+    /// Drafted with a cheap model into the draft cache, final material otherwise, see
+    /// [`crate::draft`]
+    pub draft: bool,
+}
+
+impl Report {
+    /// Fails listing every block that kept fewer than `min` names, for gating automated builds
+    ///
+    /// ```
+    /// use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+    ///
+    /// let result = futures::executor::block_on(
+    ///     Generation::builder()
+    ///         .structure("NAME = {\n    # Ship names\n    ships = {\n    }\n}\n")
+    ///         .lore("")
+    ///         .generator(MockGenerator::new(["Vex", "Ember"]))
+    ///         .cache(InMemoryCache::new())
+    ///         .run(),
+    /// )
+    /// .unwrap();
+    /// assert!(result.report.check_thin(2).is_ok());
+    /// let err = result.report.check_thin(3).unwrap_err();
+    /// assert!(err.to_string().contains("NAME/ships: 2 names"));
+    /// ```
+    pub fn check_thin(&self, min: usize) -> Result<()> {
+        let thin: Vec<String> = self
+            .blocks
+            .iter()
+            .filter(|block| block.names < min)
+            .map(|block| format!("  {}: {} names", block.path.join("/"), block.names))
+            .collect();
+        if !thin.is_empty() {
+            anyhow::bail!(
+                "{} block(s) produced fewer than {} names:\n{}",
+                thin.len(),
+                min,
+                thin.join("\n")
+            );
+        }
+        Ok(())
+    }
+
+    /// Fails listing every name emitted by more than one block of `category`
+    ///
+    /// ```
+    /// use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+    ///
+    /// let structure = "NAME = {\n    ## Ship names\n    ships = {\n    }\n    ## Fleet names\n    fleets = {\n    }\n}\n";
+    /// let result = futures::executor::block_on(
+    ///     Generation::builder()
+    ///         .structure(structure)
+    ///         .lore("")
+    ///         .generator(MockGenerator::new(["Vex", "Ember"]))
+    ///         .category("NAME/ships", "navy")
+    ///         .category("NAME/fleets", "navy")
+    ///         .cache(InMemoryCache::new())
+    ///         .run(),
+    /// )
+    /// .unwrap();
+    /// // Block scoped uniqueness lets both blocks keep both names, the cross-reference shows it
+    /// assert_eq!(
+    ///     result.report.cross_duplicates["Vex"],
+    ///     vec!["NAME/ships".to_string(), "NAME/fleets".to_string()]
+    /// );
+    /// assert!(result.report.check_cross_duplicates("army").is_ok());
+    /// let err = result.report.check_cross_duplicates("navy").unwrap_err();
+    /// assert!(err.to_string().contains("'Ember' in NAME/ships, NAME/fleets"));
+    /// ```
+    pub fn check_cross_duplicates(&self, category: &str) -> Result<()> {
+        let in_category: HashSet<String> = self
+            .blocks
+            .iter()
+            .filter(|block| block.category == category)
+            .map(|block| block.path.join("/"))
+            .collect();
+        let duplicates: Vec<String> = self
+            .cross_duplicates
+            .iter()
+            .filter_map(|(name, blocks)| {
+                let blocks: Vec<&str> = blocks
+                    .iter()
+                    .filter(|block| in_category.contains(*block))
+                    .map(String::as_str)
+                    .collect();
+                (blocks.len() > 1).then(|| format!("  '{}' in {}", name, blocks.join(", ")))
+            })
+            .collect();
+        if !duplicates.is_empty() {
+            anyhow::bail!(
+                "{} name(s) appear in more than one block of category '{}':\n{}",
+                duplicates.len(),
+                category,
+                duplicates.join("\n")
+            );
+        }
+        Ok(())
+    }
+}
+
+/// A key emitted twice with different values, only the first value is localised
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct KeyConflict {
+    pub key: String,
+    /// Block path (or structure line) that localised the key first
+    pub first_source: String,
+    pub first_value: String,
+    /// Block path (or structure line) whose value was dropped
+    pub source: String,
+    pub value: String,
+}
+
+/// Entries of a single block before they are merged into the output
+struct BlockOutcome {
+    entries: Vec<(String, String)>,
+    cached: bool,
+    generated: usize,
+    dropped: BTreeMap<String, usize>,
+    scores: BTreeMap<u8, usize>,
+    style_scores: Vec<(String, u8)>,
+    near_forms: Vec<NearForm>,
+    semantic: Vec<SemanticCluster>,
+    casing: Option<CasingReport>,
+    unsafe_names: Vec<String>,
+    diversity: Diversity,
+    distribution: Option<Balance>,
+    stages: Vec<Stage>,
+    shuffle_seed: Option<u64>,
+    elapsed_ms: u64,
+    budget_exhausted: bool,
+    salvaged: usize,
+    truncated: bool,
+    /// Unix time the names were generated at, in seconds, if known
+    generated_at: Option<u64>,
+    /// Fewer names than the block's `# count:` survived filtering under `strict_count`
+    short_count: bool,
+}
+
+/// A block's names after its filter chain, and what each filter did
+struct Filtered {
+    names: Vec<String>,
+    generated: usize,
+    stages: Vec<Stage>,
+    dropped: BTreeMap<String, usize>,
+    scores: BTreeMap<u8, usize>,
+    style_scores: Vec<(String, u8)>,
+    near_forms: Vec<NearForm>,
+    semantic: Vec<SemanticCluster>,
+    casing: Option<CasingReport>,
+    unsafe_names: Vec<String>,
+    distribution: Option<Balance>,
+}
+
+/// What came of a request sent through the circuit breaker
+enum Reply<T> {
+    /// `truncated` if the stream closed without its End event
+    Answer {
+        raw: String,
+        parsed: T,
+        truncated: bool,
+    },
+    /// The stream broke off, the request is worth repeating as it was
+    Interrupted,
+    /// The request errored or its answer was unusable
+    Failed,
+}
+
+/// Translations of a block's entries by key
+#[derive(Default)]
+struct TranslatedBlock {
+    values: HashMap<String, String>,
+    /// Translations read from the cache
+    cached: usize,
+}
+
+/// Localisation entries being merged, first value wins
+struct Localisation<'a> {
+    /// Kept in emission order so the localisation file follows the name list
+    entries: Vec<(String, String)>,
+    /// Source and value of every localised key
+    keys: HashMap<String, (String, String)>,
+    vanilla: &'a VanillaKeys,
+    /// Keys of the mod's hand-written localisation, reserved like vanilla ones
+    existing: &'a VanillaKeys,
+    /// Most bytes of a key, those written as given are only warned about
+    max_key_len: Option<usize>,
+}
+
+impl<'a> Localisation<'a> {
+    fn new(
+        vanilla: &'a VanillaKeys,
+        existing: &'a VanillaKeys,
+        max_key_len: Option<usize>,
+    ) -> Self {
+        Self {
+            entries: Vec::new(),
+            keys: HashMap::new(),
+            vanilla,
+            existing,
+            max_key_len,
+        }
+    }
+
+    /// Localises `value` under `key`, renamed away from vanilla keys and the mod's own,
+    /// returning the key to emit
+    fn add(&mut self, key: String, value: &str, source: &str, report: &mut Report) -> String {
+        let key = match self.rename(&key) {
+            Some(renamed) => {
+                let collisions = if self.vanilla.contains(&key) {
+                    &mut report.vanilla_collisions
+                } else {
+                    &mut report.existing_collisions
+                };
+                if !collisions.iter().any(|(k, _)| *k == key) {
+                    collisions.push((key, renamed.clone()));
+                }
+                renamed
+            }
+            None => key,
+        };
+        self.insert(key, value, source, report)
+    }
+
+    /// A suffixed replacement for a key reserved by either key set, free in both. It depends on
+    /// the key sets alone, so a key is renamed the same way on every run
+    fn rename(&self, key: &str) -> Option<String> {
+        let reserved = |key: &str| self.vanilla.contains(key) || self.existing.contains(key);
+        if !reserved(key) {
+            return None;
+        }
+        (2..)
+            .map(|n| format!("{}_{}", key, n))
+            .find(|candidate| !reserved(candidate))
+    }
+
+    /// Localises `value` under `key` as given, ie a pinned key
+    fn insert(&mut self, key: String, value: &str, source: &str, report: &mut Report) -> String {
+        match self.keys.get(&key) {
+            None => {
+                if let Some(max) = self.max_key_len
+                    && key.len() > max
+                {
+                    warning!(
+                        "[Key Length] '{}' in '{}' is {} bytes, over the {} byte limit",
+                        key,
+                        source,
+                        key.len(),
+                        max
+                    );
+                }
+                self.keys
+                    .insert(key.clone(), (source.to_string(), value.to_string()));
+                self.entries.push((key.clone(), value.to_string()));
+            }
+            Some((first_source, first_value)) => {
+                report.collisions.push(key.clone());
+                if first_value != value {
+                    warning!(
+                        "[Conflict] '{}' is \"{}\" in '{}' but \"{}\" in '{}', keeping the first",
+                        key,
+                        first_value,
+                        first_source,
+                        value,
+                        source
+                    );
+                    report.conflicts.push(KeyConflict {
+                        key: key.clone(),
+                        first_source: first_source.clone(),
+                        first_value: first_value.clone(),
+                        source: source.to_string(),
+                        value: value.to_string(),
+                    });
+                }
+            }
+        }
+        key
+    }
+}
+
+/// Everything a run produces
+#[derive(Debug, Clone)]
+pub struct RunResult {
+    /// The name list configuration, ie `out.txt`
+    pub name_list: String,
+    /// Localisation entries as (key, value), in emission order
+    pub localisation: Vec<(String, String)>,
+    /// Localisation of every translated language, same keys and order as the English one
+    pub translations: BTreeMap<Language, Vec<(String, String)>>,
+    pub report: Report,
+    /// Template of every localisation value between its quotes, see
+    /// [`GenerationBuilder::value_format`]
+    pub value_format: String,
+}
+
+impl RunResult {
+    /// Renders the localisation entries as an `l_english` yml document, quotes in values escaped
+    pub fn localisation_yml(&self) -> String {
+        render_yml("english", &self.localisation, &self.value_format)
+    }
+
+    /// Renders the localisation of a translated language as an `l_<language>` yml document
+    pub fn translated_yml(&self, language: Language) -> Option<String> {
+        let entries = self.translations.get(&language)?;
+        Some(render_yml(language.id(), entries, &self.value_format))
+    }
+
+    /// Renders the English localisation as one `l_english` yml document per file of `split`,
+    /// by part, see [`crate::loc_split`]
+    pub fn localisation_parts(&self, split: LocSplit) -> BTreeMap<String, String> {
+        split_entries(self, split, &self.localisation)
+            .into_iter()
+            .map(|(part, entries)| (part, render_yml("english", &entries, &self.value_format)))
+            .collect()
+    }
+
+    /// Renders the localisation of a translated language split like [`Self::localisation_parts`]
+    pub fn translated_parts(
+        &self,
+        language: Language,
+        split: LocSplit,
+    ) -> BTreeMap<String, String> {
+        let entries = self
+            .translations
+            .get(&language)
+            .map(Vec::as_slice)
+            .unwrap_or_default();
+        split_entries(self, split, entries)
+            .into_iter()
+            .map(|(part, entries)| {
+                (
+                    part,
+                    render_yml(language.id(), &entries, &self.value_format),
+                )
+            })
+            .collect()
+    }
+
+    /// Appends the localisation and report of another run, ie [`Generation::run_tasks`].
+    /// Keys this run already localised keep their value and are reported as collisions
+    pub fn merge(&mut self, other: RunResult) {
+        let keys: HashSet<String> = self
+            .localisation
+            .iter()
+            .map(|(key, _)| key.clone())
+            .collect();
+        for (key, value) in other.localisation {
+            if keys.contains(&key) {
+                self.report.collisions.push(key);
+            } else {
+                self.localisation.push((key, value));
+            }
+        }
+        self.report.blocks.extend(other.report.blocks);
+        self.report.collisions.extend(other.report.collisions);
+        self.report
+            .vanilla_collisions
+            .extend(other.report.vanilla_collisions);
+        self.report
+            .existing_collisions
+            .extend(other.report.existing_collisions);
+        self.report.conflicts.extend(other.report.conflicts);
+        self.report.translations.extend(other.report.translations);
+        // Both runs share the generation's budget, the later usage covers both
+        if other.report.budget.is_some() {
+            self.report.budget = other.report.budget;
+        }
+    }
+}
+
+/// Whether `key` caches the block at `path` under any lore or era, scores and translations
+/// left out
+fn same_block(key: &BlockKey, path: &[String]) -> bool {
+    let name = key.as_str();
+    !name.contains('~') && name.split('@').next() == Some(BlockKey::from_path(path).as_str())
+}
+
+/// Everything shaping the prompt of a block, blocks with the same signature ask for the same
+/// names
+#[derive(PartialEq, Eq, Hash)]
+struct PromptSignature<'a> {
+    theme: &'a str,
+    model: &'a str,
+    max_names: Option<usize>,
+    /// The lore, through the variant of the block's cache key
+    variant: Option<&'a str>,
+    era: Option<Era>,
+    rarity: Option<Rarity>,
+    style: Option<euphony::NameStyle>,
+    species_class: Option<&'a str>,
+    schema: Option<&'a str>,
+    avoid_initials: &'a [char],
+    flavors: Vec<String>,
+}
+
+/// For every block, the earlier block asking the same model for the same names, if any. Blocks
+/// match on everything shaping their prompt, including the flavors sampled for them from `pool`.
+/// Blocks seeded with their own entries, replaying stored parameters or composing names
+/// never match
+fn coalesce_leaders(
+    jobs: &[GenerationJob],
+    keys: &[BlockKey],
+    pool: &[String],
+    seed: u64,
+) -> Vec<Option<usize>> {
+    let mut first: HashMap<PromptSignature<'_>, usize> = HashMap::new();
+    jobs.iter()
+        .zip(keys)
+        .enumerate()
+        .map(|(idx, (job, key))| {
+            if !job.seeds.is_empty() || job.replayed.is_some() || !job.compose.is_empty() {
+                return None;
+            }
+            let signature = PromptSignature {
+                theme: &job.theme,
+                model: &job.model,
+                max_names: job.max_names,
+                variant: key.as_str().split_once('@').map(|(_, variant)| variant),
+                era: job.era,
+                rarity: job.rarity,
+                style: job.style,
+                species_class: job.species_class.as_deref(),
+                schema: job.schema.as_deref(),
+                avoid_initials: &job.avoid_initials,
+                flavors: sample_flavors(pool, seed, &job.path),
+            };
+            match first.get(&signature) {
+                Some(&leader) => Some(leader),
+                None => {
+                    first.insert(signature, idx);
+                    None
+                }
+            }
+        })
+        .collect()
+}
+
+/// Whether `block` was generated under the prefix `job` now has. Entries cached before
+/// prefixes were recorded hold for any
+fn same_prefix(block: &CachedBlock, job: &GenerationJob) -> bool {
+    block
+        .prefix
+        .as_ref()
+        .is_none_or(|prefix| *prefix == job.prefix)
+}
+
+/// Renders localisation entries as an `l_<language>` yml document, each value written through
+/// `format` with its quotes, and those of the template, escaped
+fn render_yml(language: &str, entries: &[(String, String)], format: &str) -> String {
+    let format = quoting::escape_yml(format);
+    let mut loc_out = format!("l_{}:\n", language);
+    for (key, val) in entries {
+        let value = format
+            .replace("{key}", key)
+            .replace("{val}", &quoting::escape_yml(val));
+        loc_out.push_str(&format!("    {}:0 \"{}\"\n", key, value));
+    }
+    loc_out
+}
+
+/// A configured generation run over a structure and lore held in memory
+pub struct Generation {
+    structure: String,
+    lore: String,
+    lore_profiles: LoreProfiles,
+    model: String,
+    concurrency: usize,
+    max_concurrent_models: Option<usize>,
+    model_limits: HashMap<String, usize>,
+    keep_raw: bool,
+    strict: bool,
+    /// Continues blocks whose filters left them short of their `# count:`, failing the block if
+    /// they stay short
+    strict_count: bool,
+    min_names: usize,
+    unique_scope: UniqueScope,
+    categories: HashMap<String, String>,
+    near_forms: NearFormPolicy,
+    /// Share of a block's names its largest cluster may hold before it is trimmed
+    max_cluster_fraction: Option<f64>,
+    cluster_action: ClusterAction,
+    /// Drops names meaning the same as a kept one, `None` leaves them
+    semantic_dedup: Option<SemanticDedup>,
+    /// Keeps the embedding model within its limit, apart from the models generating the blocks
+    embedding_limiter: ModelLimiter,
+    /// Rarity of blocks without a `# rarity=`
+    rarity: Rarity,
+    /// Names made up only of these words are dropped from high rarity blocks
+    common_words: CommonWords,
+    key_style: KeyStyle,
+    key_compat: KeyCompatMap,
+    key_map: KeyMap,
+    reuse_retired_keys: bool,
+    consistency: CasingCheck,
+    pins: Pins,
+    ascii_only: bool,
+    quotes: QuoteMode,
+    loc_charset: LocCharset,
+    strip_loc_charset: bool,
+    budget: Budget,
+    /// Set while [`Generation::check_determinism`] runs, refusing every request
+    replaying: AtomicBool,
+    health: HealthMonitor,
+    /// Parks the run on an exhausted quota, `None` fails its requests instead
+    quota: Option<QuotaWait>,
+    /// `max_tokens` each model accepted, for models that rejected [`DEFAULT_MAX_TOKENS`]
+    token_limits: Mutex<HashMap<String, u32>>,
+    /// Opens every generated block of the name list with a comment on its origin
+    provenance_comments: bool,
+    /// Wraps the generated lines of every block in markers, see [`crate::in_place`]
+    markers: bool,
+    /// Blocks asking a model for the same names share one call
+    coalesce: bool,
+    /// Template of localisation values, holding `{val}`
+    value_format: String,
+    /// Indentation per nesting level when the structure shows none
+    indent: String,
+    /// Names requested on top of a block's count, `None` streams the whole answer
+    early_stop_margin: Option<usize>,
+    vanilla_keys: VanillaKeys,
+    existing_keys: VanillaKeys,
+    abbreviations: Option<Abbreviations>,
+    /// Custom schemas by the path `#@ schema:` names, others are read from disk
+    schemas: HashMap<String, serde_json::Value>,
+    /// Flavor pool, empty when flavors are disabled
+    flavors: Vec<String>,
+    species: SpeciesClasses,
+    /// Species class of blocks without a `#@ species-class:`
+    species_class: Option<String>,
+    /// Prices per model, USD per million tokens
+    prices: HashMap<String, ModelPrice>,
+    /// Blocks generated again replay the parameters of their earlier names
+    match_existing: bool,
+    /// Model generating every block of a draft run, see [`crate::draft`]
+    draft_model: Option<String>,
+    /// The flavor settings were given explicitly, stored flavors never replace them
+    explicit_flavors: bool,
+    /// Per-provider credentials, telling which stored models are still available
+    providers: HashMap<String, ProviderConfig>,
+    seed: u64,
+    shuffle_seed: Option<u64>,
+    generator: Box<dyn NameGenerator>,
+    cache: Mutex<Box<dyn NameCache>>,
+}
+
+/// Builder for [`Generation`], see the crate docs for an example
+#[derive(Default)]
+pub struct GenerationBuilder {
+    structure: Option<String>,
+    lore: Option<String>,
+    lore_profiles: LoreProfiles,
+    model: Option<String>,
+    concurrency: Option<usize>,
+    max_concurrent_models: Option<usize>,
+    model_limits: HashMap<String, usize>,
+    keep_raw: bool,
+    strict: bool,
+    strict_count: Option<bool>,
+    min_names: Option<usize>,
+    unique_scope: Option<UniqueScope>,
+    near_forms: Option<NearFormPolicy>,
+    max_cluster_fraction: Option<f64>,
+    cluster_action: Option<ClusterAction>,
+    semantic_dedup: Option<bool>,
+    semantic_threshold: Option<f64>,
+    embedding_model: Option<String>,
+    rarity: Option<Rarity>,
+    common_words: CommonWords,
+    key_style: Option<KeyStyle>,
+    hash_keys: Option<HashKeys>,
+    key_case: Option<KeyCase>,
+    max_key_len: Option<usize>,
+    key_compat: KeyCompatMap,
+    key_map: KeyMap,
+    reuse_retired_keys: bool,
+    consistency: CasingCheck,
+    pins: Pins,
+    ascii_only: Option<bool>,
+    quotes: Option<QuoteMode>,
+    loc_charset: Option<LocCharset>,
+    strip_loc_charset: Option<bool>,
+    budget_requests: Option<u64>,
+    budget_tokens: Option<u64>,
+    early_stop: Option<bool>,
+    early_stop_margin: Option<usize>,
+    provenance_comments: Option<bool>,
+    markers: bool,
+    coalesce: Option<bool>,
+    value_format: Option<String>,
+    indent: Option<String>,
+    max_error_rate: Option<f64>,
+    error_window: Option<usize>,
+    wait_for_quota: Option<Duration>,
+    quota_check_interval: Option<Duration>,
+    interrupt: Option<Interrupt>,
+    categories: HashMap<String, String>,
+    vanilla_keys: VanillaKeys,
+    existing_keys: VanillaKeys,
+    abbreviations: Vec<(String, String)>,
+    schemas: HashMap<String, serde_json::Value>,
+    flavor: Option<bool>,
+    extra_flavors: Vec<String>,
+    species_class: Option<String>,
+    species_hints: Vec<(String, String)>,
+    prices: HashMap<String, ModelPrice>,
+    match_existing: bool,
+    draft_model: Option<String>,
+    explicit_flavors: bool,
+    providers: HashMap<String, ProviderConfig>,
+    seed: Option<u64>,
+    shuffle_seed: Option<u64>,
+    generator: Option<Box<dyn NameGenerator>>,
+    cache: Option<Box<dyn NameCache>>,
+}
+
+impl GenerationBuilder {
+    /// Contents of the structure file
+    pub fn structure(mut self, structure: impl Into<String>) -> Self {
+        self.structure = Some(structure.into());
+        self
+    }
+
+    /// Lore the names are generated from
+    pub fn lore(mut self, lore: impl Into<String>) -> Self {
+        self.lore = Some(lore.into());
+        self
+    }
+
+    /// Registers a named lore document that blocks select with `# lore: <name>`
+    pub fn lore_profile(mut self, name: impl Into<String>, lore: impl Into<String>) -> Self {
+        self.lore_profiles.insert(name, lore);
+        self
+    }
+
+    /// Replaces every registered lore profile
+    pub fn lore_profiles(mut self, profiles: LoreProfiles) -> Self {
+        self.lore_profiles = profiles;
+        self
+    }
+
+    /// Default model for blocks without a `# model:` directive
+    pub fn model(mut self, model: impl Into<String>) -> Self {
+        self.model = Some(model.into());
+        self
+    }
+
+    /// Number of blocks generated at once. Entries are merged once every block finished, in
+    /// document order, so the first block to emit a key keeps it whatever order the blocks
+    /// completed in:
+    ///
+    /// ```
+    /// use futures::future::BoxFuture;
+    /// use std::sync::{Arc, Mutex};
+    /// use std::time::Duration;
+    /// use stellaris_name_gen::{Generation, GenerationRequest, InMemoryCache, NameGenerator};
+    ///
+    /// /// Answers later blocks faster, recording the order blocks completed in
+    /// struct Staggered(Arc<Mutex<Vec<String>>>);
+    ///
+    /// impl NameGenerator for Staggered {
+    ///     fn generate<'a>(&'a self, request: &'a GenerationRequest) -> BoxFuture<'a, anyhow::Result<String>> {
+    ///         let (delay, answer) = match request.theme.as_str() {
+    ///             "Old fleets" => (60, r#"{"names": ["Vex", "Ash"]}"#),
+    ///             "New fleets" => (30, r#"{"names": ["VEX", "Ember"]}"#),
+    ///             _ => (0, r#"{"names": ["Ember", "Cinder"]}"#),
+    ///         };
+    ///         Box::pin(async move {
+    ///             tokio::time::sleep(Duration::from_millis(delay)).await;
+    ///             self.0.lock().unwrap().push(request.theme.clone());
+    ///             Ok(answer.to_string())
+    ///         })
+    ///     }
+    /// }
+    ///
+    /// let structure = "NAME = {\n    # Old fleets\n    # sort: alpha\n    old = {\n    }\n    # New fleets\n    # sort: alpha\n    new = {\n    }\n    # Stations\n    # sort: alpha\n    stations = {\n    }\n}\n";
+    /// let run = |concurrency| {
+    ///     let completed = Arc::new(Mutex::new(Vec::new()));
+    ///     let result = tokio::runtime::Runtime::new().unwrap().block_on(
+    ///         Generation::builder()
+    ///             .structure(structure)
+    ///             .lore("")
+    ///             .min_names(1)
+    ///             .generator(Staggered(completed.clone()))
+    ///             .concurrency(concurrency)
+    ///             .cache(InMemoryCache::new())
+    ///             .run(),
+    ///     );
+    ///     let completed = completed.lock().unwrap().clone();
+    ///     (result.unwrap(), completed)
+    /// };
+    /// let (sequential, in_order) = run(1);
+    /// let (parallel, completed) = run(3);
+    /// assert_eq!(in_order, ["Old fleets", "New fleets", "Stations"]);
+    /// assert_eq!(completed, ["Stations", "New fleets", "Old fleets"]);
+    ///
+    /// assert_eq!(parallel.localisation_yml(), sequential.localisation_yml());
+    /// assert_eq!(parallel.name_list, sequential.name_list);
+    /// // `VEX` is kept with the value of the block written first, not the one finished first
+    /// assert!(parallel.localisation_yml().contains("    VEX:0 \"Vex\"\n"));
+    /// assert_eq!(parallel.report.conflicts.len(), 1);
+    /// ```
+    pub fn concurrency(mut self, concurrency: usize) -> Self {
+        self.concurrency = Some(concurrency);
+        self
+    }
+
+    /// In-flight cap for models without their own limit
+    pub fn max_concurrent_models(mut self, limit: usize) -> Self {
+        self.max_concurrent_models = Some(limit);
+        self
+    }
+
+    /// In-flight cap for a single model
+    pub fn model_limit(mut self, model: impl Into<String>, limit: usize) -> Self {
+        self.model_limits.insert(model.into(), limit);
+        self
+    }
+
+    /// Fails the run when a key is emitted twice with different values instead of only warning
+    pub fn strict(mut self, strict: bool) -> Self {
+        self.strict = strict;
+        self
+    }
+
+    /// Guarantees every block with a `# count:` the names it asks for, or marks it failed. A
+    /// block whose filters kept fewer names is continued by up to [`MAX_CONTINUATIONS`] requests
+    /// avoiding every name it holds, and a block still short is reported as failed, naming the
+    /// shortfall, while the other blocks are written as usual. Its names stay cached, so the next
+    /// run continues from them
+    ///
+    /// ```
+    /// use futures::future::BoxFuture;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use stellaris_name_gen::{Generation, GenerationRequest, InMemoryCache, NameGenerator};
+    ///
+    /// /// Two new names per call, up to `limit` in total
+    /// struct Trickle {
+    ///     calls: AtomicUsize,
+    ///     limit: usize,
+    /// }
+    ///
+    /// impl NameGenerator for Trickle {
+    ///     fn generate<'a>(&'a self, _: &'a GenerationRequest) -> BoxFuture<'a, anyhow::Result<String>> {
+    ///         let call = self.calls.fetch_add(1, Ordering::SeqCst);
+    ///         let names: Vec<String> = ["Vex", "Ember", "Dawn", "Cinder", "Ash", "Krag"]
+    ///             [(call * 2).min(self.limit - 1)..(call * 2 + 2).min(self.limit)]
+    ///             .iter()
+    ///             .map(|name| name.to_string())
+    ///             .collect();
+    ///         Box::pin(async move { Ok(serde_json::json!({ "names": names }).to_string()) })
+    ///     }
+    /// }
+    ///
+    /// let run = |directives: &str, limit: usize| {
+    ///     futures::executor::block_on(
+    ///         Generation::builder()
+    ///             .structure(format!(
+    ///                 "NAME = {{\n    # Ship names\n    # count: 5\n{}    ships = {{\n    }}\n}}\n",
+    ///                 directives
+    ///             ))
+    ///             .lore("")
+    ///             .min_names(1)
+    ///             .strict_count(true)
+    ///             .generator(Trickle { calls: AtomicUsize::new(0), limit })
+    ///             .cache(InMemoryCache::new())
+    ///             .run(),
+    ///     )
+    /// };
+    /// // The first answer holds 2 names, two continuations bring the rest
+    /// let result = run("", 6).unwrap();
+    /// assert_eq!(result.localisation.len(), 5);
+    ///
+    /// // Names the filters drop don't count, the block gets its 5 without `Vex`
+    /// let result = run("    #@ avoid-initials: V\n", 6).unwrap();
+    /// assert_eq!(result.localisation.len(), 5);
+    /// assert!(!result.report.blocks[0].failed);
+    ///
+    /// // A model running dry fails the block instead of the run
+    /// let result = run("", 3).unwrap();
+    /// assert_eq!(result.localisation.len(), 3);
+    /// assert!(result.report.blocks[0].failed);
+    /// ```
+    pub fn strict_count(mut self, enabled: bool) -> Self {
+        self.strict_count = Some(enabled);
+        self
+    }
+
+    /// Fewest names a block may end up with after filtering, defaults to [`DEFAULT_MIN_NAMES`].
+    /// Blocks override it with `#@ min: N`
+    pub fn min_names(mut self, min_names: usize) -> Self {
+        self.min_names = Some(min_names);
+        self
+    }
+
+    /// Also caches the unrepaired model output next to the parsed names, for debugging
+    pub fn keep_raw(mut self, keep_raw: bool) -> Self {
+        self.keep_raw = keep_raw;
+        self
+    }
+
+    /// Generates blocks missing from the cache with the model, flavors and temperature of their
+    /// newest cached names, see [`crate::replay`]
+    pub fn match_existing(mut self, enabled: bool) -> Self {
+        self.match_existing = enabled;
+        self
+    }
+
+    /// Generates every block with `model`, ignoring `# model:`, for a draft run. The run's
+    /// report is labelled as a draft and its cache should be the draft tier, see [`crate::draft`]
+    pub fn draft_model(mut self, model: impl Into<String>) -> Self {
+        self.draft_model = Some(model.into());
+        self
+    }
+
+    /// Marks the flavor settings (`seed` and `flavor`) as given on the command line, so
+    /// [`match_existing`](Self::match_existing) keeps them over the stored flavors
+    pub fn explicit_flavors(mut self, explicit: bool) -> Self {
+        self.explicit_flavors = explicit;
+        self
+    }
+
+    /// How far name uniqueness reaches, defaults to [`UniqueScope::Block`]. Under a wider scope,
+    /// a block sent to the model is told the names the blocks ahead of it in the scope hold in
+    /// the cache are taken:
+    ///
+    /// ```
+    /// use futures::future::BoxFuture;
+    /// use stellaris_name_gen::{FsCache, Generation, GenerationRequest, NameGenerator, UniqueScope};
+    ///
+    /// struct Taken;
+    /// impl NameGenerator for Taken {
+    ///     fn generate<'a>(&'a self, request: &'a GenerationRequest) -> BoxFuture<'a, anyhow::Result<String>> {
+    ///         let names = if request.prompt.contains("already taken, come up with different ones: Vex, Ember") {
+    ///             ["Dawn", "Cinder"]
+    ///         } else {
+    ///             ["Vex", "Ember"]
+    ///         };
+    ///         Box::pin(async move { Ok(serde_json::json!({ "names": names }).to_string()) })
+    ///     }
+    /// }
+    ///
+    /// let dir = std::env::temp_dir().join(format!("namegen-doc-taken-{}", std::process::id()));
+    /// let _ = std::fs::remove_dir_all(&dir);
+    /// let run = |structure: &str| {
+    ///     futures::executor::block_on(
+    ///         Generation::builder()
+    ///             .structure(structure)
+    ///             .lore("")
+    ///             .min_names(1)
+    ///             .unique_scope(UniqueScope::Global)
+    ///             .generator(Taken)
+    ///             .cache(FsCache::new(&dir))
+    ///             .run(),
+    ///     )
+    ///     .unwrap()
+    /// };
+    /// run("NAME = {\n    # Ship names\n    ships = {\n    }\n}\n");
+    /// // The fleets avoid the ships' names rather than losing theirs to them
+    /// let result = run("NAME = {\n    # Ship names\n    ships = {\n    }\n    # Fleet names\n    fleets = {\n    }\n}\n");
+    /// let names: Vec<&str> = result.localisation.iter().map(|(_, name)| name.as_str()).collect();
+    /// assert_eq!(names, ["Vex", "Ember", "Dawn", "Cinder"]);
+    /// # std::fs::remove_dir_all(&dir).unwrap();
+    /// ```
+    pub fn unique_scope(mut self, scope: UniqueScope) -> Self {
+        self.unique_scope = Some(scope);
+        self
+    }
+
+    /// Groups blocks under a path prefix (`NAME/ship_names`) into a named category. A block under
+    /// no configured prefix is in the category of its top-level block, and a run with
+    /// [`UniqueScope::Category`] warns about such blocks
+    pub fn category(mut self, path_prefix: impl Into<String>, category: impl Into<String>) -> Self {
+        self.categories.insert(path_prefix.into(), category.into());
+        self
+    }
+
+    /// What to do with near forms of a name in the same block, ie `Tyran` and `Tyrans`, off by default
+    pub fn near_forms(mut self, policy: NearFormPolicy) -> Self {
+        self.near_forms = Some(policy);
+        self
+    }
+
+    /// Cuts the clusters of names sharing a stem that hold more than this share of their block
+    /// down to one representative, picked with the run's shuffle seed, see [`crate::clusters`].
+    /// Every block's diversity is reported either way
+    ///
+    /// ```
+    /// use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+    ///
+    /// let names = ["Vexmar", "Vexlor", "Vexrin", "Vexadan", "Ember", "Cinder", "Ashford", "Dawn", "Halcyon", "Orison"];
+    /// let result = futures::executor::block_on(
+    ///     Generation::builder()
+    ///         .structure("NAME = {\n    # Ship names\n    ships = {\n    }\n}\n")
+    ///         .lore("")
+    ///         .min_names(1)
+    ///         .max_cluster_fraction(0.15)
+    ///         .generator(MockGenerator::new(names))
+    ///         .cache(InMemoryCache::new())
+    ///         .run(),
+    /// )
+    /// .unwrap();
+    /// let block = &result.report.blocks[0];
+    /// assert_eq!(block.dropped["clusters"], 3);
+    /// assert_eq!(block.names, 7);
+    /// assert!(block.diversity.clusters.is_empty());
+    /// ```
+    pub fn max_cluster_fraction(mut self, fraction: f64) -> Self {
+        self.max_cluster_fraction = Some(fraction);
+        self
+    }
+
+    /// What happens to the names trimmed by [`max_cluster_fraction`](Self::max_cluster_fraction),
+    /// [`ClusterAction::Trim`] by default. [`ClusterAction::Regenerate`] asks for as many names
+    /// again, forbidding the dominant stems, and caches the answer beside the block
+    ///
+    /// ```
+    /// use futures::future::BoxFuture;
+    /// use stellaris_name_gen::clusters::ClusterAction;
+    /// use stellaris_name_gen::{Generation, GenerationRequest, InMemoryCache, NameGenerator};
+    ///
+    /// struct Monotonous;
+    ///
+    /// impl NameGenerator for Monotonous {
+    ///     fn generate<'a>(&'a self, request: &'a GenerationRequest) -> BoxFuture<'a, anyhow::Result<String>> {
+    ///         let raw = if request.prompt.contains("vex-") {
+    ///             r#"{"names": ["Vexorin", "Solace", "Ember", "Tidewater"]}"#
+    ///         } else {
+    ///             r#"{"names": ["Vexmar", "Vexlor", "Vexrin", "Vexadan", "Ember", "Cinder", "Ashford"]}"#
+    ///         };
+    ///         Box::pin(async move { Ok(raw.to_string()) })
+    ///     }
+    /// }
+    ///
+    /// let result = futures::executor::block_on(
+    ///     Generation::builder()
+    ///         .structure("NAME = {\n    # Ship names\n    ships = {\n    }\n}\n")
+    ///         .lore("")
+    ///         .min_names(1)
+    ///         .max_cluster_fraction(0.15)
+    ///         .cluster_action(ClusterAction::Regenerate)
+    ///         .generator(Monotonous)
+    ///         .cache(InMemoryCache::new())
+    ///         .run(),
+    /// )
+    /// .unwrap();
+    /// let names: Vec<&str> = result.report.blocks[0]
+    ///     .entries
+    ///     .iter()
+    ///     .map(|(_, name)| name.as_str())
+    ///     .filter(|name| !["Ember", "Cinder", "Ashford"].contains(name))
+    ///     .collect();
+    /// // A single `Vex` name is left, the answer's own `Vex` name and repeated name are dropped
+    /// assert_eq!(names.len(), 3);
+    /// assert!(names.ends_with(&["Solace", "Tidewater"]));
+    /// ```
+    pub fn cluster_action(mut self, action: ClusterAction) -> Self {
+        self.cluster_action = Some(action);
+        self
+    }
+
+    /// Embeds every block's names and drops the ones meaning the same as a name kept before
+    /// them, see [`crate::semantic`]. Off by default, as it costs a call per block
+    pub fn semantic_dedup(mut self, enabled: bool) -> Self {
+        self.semantic_dedup = Some(enabled);
+        self
+    }
+
+    /// Cosine similarity from which [`semantic_dedup`](Self::semantic_dedup) drops a name,
+    /// [`semantic::DEFAULT_THRESHOLD`] by default
+    pub fn semantic_threshold(mut self, threshold: f64) -> Self {
+        self.semantic_threshold = Some(threshold);
+        self
+    }
+
+    /// Model embedding the names for [`semantic_dedup`](Self::semantic_dedup),
+    /// [`semantic::DEFAULT_EMBEDDING_MODEL`] by default
+    pub fn embedding_model(mut self, model: impl Into<String>) -> Self {
+        self.embedding_model = Some(model.into());
+        self
+    }
+
+    /// How unusual the names of blocks without a `# rarity=` should be, normal by default. High
+    /// rarity asks the model for evocative names, see [`crate::rarity`]
+    pub fn rarity(mut self, rarity: Rarity) -> Self {
+        self.rarity = Some(rarity);
+        self
+    }
+
+    /// Everyday words, names made up only of them are dropped from high rarity blocks
+    pub fn common_words(mut self, words: CommonWords) -> Self {
+        self.common_words = words;
+        self
+    }
+
+    /// How keys are derived from names, blocks adjust it with `#@ key-style:`
+    pub fn key_style(mut self, style: KeyStyle) -> Self {
+        self.key_style = Some(style);
+        self
+    }
+
+    /// Hashes keys instead of spelling them out, always or for names that can't be, overriding
+    /// the `hash` of the key style
+    ///
+    /// ```
+    /// use stellaris_name_gen::keys::HashKeys;
+    /// use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+    ///
+    /// let result = futures::executor::block_on(
+    ///     Generation::builder()
+    ///         .structure("NAME = {\n    # Leader names\n    leaders = {\n    }\n}\n")
+    ///         .lore("")
+    ///         .generator(MockGenerator::new(["Vex!", "Vex?", "???", "Ember"]))
+    ///         .hash_keys(HashKeys::Fallback)
+    ///         .cache(InMemoryCache::new())
+    ///         .run(),
+    /// )
+    /// .unwrap();
+    /// let keys: Vec<&str> = result.localisation.iter().map(|(key, _)| key.as_str()).collect();
+    /// // Every name is kept, under a hash when its spelled out key is taken or empty
+    /// assert_eq!(keys.len(), 4);
+    /// assert_eq!((keys[0], keys[3]), ("VEX_", "EMBER"));
+    /// assert!(keys[1].chars().all(|c| c.is_ascii_hexdigit()) && keys[1].len() == 8);
+    /// assert_ne!(keys[1], keys[2]);
+    /// ```
+    pub fn hash_keys(mut self, mode: HashKeys) -> Self {
+        self.hash_keys = Some(mode);
+        self
+    }
+
+    /// Letter case of derived keys, overriding the `case` of the key style. Uppercase by default,
+    /// as Stellaris expects
+    ///
+    /// ```
+    /// use stellaris_name_gen::keys::{KeyCase, KeyStyle};
+    /// use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+    ///
+    /// let keys = |case| {
+    ///     let result = futures::executor::block_on(
+    ///         Generation::builder()
+    ///             .structure("NAME = {\n    # prefix: Void_\n    # Leader names\n    leaders = {\n    }\n}\n")
+    ///             .lore("")
+    ///             .generator(MockGenerator::new(["Saint Vex", "Saint-Vex", "Ember"]))
+    ///             .key_case(case)
+    ///             .cache(InMemoryCache::new())
+    ///             .run(),
+    ///     )
+    ///     .unwrap();
+    ///     let keys: Vec<String> = result.localisation.into_iter().map(|(key, _)| key).collect();
+    ///     keys
+    /// };
+    /// assert_eq!(keys(KeyCase::Upper), ["Void_SAINT_VEX", "Void_EMBER"]);
+    /// assert_eq!(keys(KeyCase::Lower), ["void_saint_vex", "void_ember"]);
+    /// assert_eq!(keys(KeyCase::Preserve), ["Void_Saint_Vex", "Void_Ember"]);
+    ///
+    /// // Keys colliding within a block are numbered the same way in every case
+    /// let lower = KeyStyle::default().with_options("case=lower").unwrap();
+    /// assert_eq!(lower.numbered(&lower.key("Void_", "Saint Vex"), 2), "void_saint_vex_2");
+    /// ```
+    pub fn key_case(mut self, case: KeyCase) -> Self {
+        self.key_case = Some(case);
+        self
+    }
+
+    /// Most bytes a key may have, overriding the `max-len` of the key style. Longer derived
+    /// keys are cut and end in a hash of the whole key, keys pinned or reused from a previous
+    /// version are kept and warned about
+    ///
+    /// ```
+    /// use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+    ///
+    /// let structure = "NAME = {\n    # prefix: DEEP_SPACE_RESEARCH_STATION_\n    # Station names\n    stations = {\n    }\n}\n";
+    /// let names = [
+    ///     "Saint Vex of the Burning Choir",
+    ///     "Saint Vex of the Burning Chord",
+    ///     "Ember",
+    /// ];
+    /// let result = futures::executor::block_on(
+    ///     Generation::builder()
+    ///         .structure(structure)
+    ///         .lore("")
+    ///         .generator(MockGenerator::new(names))
+    ///         .max_key_len(40)
+    ///         .cache(InMemoryCache::new())
+    ///         .run(),
+    /// )
+    /// .unwrap();
+    /// let keys: Vec<&str> = result.localisation.iter().map(|(key, _)| key.as_str()).collect();
+    /// assert_eq!(keys.len(), 3);
+    /// assert!(keys.iter().all(|key| key.len() <= 40));
+    /// // Names sharing their first 40 bytes keep distinct keys
+    /// assert!(keys[0].starts_with("DEEP_SPACE_RESEARCH_STATION_SAI_"));
+    /// assert_ne!(keys[0], keys[1]);
+    /// assert_eq!(keys[2], "DEEP_SPACE_RESEARCH_STATION_EMBER");
+    /// ```
+    pub fn max_key_len(mut self, max: usize) -> Self {
+        self.max_key_len = Some(max);
+        self
+    }
+
+    /// Keys of a previous version of the mod, reused for names matching their value exactly
+    pub fn key_compat(mut self, keys: KeyCompatMap) -> Self {
+        self.key_compat = keys;
+        self
+    }
+
+    /// Keys of earlier runs, kept by the names emitted again, see [`crate::keymap`]
+    pub fn key_map(mut self, keys: KeyMap) -> Self {
+        self.key_map = keys;
+        self
+    }
+
+    /// Lets new names take the keys of names no longer emitted instead of keying around them
+    pub fn reuse_retired_keys(mut self, reuse: bool) -> Self {
+        self.reuse_retired_keys = reuse;
+        self
+    }
+
+    /// Checks every block's names keep to the capitalization most of them follow, reporting or
+    /// fixing the others, see [`crate::casing`]
+    pub fn consistency(mut self, check: CasingCheck) -> Self {
+        self.consistency = check;
+        self
+    }
+
+    /// Names pinned to keys, emitted verbatim in their blocks, see [`Pins`]
+    pub fn pins(mut self, pins: Pins) -> Self {
+        self.pins = pins;
+        self
+    }
+
+    /// Transliterates displayed names and inline values to ASCII, for games that can't render
+    /// anything else. Off by default
+    ///
+    /// ```
+    /// use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+    ///
+    /// let result = futures::executor::block_on(
+    ///     Generation::builder()
+    ///         .structure("NAME = {\n    ## Leader names\n    leaders = {\n    }\n}\n")
+    ///         .lore("")
+    ///         .generator(MockGenerator::new(["Élodie Vàrn", "星", "Vex"]))
+    ///         .ascii_only(true)
+    ///         .cache(InMemoryCache::new())
+    ///         .run(),
+    /// )
+    /// .unwrap();
+    /// assert_eq!(
+    ///     result.localisation,
+    ///     vec![
+    ///         ("ELODIE_VARN".to_string(), "Elodie Varn".to_string()),
+    ///         ("VEX".to_string(), "Vex".to_string()),
+    ///     ]
+    /// );
+    /// assert_eq!(result.report.blocks[0].dropped["ascii-only"], 1);
+    /// ```
+    pub fn ascii_only(mut self, ascii_only: bool) -> Self {
+        self.ascii_only = Some(ascii_only);
+        self
+    }
+
+    /// What happens to the double quotes and backslashes of names, [`QuoteMode::Escape`] by
+    /// default, see [`crate::quoting`]
+    pub fn quotes(mut self, mode: QuoteMode) -> Self {
+        self.quotes = Some(mode);
+        self
+    }
+
+    /// Characters the game accepts in localisation values, [`LocCharset::Permissive`] by
+    /// default. Values holding others are logged and reported, see [`crate::charset`]
+    pub fn loc_charset(mut self, charset: LocCharset) -> Self {
+        self.loc_charset = Some(charset);
+        self
+    }
+
+    /// Removes the characters [`loc_charset`](Self::loc_charset) rejects from the values
+    pub fn strip_loc_charset(mut self, strip: bool) -> Self {
+        self.strip_loc_charset = Some(strip);
+        self
+    }
+
+    /// Most requests the run may send to models, blocks past it are only read from the cache
+    pub fn budget_requests(mut self, requests: u64) -> Self {
+        self.budget_requests = Some(requests);
+        self
+    }
+
+    /// Most tokens the run may spend, counted from provider usage or estimated without it.
+    /// Blocks past it are only read from the cache, see [`crate::budget`]
+    pub fn budget_tokens(mut self, tokens: u64) -> Self {
+        self.budget_tokens = Some(tokens);
+        self
+    }
+
+    /// Opens every generated block of the name list with a comment on when, from which theme and
+    /// by which model its names were generated, see [`crate::provenance::block_comment`]. Off by
+    /// default
+    pub fn provenance_comments(mut self, enabled: bool) -> Self {
+        self.provenance_comments = Some(enabled);
+        self
+    }
+
+    /// Wraps the generated lines of every block of the name list in `# namegen:begin <path>` and
+    /// `# namegen:end <path>`, so a later run can replace them in place, see [`crate::in_place`].
+    /// Off by default
+    pub fn markers(mut self, enabled: bool) -> Self {
+        self.markers = enabled;
+        self
+    }
+
+    /// Generates blocks asking the same model for the same names once, rather than sending
+    /// identical requests back to back. Blocks share a call when their theme, lore, count,
+    /// model, sampled flavors and every other directive shaping the prompt match. The later blocks reuse the
+    /// answer of the first, each applying its own filters, and the report lists them. Sharing
+    /// names is the point, so the later blocks keep them under any [`UniqueScope`]. Off by
+    /// default:
+    ///
+    /// ```
+    /// use futures::future::BoxFuture;
+    /// use std::sync::atomic::{AtomicUsize, Ordering};
+    /// use stellaris_name_gen::flavor::{builtin_flavors, sample_flavors};
+    /// use stellaris_name_gen::{
+    ///     Generation, GenerationRequest, InMemoryCache, NameGenerator, UniqueScope,
+    /// };
+    ///
+    /// static CALLS: AtomicUsize = AtomicUsize::new(0);
+    ///
+    /// struct Counting;
+    ///
+    /// impl NameGenerator for Counting {
+    ///     fn generate<'a>(&'a self, _: &'a GenerationRequest) -> BoxFuture<'a, anyhow::Result<String>> {
+    ///         CALLS.fetch_add(1, Ordering::SeqCst);
+    ///         Box::pin(async { Ok(r#"{"names": ["Vex", "Ember"]}"#.to_string()) })
+    ///     }
+    /// }
+    ///
+    /// let structure = "NAME = {\n    # Ship names\n    # prefix: ARK_\n    ark = {\n    }\n    # Ship names\n    # prefix: VOID_\n    void = {\n    }\n    # Fleet names\n    fleets = {\n    }\n}\n";
+    /// let run = |scope: UniqueScope| {
+    ///     futures::executor::block_on(
+    ///         Generation::builder()
+    ///             .structure(structure)
+    ///             .lore("")
+    ///             .min_names(1)
+    ///             .coalesce(true)
+    ///             .flavor(false)
+    ///             .unique_scope(scope)
+    ///             .generator(Counting)
+    ///             .cache(InMemoryCache::new())
+    ///             .run(),
+    ///     )
+    ///     .unwrap()
+    /// };
+    /// let result = run(UniqueScope::Block);
+    /// assert_eq!(CALLS.load(Ordering::SeqCst), 2);
+    /// assert_eq!(result.report.coalesced["NAME/void"], "NAME/ark");
+    /// assert_eq!(result.report.coalesced.len(), 1);
+    /// let keys: Vec<&str> = result.localisation.iter().map(|(key, _)| key.as_str()).collect();
+    /// assert_eq!(keys, ["ARK_VEX", "ARK_EMBER", "VOID_VEX", "VOID_EMBER", "VEX", "EMBER"]);
+    ///
+    /// // Unique across the run, only the block that merely happens to get the same names loses them
+    /// let result = run(UniqueScope::Global);
+    /// let keys: Vec<&str> = result.localisation.iter().map(|(key, _)| key.as_str()).collect();
+    /// assert_eq!(keys, ["ARK_VEX", "ARK_EMBER", "VOID_VEX", "VOID_EMBER"]);
+    ///
+    /// // Blocks sampling different flavors ask for different names, so each gets its own call
+    /// let pool = builtin_flavors();
+    /// let path = |key: &str| ["NAME".to_string(), key.to_string()];
+    /// let seed = (0..)
+    ///     .find(|seed| sample_flavors(&pool, *seed, &path("ark")) != sample_flavors(&pool, *seed, &path("void")))
+    ///     .unwrap();
+    /// CALLS.store(0, Ordering::SeqCst);
+    /// let result = futures::executor::block_on(
+    ///     Generation::builder()
+    ///         .structure(structure)
+    ///         .lore("")
+    ///         .min_names(1)
+    ///         .coalesce(true)
+    ///         .seed(seed)
+    ///         .generator(Counting)
+    ///         .cache(InMemoryCache::new())
+    ///         .run(),
+    /// )
+    /// .unwrap();
+    /// assert_eq!(CALLS.load(Ordering::SeqCst), 3);
+    /// assert!(result.report.coalesced.is_empty());
+    /// ```
+    pub fn coalesce(mut self, enabled: bool) -> Self {
+        self.coalesce = Some(enabled);
+        self
+    }
+
+    /// Template of every localisation value between its quotes, `{val}` by default. It must
+    /// hold `{val}`, the value, and may hold `{key}`, the entry's key. Quotes in the template and
+    /// in the value are escaped alike:
+    ///
+    /// ```
+    /// use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+    ///
+    /// let generation = |format: &str| {
+    ///     Generation::builder()
+    ///         .structure("NAME = {\n    ## Leader names\n    leaders = {\n    }\n}\n")
+    ///         .lore("")
+    ///         .generator(MockGenerator::new(["Vex"]))
+    ///         .value_format(format)
+    ///         .cache(InMemoryCache::new())
+    ///         .build()
+    /// };
+    /// let result = futures::executor::block_on(generation("§Y\"{val}\"§! ({key})").unwrap().run());
+    /// let result = result.unwrap();
+    /// assert!(result.localisation_yml().contains("    VEX:0 \"§Y\\\"Vex\\\"§! (VEX)\"\n"));
+    /// // The entries themselves keep the plain value
+    /// assert_eq!(result.localisation[0].1, "Vex");
+    ///
+    /// assert!(generation("[{key}]").is_err());
+    /// ```
+    pub fn value_format(mut self, format: impl Into<String>) -> Self {
+        self.value_format = Some(format.into());
+        self
+    }
+
+    /// Indentation per nesting level of structures that show none, four spaces by default.
+    /// Generated lines take the indentation of their block's first child line, else the unit
+    /// of the first block that has one, and only then this
+    ///
+    /// ```
+    /// use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+    ///
+    /// let run = |structure: &str| {
+    ///     futures::executor::block_on(
+    ///         Generation::builder()
+    ///             .structure(structure)
+    ///             .lore("")
+    ///             .generator(MockGenerator::new(["Vex"]))
+    ///             .indent("\t")
+    ///             .cache(InMemoryCache::new())
+    ///             .run(),
+    ///     )
+    ///     .unwrap()
+    ///     .name_list
+    /// };
+    /// assert_eq!(run("NAME = {\n# Ship names\nships = {\n}\n}\n"), "NAME = {\nships = {\n\tVEX,\n}\n}");
+    /// // The structure's own indentation wins
+    /// assert_eq!(
+    ///     run("NAME = {\n  # Ship names\n  ships = {\n  }\n}\n"),
+    ///     "NAME = {\n  ships = {\n    VEX,\n  }\n}"
+    /// );
+    /// ```
+    pub fn indent(mut self, indent: impl Into<String>) -> Self {
+        self.indent = Some(indent.into());
+        self
+    }
+
+    /// Share of the recent model calls that may fail before the run is aborted, 0.5 by default.
+    /// Clamped to 0..=1, where 1 aborts once every call in the window failed. See
+    /// [`crate::health`]
+    pub fn max_error_rate(mut self, rate: f64) -> Self {
+        self.max_error_rate = Some(rate);
+        self
+    }
+
+    /// Number of recent model calls the error rate is measured over, 20 by default
+    pub fn error_window(mut self, calls: usize) -> Self {
+        self.error_window = Some(calls);
+        self
+    }
+
+    /// Waits up to `max` for an exhausted daily quota to reset instead of failing its requests,
+    /// see [`crate::quota`]. Parking needs a tokio runtime
+    pub fn wait_for_quota(mut self, max: Duration) -> Self {
+        self.wait_for_quota = Some(max);
+        self
+    }
+
+    /// How often a parked run checks whether its quota is back, every 15 minutes by default
+    pub fn quota_check_interval(mut self, interval: Duration) -> Self {
+        self.quota_check_interval = Some(interval);
+        self
+    }
+
+    /// Ends a wait for the quota on [`Interrupt::interrupt`], ie [`Interrupt::ctrl_c`] set up
+    /// once by the caller. Without one, a wait only ends with the quota back or past its limit
+    pub fn interrupt(mut self, interrupt: Interrupt) -> Self {
+        self.interrupt = Some(interrupt);
+        self
+    }
+
+    /// Stops a block's stream once its `# count:` names arrived, plus the overshoot margin.
+    /// On by default, turning it off streams every answer to its end
+    pub fn early_stop(mut self, early_stop: bool) -> Self {
+        self.early_stop = Some(early_stop);
+        self
+    }
+
+    /// Names requested on top of a block's `# count:` so filtering still leaves enough of them,
+    /// defaults to [`DEFAULT_EARLY_STOP_MARGIN`]. Blocks keep at most their count either way
+    ///
+    /// ```
+    /// use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+    ///
+    /// let structure = "NAME = {\n    # Ship names\n    # count: 40\n    ships = {\n    }\n}\n";
+    /// // The model would stream 500 names
+    /// let names: Vec<String> = (1..=500).map(|n| format!("Vessel {}", n)).collect();
+    /// let result = futures::executor::block_on(
+    ///     Generation::builder()
+    ///         .structure(structure)
+    ///         .lore("")
+    ///         .generator(MockGenerator::new(names.clone()))
+    ///         .early_stop_margin(5)
+    ///         .cache(InMemoryCache::new())
+    ///         .run(),
+    /// )
+    /// .unwrap();
+    /// let block = &result.report.blocks[0];
+    /// // The stream stopped after 45 names, 40 of which are kept
+    /// assert_eq!((block.generated, block.names), (45, 40));
+    /// assert_eq!(block.dropped["count"], 5);
+    ///
+    /// let result = futures::executor::block_on(
+    ///     Generation::builder()
+    ///         .structure(structure)
+    ///         .lore("")
+    ///         .generator(MockGenerator::new(names))
+    ///         .early_stop(false)
+    ///         .cache(InMemoryCache::new())
+    ///         .run(),
+    /// )
+    /// .unwrap();
+    /// let block = &result.report.blocks[0];
+    /// assert_eq!((block.generated, block.names), (500, 40));
+    /// ```
+    pub fn early_stop_margin(mut self, margin: usize) -> Self {
+        self.early_stop_margin = Some(margin);
+        self
+    }
+
+    /// Keys of the base game's localisation, generated keys matching one are renamed with a suffix
+    pub fn vanilla_keys(mut self, keys: VanillaKeys) -> Self {
+        self.vanilla_keys = keys;
+        self
+    }
+
+    /// Keys of the mod's own hand-written localisation, generated keys matching one are renamed
+    /// with a suffix like vanilla ones, see [`crate::vanilla`]
+    pub fn existing_keys(mut self, keys: VanillaKeys) -> Self {
+        self.existing_keys = keys;
+        self
+    }
+
+    /// Spelled-out form of an abbreviation, used by blocks with `#@ abbreviate: yes`
+    pub fn abbreviation(mut self, short: impl Into<String>, full: impl Into<String>) -> Self {
+        self.abbreviations.push((short.into(), full.into()));
+        self
+    }
+
+    /// Registers the JSON schema for blocks with `#@ schema: <path>`, instead of reading `path`.
+    ///
+    /// A custom schema replaces the built-in `{"names": [...]}` one and must keep a `names`
+    /// array. Its entries may be objects with a `name`, the displayed name, an optional `key`,
+    /// the key stem used instead of one derived from the name, and any other field, which is
+    /// kept in the cache
+    ///
+    /// ```
+    /// use futures::future::BoxFuture;
+    /// use stellaris_name_gen::{Generation, GenerationRequest, InMemoryCache, NameGenerator};
+    ///
+    /// struct Rare;
+    /// impl NameGenerator for Rare {
+    ///     fn generate<'a>(&'a self, request: &'a GenerationRequest) -> BoxFuture<'a, anyhow::Result<String>> {
+    ///         assert!(request.schema.is_some());
+    ///         Box::pin(async {
+    ///             Ok(r#"{"names": [{"name": "Vex Prime", "key": "vex", "rarity": "legendary"}, "Ember"]}"#.to_string())
+    ///         })
+    ///     }
+    /// }
+    ///
+    /// let schema = serde_json::json!({
+    ///     "type": "object",
+    ///     "properties": {
+    ///         "names": {
+    ///             "type": "array",
+    ///             "items": {
+    ///                 "type": "object",
+    ///                 "properties": { "name": { "type": "string" }, "rarity": { "type": "string" } }
+    ///             }
+    ///         }
+    ///     }
+    /// });
+    /// let result = futures::executor::block_on(
+    ///     Generation::builder()
+    ///         .structure("NAME = {\n    ## Relic names\n    #@ schema: relic.json\n    relics = {\n    }\n}\n")
+    ///         .lore("")
+    ///         .generator(Rare)
+    ///         .schema("relic.json", schema)
+    ///         .cache(InMemoryCache::new())
+    ///         .run(),
+    /// )
+    /// .unwrap();
+    /// assert_eq!(
+    ///     result.localisation,
+    ///     vec![
+    ///         ("VEX".to_string(), "Vex Prime".to_string()),
+    ///         ("EMBER".to_string(), "Ember".to_string()),
+    ///     ]
+    /// );
+    /// ```
+    pub fn schema(mut self, path: impl Into<String>, schema: serde_json::Value) -> Self {
+        self.schemas.insert(path.into(), schema);
+        self
+    }
+
+    /// Whether one or two stylistic flavors are sampled into each block's prompt, on by default
+    pub fn flavor(mut self, flavor: bool) -> Self {
+        self.flavor = Some(flavor);
+        self
+    }
+
+    /// Adds a flavor to the built-in pool, ie `favor names of lost ships`
+    pub fn add_flavor(mut self, flavor: impl Into<String>) -> Self {
+        self.extra_flavors.push(flavor.into());
+        self
+    }
+
+    /// Species class of every block without a `#@ species-class:`, whose hint is added to the
+    /// prompts, see [`crate::species`]
+    pub fn species_class(mut self, class: impl Into<String>) -> Self {
+        self.species_class = Some(class.into());
+        self
+    }
+
+    /// Replaces the hint of a species class, or adds the class
+    pub fn species_hint(mut self, class: impl Into<String>, hint: impl Into<String>) -> Self {
+        self.species_hints.push((class.into(), hint.into()));
+        self
+    }
+
+    /// Price of a model for [`Generation::estimate`], see [`crate::estimate`]
+    pub fn price(mut self, model: impl Into<String>, price: ModelPrice) -> Self {
+        self.prices.insert(model.into(), price);
+        self
+    }
+
+    /// Seed of the run's flavor sampling, random by default. Reusing a seed reproduces the flavors
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.seed = Some(seed);
+        self
+    }
+
+    /// Shuffles the names of every block without a sort directive with this seed, except
+    /// sequential (`randomized = no`) blocks. The same seed and inputs give the same output
+    ///
+    /// ```
+    /// use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+    ///
+    /// let run = |seed| {
+    ///     futures::executor::block_on(
+    ///         Generation::builder()
+    ///             .structure("NAME = {\n    ## Ship names\n    ships = {\n    }\n}\n")
+    ///             .lore("")
+    ///             .generator(MockGenerator::new(["A", "B", "C", "D", "E", "F", "G", "H"]))
+    ///             .shuffle_seed(seed)
+    ///             .cache(InMemoryCache::new())
+    ///             .run(),
+    ///     )
+    ///     .unwrap()
+    /// };
+    /// let (first, again) = (run(7), run(7));
+    /// assert_eq!(first.name_list, again.name_list);
+    /// assert_eq!(first.localisation, again.localisation);
+    /// let mut names: Vec<&str> = first.localisation.iter().map(|(_, name)| name.as_str()).collect();
+    /// assert_ne!(names, ["A", "B", "C", "D", "E", "F", "G", "H"]);
+    /// names.sort();
+    /// assert_eq!(names, ["A", "B", "C", "D", "E", "F", "G", "H"]);
+    /// ```
+    pub fn shuffle_seed(mut self, seed: u64) -> Self {
+        self.shuffle_seed = Some(seed);
+        self
+    }
+
+    /// Applies the values of a loaded config file, later builder calls still win
+    pub fn config(mut self, config: &Config) -> Self {
+        self.model = config.model.clone().or(self.model);
+        self.concurrency = config.concurrency.or(self.concurrency);
+        self.max_concurrent_models = config.max_concurrent_models.or(self.max_concurrent_models);
+        self.model_limits.extend(config.model_limits.clone());
+        self.min_names = config.min_names.or(self.min_names);
+        self.strict_count = config.strict_count.or(self.strict_count);
+        self.flavor = config.flavor.or(self.flavor);
+        self.extra_flavors.extend(config.flavors.clone());
+        self.species_class = config.species_class.clone().or(self.species_class);
+        let mut species_hints: Vec<_> = config.species_classes.clone().into_iter().collect();
+        species_hints.sort();
+        self.species_hints.extend(species_hints);
+        self.prices.extend(config.prices.clone());
+        self.providers.extend(config.providers.clone());
+        self.seed = config.seed.or(self.seed);
+        self.shuffle_seed = config.shuffle_seed.or(self.shuffle_seed);
+        self.unique_scope = config.unique_scope.or(self.unique_scope);
+        self.near_forms = config.near_forms.or(self.near_forms);
+        self.max_cluster_fraction = config.max_cluster_fraction.or(self.max_cluster_fraction);
+        self.cluster_action = config.cluster_action.or(self.cluster_action);
+        self.semantic_dedup = config.semantic_dedup.or(self.semantic_dedup);
+        self.semantic_threshold = config.semantic_threshold.or(self.semantic_threshold);
+        self.embedding_model = config.embedding_model.clone().or(self.embedding_model);
+        self.rarity = config.rarity.or(self.rarity);
+        self.key_style = config.key_style.clone().or(self.key_style);
+        self.ascii_only = config.ascii_only.or(self.ascii_only);
+        self.quotes = config.quotes.or(self.quotes);
+        self.loc_charset = config.loc_charset.or(self.loc_charset);
+        self.strip_loc_charset = config.strip_loc_charset.or(self.strip_loc_charset);
+        self.budget_requests = config.budget_requests.or(self.budget_requests);
+        self.budget_tokens = config.budget_tokens.or(self.budget_tokens);
+        self.provenance_comments = config.provenance_comments.or(self.provenance_comments);
+        self.coalesce = config.coalesce.or(self.coalesce);
+        self.value_format = config.value_format.clone().or(self.value_format);
+        self.indent = config.indent.clone().or(self.indent);
+        self.max_error_rate = config.max_error_rate.or(self.max_error_rate);
+        self.error_window = config.error_window.or(self.error_window);
+        self.wait_for_quota = config
+            .wait_for_quota
+            .map(|hours| Duration::from_secs_f64(hours.max(0.0) * 3600.0))
+            .or(self.wait_for_quota);
+        self.early_stop = config.early_stop.or(self.early_stop);
+        self.early_stop_margin = config.early_stop_margin.or(self.early_stop_margin);
+        self.categories.extend(config.categories.clone());
+        let mut abbreviations: Vec<_> = config.abbreviations.clone().into_iter().collect();
+        abbreviations.sort();
+        self.abbreviations.extend(abbreviations);
+        self
+    }
+
+    /// Source of names, defaults to [`GenAiGenerator`]
+    pub fn generator(mut self, generator: impl NameGenerator + 'static) -> Self {
+        self.generator = Some(Box::new(generator));
+        self
+    }
+
+    /// Cache backend, defaults to [`FsCache`] in `cache/`. Boxed backends from
+    /// [`CacheConfig::open`](crate::cache::CacheConfig::open) work too
+    pub fn cache(mut self, cache: impl NameCache + 'static) -> Self {
+        self.cache = Some(Box::new(cache));
+        self
+    }
+
+    pub fn build(self) -> Result<Generation> {
+        let species = SpeciesClasses::default().with_hints(self.species_hints);
+        if let Some(class) = &self.species_class {
+            species.hint(class)?;
+        }
+        let value_format = self
+            .value_format
+            .unwrap_or_else(|| VALUE_FORMAT.to_string());
+        if !value_format.contains("{val}") {
+            anyhow::bail!("value-format '{}' must hold {{val}}", value_format);
+        }
+        let indent = self.indent.unwrap_or_else(|| INDENT.to_string());
+        if indent.is_empty() || !indent.chars().all(|c| c == ' ' || c == '\t') {
+            anyhow::bail!("indent {:?} must be spaces or tabs", indent);
+        }
+        let mut key_style = self.key_style.unwrap_or_default();
+        key_style.hash = self.hash_keys.unwrap_or(key_style.hash);
+        key_style.case = self.key_case.unwrap_or(key_style.case);
+        key_style.max_len = self.max_key_len.or(key_style.max_len);
+        key_style.check()?;
+        let threshold = self
+            .semantic_threshold
+            .unwrap_or(semantic::DEFAULT_THRESHOLD);
+        if threshold.is_nan() || threshold <= 0.0 || threshold > 1.0 {
+            anyhow::bail!(
+                "semantic-threshold {} must be above 0 and at most 1",
+                threshold
+            );
+        }
+        let semantic_dedup = self.semantic_dedup.unwrap_or(false).then(|| SemanticDedup {
+            model: self
+                .embedding_model
+                .unwrap_or_else(|| semantic::DEFAULT_EMBEDDING_MODEL.to_string()),
+            threshold,
+        });
+        let concurrency = self.concurrency.unwrap_or(1).max(1);
+        let embedding_limiter = ModelLimiter::new(
+            semantic_dedup.iter().map(|dedup| dedup.model.as_str()),
+            &self.model_limits,
+            self.max_concurrent_models.unwrap_or(concurrency),
+        );
+        Ok(Generation {
+            structure: self.structure.context("A structure is required")?,
+            lore: self.lore.context("Lore is required")?,
+            lore_profiles: self.lore_profiles,
+            model: self.model.unwrap_or_else(|| AI_MODEL.to_string()),
+            concurrency,
+            max_concurrent_models: self.max_concurrent_models,
+            model_limits: self.model_limits,
+            keep_raw: self.keep_raw,
+            strict: self.strict,
+            strict_count: self.strict_count.unwrap_or(false),
+            min_names: self.min_names.unwrap_or(DEFAULT_MIN_NAMES),
+            unique_scope: self.unique_scope.unwrap_or_default(),
+            near_forms: self.near_forms.unwrap_or_default(),
+            max_cluster_fraction: self.max_cluster_fraction,
+            cluster_action: self.cluster_action.unwrap_or_default(),
+            semantic_dedup,
+            embedding_limiter,
+            rarity: self.rarity.unwrap_or_default(),
+            common_words: self.common_words,
+            key_style,
+            key_compat: self.key_compat,
+            key_map: self.key_map,
+            reuse_retired_keys: self.reuse_retired_keys,
+            consistency: self.consistency,
+            pins: self.pins,
+            ascii_only: self.ascii_only.unwrap_or(false),
+            quotes: self.quotes.unwrap_or_default(),
+            loc_charset: self.loc_charset.unwrap_or_default(),
+            strip_loc_charset: self.strip_loc_charset.unwrap_or(false),
+            budget: Budget::new(self.budget_requests, self.budget_tokens),
+            replaying: AtomicBool::new(false),
+            provenance_comments: self.provenance_comments.unwrap_or(false),
+            markers: self.markers,
+            coalesce: self.coalesce.unwrap_or(false),
+            value_format,
+            indent,
+            token_limits: Mutex::default(),
+            health: HealthMonitor::new(
+                self.max_error_rate.unwrap_or(DEFAULT_MAX_ERROR_RATE),
+                self.error_window.unwrap_or(DEFAULT_ERROR_WINDOW),
+            ),
+            quota: self.wait_for_quota.map(|max| {
+                QuotaWait::new(
+                    max,
+                    self.quota_check_interval.unwrap_or(DEFAULT_CHECK_INTERVAL),
+                    self.interrupt.clone().unwrap_or_default(),
+                )
+            }),
+            early_stop_margin: self
+                .early_stop
+                .unwrap_or(true)
+                .then(|| self.early_stop_margin.unwrap_or(DEFAULT_EARLY_STOP_MARGIN)),
+            categories: self.categories,
+            vanilla_keys: self.vanilla_keys,
+            existing_keys: self.existing_keys,
+            abbreviations: if self.abbreviations.is_empty() {
+                None
+            } else {
+                Some(Abbreviations::new(self.abbreviations)?)
+            },
+            schemas: self.schemas,
+            flavors: if self.flavor.unwrap_or(true) {
+                let mut flavors = builtin_flavors();
+                flavors.extend(self.extra_flavors);
+                flavors
+            } else {
+                Vec::new()
+            },
+            species,
+            species_class: self.species_class,
+            prices: self.prices,
+            match_existing: self.match_existing,
+            draft_model: self.draft_model,
+            explicit_flavors: self.explicit_flavors,
+            providers: self.providers,
+            seed: self.seed.unwrap_or_else(|| {
+                SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .map(|elapsed| elapsed.as_nanos() as u64)
+                    .unwrap_or_default()
+            }),
+            shuffle_seed: self.shuffle_seed,
+            generator: self
+                .generator
+                .unwrap_or_else(|| Box::new(GenAiGenerator::default())),
+            cache: Mutex::new(self.cache.unwrap_or_else(|| Box::new(FsCache::default()))),
+        })
+    }
+
+    /// Builds and runs the generation
+    pub async fn run(self) -> Result<RunResult> {
+        self.build()?.run().await
+    }
+}
+
+impl Generation {
+    pub fn builder() -> GenerationBuilder {
+        GenerationBuilder::default()
+    }
+
+    /// Seed of the flavor sampling, pass it to [`GenerationBuilder::seed`] to reproduce a run
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// The run's provenance log, from its start time and outcome, see [`crate::provenance`]
+    pub fn provenance(
+        &self,
+        started: SystemTime,
+        result: Result<&RunResult, &anyhow::Error>,
+    ) -> Provenance {
+        let blocks: &[BlockReport] = match result {
+            Ok(result) => &result.report.blocks,
+            Err(_) => &[],
+        };
+        let hits = blocks.iter().filter(|block| block.cached).count();
+        let mut models = self.models().unwrap_or_default();
+        models.extend(blocks.iter().map(|block| block.model.clone()));
+        models.sort();
+        models.dedup();
+        Provenance {
+            version: PROVENANCE_VERSION,
+            tool_version: env!("CARGO_PKG_VERSION").to_string(),
+            started_at: started
+                .duration_since(UNIX_EPOCH)
+                .map(|elapsed| elapsed.as_secs())
+                .unwrap_or_default(),
+            duration_ms: started
+                .elapsed()
+                .map(|elapsed| elapsed.as_millis() as u64)
+                .unwrap_or_default(),
+            status: match result {
+                Ok(_) => RunStatus::Completed,
+                Err(_) => RunStatus::Failed,
+            },
+            error: result.err().map(|e| format!("{:#}", e)),
+            settings: RunSettings {
+                model: self.model.clone(),
+                concurrency: self.concurrency,
+                max_concurrent_models: self.max_concurrent_models,
+                model_limits: self.model_limits.clone().into_iter().collect(),
+                strict: self.strict,
+                keep_raw: self.keep_raw,
+                min_names: self.min_names,
+                unique_scope: self.unique_scope,
+                near_forms: self.near_forms,
+                max_cluster_fraction: self.max_cluster_fraction,
+                cluster_action: self.cluster_action,
+                semantic_dedup: self.semantic_dedup.clone(),
+                rarity: self.rarity,
+                key_style: self.key_style.clone(),
+                ascii_only: self.ascii_only,
+                quotes: self.quotes,
+                loc_charset: self.loc_charset,
+                strip_loc_charset: self.strip_loc_charset,
+                early_stop_margin: self.early_stop_margin,
+                categories: self.categories.clone().into_iter().collect(),
+                seed: self.seed,
+                shuffle_seed: self.shuffle_seed,
+                flavors: self.flavors.clone(),
+            },
+            models,
+            temperature: self.generator.temperature(),
+            lore: LoreDigests {
+                default: digest(&self.lore),
+                profiles: self.lore_profiles.digests(),
+            },
+            cache: CacheStats {
+                hits,
+                misses: blocks.len() - hits,
+            },
+            usage: self.generator.usage(),
+            blocks: blocks
+                .iter()
+                .map(|block| BlockProvenance {
+                    path: block.path.join("/"),
+                    model: block.model.clone(),
+                    cached: block.cached,
+                    flavor_seed: self.seed,
+                    shuffle_seed: block.shuffle_seed,
+                    generated: block.generated,
+                    names: block.names,
+                    failed: block.failed,
+                    elapsed_ms: block.elapsed_ms,
+                    ordinal_expand: block.ordinal_expand.clone(),
+                })
+                .collect(),
+        }
+    }
+
+    /// The structure's output segments and jobs, blocks without a `# rarity=` taking the run's
+    fn parse(&self) -> Result<(Vec<Segment>, Vec<GenerationJob>)> {
+        let (output, mut jobs) = parse_structure(&self.structure, &self.model, &self.indent)?;
+        for job in &mut jobs {
+            job.rarity = job.rarity.or(Some(self.rarity));
+            if let Some(model) = &self.draft_model {
+                job.model = model.clone();
+            }
+        }
+        Ok((output, jobs))
+    }
+
+    /// What the run would generate, block by block, with the cache status of each and without
+    /// sending any request. See [`crate::plan`]
+    pub fn plan(&self) -> Result<Vec<PlannedBlock>> {
+        let (output, jobs) = self.parse()?;
+        let cache = self.cache.lock().unwrap();
+        let mut plan = Vec::new();
+        // Composed blocks send no request of their own
+        for job in jobs.iter().filter(|job| job.compose.is_empty()) {
+            let species = self.species_hint(job)?;
+            plan.push(PlannedBlock {
+                path: job.path.clone(),
+                theme: job.theme.clone(),
+                prefix: job.prefix.clone(),
+                model: job.model.clone(),
+                min_names: Some(job.min_names.unwrap_or(self.min_names)),
+                count: job.max_names,
+                era: job.era.map(|era| era.as_str().to_string()),
+                format: match &job.schema {
+                    Some(path) => format!("custom schema {}", path),
+                    None => ResponseFormat::Names.as_str().to_string(),
+                },
+                cached: self
+                    .cached_or_aliased(&**cache, job)?
+                    .is_some_and(|block| block.species_hint == species && same_prefix(&block, job)),
+            });
+        }
+        for segment in &output {
+            let Segment::Inline(line) = segment else {
+                continue;
+            };
+            for (idx, placeholder) in line.placeholders.iter().enumerate() {
+                let mut path = line.path.clone();
+                path.push(match idx {
+                    0 => format!("line {}", line.line_no),
+                    _ => format!("line {}.{}", line.line_no, idx + 1),
+                });
+                plan.push(PlannedBlock {
+                    path,
+                    theme: placeholder.theme.clone(),
+                    prefix: line.prefix.clone(),
+                    model: line.model.clone(),
+                    min_names: None,
+                    count: None,
+                    era: None,
+                    format: "value".to_string(),
+                    cached: cache
+                        .get(&line.cache_key(idx, &self.lore_profiles)?)
+                        .is_some(),
+                });
+            }
+        }
+        Ok(plan)
+    }
+
+    /// Lists the names cached for the blocks ahead of each block in its unique scope as taken,
+    /// so a block generated again is told which of its names would be dropped. Under the
+    /// category scope, warns about blocks no configured category covers
+    fn mark_taken(&self, jobs: &mut [GenerationJob], keys: &[BlockKey]) {
+        let unique = UniqueNames::new(self.unique_scope, &self.categories);
+        if self.unique_scope == UniqueScope::Category {
+            let uncovered: BTreeSet<String> = jobs
+                .iter()
+                .filter(|job| unique.configured_category(&job.path).is_none())
+                .map(|job| unique.category(&job.path))
+                .collect();
+            if !uncovered.is_empty() {
+                warning!(
+                    "[Unique] Blocks under no configured category share the one of their top-level block: {}",
+                    uncovered.into_iter().collect::<Vec<_>>().join(", ")
+                );
+            }
+        }
+        let mut taken: HashMap<String, Vec<String>> = HashMap::new();
+        for (job, key) in jobs.iter_mut().zip(keys) {
+            let names = taken.entry(unique.scope_id(&job.path)).or_default();
+            job.taken = names[names.len().saturating_sub(MAX_TAKEN)..].to_vec();
+            let cached = self.cache.lock().unwrap().get(key);
+            if let Some(items) = cached.and_then(|block| parse_items(&block.raw).ok()) {
+                names.extend(items.iter().map(|item| item.name().to_string()));
+            }
+        }
+    }
+
+    /// Replays the parameters stored with the block's newest cached names when the block
+    /// itself misses the cache, see [`crate::replay`]
+    fn match_stored(&self, job: &mut GenerationJob, key: &BlockKey) -> Result<()> {
+        let species = self.species_hint(job)?;
+        let cache = self.cache.lock().unwrap();
+        if cache
+            .get(key)
+            .is_some_and(|block| block.species_hint == species && same_prefix(&block, job))
+        {
+            return Ok(());
+        }
+        let Some((from, stored)) = cache
+            .keys()?
+            .into_iter()
+            .filter(|other| same_block(other, &job.path))
+            .filter_map(|other| cache.get(&other).map(|block| (other, block)))
+            .max_by_key(|(_, block)| block.timestamp)
+        else {
+            return Ok(());
+        };
+        drop(cache);
+        let replayed = replay(&stored, &from, job, self.explicit_flavors, |model| {
+            providers::resolve(model, &self.providers, |var| std::env::var(var).ok()).is_ok()
+        });
+        println!(
+            "[Match] '{}': replaying {} of '{}'",
+            job.path.join("/"),
+            if replayed.applied.is_empty() {
+                "nothing".to_string()
+            } else {
+                replayed.applied.join(", ")
+            },
+            replayed.from
+        );
+        for skipped in &replayed.skipped {
+            println!(
+                "[Match] '{}': not replaying {}",
+                job.path.join("/"),
+                skipped
+            );
+        }
+        job.replayed = Some(replayed);
+        Ok(())
+    }
+
+    /// What the run would spend, block by block, from the prompts it would send and without
+    /// sending any. See [`crate::estimate`]
+    pub fn estimate(&self) -> Result<Estimate> {
+        let (output, jobs) = self.parse()?;
+        let cache = self.cache.lock().unwrap();
+        let keys = cache.keys()?;
+        let mut blocks = Vec::new();
+        for job in jobs.iter().filter(|job| job.compose.is_empty()) {
+            let species = self.species_hint(job)?;
+            let cached = self
+                .cached_or_aliased(&**cache, job)?
+                .is_some_and(|block| block.species_hint == species && same_prefix(&block, job));
+            let (prompt_tokens, output_bytes, basis) = if cached {
+                (0, 0, OutputBasis::Cached)
+            } else {
+                let flavors = sample_flavors(&self.flavors, self.seed, &job.path);
+                let prompt = build_prompt(
+                    job,
+                    self.lore_for(&job.lore)?,
+                    RETRY_FORMATS[0],
+                    &flavors,
+                    species.as_deref(),
+                    &[],
+                );
+                let sizes: Vec<usize> = keys
+                    .iter()
+                    .filter(|other| same_block(other, &job.path))
+                    .filter_map(|other| cache.get(other))
+                    .map(|block| block.raw.len())
+                    .collect();
+                let (bytes, basis) = match job.max_names {
+                    Some(count) => {
+                        let names = count + self.early_stop_margin.unwrap_or(0);
+                        (names * NAME_BYTES, OutputBasis::Count(names))
+                    }
+                    None if !sizes.is_empty() => {
+                        let mean = sizes.iter().sum::<usize>() / sizes.len();
+                        (mean, OutputBasis::History(mean))
+                    }
+                    None => (ASSUMED_NAMES * NAME_BYTES, OutputBasis::Assumed),
+                };
+                (estimate_tokens(prompt.len()), bytes, basis)
+            };
+            blocks.push(self.block_estimate(
+                job.path.clone(),
+                &job.model,
+                prompt_tokens,
+                output_bytes,
+                basis,
+            ));
+        }
+        for segment in &output {
+            let Segment::Inline(line) = segment else {
+                continue;
+            };
+            for (idx, placeholder) in line.placeholders.iter().enumerate() {
+                let mut path = line.path.clone();
+                path.push(match idx {
+                    0 => format!("line {}", line.line_no),
+                    _ => format!("line {}.{}", line.line_no, idx + 1),
+                });
+                let cached = cache
+                    .get(&line.cache_key(idx, &self.lore_profiles)?)
+                    .is_some_and(|block| parse_value(&block.raw).is_ok());
+                blocks.push(if cached {
+                    self.block_estimate(path, &line.model, 0, 0, OutputBasis::Cached)
+                } else {
+                    let prompt = build_value_prompt(&placeholder.theme, self.lore_for(&line.lore)?);
+                    self.block_estimate(
+                        path,
+                        &line.model,
+                        estimate_tokens(prompt.len()),
+                        VALUE_BYTES,
+                        OutputBasis::Value,
+                    )
+                });
+            }
+        }
+        Ok(Estimate::new(blocks))
+    }
+
+    fn block_estimate(
+        &self,
+        path: Vec<String>,
+        model: &str,
+        prompt_tokens: u64,
+        output_bytes: usize,
+        basis: OutputBasis,
+    ) -> BlockEstimate {
+        let output_tokens = estimate_tokens(output_bytes);
+        BlockEstimate {
+            path,
+            model: model.to_string(),
+            prompt_tokens,
+            output_tokens,
+            basis,
+            cost: self
+                .prices
+                .get(model)
+                .map(|price| price.cost(prompt_tokens, output_tokens)),
+        }
+    }
+
+    /// Every model the structure's blocks resolve to, scoring models included, sorted
+    pub fn models(&self) -> Result<Vec<String>> {
+        let (output, jobs) = self.parse()?;
+        let mut models: Vec<String> = Vec::new();
+        for job in jobs {
+            if let Some(model) = job.score_filter.and_then(|filter| filter.model) {
+                models.push(model);
+            }
+            models.push(job.model);
+        }
+        models.extend(output.into_iter().filter_map(|segment| match segment {
+            Segment::Inline(line) => Some(line.model),
+            _ => None,
+        }));
+        models.sort();
+        models.dedup();
+        Ok(models)
+    }
+
+    /// Sends every model a trivial request through the run's generator, failing fast on
+    /// bad credentials, an unreachable endpoint or an unknown model
+    pub async fn preflight(&self) -> Result<()> {
+        for model in self.models()? {
+            println!("[Preflight] Checking '{}'", model);
+            let request = GenerationRequest {
+                model: model.clone(),
+                theme: "preflight".to_string(),
+                prompt: "Reply with the single value ok".to_string(),
+                format: ResponseFormat::Value,
+                schema: None,
+                max_names: None,
+                max_tokens: None,
+                temperature: None,
+            };
+            let reply = self.generate(&request).await;
+            // Streams cut off by the provider come back empty rather than as an error
+            if !reply.as_ref().is_ok_and(|reply| !reply.trim().is_empty()) {
+                let reason = reply
+                    .err()
+                    .map(|e| format!(": {:#}", e))
+                    .unwrap_or_default();
+                anyhow::bail!(
+                    "Preflight failed for model '{}', check its API key, endpoint and name{}",
+                    model,
+                    reason
+                );
+            }
+        }
+        Ok(())
+    }
+
+    /// Replays every stage of the pipeline from the inputs `result`'s run cached, sending no
+    /// request, and fails on the first difference from `result`, see [`crate::determinism`].
+    /// `result` is the outcome of [`run`](Self::run), before titles or translations are merged
+    pub async fn check_determinism(&self, result: &RunResult) -> Result<()> {
+        self.replaying.store(true, Ordering::SeqCst);
+        let replayed = self.run().await;
+        self.replaying.store(false, Ordering::SeqCst);
+        determinism::check(result, &replayed?)
+    }
+
+    /// Generates the blocks matching the glob `pattern` again with their own model, showing the
+    /// model the names a draft run cached for them in `draft`, over their names in the run's
+    /// cache. Blocks without draft names are skipped, see [`crate::draft`]
+    pub async fn promote(&self, pattern: &str, draft: &dyn NameCache) -> Result<Vec<Promotion>> {
+        if self.draft_model.is_some() {
+            anyhow::bail!("Blocks are promoted with their primary model, not the draft model");
+        }
+        let (_, jobs) = self.parse()?;
+        let schemas = self.load_schemas(&jobs)?;
+        let mut promotions = Vec::new();
+        let mut matched = 0;
+        // Composed blocks combine the names of their pools, which are promoted on their own
+        for mut job in jobs
+            .into_iter()
+            .filter(|job| job.compose.is_empty() && draft::matches(pattern, &job.path.join("/")))
+        {
+            matched += 1;
+            let path = job.path.join("/");
+            let key = job.cache_key(&self.lore_profiles)?;
+            let Some(names) = draft
+                .get(&key)
+                .and_then(|block| parse_items(&block.raw).ok())
+            else {
+                println!("[Promote] '{}' has no draft names, skipped", path);
+                continue;
+            };
+            job.examples = names.iter().map(|item| item.name().to_string()).collect();
+            let schema = job.schema.as_ref().map(|path| &schemas[path]);
+            let outcome = self.generate_localized_entries(&job, &key, schema).await?;
+            println!(
+                "[Promote] '{}': {} names from {} after {} draft names",
+                path,
+                outcome.entries.len(),
+                job.model,
+                job.examples.len()
+            );
+            promotions.push(Promotion {
+                path,
+                model: job.model.clone(),
+                draft: job.examples.len(),
+                names: outcome.entries.len(),
+            });
+        }
+        if matched == 0 {
+            anyhow::bail!("No generated block matches '{}'", pattern);
+        }
+        Ok(promotions)
+    }
+
+    /// The custom schemas of the jobs, read from disk unless given to the builder, and checked
+    fn load_schemas(&self, jobs: &[GenerationJob]) -> Result<HashMap<String, serde_json::Value>> {
+        let mut schemas = self.schemas.clone();
+        for job in jobs {
+            let Some(path) = &job.schema else { continue };
+            let context = || format!("Invalid schema '{}' of '{}'", path, job.path.join("/"));
+            if !schemas.contains_key(path) {
+                let text = fs::read_to_string(path).with_context(context)?;
+                schemas.insert(
+                    path.clone(),
+                    serde_json::from_str(&text).with_context(context)?,
+                );
+            }
+            validate_schema(&schemas[path]).with_context(context)?;
+        }
+        Ok(schemas)
+    }
+
+    pub async fn run(&self) -> Result<RunResult> {
+        let (output, mut jobs) = self.parse()?;
+        if self.abbreviations.is_none()
+            && let Some(job) = jobs.iter().find(|job| job.abbreviate)
+        {
+            anyhow::bail!(
+                "'{}' abbreviates its names but no abbreviations are configured",
+                job.path.join("/")
+            );
+        }
+        for path in self.pins.paths() {
+            if !jobs.iter().any(|job| job.path.join("/") == path) {
+                warning!(
+                    "[Pin Warning] '{}' is not a generated block, its pins are not emitted",
+                    path
+                );
+            }
+        }
+        // Custom schemas are loaded and checked before any request is sent as well
+        let schemas = self.load_schemas(&jobs)?;
+        for job in &jobs {
+            self.species_hint(job)?;
+            if self.ascii_only
+                && let Some(name) = job.first.iter().find(|name| to_ascii(name) != **name)
+            {
+                anyhow::bail!(
+                    "'{}' lists '{}' first but the run keeps names ASCII only",
+                    job.path.join("/"),
+                    name
+                );
+            }
+        }
+        // Resolve every key up front so an unknown lore profile fails before any request is sent
+        let keys: Vec<BlockKey> = jobs
+            .iter()
+            .map(|job| job.cache_key(&self.lore_profiles))
+            .collect::<Result<_>>()?;
+        let mut aliased = BTreeMap::new();
+        for (job, key) in jobs.iter().zip(&keys) {
+            if let Some(alias) = self.migrate_alias(job, key)? {
+                let path = job.path.join("/");
+                println!("[Alias] Moved the cache of '{}' to '{}'", alias, path);
+                aliased.insert(path, alias);
+            }
+        }
+        // Drafts are generated with the draft model whatever the blocks were generated with
+        if self.match_existing && self.draft_model.is_none() {
+            for (job, key) in jobs.iter_mut().zip(&keys) {
+                self.match_stored(job, key)?;
+            }
+        }
+        if self.unique_scope != UniqueScope::Block {
+            self.mark_taken(&mut jobs, &keys);
+        }
+        let inline: Vec<&InlineLine> = output
+            .iter()
+            .filter_map(|segment| match segment {
+                Segment::Inline(line) => Some(line),
+                _ => None,
+            })
+            .collect();
+        let inline_keys: Vec<Vec<BlockKey>> = inline
+            .iter()
+            .map(|line| {
+                (0..line.placeholders.len())
+                    .map(|idx| line.cache_key(idx, &self.lore_profiles))
+                    .collect()
+            })
+            .collect::<Result<_>>()?;
+        // Taken before any block is generated, so the report compares it with the actual usage
+        let estimate = self.estimate()?;
+
+        // Blocks run concurrently, but no model sees more than its own limit in flight
+        let limiter = ModelLimiter::new(
+            jobs.iter()
+                .map(|job| job.model.as_str())
+                .chain(inline.iter().map(|line| line.model.as_str())),
+            &self.model_limits,
+            self.max_concurrent_models.unwrap_or(self.concurrency),
+        );
+        // Blocks asking a model for the same names go after the block they share a call with,
+        // reading its answer from the cache
+        let leaders: Vec<Option<usize>> = if self.coalesce {
+            coalesce_leaders(&jobs, &keys, &self.flavors, self.seed)
+        } else {
+            vec![None; jobs.len()]
+        };
+        let (composing, generating): (Vec<usize>, Vec<usize>) =
+            (0..jobs.len()).partition(|idx| !jobs[*idx].compose.is_empty());
+        let (leading, following): (Vec<usize>, Vec<usize>) = generating
+            .into_iter()
+            .partition(|idx| leaders[*idx].is_none());
+        let mut outcomes: Vec<Option<BlockOutcome>> = jobs.iter().map(|_| None).collect();
+        let leading_outcomes = self
+            .generate_blocks(&jobs, &keys, &leading, &schemas, &limiter)
+            .await?;
+        for (idx, outcome) in leading.iter().zip(leading_outcomes) {
+            outcomes[*idx] = Some(outcome);
+        }
+        let mut coalesced = BTreeMap::new();
+        for &idx in &following {
+            let leader = leaders[idx].unwrap_or(idx);
+            if self.share_cached(&keys[leader], &keys[idx], &jobs[idx].prefix)? {
+                let path = jobs[idx].path.join("/");
+                let leader = jobs[leader].path.join("/");
+                println!(
+                    "[Coalesce] '{}' shares the names generated for '{}'",
+                    path, leader
+                );
+                coalesced.insert(path, leader);
+            }
+        }
+        let following_outcomes = self
+            .generate_blocks(&jobs, &keys, &following, &schemas, &limiter)
+            .await?;
+        for (idx, outcome) in following.iter().zip(following_outcomes) {
+            outcomes[*idx] = Some(outcome);
+        }
+        // Composed blocks combine the names their pools ended up with, one after the other as a
+        // pool may compose names itself
+        for idx in composing {
+            let pools: Vec<Vec<String>> = jobs[idx]
+                .compose
+                .iter()
+                .map(|&pool| match &outcomes[pool] {
+                    Some(outcome) => outcome
+                        .entries
+                        .iter()
+                        .map(|(_, name)| name.clone())
+                        .collect(),
+                    None => Vec::new(),
+                })
+                .collect();
+            if let Some((&pool, _)) = jobs[idx]
+                .compose
+                .iter()
+                .zip(&pools)
+                .find(|(_, names)| names.is_empty())
+            {
+                anyhow::bail!(
+                    "'{}' composes '{}', which ended up without names",
+                    jobs[idx].path.join("/"),
+                    jobs[pool].path.last().map_or("", String::as_str)
+                );
+            }
+            let seed =
+                self.shuffle_seed.unwrap_or_default() ^ fnv1a(jobs[idx].path.join("/").as_bytes());
+            let cap = jobs[idx].max_names.unwrap_or(compose::MAX_COMBINATIONS);
+            jobs[idx].composed = Some(compose::combine(&pools, seed, cap));
+            let composed = self
+                .generate_blocks(&jobs, &keys, &[idx], &schemas, &limiter)
+                .await?;
+            outcomes[idx] = composed.into_iter().next();
+        }
+        let results: Vec<BlockOutcome> = outcomes.into_iter().flatten().collect();
+        // A line missing a value is left out as a whole, `None`
+        let values: Vec<Option<Vec<String>>> =
+            futures::stream::iter(inline.iter().zip(&inline_keys))
+                .map(|(line, keys)| {
+                    let limiter = &limiter;
+                    async move {
+                        let _permit = limiter.acquire(&line.model).await;
+                        let mut values = Vec::new();
+                        for (idx, key) in keys.iter().enumerate() {
+                            match self.generate_value(line, idx, key).await? {
+                                Some(value) => values.push(value),
+                                None => return Ok(None),
+                            }
+                        }
+                        Ok(Some(values))
+                    }
+                })
+                .buffered(self.concurrency)
+                .collect::<Vec<_>>()
+                .await
+                .into_iter()
+                .collect::<Result<_>>()?;
+        let mut values = values.into_iter();
+
+        let mut lines: Vec<String> = Vec::new();
+        let mut report = Report {
+            estimate: Some(estimate),
+            coalesced,
+            aliased,
+            draft: self.draft_model.is_some(),
+            ..Report::default()
+        };
+        let mut localisation = Localisation::new(
+            &self.vanilla_keys,
+            &self.existing_keys,
+            self.key_style.max_len,
+        );
+        let mut unique = UniqueNames::new(self.unique_scope, &self.categories);
+        for segment in output {
+            match segment {
+                Segment::Line(line) => lines.push(line),
+                Segment::Inline(line) => {
+                    let style = self.key_style_for(&line.key_style)?;
+                    let source = format!("file_structure.txt:{}", line.line_no);
+                    let Some(line_values) = values.next().unwrap() else {
+                        report.budget_skipped.push(source);
+                        continue;
+                    };
+                    let mut substitutions = Vec::new();
+                    for (placeholder, value) in line.placeholders.iter().zip(line_values) {
+                        let value = if self.ascii_only {
+                            let ascii = to_ascii(&value);
+                            if ascii != value {
+                                warning!("[ASCII] '{}': '{}' -> '{}'", source, value, ascii);
+                            }
+                            ascii
+                        } else {
+                            value
+                        };
+                        if placeholder.literal {
+                            let value = self
+                                .quotes
+                                .apply(&value, Target::Script)
+                                .unwrap_or_default();
+                            substitutions.push(quoting::escape_script(&value));
+                            continue;
+                        }
+                        let value = match self.quotes.apply(&value, Target::Localisation) {
+                            Some(value) => value,
+                            None => {
+                                let stripped = QuoteMode::Strip
+                                    .apply(&value, Target::Localisation)
+                                    .unwrap_or_default();
+                                warning!(
+                                    "[Quotes] '{}': no localisation value can hold '{}', emitting '{}'",
+                                    source,
+                                    value,
+                                    stripped
+                                );
+                                report.unsafe_values.push((source.clone(), value));
+                                stripped
+                            }
+                        };
+                        let key = match self.key_compat.key_for(&value) {
+                            Some(key) => key.to_string(),
+                            None => style.key(&line.prefix, &value),
+                        };
+                        let Some(value) = self.fit_charset(
+                            "english",
+                            &key,
+                            &value,
+                            &mut report.charset_violations,
+                        ) else {
+                            anyhow::bail!(
+                                "'{}': nothing of '{}' is left once the characters {} rejects are stripped",
+                                source,
+                                value,
+                                self.loc_charset.as_str()
+                            );
+                        };
+                        substitutions.push(localisation.add(key, &value, &source, &mut report));
+                    }
+                    lines.push(line.render(&substitutions));
+                }
+                Segment::Generated(idx) => {
+                    let job = &jobs[idx];
+                    let outcome = &results[idx];
+                    let mut dropped = outcome.dropped.clone();
+                    let mut emitted = Vec::new();
+                    let source = job.path.join("/");
+                    // Blocks sharing a call share its names by design, the first claimed them
+                    let follower = report.coalesced.contains_key(&source);
+                    let comment_at = lines.len();
+                    for (key, val) in self.pins.for_block(&source) {
+                        let Some(val) =
+                            self.fit_charset("english", key, val, &mut report.charset_violations)
+                        else {
+                            continue;
+                        };
+                        unique.admit(&job.path, &val);
+                        let key = localisation.insert(key.clone(), &val, &source, &mut report);
+                        lines.push(format!("{}{},", job.child_indent, key));
+                        emitted.push((key, val));
+                    }
+                    for (idx, (key, val)) in outcome.entries.iter().enumerate() {
+                        let Some(val) =
+                            self.fit_charset("english", key, val, &mut report.charset_violations)
+                        else {
+                            if idx < job.first.len() {
+                                anyhow::bail!(
+                                    "'{}' lists '{}' first but nothing of it is left once the characters {} rejects are stripped",
+                                    source,
+                                    val,
+                                    self.loc_charset.as_str()
+                                );
+                            }
+                            *dropped.entry("charset".to_string()).or_default() += 1;
+                            continue;
+                        };
+                        let pinned = self.pins.pins_key(key);
+                        let admitted = !pinned && (unique.admit(&job.path, &val) || follower);
+                        // Names listed first head the entries and must survive to the output
+                        if idx < job.first.len() && !admitted {
+                            anyhow::bail!(
+                                "'{}' lists '{}' first but it is already emitted elsewhere",
+                                source,
+                                val
+                            );
+                        }
+                        if pinned {
+                            warning!(
+                                "[Pin] '{}' is pinned, dropping \"{}\" of '{}'",
+                                key,
+                                val,
+                                source
+                            );
+                            *dropped.entry("pinned".to_string()).or_default() += 1;
+                            continue;
+                        }
+                        if !admitted {
+                            *dropped
+                                .entry(format!("unique-{}", self.unique_scope.as_str()))
+                                .or_default() += 1;
+                            continue;
+                        }
+                        let key = localisation.add(key.clone(), &val, &source, &mut report);
+                        lines.push(format!("{}{},", job.child_indent, key));
+                        emitted.push((key, val));
+                    }
+                    // Conditional inserts follow the block's fate, empty blocks go without
+                    if !emitted.is_empty() {
+                        lines.splice(
+                            comment_at..comment_at,
+                            job.if_generated
+                                .iter()
+                                .map(|kv| format!("{}{}", job.child_indent, kv)),
+                        );
+                    }
+                    if self.provenance_comments {
+                        let comment = block_comment(
+                            outcome.generated_at,
+                            &job.theme,
+                            &job.model,
+                            emitted.len(),
+                        );
+                        lines.insert(comment_at, format!("{}{}", job.child_indent, comment));
+                    }
+                    if self.markers {
+                        lines.insert(
+                            comment_at,
+                            format!("{}{}{}", job.child_indent, BEGIN_MARKER, source),
+                        );
+                        lines.push(format!("{}{}{}", job.child_indent, END_MARKER, source));
+                    }
+                    let min_names = job.min_names.unwrap_or(self.min_names);
+                    let failed = emitted.len() < min_names || outcome.short_count;
+                    if emitted.len() < min_names {
+                        warning!(
+                            "[Floor] '{}': only {} of {} names survived filtering, below the minimum of {}",
+                            source,
+                            emitted.len(),
+                            outcome.generated,
+                            min_names
+                        );
+                    }
+                    let order = if job.sequential {
+                        emitted
+                            .iter()
+                            .take(10)
+                            .map(|(_, name)| name.clone())
+                            .collect()
+                    } else {
+                        Vec::new()
+                    };
+                    report.blocks.push(BlockReport {
+                        path: job.path.clone(),
+                        category: unique.category(&job.path),
+                        theme: job.theme.clone(),
+                        era: job.era.map(|era| era.as_str().to_string()),
+                        prefix: job.prefix.clone(),
+                        model: job.model.clone(),
+                        generated: outcome.generated,
+                        names: emitted.len(),
+                        dropped,
+                        scores: outcome.scores.clone(),
+                        style_scores: outcome.style_scores.clone(),
+                        near_forms: outcome.near_forms.clone(),
+                        semantic: outcome.semantic.clone(),
+                        casing: outcome.casing.clone(),
+                        unsafe_names: outcome.unsafe_names.clone(),
+                        diversity: outcome.diversity.clone(),
+                        distribution: outcome.distribution.clone(),
+                        cached: outcome.cached,
+                        entries: emitted,
+                        failed,
+                        shuffle_seed: outcome.shuffle_seed,
+                        elapsed_ms: outcome.elapsed_ms,
+                        budget_exhausted: outcome.budget_exhausted,
+                        salvaged: outcome.salvaged,
+                        truncated: outcome.truncated,
+                        translatable: job.translatable,
+                        order,
+                        replayed: job.replayed.clone(),
+                        ordinal_expand: job.ordinal_expand.map(|ordinals| ordinals.to_string()),
+                        stages: outcome.stages.clone(),
+                    });
+                }
+            }
+        }
+
+        // Cross-reference what actually ships, after every filter
+        let mut blocks_by_name: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for block in &report.blocks {
+            let path = block.path.join("/");
+            for (_, name) in &block.entries {
+                let blocks = blocks_by_name.entry(name.clone()).or_default();
+                if !blocks.contains(&path) {
+                    blocks.push(path.clone());
+                }
+            }
+        }
+        report.cross_duplicates = blocks_by_name
+            .into_iter()
+            .filter(|(_, blocks)| blocks.len() > 1)
+            .collect();
+
+        if self.strict {
+            let mut problems: Vec<String> = report
+                .conflicts
+                .iter()
+                .map(|c| {
+                    format!(
+                        "'{}' has conflicting values \"{}\" ({}) and \"{}\" ({})",
+                        c.key, c.first_value, c.first_source, c.value, c.source
+                    )
+                })
+                .collect();
+            problems.extend(
+                report
+                    .blocks
+                    .iter()
+                    .filter(|block| block.failed)
+                    .map(|block| {
+                        format!(
+                            "'{}' kept only {} of {} names",
+                            block.path.join("/"),
+                            block.names,
+                            block.generated
+                        )
+                    }),
+            );
+            if !problems.is_empty() {
+                anyhow::bail!(
+                    "Strict run failed with {} problems:\n  {}",
+                    problems.len(),
+                    problems.join("\n  ")
+                );
+            }
+        }
+
+        report.budget = self.budget_usage();
+        report.errors = self.health.failures();
+        report.usage = self.generator.usage();
+        report.quota_wait_ms = self.quota_waited().as_millis() as u64;
+        report.warnings = warnings::take();
+        Ok(RunResult {
+            name_list: lines.join("\n"),
+            localisation: localisation.entries,
+            translations: BTreeMap::new(),
+            report,
+            value_format: self.value_format.clone(),
+        })
+    }
+
+    /// Generates a value for each localisation key, bypassing the structure file. Tasks sharing a
+    /// theme and model are asked for in one request and take its names in order, so their values
+    /// differ. Each group is cached and reported like a block under `titles/<first key>`
+    pub async fn run_tasks(&self, tasks: &[LocalisationTask]) -> Result<RunResult> {
+        let mut groups: Vec<(String, String, Vec<&str>)> = Vec::new();
+        for task in tasks {
+            let model = task.model.clone().unwrap_or_else(|| self.model.clone());
+            match groups
+                .iter_mut()
+                .find(|(theme, m, _)| *theme == task.theme && *m == model)
+            {
+                Some((_, _, keys)) => keys.push(&task.key),
+                None => groups.push((task.theme.clone(), model, vec![&task.key])),
+            }
+        }
+        let jobs: Vec<GenerationJob> = groups
+            .iter()
+            .map(|(theme, model, keys)| {
+                let mut job = GenerationJob::standalone(
+                    vec!["titles".to_string(), keys[0].to_string()],
+                    theme.clone(),
+                    model.clone(),
+                );
+                // Only one name per key is used, the rest of the answer would be wasted
+                job.max_names = Some(keys.len());
+                job
+            })
+            .collect();
+        let block_keys: Vec<BlockKey> = jobs
+            .iter()
+            .map(|job| job.cache_key(&self.lore_profiles))
+            .collect::<Result<_>>()?;
+        let limiter = ModelLimiter::new(
+            jobs.iter().map(|job| job.model.as_str()),
+            &self.model_limits,
+            self.max_concurrent_models.unwrap_or(self.concurrency),
+        );
+        let results: Vec<BlockOutcome> = futures::stream::iter(jobs.iter().zip(&block_keys))
+            .map(|(job, key)| {
+                let limiter = &limiter;
+                async move {
+                    let _permit = limiter.acquire(&job.model).await;
+                    self.generate_localized_entries(job, key, None).await
+                }
+            })
+            .buffered(self.concurrency)
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect::<Result<_>>()?;
+
+        let mut report = Report {
+            draft: self.draft_model.is_some(),
+            ..Report::default()
+        };
+        let mut localisation = Localisation::new(
+            &self.vanilla_keys,
+            &self.existing_keys,
+            self.key_style.max_len,
+        );
+        for ((job, (_, _, keys)), outcome) in jobs.iter().zip(&groups).zip(results) {
+            let source = job.path.join("/");
+            let mut emitted = Vec::new();
+            // Keys are given, only the values of the generated entries are used
+            for (key, (_, value)) in keys.iter().zip(&outcome.entries) {
+                let Some(value) =
+                    self.fit_charset("english", key, value, &mut report.charset_violations)
+                else {
+                    continue;
+                };
+                let key = localisation.add(key.to_string(), &value, &source, &mut report);
+                emitted.push((key, value));
+            }
+            let failed = emitted.len() < keys.len();
+            if failed {
+                warning!(
+                    "[Floor] '{}': only {} of {} keys got a value",
+                    source,
+                    emitted.len(),
+                    keys.len()
+                );
+            }
+            report.blocks.push(BlockReport {
+                path: job.path.clone(),
+                category: "titles".to_string(),
+                theme: job.theme.clone(),
+                era: None,
+                prefix: job.prefix.clone(),
+                model: job.model.clone(),
+                generated: outcome.generated,
+                names: emitted.len(),
+                dropped: outcome.dropped,
+                scores: outcome.scores,
+                style_scores: outcome.style_scores,
+                near_forms: outcome.near_forms,
+                semantic: outcome.semantic,
+                casing: outcome.casing,
+                unsafe_names: outcome.unsafe_names,
+                diversity: outcome.diversity,
+                distribution: outcome.distribution,
+                cached: outcome.cached,
+                entries: emitted,
+                failed,
+                shuffle_seed: outcome.shuffle_seed,
+                elapsed_ms: outcome.elapsed_ms,
+                budget_exhausted: outcome.budget_exhausted,
+                salvaged: outcome.salvaged,
+                truncated: outcome.truncated,
+                translatable: false,
+                order: Vec::new(),
+                replayed: None,
+                ordinal_expand: None,
+                stages: outcome.stages,
+            });
+        }
+        if self.strict
+            && let Some(block) = report.blocks.iter().find(|block| block.failed)
+        {
+            anyhow::bail!(
+                "Strict run failed: '{}' got only {} values",
+                block.path.join("/"),
+                block.names
+            );
+        }
+        report.budget = self.budget_usage();
+        report.errors = self.health.failures();
+        report.usage = self.generator.usage();
+        report.quota_wait_ms = self.quota_waited().as_millis() as u64;
+        report.warnings = warnings::take();
+        Ok(RunResult {
+            name_list: String::new(),
+            localisation: localisation.entries,
+            translations: BTreeMap::new(),
+            report,
+            value_format: self.value_format.clone(),
+        })
+    }
+
+    /// Translates the entries of every `#@ translatable` block into each language, adding a full
+    /// localisation per language to the result, see [`crate::translate`]. Every other entry,
+    /// and every entry whose translation failed, keeps its English value. Failures only warn
+    pub async fn translate(&self, result: &mut RunResult, languages: &[Language]) -> Result<()> {
+        let blocks: Vec<&BlockReport> = result
+            .report
+            .blocks
+            .iter()
+            .filter(|block| block.translatable && !block.entries.is_empty())
+            .collect();
+        let limiter = ModelLimiter::new(
+            blocks.iter().map(|block| block.model.as_str()),
+            &self.model_limits,
+            self.max_concurrent_models.unwrap_or(self.concurrency),
+        );
+        for &language in languages {
+            let outcomes: Vec<Result<TranslatedBlock>> = futures::stream::iter(&blocks)
+                .map(|block| {
+                    let limiter = &limiter;
+                    async move {
+                        let _permit = limiter.acquire(&block.model).await;
+                        self.translate_block(block, language).await
+                    }
+                })
+                .buffered(self.concurrency)
+                .collect()
+                .await;
+            let mut report = TranslationReport::new(language);
+            // Translated values by key, along with the English value they translate
+            let mut translated: HashMap<&str, (&str, String)> = HashMap::new();
+            for (block, outcome) in blocks.iter().zip(outcomes) {
+                match outcome {
+                    Ok(outcome) => {
+                        report.cached += outcome.cached;
+                        for (key, value) in &block.entries {
+                            match outcome.values.get(key) {
+                                Some(translation) => {
+                                    translated.insert(key, (value, translation.clone()));
+                                }
+                                None => report.fallback += 1,
+                            }
+                        }
+                    }
+                    Err(e) if e.is::<Unhealthy>() || e.is::<QuotaExhausted>() => return Err(e),
+                    Err(e) => {
+                        report.fallback += block.entries.len();
+                        report
+                            .errors
+                            .push(format!("{}: {:#}", block.path.join("/"), e));
+                    }
+                }
+            }
+            // Colliding keys hold the first block's value, which may not be the translated one
+            let mut entries: Vec<(String, String)> = Vec::new();
+            for (key, value) in &result.localisation {
+                let value = match translated.get(key.as_str()) {
+                    Some((source, translation)) if source == value => {
+                        match self.fit_charset(
+                            language.id(),
+                            key,
+                            translation,
+                            &mut result.report.charset_violations,
+                        ) {
+                            Some(translation) => {
+                                report.translated += 1;
+                                translation
+                            }
+                            None => {
+                                report.fallback += 1;
+                                value.clone()
+                            }
+                        }
+                    }
+                    _ => value.clone(),
+                };
+                entries.push((key.clone(), value));
+            }
+            if report.fallback > 0 {
+                warning!(
+                    "[Translate Warning] {}: {} entries kept in English{}",
+                    language.name(),
+                    report.fallback,
+                    match report.errors.first() {
+                        Some(error) => format!(", {}", error),
+                        None => String::new(),
+                    }
+                );
+            }
+            println!(
+                "[Translate] {}: {} entries translated, {} from the cache",
+                language.name(),
+                report.translated,
+                report.cached
+            );
+            result.translations.insert(language, entries);
+            result.report.translations.push(report);
+        }
+        result.report.budget = self.budget_usage();
+        result.report.errors = self.health.failures();
+        result.report.usage = self.generator.usage();
+        result.report.quota_wait_ms = self.quota_waited().as_millis() as u64;
+        Ok(())
+    }
+
+    /// Translates a block's entries, reading each from the cache under its key, the language
+    /// and the digest of its English value. The rest are asked for in one request
+    async fn translate_block(
+        &self,
+        block: &BlockReport,
+        language: Language,
+    ) -> Result<TranslatedBlock> {
+        let cache_key = |key: &str, value: &str| {
+            BlockKey::from(format!("{}~{}~{}", key, language.id(), digest(value)).as_str())
+        };
+        let mut outcome = TranslatedBlock::default();
+        let mut missing = Vec::new();
+        {
+            let cache = self.cache.lock().unwrap();
+            for (key, value) in &block.entries {
+                match cache.get(&cache_key(key, value)) {
+                    Some(cached) => {
+                        outcome.values.insert(key.clone(), cached.raw);
+                        outcome.cached += 1;
+                    }
+                    None => missing.push((key.clone(), value.clone())),
+                }
+            }
+        }
+        if missing.is_empty() {
+            return Ok(outcome);
+        }
+        if !self.spend() {
+            anyhow::bail!("budget exhausted");
+        }
+        // Every key is a required property, described by the value it translates
+        let properties: serde_json::Map<String, serde_json::Value> = missing
+            .iter()
+            .map(|(key, value)| {
+                (
+                    key.clone(),
+                    serde_json::json!({ "type": "string", "description": value }),
+                )
+            })
+            .collect();
+        let request = GenerationRequest {
+            model: block.model.clone(),
+            theme: format!("{} ({})", block.path.join("/"), language.name()),
+            prompt: build_translation_prompt(&block.theme, language.name(), &missing),
+            format: ResponseFormat::Translations,
+            schema: Some(serde_json::json!({
+                "type": "object",
+                "properties": {
+                    "translations": {
+                        "type": "object",
+                        "properties": properties,
+                        "required": missing.iter().map(|(key, _)| key).collect::<Vec<_>>(),
+                    }
+                }
+            })),
+            max_names: None,
+            max_tokens: None,
+            temperature: None,
+        };
+        let Reply::Answer {
+            parsed: mut translations,
+            ..
+        } = self.call(&request, parse_translations).await?
+        else {
+            anyhow::bail!("the translation request failed");
+        };
+        let mut cache = self.cache.lock().unwrap();
+        for (key, value) in missing {
+            let Some(translation) = translations.remove(&key) else {
+                continue;
+            };
+            let cached = CachedBlock {
+                raw: translation.clone(),
+                model: Some(block.model.clone()),
+                timestamp: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .ok()
+                    .map(|elapsed| elapsed.as_secs()),
+                prompt_hash: None,
+                theme_hash: None,
+                raw_stream: None,
+                flavors: Vec::new(),
+                max_tokens: Some(self.max_tokens(&block.model)),
+                species_hint: None,
+                temperature: self.generator.temperature(),
+                prompt_version: Some(PROMPT_VERSION),
+                prefix: None,
+                sources: Vec::new(),
+            };
+            cache.put(&cache_key(&key, &value), cached)?;
+            outcome.values.insert(key, translation);
+        }
+        Ok(outcome)
+    }
+
+    /// Sends a request through the circuit breaker, see [`crate::health`]. A request that errors
+    /// or whose answer `parse` rejects is logged and counted as a failure, coming back without an
+    /// answer to be retried, until too many recent calls failed and the run is aborted. A
+    /// truncated stream counts as a failure too, but its text is still parsed for salvage
+    async fn call<T>(
+        &self,
+        request: &GenerationRequest,
+        parse: impl Fn(&str) -> Result<T>,
+    ) -> Result<Reply<T>> {
+        self.health.check()?;
+        let (raw, truncated) = match self.generate(request).await {
+            Ok(raw) => (raw, false),
+            Err(e) if e.is::<QuotaExhausted>() => return Err(e),
+            Err(e) => {
+                warning!("[Gen AI Error]: {:#}", e);
+                self.health.failure(classify(&e))?;
+                match e.downcast::<StreamError>() {
+                    Ok(StreamError::Truncated(partial)) => (partial, true),
+                    Ok(StreamError::Interrupted(_)) => return Ok(Reply::Interrupted),
+                    Err(_) => return Ok(Reply::Failed),
+                }
+            }
+        };
+        self.budget.record(&request.prompt, &raw);
+        match parse(&raw) {
+            Ok(parsed) => {
+                if !truncated {
+                    self.health.success();
+                }
+                Ok(Reply::Answer {
+                    raw,
+                    parsed,
+                    truncated,
+                })
+            }
+            Err(e) => {
+                warning!("[Gen AI Error]: {:#}", e);
+                if !truncated {
+                    self.health.failure("unusable output")?;
+                }
+                Ok(Reply::Failed)
+            }
+        }
+    }
+
+    /// Sends a request with the model's `max_tokens`. A provider rejecting the limit is asked again
+    /// with half of it, down to [`MIN_MAX_TOKENS`], and the limit that worked is kept for the
+    /// model's later requests. With [`GenerationBuilder::wait_for_quota`], a request failing on
+    /// an exhausted quota parks the run and goes again once the quota is back
+    async fn generate(&self, request: &GenerationRequest) -> Result<String> {
+        loop {
+            let limit = self.max_tokens(&request.model);
+            let sized = GenerationRequest {
+                max_tokens: Some(limit),
+                ..request.clone()
+            };
+            let resumed = self.quota.as_ref().map(QuotaWait::resumed);
+            match self.generator.generate(&sized).await {
+                Err(e) if limit > MIN_MAX_TOKENS && rejects_max_tokens(&e) => {
+                    let lower = (limit / 2).max(MIN_MAX_TOKENS);
+                    println!(
+                        "[AI] '{}' rejected max_tokens {}, retrying with {}",
+                        request.model, limit, lower
+                    );
+                    let mut limits = self.token_limits.lock().unwrap();
+                    let known = limits.entry(request.model.clone()).or_insert(lower);
+                    *known = (*known).min(lower);
+                }
+                Err(e) if self.quota.is_some() && quota_exhausted(&e) => {
+                    warning!("[Quota] '{}': {:#}", request.theme, e);
+                    if let Some(quota) = &self.quota {
+                        quota
+                            .park(
+                                resumed.unwrap_or_default(),
+                                self.generator.as_ref(),
+                                &request.model,
+                                &self.budget,
+                            )
+                            .await?;
+                    }
+                }
+                reply => return reply,
+            }
+        }
+    }
+
+    /// `max_tokens` sent with the model's requests, lowered once the provider rejected a limit
+    fn max_tokens(&self, model: &str) -> u32 {
+        self.token_limits
+            .lock()
+            .unwrap()
+            .get(model)
+            .copied()
+            .unwrap_or(DEFAULT_MAX_TOKENS)
+    }
+
+    /// Generates the blocks at `indices` concurrently, their outcomes in the same order
+    async fn generate_blocks(
+        &self,
+        jobs: &[GenerationJob],
+        keys: &[BlockKey],
+        indices: &[usize],
+        schemas: &HashMap<String, serde_json::Value>,
+        limiter: &ModelLimiter,
+    ) -> Result<Vec<BlockOutcome>> {
+        // `buffered` yields in job order, keeping the output independent of completion order
+        futures::stream::iter(indices)
+            .map(|&idx| {
+                let job = &jobs[idx];
+                let schema = job.schema.as_ref().map(|path| &schemas[path]);
+                // Warnings repeated by the block are summed up once it is done
+                warnings::scoped(job.path.join("/"), async move {
+                    let _permit = limiter.acquire(&job.model).await;
+                    self.generate_localized_entries(job, &keys[idx], schema)
+                        .await
+                })
+            })
+            .buffered(self.concurrency)
+            .collect::<Vec<_>>()
+            .await
+            .into_iter()
+            .collect()
+    }
+
+    /// Stores the names cached for `leader` under `follower` too, with the follower's prefix.
+    /// Whether this saved the follower a call, it has no names of its own yet
+    fn share_cached(&self, leader: &BlockKey, follower: &BlockKey, prefix: &str) -> Result<bool> {
+        let mut cache = self.cache.lock().unwrap();
+        if cache.get(follower).is_some() {
+            return Ok(false);
+        }
+        let Some(mut block) = cache.get(leader) else {
+            return Ok(false);
+        };
+        block.prefix = Some(prefix.to_string());
+        cache.put(follower, block)?;
+        Ok(true)
+    }
+
+    /// The block's cache, else the cache of the first of its aliases holding one, which the
+    /// run moves to it
+    fn cached_or_aliased(
+        &self,
+        cache: &dyn NameCache,
+        job: &GenerationJob,
+    ) -> Result<Option<CachedBlock>> {
+        if let Some(block) = cache.get(&job.cache_key(&self.lore_profiles)?) {
+            return Ok(Some(block));
+        }
+        let aliases = job.alias_keys(&self.lore_profiles)?;
+        Ok(aliases.iter().find_map(|key| cache.get(key)))
+    }
+
+    /// Moves the cache of a renamed block from the first of its aliases holding one to its own
+    /// key, along with the entries cached beside it. Translations are cached by localisation key,
+    /// those of a block whose prefix changed with its path move to the keys under the new prefix.
+    /// Returns the alias it was moved from
+    fn migrate_alias(&self, job: &GenerationJob, key: &BlockKey) -> Result<Option<String>> {
+        if job.aliases.is_empty() {
+            return Ok(None);
+        }
+        let mut cache = self.cache.lock().unwrap();
+        if cache.get(key).is_some() {
+            return Ok(None);
+        }
+        for (alias, old) in job.aliases.iter().zip(job.alias_keys(&self.lore_profiles)?) {
+            let Some(block) = cache.get(&old) else {
+                continue;
+            };
+            if let Some(prefix) = block
+                .prefix
+                .as_deref()
+                .filter(|prefix| *prefix != job.prefix)
+            {
+                let style = self.key_style_for(&job.key_style)?;
+                let renamed: HashMap<String, String> = parse_items(&block.raw)
+                    .unwrap_or_default()
+                    .iter()
+                    .map(|item| {
+                        let stem = item.key().unwrap_or(item.name());
+                        (style.key(prefix, stem), style.key(&job.prefix, stem))
+                    })
+                    .collect();
+                for cached in cache.keys()? {
+                    // `{key}~{lang}~{digest}`, the key holding no `~` of its own
+                    let mut parts = cached.as_str().splitn(2, '~');
+                    let (Some(entry), Some(rest)) = (parts.next(), parts.next()) else {
+                        continue;
+                    };
+                    let Some(moved) = renamed.get(entry) else {
+                        continue;
+                    };
+                    if rest.split('~').count() != 2 {
+                        continue;
+                    }
+                    if let Some(translation) = cache.get(&cached) {
+                        let target = BlockKey::from(format!("{}~{}", moved, rest).as_str());
+                        cache.put(&target, translation)?;
+                        cache.remove(&cached)?;
+                    }
+                }
+            }
+            cache.put(key, block)?;
+            cache.remove(&old)?;
+            for suffix in DERIVED_SUFFIXES {
+                let derived = BlockKey::from(format!("{}{}", old.as_str(), suffix).as_str());
+                if let Some(block) = cache.get(&derived) {
+                    let moved = BlockKey::from(format!("{}{}", key.as_str(), suffix).as_str());
+                    cache.put(&moved, block)?;
+                    cache.remove(&derived)?;
+                }
+            }
+            return Ok(Some(alias.join("/")));
+        }
+        Ok(None)
+    }
+
+    /// Time the run spent parked on an exhausted quota
+    fn quota_waited(&self) -> Duration {
+        self.quota
+            .as_ref()
+            .map(QuotaWait::waited)
+            .unwrap_or_default()
+    }
+
+    /// Reserves a request against the budget, `false` once it is exhausted
+    fn spend(&self) -> bool {
+        !self.replaying.load(Ordering::SeqCst) && self.budget.try_acquire(self.generator.usage())
+    }
+
+    /// Spending against the budget, `None` without one
+    fn budget_usage(&self) -> Option<BudgetUsage> {
+        self.budget
+            .is_limited()
+            .then(|| self.budget.usage(self.generator.usage()))
+    }
+
+    /// Checks a localisation value against the run's charset, see [`crate::charset`]. Stripped
+    /// of the characters it rejects if asked, `None` once nothing of it is left
+    fn fit_charset(
+        &self,
+        language: &str,
+        key: &str,
+        value: &str,
+        violations: &mut Vec<CharsetViolation>,
+    ) -> Option<String> {
+        let strip = self.strip_loc_charset;
+        let Some(violation) = check_value(self.loc_charset, strip, language, key, value) else {
+            return Some(value.to_string());
+        };
+        warning!(
+            "[Charset] '{}' ({}): \"{}\" holds \"{}\", not allowed by {}{}",
+            violation.key,
+            language,
+            violation.value.escape_debug(),
+            violation.disallowed.escape_debug(),
+            self.loc_charset.as_str(),
+            if strip { ", stripped" } else { "" }
+        );
+        violations.push(violation);
+        if !strip {
+            return Some(value.to_string());
+        }
+        let stripped = self.loc_charset.strip(value);
+        if stripped.is_empty() {
+            warning!(
+                "[Charset] '{}' ({}): nothing is left of \"{}\", dropping",
+                key,
+                language,
+                value.escape_debug()
+            );
+            return None;
+        }
+        Some(stripped)
+    }
+
+    /// Transliterates names to ASCII, warning about every name it changed and dropping names
+    /// left empty
+    fn transliterate(
+        &self,
+        source: &str,
+        names: Vec<String>,
+        dropped: &mut BTreeMap<String, usize>,
+    ) -> Vec<String> {
+        let before = names.len();
+        let names: Vec<String> = names
+            .into_iter()
+            .filter_map(|name| {
+                let ascii = to_ascii(&name);
+                if ascii != name {
+                    warning!("[ASCII] '{}': '{}' -> '{}'", source, name, ascii);
+                }
+                (!ascii.is_empty()).then_some(ascii)
+            })
+            .collect();
+        let removed = before - names.len();
+        if removed > 0 {
+            dropped.insert("ascii-only".to_string(), removed);
+        }
+        names
+    }
+
+    /// Applies the run's quote mode to names, dropping those it leaves empty or no localisation
+    /// value can hold, the latter kept in `rejected`
+    fn quote(
+        &self,
+        source: &str,
+        names: Vec<String>,
+        dropped: &mut BTreeMap<String, usize>,
+        rejected: &mut Vec<String>,
+    ) -> Vec<String> {
+        let before = names.len();
+        let names: Vec<String> = names
+            .into_iter()
+            .filter_map(
+                |name| match self.quotes.apply(&name, Target::Localisation) {
+                    Some(quoted) => (!quoted.is_empty()).then_some(quoted),
+                    None => {
+                        warning!(
+                            "[Quotes] '{}': no localisation value can hold '{}', dropping",
+                            source,
+                            name
+                        );
+                        rejected.push(name);
+                        None
+                    }
+                },
+            )
+            .collect();
+        let removed = before - names.len();
+        if removed > 0 {
+            *dropped.entry("quotes".to_string()).or_default() += removed;
+        }
+        names
+    }
+
+    /// The run's key style with a block's `#@ key-style:` options applied
+    fn key_style_for(&self, options: &Option<String>) -> Result<KeyStyle> {
+        match options {
+            Some(options) => self.key_style.with_options(options),
+            None => Ok(self.key_style.clone()),
+        }
+    }
+
+    /// `class: hint` of the block's species class, `None` without one
+    fn species_hint(&self, job: &GenerationJob) -> Result<Option<String>> {
+        let Some(class) = job.species_class.as_ref().or(self.species_class.as_ref()) else {
+            return Ok(None);
+        };
+        let hint = self
+            .species
+            .hint(class)
+            .with_context(|| format!("Invalid species-class for '{}'", job.path.join("/")))?;
+        Ok(Some(format!("{}: {}", class.trim().to_lowercase(), hint)))
+    }
+
+    /// Lore selected by a `# lore:` profile, or the default lore
+    fn lore_for(&self, profile: &Option<String>) -> Result<&str> {
+        match profile {
+            Some(profile) => self.lore_profiles.get(profile),
+            None => Ok(&self.lore),
+        }
+    }
+
+    /// Generates or reads the cached value of one inline placeholder, `None` once the budget
+    /// ran out without a cached value
+    async fn generate_value(
+        &self,
+        line: &InlineLine,
+        idx: usize,
+        key: &BlockKey,
+    ) -> Result<Option<String>> {
+        let cached = self.cache.lock().unwrap().get(key);
+        if let Some(value) = cached.and_then(|block| parse_value(&block.raw).ok()) {
+            println!("[Cache] '{}' exists—using cached value", key.as_str());
+            return Ok(Some(value));
+        }
+        let theme = &line.placeholders[idx].theme;
+        let request = GenerationRequest {
+            model: line.model.clone(),
+            theme: theme.clone(),
+            prompt: build_value_prompt(theme, self.lore_for(&line.lore)?),
+            format: ResponseFormat::Value,
+            schema: None,
+            max_names: None,
+            max_tokens: None,
+            temperature: None,
+        };
+        for _ in 0..RETRY_FORMATS.len() {
+            if !self.spend() {
+                warning!(
+                    "[Budget] file_structure.txt:{}: budget exhausted with no cached value for '{{{{generate: {}}}}}', leaving the line out",
+                    line.line_no,
+                    theme
+                );
+                return Ok(None);
+            }
+            let Reply::Answer {
+                raw, parsed: value, ..
+            } = self.call(&request, parse_value).await?
+            else {
+                continue;
+            };
+            let block = CachedBlock {
+                raw: serde_json::json!({ "value": value }).to_string(),
+                model: Some(line.model.clone()),
+                timestamp: SystemTime::now()
+                    .duration_since(UNIX_EPOCH)
+                    .ok()
+                    .map(|elapsed| elapsed.as_secs()),
+                prompt_hash: Some(digest(&request.prompt)),
+                theme_hash: None,
+                raw_stream: self.keep_raw.then_some(raw),
+                flavors: Vec::new(),
+                max_tokens: Some(self.max_tokens(&line.model)),
+                species_hint: None,
+                temperature: self.generator.temperature(),
+                prompt_version: Some(PROMPT_VERSION),
+                prefix: None,
+                sources: Vec::new(),
+            };
+            self.cache.lock().unwrap().put(key, block)?;
+            return Ok(Some(value));
+        }
+        anyhow::bail!(
+            "file_structure.txt:{}: no usable value for '{{{{generate: {}}}}}'",
+            line.line_no,
+            theme
+        )
+    }
+
+    /// Has a model rate the candidate names, reusing cached scores while the candidates are unchanged
+    async fn score_names(
+        &self,
+        job: &GenerationJob,
+        key: &BlockKey,
+        filter: &ScoreFilter,
+        names: &[String],
+    ) -> Result<BTreeMap<String, u8>> {
+        let score_key = BlockKey::from(format!("{}~scores", key.as_str()).as_str());
+        let request = GenerationRequest {
+            model: filter.model.clone().unwrap_or_else(|| job.model.clone()),
+            theme: job.theme.clone(),
+            prompt: build_score_prompt(job, self.lore_for(&job.lore)?, names),
+            format: ResponseFormat::Scores,
+            schema: None,
+            max_names: None,
+            max_tokens: None,
+            temperature: None,
+        };
+        let prompt_hash = digest(&request.prompt);
+        let cached = self.cache.lock().unwrap().get(&score_key);
+        if let Some(block) = cached
+            && block.prompt_hash.as_deref() == Some(prompt_hash.as_str())
+            && let Ok(scores) = parse_scores(&block.raw)
+        {
+            println!(
+                "[Cache] '{}' exists—using cached scores",
+                score_key.as_str()
+            );
+            return Ok(scores);
+        }
+        if !self.spend() {
+            anyhow::bail!("budget exhausted");
+        }
+        let Reply::Answer {
+            raw,
+            parsed: scores,
+            ..
+        } = self.call(&request, parse_scores).await?
+        else {
+            anyhow::bail!("the scoring request failed");
+        };
+        let block = CachedBlock {
+            raw: serde_json::json!({ "scores": scores }).to_string(),
+            model: Some(request.model.clone()),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .map(|elapsed| elapsed.as_secs()),
+            prompt_hash: Some(prompt_hash),
+            theme_hash: None,
+            raw_stream: self.keep_raw.then_some(raw),
+            flavors: Vec::new(),
+            max_tokens: Some(self.max_tokens(&request.model)),
+            species_hint: None,
+            temperature: self.generator.temperature(),
+            prompt_version: Some(PROMPT_VERSION),
+            prefix: None,
+            sources: Vec::new(),
+        };
+        self.cache.lock().unwrap().put(&score_key, block)?;
+        Ok(scores)
+    }
+
+    /// Embeddings of `names` by name, read from the model's cache entry where known. The
+    /// entry is shared by every block, so a name is embedded once per model; the others are
+    /// embedded in one call and merged into it
+    async fn embeddings(
+        &self,
+        names: &[String],
+        settings: &SemanticDedup,
+    ) -> Result<BTreeMap<String, Vec<f32>>> {
+        let model_key = BlockKey::from_path(std::slice::from_ref(&settings.model));
+        let embeddings_key = BlockKey::from(format!("embeddings~{}", model_key.as_str()).as_str());
+        let cached = |cache: &dyn NameCache| -> BTreeMap<String, Vec<f32>> {
+            cache
+                .get(&embeddings_key)
+                .filter(|block| block.model.as_deref() == Some(settings.model.as_str()))
+                .and_then(|block| serde_json::from_str(&block.raw).ok())
+                .unwrap_or_default()
+        };
+        let mut embeddings = cached(self.cache.lock().unwrap().as_ref());
+        let missing: Vec<String> = names
+            .iter()
+            .filter(|name| !embeddings.contains_key(*name))
+            .cloned()
+            .collect();
+        if missing.is_empty() {
+            println!(
+                "[Cache] '{}' exists—using cached embeddings",
+                embeddings_key.as_str()
+            );
+            return Ok(embeddings);
+        }
+        let vectors = self.embed(&settings.model, &missing).await?;
+        if vectors.len() != missing.len() {
+            anyhow::bail!(
+                "asked to embed {} names, got {} embeddings",
+                missing.len(),
+                vectors.len()
+            );
+        }
+        // Blocks embed concurrently, so the entry is read again to keep what they added
+        let mut cache = self.cache.lock().unwrap();
+        let mut merged = cached(cache.as_ref());
+        merged.extend(missing.into_iter().zip(vectors));
+        let block = CachedBlock {
+            raw: serde_json::to_string(&merged)?,
+            model: Some(settings.model.clone()),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .map(|elapsed| elapsed.as_secs()),
+            prompt_hash: None,
+            theme_hash: None,
+            raw_stream: None,
+            flavors: Vec::new(),
+            max_tokens: None,
+            species_hint: None,
+            temperature: None,
+            prompt_version: None,
+            prefix: None,
+            sources: Vec::new(),
+        };
+        cache.put(&embeddings_key, block)?;
+        embeddings.extend(merged);
+        Ok(embeddings)
+    }
+
+    /// Embeds `names` with `model` like [`call`](Self::call) sends a request: within the model's
+    /// concurrency limit, through the circuit breaker, and parked on an exhausted quota
+    async fn embed(&self, model: &str, names: &[String]) -> Result<Vec<Vec<f32>>> {
+        if !self.spend() {
+            anyhow::bail!("budget exhausted");
+        }
+        let _permit = self.embedding_limiter.acquire(model).await;
+        loop {
+            self.health.check()?;
+            let resumed = self.quota.as_ref().map(QuotaWait::resumed);
+            match self.generator.embed(model, names).await {
+                Ok(vectors) => {
+                    self.health.success();
+                    return Ok(vectors);
+                }
+                Err(e) if self.quota.is_some() && quota_exhausted(&e) => {
+                    warning!("[Quota] embeddings: {:#}", e);
+                    if let Some(quota) = &self.quota {
+                        quota
+                            .park(
+                                resumed.unwrap_or_default(),
+                                self.generator.as_ref(),
+                                model,
+                                &self.budget,
+                            )
+                            .await?;
+                    }
+                }
+                Err(e) => {
+                    self.health.failure(classify(&e))?;
+                    return Err(e);
+                }
+            }
+        }
+    }
+
+    /// Asks for names in place of the ones trimmed from oversized clusters, forbidding their
+    /// stems. The answer is cached beside the block while the kept names stay the same
+    async fn diversify(
+        &self,
+        job: &GenerationJob,
+        key: &BlockKey,
+        kept: &[String],
+        trimmed: &[Cluster],
+    ) -> Result<Vec<String>> {
+        let diverse_key = BlockKey::from(format!("{}~diverse", key.as_str()).as_str());
+        let species = self.species_hint(job)?;
+        let request = GenerationRequest {
+            model: job.model.clone(),
+            theme: job.theme.clone(),
+            prompt: build_diversify_prompt(
+                job,
+                self.lore_for(&job.lore)?,
+                species.as_deref(),
+                kept,
+                trimmed,
+            ),
+            format: ResponseFormat::JsonNames,
+            schema: None,
+            max_names: None,
+            max_tokens: None,
+            temperature: None,
+        };
+        let prompt_hash = digest(&request.prompt);
+        let cached = self.cache.lock().unwrap().get(&diverse_key);
+        if let Some(block) = cached
+            && block.prompt_hash.as_deref() == Some(prompt_hash.as_str())
+            && let Ok(names) = parse_names(&block.raw)
+        {
+            println!(
+                "[Cache] '{}' exists—using cached names",
+                diverse_key.as_str()
+            );
+            return Ok(names);
+        }
+        if !self.spend() {
+            anyhow::bail!("budget exhausted");
+        }
+        let Reply::Answer {
+            raw, parsed: names, ..
+        } = self.call(&request, parse_names).await?
+        else {
+            anyhow::bail!("the request for more varied names failed");
+        };
+        let block = CachedBlock {
+            raw: serde_json::json!({ "names": names }).to_string(),
+            model: Some(request.model.clone()),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .map(|elapsed| elapsed.as_secs()),
+            prompt_hash: Some(prompt_hash),
+            theme_hash: None,
+            raw_stream: self.keep_raw.then_some(raw),
+            flavors: Vec::new(),
+            max_tokens: Some(self.max_tokens(&request.model)),
+            species_hint: species,
+            temperature: self.generator.temperature(),
+            prompt_version: Some(PROMPT_VERSION),
+            prefix: Some(job.prefix.clone()),
+            sources: Vec::new(),
+        };
+        self.cache.lock().unwrap().put(&diverse_key, block)?;
+        Ok(names)
+    }
+
+    /// Balances a block's names toward the `target` names per letter of its `# distribution:`.
+    /// Names past their letter's share are dropped and the short letters filled
+    /// by one more request, a failed one leaves them short. The fill goes through the block's
+    /// filters like its own names, see [`Self::filter_names`]
+    async fn balance(
+        &self,
+        job: &GenerationJob,
+        key: &BlockKey,
+        target: BTreeMap<char, usize>,
+        names: &mut Vec<String>,
+        dropped: &mut BTreeMap<String, usize>,
+        unsafe_names: &mut Vec<String>,
+    ) -> Result<Balance> {
+        let block = job.path.join("/");
+        let trimmed = Distribution::trim(names, &target);
+        if trimmed > 0 {
+            warning!(
+                "[Distribution] '{}': dropped {} names past their letter's share",
+                block,
+                trimmed
+            );
+            dropped.insert("distribution".to_string(), trimmed);
+        }
+        let wanted = Distribution::missing(names, &target);
+        let mut filled = 0;
+        if !wanted.is_empty() {
+            let fill = match self.balance_fill(job, key, names, &wanted).await {
+                Ok(more) => {
+                    let items: Vec<NameItem> = more.into_iter().map(NameItem::Plain).collect();
+                    let fill_key = BlockKey::from(format!("{}~balance", key.as_str()).as_str());
+                    // Boxed, as the chain balancing the block is what called it
+                    Box::pin(self.filter_names(job, &fill_key, &items, Some(names.as_slice())))
+                        .await
+                }
+                Err(e) => Err(e),
+            };
+            match fill {
+                Ok(fill) => {
+                    for (filter, count) in fill.dropped {
+                        *dropped.entry(filter).or_default() += count;
+                    }
+                    unsafe_names.extend(fill.unsafe_names);
+                    let mut known: HashSet<String> =
+                        names.iter().map(|name| name.to_lowercase()).collect();
+                    let mut short = wanted.clone();
+                    let more: Vec<String> = fill
+                        .names
+                        .into_iter()
+                        .filter(|name| {
+                            let letter = distribution::initial(name);
+                            match letter.and_then(|letter| short.get_mut(&letter)) {
+                                Some(left) if *left > 0 && known.insert(name.to_lowercase()) => {
+                                    *left -= 1;
+                                    true
+                                }
+                                _ => false,
+                            }
+                        })
+                        .collect();
+                    println!(
+                        "[Distribution] '{}': {} names generated for the short letters",
+                        block,
+                        more.len()
+                    );
+                    filled = more.len();
+                    names.extend(more);
+                }
+                Err(e) if e.is::<Unhealthy>() || e.is::<QuotaExhausted>() => return Err(e),
+                Err(e) => warning!(
+                    "[Distribution Warning] '{}': {:#}, keeping the letters short",
+                    block,
+                    e
+                ),
+            }
+        }
+        let balance = Balance {
+            achieved: distribution::count(names),
+            target,
+            trimmed,
+            filled,
+        };
+        let off = balance.off_target();
+        if off > 0 {
+            warning!(
+                "[Distribution Warning] '{}': {} names short of the target",
+                block,
+                off
+            );
+        }
+        Ok(balance)
+    }
+
+    /// Asks for names starting with the letters a `# distribution:` block is short of, twice as
+    /// many as missing up to [`MAX_FILL`]. The answer is cached beside the block while the kept
+    /// names stay the same
+    async fn balance_fill(
+        &self,
+        job: &GenerationJob,
+        key: &BlockKey,
+        kept: &[String],
+        wanted: &BTreeMap<char, usize>,
+    ) -> Result<Vec<String>> {
+        let balance_key = BlockKey::from(format!("{}~balance", key.as_str()).as_str());
+        let species = self.species_hint(job)?;
+        let missing: usize = wanted.values().sum();
+        let request = GenerationRequest {
+            model: job.model.clone(),
+            theme: job.theme.clone(),
+            prompt: build_balance_prompt(
+                job,
+                self.lore_for(&job.lore)?,
+                species.as_deref(),
+                kept,
+                wanted,
+            ),
+            format: ResponseFormat::JsonNames,
+            schema: None,
+            max_names: Some((missing * 2).min(MAX_FILL)),
+            max_tokens: None,
+            temperature: None,
+        };
+        let prompt_hash = digest(&request.prompt);
+        let cached = self.cache.lock().unwrap().get(&balance_key);
+        if let Some(block) = cached
+            && block.prompt_hash.as_deref() == Some(prompt_hash.as_str())
+            && let Ok(names) = parse_names(&block.raw)
+        {
+            println!(
+                "[Cache] '{}' exists—using cached names",
+                balance_key.as_str()
+            );
+            return Ok(names);
+        }
+        if !self.spend() {
+            anyhow::bail!("budget exhausted");
+        }
+        let Reply::Answer {
+            raw, parsed: names, ..
+        } = self.call(&request, parse_names).await?
+        else {
+            anyhow::bail!("the request for names of the short letters failed");
+        };
+        let block = CachedBlock {
+            raw: serde_json::json!({ "names": names }).to_string(),
+            model: Some(request.model.clone()),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .map(|elapsed| elapsed.as_secs()),
+            prompt_hash: Some(prompt_hash),
+            theme_hash: None,
+            raw_stream: self.keep_raw.then_some(raw),
+            flavors: Vec::new(),
+            max_tokens: Some(self.max_tokens(&request.model)),
+            species_hint: species,
+            temperature: self.generator.temperature(),
+            prompt_version: Some(PROMPT_VERSION),
+            prefix: Some(job.prefix.clone()),
+            sources: Vec::new(),
+        };
+        self.cache.lock().unwrap().put(&balance_key, block)?;
+        Ok(names)
+    }
+
+    /// Asks again for the names of `request`, avoiding the names already in `names`, and adds
+    /// the new ones. An answer without names adds none
+    async fn continue_answer(
+        &self,
+        job: &GenerationJob,
+        request: &GenerationRequest,
+        flavors: &[String],
+        species: Option<&str>,
+        names: &mut Vec<NameItem>,
+    ) -> Result<()> {
+        let format = request.format;
+        let avoid: Vec<String> = job
+            .taken
+            .iter()
+            .cloned()
+            .chain(names.iter().map(|item| item.name().to_string()))
+            .collect();
+        let retry = GenerationRequest {
+            prompt: build_prompt(
+                job,
+                self.lore_for(&job.lore)?,
+                format,
+                flavors,
+                species,
+                &avoid,
+            ),
+            ..request.clone()
+        };
+        if let Reply::Answer { parsed: more, .. } =
+            self.call(&retry, |raw| parse_answer(raw, format)).await?
+        {
+            let mut known: HashSet<String> = avoid.iter().map(|name| name.to_lowercase()).collect();
+            names.extend(
+                more.into_iter()
+                    .filter(|item| known.insert(item.name().to_lowercase())),
+            );
+        }
+        Ok(())
+    }
+
+    /// Request for a block's names in `format`
+    fn block_request(
+        &self,
+        job: &GenerationJob,
+        format: ResponseFormat,
+        schema: Option<&serde_json::Value>,
+        flavors: &[String],
+        species: Option<&str>,
+    ) -> Result<GenerationRequest> {
+        Ok(GenerationRequest {
+            model: job.model.clone(),
+            theme: job.theme.clone(),
+            prompt: build_prompt(
+                job,
+                self.lore_for(&job.lore)?,
+                format,
+                flavors,
+                species,
+                &job.taken,
+            ),
+            format,
+            schema: schema.cloned(),
+            max_names: job
+                .max_names
+                .zip(self.early_stop_margin)
+                .map(|(count, margin)| count + margin),
+            max_tokens: None,
+            temperature: job
+                .replayed
+                .as_ref()
+                .and_then(|replayed| replayed.temperature),
+        })
+    }
+
+    /// Asks for more names of a block its filters left short of its `# count:`, avoiding every
+    /// name it has, and caches them with the others
+    async fn continue_block(
+        &self,
+        job: &GenerationJob,
+        key: &BlockKey,
+        schema: Option<&serde_json::Value>,
+        items: &mut Vec<NameItem>,
+    ) -> Result<()> {
+        let cached = self.cache.lock().unwrap().get(key);
+        let flavors = match &cached {
+            Some(block) => block.flavors.clone(),
+            None => sample_flavors(&self.flavors, self.seed, &job.path),
+        };
+        let species = self.species_hint(job)?;
+        let request =
+            self.block_request(job, RETRY_FORMATS[0], schema, &flavors, species.as_deref())?;
+        self.continue_answer(job, &request, &flavors, species.as_deref(), items)
+            .await?;
+        if let Some(mut block) = cached {
+            block.raw = serde_json::json!({ "names": items }).to_string();
+            self.cache.lock().unwrap().put(key, block)?;
+        }
+        Ok(())
+    }
+
+    /// Generates a block's names and caches them once they parse. An answer that was cut off,
+    /// or whose stream was truncated, keeps its names and is completed by one more request
+    /// avoiding them, the number of names salvaged is returned alongside
+    async fn generate_and_cache(
+        &self,
+        job: &GenerationJob,
+        key: &BlockKey,
+        format: ResponseFormat,
+        schema: Option<&serde_json::Value>,
+    ) -> Result<Reply<(Vec<NameItem>, usize)>> {
+        let replayed = job.replayed.as_ref();
+        let flavors = match replayed.and_then(|replayed| replayed.flavors.clone()) {
+            Some(flavors) => flavors,
+            None => sample_flavors(&self.flavors, self.seed, &job.path),
+        };
+        let species = self.species_hint(job)?;
+        let request = self.block_request(job, format, schema, &flavors, species.as_deref())?;
+        // Flavors are sampled per run, the prompt without them tells themes apart across runs
+        let theme_hash = digest(&build_prompt(
+            job,
+            self.lore_for(&job.lore)?,
+            format,
+            &[],
+            species.as_deref(),
+            &[],
+        ));
+        let (raw, mut names, truncated) =
+            match self.call(&request, |raw| parse_answer(raw, format)).await? {
+                Reply::Answer {
+                    raw,
+                    parsed,
+                    truncated,
+                } => (raw, parsed, truncated),
+                Reply::Interrupted => return Ok(Reply::Interrupted),
+                Reply::Failed => return Ok(Reply::Failed),
+            };
+        println!(
+            "[AI] '{}' generated with max_tokens {}",
+            key.as_str(),
+            self.max_tokens(&job.model)
+        );
+        // JSON ending before its object closes was cut off, ie by the token limit
+        let mut salvaged = 0;
+        if truncated || format != ResponseFormat::Lines && is_cut_off(&raw) {
+            salvaged = names.len();
+            println!(
+                "[Partial] '{}': answer cut off after {} names, asking for more around them",
+                job.path.join("/"),
+                salvaged
+            );
+            // Without more names, the partial names stand on their own
+            if self.spend() {
+                self.continue_answer(job, &request, &flavors, species.as_deref(), &mut names)
+                    .await?;
+            }
+        }
+        // Persist the repaired names so reloads never depend on re-running the repair
+        let block = CachedBlock {
+            raw: serde_json::json!({ "names": names }).to_string(),
+            model: Some(job.model.clone()),
+            timestamp: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .ok()
+                .map(|elapsed| elapsed.as_secs()),
+            prompt_hash: Some(digest(&request.prompt)),
+            theme_hash: Some(theme_hash),
+            raw_stream: self.keep_raw.then(|| raw.clone()),
+            flavors,
+            max_tokens: Some(self.max_tokens(&job.model)),
+            species_hint: species,
+            temperature: request.temperature.or(self.generator.temperature()),
+            prompt_version: Some(PROMPT_VERSION),
+            prefix: Some(job.prefix.clone()),
+            sources: Vec::new(),
+        };
+        self.cache.lock().unwrap().put(key, block)?;
+        Ok(Reply::Answer {
+            raw,
+            parsed: (names, salvaged),
+            truncated,
+        })
+    }
+
+    /// Runs a block's names through its filter chain, from normalizing them to cutting them
+    /// down to its count. With `fill`, the names are more for a block that kept `fill` already:
+    /// the filters comparing names compare them with the kept ones too but only drop the new
+    /// ones, and they are neither balanced nor cut down
+    async fn filter_names(
+        &self,
+        job: &GenerationJob,
+        key: &BlockKey,
+        items: &[NameItem],
+        fill: Option<&[String]>,
+    ) -> Result<Filtered> {
+        let kept = fill.unwrap_or_default();
+        // Stray whitespace and invisible characters would carry over into keys unseen
+        let mut names: Vec<String> = items
+            .iter()
+            .map(|item| normalize_name(item.name()))
+            .filter(|nm| !nm.is_empty())
+            .collect();
+        let generated = names.len();
+        let mut stages = vec![Stage::new("generated", &names)];
+        let mut dropped = BTreeMap::new();
+        if self.ascii_only {
+            names = self.transliterate(&job.path.join("/"), names, &mut dropped);
+        }
+        let mut unsafe_names = Vec::new();
+        names = self.quote(&job.path.join("/"), names, &mut dropped, &mut unsafe_names);
+        let casing = match self.consistency {
+            CasingCheck::Off => None,
+            check => {
+                let report = casing::check(&mut names, check == CasingCheck::Fix);
+                if let Some(report) = &report {
+                    let outliers: Vec<String> = report
+                        .outliers
+                        .iter()
+                        .map(|(name, rewritten)| format!("{} -> {}", name, rewritten))
+                        .collect();
+                    warning!(
+                        "[Casing] '{}': {} names off its {}, {}: {}",
+                        job.path.join("/"),
+                        outliers.len(),
+                        report.scheme.as_str(),
+                        if report.fixed { "fixed" } else { "kept" },
+                        outliers.join(", ")
+                    );
+                }
+                stages.push(Stage::new("casing", &names));
+                report
+            }
+        };
+        let style = self.key_style_for(&job.key_style)?;
+        if !job.seeds.is_empty() {
+            // Augmented blocks never repeat a hand-written entry, by name or by key
+            let before = names.len();
+            names.retain(|name| {
+                let key = style.key(&job.prefix, name);
+                !job.seeds
+                    .iter()
+                    .any(|seed| seed.eq_ignore_ascii_case(name) || *seed == key)
+            });
+            let removed = before - names.len();
+            if removed > 0 {
+                dropped.insert("seeds".to_string(), removed);
+            }
+        }
+        if !job.avoid_initials.is_empty() {
+            let before = names.len();
+            names.retain(|name| {
+                name.chars()
+                    .find(|c| c.is_alphabetic())
+                    .is_none_or(|c| !c.to_uppercase().any(|c| job.avoid_initials.contains(&c)))
+            });
+            let removed = before - names.len();
+            if removed > 0 {
+                warning!(
+                    "[Filter] '{}': dropped {} names with avoided initials",
+                    job.path.join("/"),
+                    removed
+                );
+                dropped.insert("avoid-initials".to_string(), removed);
+            }
+        }
+        if job.rarity == Some(Rarity::High) && !self.common_words.is_empty() {
+            let before = names.len();
+            names.retain(|name| {
+                job.first.iter().any(|pin| pin.eq_ignore_ascii_case(name))
+                    || !self.common_words.is_common(name)
+            });
+            let removed = before - names.len();
+            if removed > 0 {
+                warning!(
+                    "[Filter] '{}': dropped {} common names",
+                    job.path.join("/"),
+                    removed
+                );
+                dropped.insert("common".to_string(), removed);
+            }
+        }
+        stages.push(Stage::new("filters", &names));
+        let mut scores = BTreeMap::new();
+        if let Some(filter) = &job.score_filter {
+            // Scoring only refines the list, a failure keeps every name
+            match self.score_names(job, key, filter, &names).await {
+                Ok(rated) => {
+                    let before = names.len();
+                    let listed: Vec<&String> = job
+                        .first
+                        .iter()
+                        .filter(|pin| names.iter().any(|name| name.eq_ignore_ascii_case(pin)))
+                        .collect();
+                    scores = prune_by_score(&mut names, &rated, filter);
+                    if let Some(pin) = listed
+                        .iter()
+                        .find(|pin| !names.iter().any(|name| name.eq_ignore_ascii_case(pin)))
+                    {
+                        anyhow::bail!(
+                            "'{}' lists '{}' first but its score filter drops it",
+                            job.path.join("/"),
+                            pin
+                        );
+                    }
+                    let removed = before - names.len();
+                    if removed > 0 {
+                        warning!(
+                            "[Filter] '{}': dropped {} low scoring names",
+                            job.path.join("/"),
+                            removed
+                        );
+                        dropped.insert("score-filter".to_string(), removed);
+                    }
+                }
+                Err(e) if e.is::<Unhealthy>() || e.is::<QuotaExhausted>() => return Err(e),
+                Err(e) => warning!(
+                    "[Score Warning] '{}': {:#}, keeping unscored names",
+                    job.path.join("/"),
+                    e
+                ),
+            }
+        }
+        stages.push(Stage::new("score-filter", &names));
+        let mut near_kept = 0;
+        if matches!(
+            self.near_forms,
+            NearFormPolicy::Longer | NearFormPolicy::Shorter
+        ) {
+            names.retain(|name| {
+                let near = kept.iter().any(|other| {
+                    near_forms::is_near_form(other, name) || near_forms::is_near_form(name, other)
+                });
+                near_kept += usize::from(near);
+                !near
+            });
+        }
+        let near_forms = near_forms::resolve(&mut names, self.near_forms);
+        for pair in &near_forms {
+            warning!(
+                "[Near Form] '{}': '{}' / '{}', {}",
+                job.path.join("/"),
+                pair.shorter,
+                pair.longer,
+                match &pair.dropped {
+                    Some(name) => format!("dropped '{}'", name),
+                    None => "kept both".to_string(),
+                }
+            );
+        }
+        let removed = near_kept
+            + near_forms
+                .iter()
+                .filter(|pair| pair.dropped.is_some())
+                .count();
+        if removed > 0 {
+            dropped.insert("near-forms".to_string(), removed);
+        }
+        stages.push(Stage::new("near-forms", &names));
+        let mut synonyms = Vec::new();
+        if let Some(settings) = &self.semantic_dedup
+            && !names.is_empty()
+        {
+            // Like scoring, the pass only refines the list, a failure keeps every name. Kept
+            // names come first, so a new name means the same as one of them rather than the
+            // other way around
+            let mut all: Vec<String> = kept.iter().chain(&names).cloned().collect();
+            match self.embeddings(&all, settings).await {
+                Ok(embeddings) => {
+                    let before = names.len();
+                    synonyms =
+                        semantic::dedup(&mut all, &embeddings, settings.threshold, &job.first);
+                    names = all
+                        .into_iter()
+                        .filter(|name| !kept.contains(name))
+                        .collect();
+                    for cluster in &synonyms {
+                        warning!(
+                            "[Semantic] '{}': kept '{}', dropped {}",
+                            job.path.join("/"),
+                            cluster.kept,
+                            cluster.dropped.join(", ")
+                        );
+                    }
+                    let removed = before - names.len();
+                    if removed > 0 {
+                        dropped.insert("semantic".to_string(), removed);
+                    }
+                }
+                Err(e) if e.is::<Unhealthy>() || e.is::<QuotaExhausted>() => return Err(e),
+                Err(e) => warning!(
+                    "[Semantic Warning] '{}': {:#}, keeping every name",
+                    job.path.join("/"),
+                    e
+                ),
+            }
+        }
+        stages.push(Stage::new("semantic", &names));
+        // Combinations share their words by design, they are never trimmed as clusters
+        if let Some(max_fraction) = self.max_cluster_fraction.filter(|_| job.composed.is_none()) {
+            let seed = self.shuffle_seed.unwrap_or_default() ^ fnv1a(job.path.join("/").as_bytes());
+            let before = names.len();
+            // Kept names count toward their clusters but are never trimmed
+            let mut all: Vec<String> = kept.iter().chain(&names).cloned().collect();
+            let pinned: Vec<String> = job.first.iter().chain(kept).cloned().collect();
+            let trimmed = clusters::trim(&mut all, max_fraction, seed, &pinned);
+            names = all
+                .into_iter()
+                .filter(|name| !kept.contains(name))
+                .collect();
+            let removed = before - names.len();
+            if removed > 0 {
+                let labels: Vec<String> = trimmed.iter().map(Cluster::label).collect();
+                warning!(
+                    "[Clusters] '{}': trimmed {} names sharing {}",
+                    job.path.join("/"),
+                    removed,
+                    labels.join(", ")
+                );
+                dropped.insert("clusters".to_string(), removed);
+            }
+            if removed > 0 && fill.is_none() && self.cluster_action == ClusterAction::Regenerate {
+                match self.diversify(job, key, &names, &trimmed).await {
+                    Ok(more) => {
+                        let mut known: HashSet<String> =
+                            names.iter().map(|name| name.to_lowercase()).collect();
+                        let mut more: Vec<String> = more
+                            .iter()
+                            .map(|name| normalize_name(name))
+                            .filter(|name| {
+                                !name.is_empty()
+                                    && !trimmed.iter().any(|cluster| cluster.contains(name))
+                                    && known.insert(name.to_lowercase())
+                            })
+                            .take(removed)
+                            .collect();
+                        if self.ascii_only {
+                            let mut emptied = BTreeMap::new();
+                            more = self.transliterate(&job.path.join("/"), more, &mut emptied);
+                            for (filter, count) in emptied {
+                                *dropped.entry(filter).or_default() += count;
+                            }
+                        }
+                        more =
+                            self.quote(&job.path.join("/"), more, &mut dropped, &mut unsafe_names);
+                        println!(
+                            "[Clusters] '{}': {} names generated in their place",
+                            job.path.join("/"),
+                            more.len()
+                        );
+                        names.extend(more);
+                    }
+                    Err(e) if e.is::<Unhealthy>() || e.is::<QuotaExhausted>() => return Err(e),
+                    Err(e) => warning!(
+                        "[Clusters Warning] '{}': {:#}, keeping the trimmed names",
+                        job.path.join("/"),
+                        e
+                    ),
+                }
+            }
+        }
+        stages.push(Stage::new("clusters", &names));
+        // Names listed first lead the block whether or not the model came up with them
+        names.retain(|name| !job.first.iter().any(|pin| pin.eq_ignore_ascii_case(name)));
+        let count = job
+            .max_names
+            .filter(|_| fill.is_none())
+            .map(|count| count - job.first.len());
+        let mut style_scores = Vec::new();
+        if let Some(style) = job.style {
+            let before = names.len();
+            let min = job.min_names.unwrap_or(self.min_names);
+            style_scores = euphony::select(
+                &mut names,
+                style,
+                count,
+                min.saturating_sub(job.first.len()),
+            );
+            let removed = before - names.len();
+            if removed > 0 {
+                warning!(
+                    "[Filter] '{}': dropped {} names least {}",
+                    job.path.join("/"),
+                    removed,
+                    style.as_str()
+                );
+                dropped.insert("style".to_string(), removed);
+            }
+        }
+        stages.push(Stage::new("style", &names));
+        let balance = match job.distribution.as_ref().filter(|_| fill.is_none()) {
+            Some(weights) => {
+                // Shared out of the block's count, or as many names as it has
+                let target = weights.targets(count.unwrap_or(names.len()));
+                let balance = self
+                    .balance(
+                        job,
+                        key,
+                        target,
+                        &mut names,
+                        &mut dropped,
+                        &mut unsafe_names,
+                    )
+                    .await?;
+                Some(balance)
+            }
+            None => None,
+        };
+        stages.push(Stage::new("distribution", &names));
+        // The overshoot of an early stop, or a full answer, is cut down to the block's count
+        if let Some(count) = count
+            && names.len() > count
+        {
+            dropped.insert("count".to_string(), names.len() - count);
+            names.truncate(count);
+        }
+        Ok(Filtered {
+            names,
+            generated,
+            stages,
+            dropped,
+            scores,
+            style_scores,
+            near_forms,
+            semantic: synonyms,
+            casing,
+            unsafe_names,
+            distribution: balance,
+        })
+    }
+
+    /// Generates or reads cached names, then applies filtering, ordering, decoration and prefix formatting
+    async fn generate_localized_entries(
+        &self,
+        job: &GenerationJob,
+        key: &BlockKey,
+        schema: Option<&serde_json::Value>,
+    ) -> Result<BlockOutcome> {
+        let started = Instant::now();
+        // Time parked on the quota meanwhile is left out of the block's duration
+        let waited = self.quota_waited();
+        let species = self.species_hint(job)?;
+        // Composed blocks ask for no names, they combine the names of their pools. Promoted
+        // blocks replace their cached names
+        let cached = match job.composed {
+            Some(_) => None,
+            None if !job.examples.is_empty() => None,
+            None => self.cache.lock().unwrap().get(key),
+        };
+        let cached = cached.filter(|block| {
+            let current = block.species_hint == species;
+            if !current {
+                println!(
+                    "[Cache] '{}' was generated for another species class—regenerating",
+                    key.as_str()
+                );
+                return false;
+            }
+            if !same_prefix(block, job) {
+                println!(
+                    "[Cache] '{}' was generated under the prefix '{}', now '{}'—regenerating",
+                    key.as_str(),
+                    block.prefix.as_deref().unwrap_or_default(),
+                    job.prefix
+                );
+                return false;
+            }
+            true
+        });
+        let from_cache = cached.is_some();
+        let mut generated_at = cached.as_ref().and_then(|block| block.timestamp);
+        let mut names = match &job.composed {
+            Some(composed) => Some(composed.iter().cloned().map(NameItem::Plain).collect()),
+            None => cached.and_then(|block| {
+                println!("[Cache] '{}' exists—using cached names", key.as_str());
+                parse_items(&block.raw)
+                    .map_err(|e| warning!("[Gen AI Error]: {:#}", e))
+                    .ok()
+            }),
+        };
+        // keep trying up to MAX_ATTEMPTS, stepping down to simpler formats instead of repeating a failing request
+        let mut attempt = 0;
+        let mut step = 0;
+        let mut budget_exhausted = false;
+        let mut salvaged = 0;
+        let mut truncated = false;
+        while names.is_none() {
+            if attempt == MAX_ATTEMPTS {
+                warning!(
+                    "[Retry] '{}' gave no usable names in {} attempts, leaving the block empty",
+                    job.path.join("/"),
+                    MAX_ATTEMPTS
+                );
+                break;
+            }
+            // Checked before every round, so retries stop as soon as the budget runs out
+            if !self.spend() {
+                println!(
+                    "[Budget] '{}': budget exhausted, leaving the block empty",
+                    job.path.join("/")
+                );
+                budget_exhausted = true;
+                break;
+            }
+            let format = RETRY_FORMATS[step.min(RETRY_FORMATS.len() - 1)];
+            if attempt > 0 {
+                println!(
+                    "[Retry] '{}': attempt {} of {} asking for {}",
+                    job.path.join("/"),
+                    attempt + 1,
+                    MAX_ATTEMPTS,
+                    format.as_str()
+                );
+            }
+            attempt += 1;
+            match self.generate_and_cache(job, key, format, schema).await? {
+                Reply::Answer {
+                    parsed: (items, partial),
+                    truncated: cut,
+                    ..
+                } => {
+                    salvaged = partial;
+                    truncated = cut;
+                    // Taken from the cache, so a replay of the block dates it the same
+                    generated_at = self
+                        .cache
+                        .lock()
+                        .unwrap()
+                        .get(key)
+                        .and_then(|block| block.timestamp);
+                    names = Some(items);
+                }
+                // A broken stream says nothing about the format, the same request goes again
+                Reply::Interrupted => continue,
+                Reply::Failed => {}
+            }
+            step += 1;
+        }
+        let mut items = names.unwrap_or_default();
+        let mut filtered = self.filter_names(job, key, &items, None).await?;
+        // The count is checked against the names the filters kept, names they dropped are
+        // asked for again
+        let mut short_count = false;
+        if self.strict_count
+            && job.composed.is_none()
+            && !budget_exhausted
+            && let Some(count) = job.max_names.map(|count| count - job.first.len())
+        {
+            let mut continuations = 0;
+            while filtered.names.len() < count && continuations < MAX_CONTINUATIONS && self.spend()
+            {
+                continuations += 1;
+                println!(
+                    "[Count] '{}': kept {} of {} names, continuing ({} of {})",
+                    job.path.join("/"),
+                    filtered.names.len(),
+                    count,
+                    continuations,
+                    MAX_CONTINUATIONS
+                );
+                self.continue_block(job, key, schema, &mut items).await?;
+                filtered = self.filter_names(job, key, &items, None).await?;
+            }
+            // The names are cached, so the next run continues from them
+            if filtered.names.len() < count {
+                short_count = true;
+                warning!(
+                    "[Count] '{}' asked for {} names but kept {} after {} continuations",
+                    job.path.join("/"),
+                    count,
+                    filtered.names.len(),
+                    continuations
+                );
+            }
+        }
+        // Custom schemas may pick a name's key themselves
+        let key_stems: HashMap<String, String> = items
+            .iter()
+            .filter_map(|item| Some((normalize_name(item.name()), item.key()?.to_string())))
+            .collect();
+        let Filtered {
+            mut names,
+            generated,
+            mut stages,
+            dropped,
+            scores,
+            style_scores,
+            near_forms,
+            semantic: synonyms,
+            casing,
+            unsafe_names,
+            distribution: balance,
+        } = filtered;
+        let style = self.key_style_for(&job.key_style)?;
+        let sort = match (job.sort, self.shuffle_seed) {
+            (Some(sort), _) => sort,
+            // The run-wide shuffle leaves sequential blocks in their generated order
+            (None, Some(seed)) if !job.sequential => {
+                SortMode::Shuffle(seed ^ fnv1a(job.path.join("/").as_bytes()))
+            }
+            (None, _) => SortMode::Generated,
+        };
+        sort.apply(&mut names);
+        names.splice(0..0, job.first.iter().cloned());
+        stages.push(Stage::new("order", &names));
+        let shuffle_seed = match sort {
+            SortMode::Shuffle(seed) => Some(seed),
+            _ => None,
+        };
+        let diversity = clusters::analyze(&names);
+        let abbreviations = self.abbreviations.as_ref().filter(|_| job.abbreviate);
+        let mut entries = Vec::new();
+        let mut seen_keys: HashMap<String, String> = HashMap::new();
+        let path = job.path.join("/");
+        // Ordinal variants stand in for their base name from here on, keyed like any name
+        let names: Vec<(String, Option<String>)> = names
+            .iter()
+            .flat_map(|name| {
+                let key_stem = key_stems.get(name);
+                match &job.ordinal_expand {
+                    Some(ordinals) => {
+                        let stems = key_stem.map(|stem| ordinals.expand(stem));
+                        ordinals
+                            .expand(name)
+                            .into_iter()
+                            .enumerate()
+                            .map(|(n, variant)| {
+                                (variant, stems.as_ref().map(|stems| stems[n].clone()))
+                            })
+                            .collect()
+                    }
+                    None => vec![(name.clone(), key_stem.cloned())],
+                }
+            })
+            .collect();
+        for (idx, (name, key_stem)) in names.iter().enumerate() {
+            // Keys derive from the decorated form so they never collide with the bare name elsewhere
+            let name = match &job.decoration {
+                Some(decoration) if self.ascii_only => to_ascii(&decoration.apply(name, idx)),
+                Some(decoration) => decoration.apply(name, idx),
+                None => name.clone(),
+            };
+            // Abbreviating blocks key on the compact form but display the spelled-out one
+            let (name, key_source) = match abbreviations {
+                Some(abbreviations) => {
+                    let display = abbreviations.expand(&name);
+                    let compact = abbreviations.compact(&display);
+                    (display, compact)
+                }
+                None => (name.clone(), name),
+            };
+            // Names an earlier run emitted keep their key, then names a previous version of the
+            // mod localised keep its key
+            let mapped = self.key_map.key_for(&path, &name);
+            let base = match mapped.or_else(|| self.key_compat.key_for(&name)) {
+                Some(key) => key.to_string(),
+                None => style.key(&job.prefix, key_stem.as_ref().unwrap_or(&key_source)),
+            };
+            // New names stay off the keys the map holds for other names
+            let claimed = |key: &str| {
+                mapped.is_none() && self.key_map.claimed(key, &name, self.reuse_retired_keys)
+            };
+            // Distinct names compacting to one key get numbered, true duplicates are dropped
+            let mut key = base.clone();
+            if style.hash == HashKeys::Fallback
+                && mapped.is_none()
+                && (seen_keys
+                    .get(&key)
+                    .is_some_and(|existing| *existing != name)
+                    || claimed(&key))
+            {
+                key = style.hashed(&job.prefix, &name);
+            }
+            let mut n = 1;
+            loop {
+                let taken = seen_keys
+                    .get(&key)
+                    .is_some_and(|existing| *existing != name && abbreviations.is_some());
+                if !taken && !claimed(&key) {
+                    break;
+                }
+                n += 1;
+                key = style.numbered(&base, n);
+            }
+            if !seen_keys.contains_key(&key) {
+                seen_keys.insert(key.clone(), name.clone());
+                entries.push((key, name));
+            }
+        }
+        let keys: Vec<String> = entries
+            .iter()
+            .map(|(key, name)| format!("{}: {}", key, name))
+            .collect();
+        stages.push(Stage::new("keys", &keys));
+        Ok(BlockOutcome {
+            entries,
+            cached: from_cache,
+            generated,
+            dropped,
+            scores,
+            style_scores,
+            near_forms,
+            semantic: synonyms,
+            casing,
+            unsafe_names,
+            diversity,
+            distribution: balance,
+            stages,
+            shuffle_seed,
+            elapsed_ms: started
+                .elapsed()
+                .saturating_sub(self.quota_waited() - waited)
+                .as_millis() as u64,
+            budget_exhausted,
+            salvaged,
+            truncated,
+            generated_at,
+            short_count,
+        })
+    }
+}
+
+/// Parses a block's answer in the format it was asked for
+fn parse_answer(raw: &str, format: ResponseFormat) -> Result<Vec<NameItem>> {
+    match format {
+        ResponseFormat::Lines => {
+            parse_lines(raw).map(|names| names.into_iter().map(NameItem::Plain).collect())
+        }
+        _ => parse_items(raw),
+    }
+}
+
+/// Keeps the names passing the filter in their original order, unrated names count as 0.
+/// Returns how many candidates received each score
+fn prune_by_score(
+    names: &mut Vec<String>,
+    rated: &BTreeMap<String, u8>,
+    filter: &ScoreFilter,
+) -> BTreeMap<u8, usize> {
+    // Models tend to echo names with different casing
+    let rated: HashMap<String, u8> = rated
+        .iter()
+        .map(|(name, score)| (name.to_lowercase(), *score))
+        .collect();
+    let score_of = |name: &String| rated.get(&name.to_lowercase()).copied().unwrap_or(0);
+    let mut distribution = BTreeMap::new();
+    for name in names.iter() {
+        let score = score_of(name);
+        if score > 0 {
+            *distribution.entry(score).or_default() += 1;
+        }
+    }
+    if let Some(min) = filter.min {
+        names.retain(|name| score_of(name) >= min);
+    }
+    if let Some(top) = filter.top
+        && names.len() > top
+    {
+        let mut ranked: Vec<usize> = (0..names.len()).collect();
+        ranked.sort_by_key(|&idx| std::cmp::Reverse(score_of(&names[idx])));
+        let keep: HashSet<usize> = ranked.into_iter().take(top).collect();
+        let mut idx = 0;
+        names.retain(|_| {
+            idx += 1;
+            keep.contains(&(idx - 1))
+        });
+    }
+    distribution
+}
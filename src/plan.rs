@@ -0,0 +1,191 @@
+//! The planned generation of a run, rendered as a tree without sending any request.
+//!
+//! ```
+//! use stellaris_name_gen::plan::render_tree;
+//! use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+//!
+//! let structure = r#"
+//! NAME = {
+//!     ## prefix: VOID_
+//!     ship_names = {
+//!         ## Warship names
+//!         military = {
+//!         }
+//!         ## Civilian ship names
+//!         ## min: 25
+//!         civilian = {
+//!         }
+//!     }
+//!     motto = "{{generate: imperial motto}}"
+//! }
+//! "#;
+//! let generation = Generation::builder()
+//!     .structure(structure)
+//!     .lore("")
+//!     .model("local-model")
+//!     .generator(MockGenerator::new(["Vex"]))
+//!     .cache(InMemoryCache::new())
+//!     .build()
+//!     .unwrap();
+//! let plan = generation.plan().unwrap();
+//! assert_eq!(plan.len(), 3);
+//! assert!(!plan[0].cached);
+//! assert_eq!(
+//!     render_tree(&plan),
+//!     "\
+//! NAME
+//! ├── ship_names
+//! │   ├── military  \"Warship names\"  prefix=VOID_ model=local-model min=10 format=json schema  [generate]
+//! │   └── civilian  \"Civilian ship names\"  prefix=VOID_ model=local-model min=25 format=json schema  [generate]
+//! └── line 13  \"imperial motto\"  model=local-model format=value  [generate]
+//! "
+//! );
+//! ```
+//!
+//! A structure whose blocks all hold hand-written entries or lack a theme has nothing to
+//! generate, which is told apart before a run is set up:
+//!
+//! ```
+//! use stellaris_name_gen::plan::generated_blocks;
+//!
+//! assert_eq!(generated_blocks("NAME = {\n    # Ship names\n    ships = {\n    }\n    motto = \"{{generate: motto}}\"\n}\n").unwrap(), 2);
+//! assert_eq!(generated_blocks("NAME = {\n    ships = {\n    }\n}\n").unwrap(), 0);
+//! assert_eq!(generated_blocks("NAME = {\n    # Ship names\n    ships = {\n        MY_SHIP,\n    }\n}\n").unwrap(), 0);
+//! assert_eq!(generated_blocks(" \n").unwrap(), 0);
+//! ```
+
+use crate::pipeline::{AI_MODEL, INDENT};
+use crate::structure::{Segment, parse_structure};
+use anyhow::Result;
+
+/// A block or inline placeholder the run would generate
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlannedBlock {
+    /// Block path, inline placeholders end in `line <n>`
+    pub path: Vec<String>,
+    pub theme: String,
+    pub prefix: String,
+    pub model: String,
+    /// Fewest names the block must keep, `None` for inline placeholders
+    pub min_names: Option<usize>,
+    /// Names the block needs, from `# count:`
+    pub count: Option<usize>,
+    /// Period the names belong to, from `# era:`
+    pub era: Option<String>,
+    /// Shape of the requested answer, ie `json schema` or the path of a custom schema
+    pub format: String,
+    /// Whether the answer is already cached, so no request would be sent
+    pub cached: bool,
+}
+
+impl PlannedBlock {
+    /// The block's settings on one line
+    fn describe(&self) -> String {
+        let theme = self.theme.lines().next().unwrap_or_default();
+        let more = if self.theme.lines().nth(1).is_some() {
+            "…"
+        } else {
+            ""
+        };
+        let mut settings = Vec::new();
+        if !self.prefix.is_empty() {
+            settings.push(format!("prefix={}", self.prefix));
+        }
+        settings.push(format!("model={}", self.model));
+        if let Some(min) = self.min_names {
+            settings.push(format!("min={}", min));
+        }
+        if let Some(count) = self.count {
+            settings.push(format!("count={}", count));
+        }
+        if let Some(era) = &self.era {
+            settings.push(format!("era={}", era));
+        }
+        settings.push(format!("format={}", self.format));
+        format!(
+            "\"{}{}\"  {}  [{}]",
+            theme,
+            more,
+            settings.join(" "),
+            if self.cached { "cached" } else { "generate" }
+        )
+    }
+}
+
+/// A level of the rendered tree
+#[derive(Default)]
+struct Node<'a> {
+    name: &'a str,
+    block: Option<&'a PlannedBlock>,
+    children: Vec<Node<'a>>,
+}
+
+impl<'a> Node<'a> {
+    fn insert(&mut self, path: &'a [String], block: &'a PlannedBlock) {
+        let Some((first, rest)) = path.split_first() else {
+            self.block = Some(block);
+            return;
+        };
+        let idx = match self.children.iter().position(|child| child.name == first) {
+            Some(idx) => idx,
+            None => {
+                self.children.push(Node {
+                    name: first,
+                    ..Node::default()
+                });
+                self.children.len() - 1
+            }
+        };
+        self.children[idx].insert(rest, block);
+    }
+
+    fn render(&self, indent: &str, out: &mut String) {
+        for (idx, child) in self.children.iter().enumerate() {
+            let last = idx + 1 == self.children.len();
+            out.push_str(indent);
+            out.push_str(if last { "└── " } else { "├── " });
+            out.push_str(child.name);
+            if let Some(block) = child.block {
+                out.push_str("  ");
+                out.push_str(&block.describe());
+            }
+            out.push('\n');
+            child.render(
+                &format!("{}{}", indent, if last { "    " } else { "│   " }),
+                out,
+            );
+        }
+    }
+}
+
+/// Number of blocks and inline placeholders a structure generates, read without a run
+pub fn generated_blocks(structure: &str) -> Result<usize> {
+    let (output, jobs) = parse_structure(structure, AI_MODEL, INDENT)?;
+    let placeholders: usize = output
+        .iter()
+        .map(|segment| match segment {
+            Segment::Inline(line) => line.placeholders.len(),
+            _ => 0,
+        })
+        .sum();
+    Ok(jobs.len() + placeholders)
+}
+
+/// Renders planned blocks as an indented tree following their paths, in plan order
+pub fn render_tree(blocks: &[PlannedBlock]) -> String {
+    let mut root = Node::default();
+    for block in blocks {
+        root.insert(&block.path, block);
+    }
+    let mut out = String::new();
+    for top in &root.children {
+        out.push_str(top.name);
+        if let Some(block) = top.block {
+            out.push_str("  ");
+            out.push_str(&block.describe());
+        }
+        out.push('\n');
+        top.render("", &mut out);
+    }
+    out
+}
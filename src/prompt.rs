@@ -0,0 +1,209 @@
+use crate::clusters::{Cluster, ClusterKind};
+use crate::generator::ResponseFormat;
+use crate::rarity::Rarity;
+use crate::structure::GenerationJob;
+use std::collections::BTreeMap;
+
+/// Builds the name generation prompt for a block
+pub(crate) fn build_prompt(
+    job: &GenerationJob,
+    lore: &str,
+    format: ResponseFormat,
+    flavors: &[String],
+    species: Option<&str>,
+    avoid: &[String],
+) -> String {
+    let mut rules = String::new();
+    match format {
+        ResponseFormat::JsonNames => {
+            rules.push_str("- Answer only with a JSON object of the form {\"names\": [\"...\"]}\n")
+        }
+        ResponseFormat::Lines => {
+            rules.push_str("- Answer with one name per line and nothing else\n")
+        }
+        ResponseFormat::Names if job.schema.is_some() => rules.push_str(
+            "- Give every entry of `names` its `name` and fill in the other fields of the schema\n",
+        ),
+        ResponseFormat::Names
+        | ResponseFormat::Scores
+        | ResponseFormat::Value
+        | ResponseFormat::Translations => {}
+    }
+    if !job.avoid_initials.is_empty() {
+        let letters: Vec<String> = job.avoid_initials.iter().map(char::to_string).collect();
+        rules.push_str(&format!(
+            "- Do not start any name with the letters {}\n",
+            letters.join(", ")
+        ));
+    }
+    if !job.seeds.is_empty() {
+        rules.push_str(&format!(
+            "- Complement these existing names in the same spirit, without repeating any: {}\n",
+            job.seeds.join(", ")
+        ));
+    }
+    if !job.examples.is_empty() {
+        rules.push_str(&format!(
+            "- These draft names show the style wanted, keep the best of them and improve on the rest: {}\n",
+            job.examples.join(", ")
+        ));
+    }
+    if !avoid.is_empty() {
+        rules.push_str(&format!(
+            "- These names are already taken, come up with different ones: {}\n",
+            avoid.join(", ")
+        ));
+    }
+    if let Some(era) = job.era {
+        rules.push_str(&format!("- Make the names fit {}\n", era.describe()));
+    }
+    if let Some(species) = species {
+        rules.push_str(&format!("- Suit the names to the species, {}\n", species));
+    }
+    if job.rarity == Some(Rarity::High) {
+        rules.push_str(
+            "- Favor unusual, evocative names, avoid everyday words and names that sound mundane or common\n",
+        );
+    }
+    for flavor in flavors {
+        rules.push_str(&format!("- Style: {}\n", flavor));
+    }
+    format!(
+        r#"
+- Prefer to use Latinization of languages (a-z alphabet) and **do not under accents**
+- Come up with **as many** possible names
+- Avoid duplicates
+{}Come up with as many {} names as possible {}
+"#,
+        rules,
+        job.theme,
+        grounding(lore)
+    )
+}
+
+/// Builds the prompt asking for names in place of the ones trimmed from a block's oversized
+/// clusters, forbidding their stems, see [`crate::clusters`]
+pub(crate) fn build_diversify_prompt(
+    job: &GenerationJob,
+    lore: &str,
+    species: Option<&str>,
+    kept: &[String],
+    trimmed: &[Cluster],
+) -> String {
+    let mut rules = String::new();
+    let stems: Vec<String> = trimmed
+        .iter()
+        .filter(|cluster| cluster.kind != ClusterKind::Neighbors)
+        .map(Cluster::label)
+        .collect();
+    if !stems.is_empty() {
+        rules.push_str(&format!(
+            "\n- The names so far lean on a few sounds, do not start or end any name like these, the dash standing for the rest of the name: {}",
+            stems.join(", ")
+        ));
+    }
+    let close: Vec<&str> = trimmed
+        .iter()
+        .filter(|cluster| cluster.kind == ClusterKind::Neighbors)
+        .map(|cluster| cluster.stem.as_str())
+        .collect();
+    if !close.is_empty() {
+        rules.push_str(&format!(
+            "\n- Do not come up with names resembling any of: {}",
+            close.join(", ")
+        ));
+    }
+    format!(
+        "{}{}",
+        rules,
+        build_prompt(job, lore, ResponseFormat::JsonNames, &[], species, kept)
+    )
+}
+
+/// Builds the prompt asking for names starting with the letters a `# distribution:` block is
+/// short of, `wanted` holding how many of each
+pub(crate) fn build_balance_prompt(
+    job: &GenerationJob,
+    lore: &str,
+    species: Option<&str>,
+    kept: &[String],
+    wanted: &BTreeMap<char, usize>,
+) -> String {
+    let letters: Vec<String> = wanted
+        .iter()
+        .map(|(letter, count)| format!("{} starting with {}", count, letter))
+        .collect();
+    format!(
+        "\n- Every name must start with one of these letters, as many names as listed: {}{}",
+        letters.join(", "),
+        build_prompt(job, lore, ResponseFormat::JsonNames, &[], species, kept)
+    )
+}
+
+/// How the prompts ground the names, in the lore or, without any, in nothing
+fn grounding(lore: &str) -> String {
+    if lore.trim().is_empty() {
+        "inventing them freely, there is no lore to follow".to_string()
+    } else {
+        format!("using the lore:\n{}", lore)
+    }
+}
+
+/// Builds the prompt asking a model to rate a block's candidate names
+pub(crate) fn build_score_prompt(job: &GenerationJob, lore: &str, names: &[String]) -> String {
+    let list: Vec<String> = names.iter().map(|name| format!("- {}", name)).collect();
+    let (fits, lore) = if lore.trim().is_empty() {
+        ("", String::new())
+    } else {
+        (" and the lore", format!("Lore:\n{}\n", lore))
+    };
+    format!(
+        r#"
+- Rate each name from 1 to 5 for how well it fits {} names{}, 5 being a perfect fit
+- Use every name exactly as written as the key of its score
+Names:
+{}
+{}"#,
+        job.theme,
+        fits,
+        list.join("\n"),
+        lore
+    )
+}
+
+/// Builds the prompt translating a block's localisation, one `KEY: value` line per entry
+pub(crate) fn build_translation_prompt(
+    theme: &str,
+    language: &str,
+    entries: &[(String, String)],
+) -> String {
+    let list: Vec<String> = entries
+        .iter()
+        .map(|(key, value)| format!("{}: {}", key, value))
+        .collect();
+    format!(
+        r#"
+- Translate every value into {}, they are {} in a science fiction game
+- Keep invented words and proper nouns as they are, only translate descriptive words
+- Use every key exactly as written as the key of its translation
+Values:
+{}
+"#,
+        language,
+        theme,
+        list.join("\n")
+    )
+}
+
+/// Builds the prompt for the single value of an inline `{{generate: ...}}` placeholder
+pub(crate) fn build_value_prompt(theme: &str, lore: &str) -> String {
+    format!(
+        r#"
+- Prefer to use Latinization of languages (a-z alphabet) and **do not under accents**
+- Come up with exactly one value
+Come up with a single {} {}
+"#,
+        theme,
+        grounding(lore)
+    )
+}
@@ -0,0 +1,256 @@
+//! A run's provenance log, `run.json`: everything needed to understand or reproduce a run.
+//!
+//! The layout is versioned by [`PROVENANCE_VERSION`], fields are only ever added within a
+//! version. A failed run still gets a log with its settings and error.
+//!
+//! ```
+//! use std::time::SystemTime;
+//! use stellaris_name_gen::provenance::{PROVENANCE_VERSION, RunStatus};
+//! use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+//!
+//! let generation = Generation::builder()
+//!     .structure("NAME = {\n    ## Ship names\n    ships = {\n    }\n}\n")
+//!     .lore("An ancient empire")
+//!     .generator(MockGenerator::new(["Vex", "Ember"]))
+//!     .seed(7)
+//!     .cache(InMemoryCache::new())
+//!     .build()
+//!     .unwrap();
+//! let started = SystemTime::now();
+//! let result = futures::executor::block_on(generation.run());
+//! let log = generation.provenance(started, result.as_ref());
+//! assert_eq!(log.version, PROVENANCE_VERSION);
+//! assert_eq!(log.status, RunStatus::Completed);
+//! assert_eq!(log.settings.seed, 7);
+//! assert_eq!((log.cache.hits, log.cache.misses), (0, 1));
+//! assert_eq!(log.blocks[0].path, "NAME/ships");
+//!
+//! let json = serde_json::to_value(&log).unwrap();
+//! assert_eq!(json["version"], 1);
+//! assert_eq!(json["blocks"][0]["flavor_seed"], 7);
+//!
+//! // A failing run keeps its error
+//! let broken = Generation::builder()
+//!     .structure("NAME = {\n    # lore: missing\n    # Ship names\n    ships = {\n    }\n}\n")
+//!     .lore("")
+//!     .generator(MockGenerator::new(["Vex"]))
+//!     .cache(InMemoryCache::new())
+//!     .build()
+//!     .unwrap();
+//! let result = futures::executor::block_on(broken.run());
+//! let log = broken.provenance(started, result.as_ref());
+//! assert_eq!(log.status, RunStatus::Failed);
+//! assert!(log.error.unwrap().contains("Unknown lore profile 'missing'"));
+//! ```
+//!
+//! With `provenance_comments`, every generated block of the name list opens with a comment the
+//! game ignores. Its date is when the names were generated, so re-runs from the cache emit the
+//! same comment, and comments of an earlier output found in the structure are replaced:
+//!
+//! ```
+//! use stellaris_name_gen::provenance::block_comment;
+//! use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+//!
+//! assert_eq!(
+//!     block_comment(Some(1717200000), "insectoid warship names", "gemini-2.5-flash", 214),
+//!     "# generated 2024-06-01, theme: \"insectoid warship names\", model: gemini-2.5-flash, 214 names"
+//! );
+//!
+//! let structure = "\
+//! NAME = {
+//!     ## Ship names
+//!     ## augment-inline
+//!     ships = {
+//!         ## generated 2020-01-01, theme: \"Ship names\", model: old-model, 3 names
+//!         \"Dauntless\"
+//!     }
+//! }
+//! ";
+//! let generation = Generation::builder()
+//!     .structure(structure)
+//!     .lore("")
+//!     .model("local-model")
+//!     .generator(MockGenerator::new(["Vex", "Ember"]))
+//!     .provenance_comments(true)
+//!     .cache(InMemoryCache::new())
+//!     .build()
+//!     .unwrap();
+//! let first = futures::executor::block_on(generation.run()).unwrap().name_list;
+//! assert_eq!(first.matches("# generated").count(), 1);
+//! assert!(first.contains("theme: \"Ship names\", model: local-model, 2 names\n"));
+//! let again = futures::executor::block_on(generation.run()).unwrap().name_list;
+//! assert_eq!(again, first);
+//! ```
+
+use crate::charset::LocCharset;
+use crate::clusters::ClusterAction;
+use crate::generator::TokenUsage;
+use crate::keys::KeyStyle;
+use crate::near_forms::NearFormPolicy;
+use crate::quoting::QuoteMode;
+use crate::rarity::Rarity;
+use crate::semantic::SemanticDedup;
+use crate::unique::UniqueScope;
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::Path;
+
+/// Version of the `run.json` layout
+pub const PROVENANCE_VERSION: u32 = 1;
+
+/// How the run ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RunStatus {
+    Completed,
+    Failed,
+}
+
+/// The `run.json` document
+#[derive(Debug, Clone, Serialize)]
+pub struct Provenance {
+    pub version: u32,
+    /// Version of the generator that produced the run
+    pub tool_version: String,
+    /// Unix time the run started at, in seconds
+    pub started_at: u64,
+    pub duration_ms: u64,
+    pub status: RunStatus,
+    /// Error the run failed with, if any
+    pub error: Option<String>,
+    pub settings: RunSettings,
+    /// Every model the run resolved to, sorted
+    pub models: Vec<String>,
+    /// Sampling temperature of the requests, `None` if the generator has none
+    pub temperature: Option<f64>,
+    pub lore: LoreDigests,
+    pub cache: CacheStats,
+    /// Tokens spent, `None` if the generator does not report them
+    pub usage: Option<TokenUsage>,
+    pub blocks: Vec<BlockProvenance>,
+}
+
+/// The run's settings after the config file, flags and defaults were applied
+#[derive(Debug, Clone, Serialize)]
+pub struct RunSettings {
+    pub model: String,
+    pub concurrency: usize,
+    pub max_concurrent_models: Option<usize>,
+    pub model_limits: BTreeMap<String, usize>,
+    pub strict: bool,
+    pub keep_raw: bool,
+    pub min_names: usize,
+    pub unique_scope: UniqueScope,
+    pub near_forms: NearFormPolicy,
+    /// Share of a block's names its largest cluster may hold, see [`crate::clusters`]
+    pub max_cluster_fraction: Option<f64>,
+    pub cluster_action: ClusterAction,
+    /// Embedding model and threshold of the semantic dedup, see [`crate::semantic`]
+    pub semantic_dedup: Option<SemanticDedup>,
+    /// Rarity of blocks without a `# rarity=`
+    pub rarity: Rarity,
+    pub key_style: KeyStyle,
+    pub ascii_only: bool,
+    pub quotes: QuoteMode,
+    pub loc_charset: LocCharset,
+    pub strip_loc_charset: bool,
+    /// Names requested on top of a block's `# count:`, `None` without early stops
+    pub early_stop_margin: Option<usize>,
+    pub categories: BTreeMap<String, String>,
+    /// Seed of the flavor sampling
+    pub seed: u64,
+    pub shuffle_seed: Option<u64>,
+    /// Flavor pool, empty when flavors are disabled
+    pub flavors: Vec<String>,
+}
+
+/// Digests of the lore the names were generated from
+#[derive(Debug, Clone, Serialize)]
+pub struct LoreDigests {
+    pub default: String,
+    /// By profile name
+    pub profiles: BTreeMap<String, String>,
+}
+
+/// Blocks read from the cache and blocks generated
+#[derive(Debug, Clone, Copy, Default, Serialize)]
+pub struct CacheStats {
+    pub hits: usize,
+    pub misses: usize,
+}
+
+/// How a single block was produced
+#[derive(Debug, Clone, Serialize)]
+pub struct BlockProvenance {
+    pub path: String,
+    pub model: String,
+    pub cached: bool,
+    /// Seed the block's flavors were sampled with
+    pub flavor_seed: u64,
+    /// Seed the block's names were shuffled with, `None` if they were not shuffled by the run
+    pub shuffle_seed: Option<u64>,
+    pub generated: usize,
+    pub names: usize,
+    pub failed: bool,
+    /// Time spent generating or loading the block
+    pub elapsed_ms: u64,
+    /// Numbered variants every name was emitted as, ie `roman:5`, `names` counting the variants
+    pub ordinal_expand: Option<String>,
+}
+
+impl Provenance {
+    /// Writes the log as pretty-printed JSON
+    pub fn write(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self).context("Failed to serialize the run log")?;
+        fs::write(path, json).with_context(|| format!("Failed to write {}", path.display()))
+    }
+}
+
+/// The comment heading a generated block in the name list, ie
+/// `# generated 2024-06-01, theme: "insectoid warship names", model: gemini-2.5-flash, 214 names`.
+/// `generated_at` is a unix time in seconds, the date is left out when unknown
+pub fn block_comment(generated_at: Option<u64>, theme: &str, model: &str, names: usize) -> String {
+    let theme: Vec<&str> = theme.lines().map(str::trim).collect();
+    format!(
+        "# generated{}, theme: \"{}\", model: {}, {} names",
+        generated_at
+            .map(|secs| format!(" {}", date(secs)))
+            .unwrap_or_default(),
+        theme.join(" ").replace('"', "'"),
+        model,
+        names
+    )
+}
+
+/// Whether a comment, without its `#`, is a [`block_comment`] of an earlier output
+pub(crate) fn is_block_comment(comment: &str) -> bool {
+    comment.starts_with("generated") && comment.contains(", theme: \"")
+}
+
+/// The UTC `HH:MM` time of a unix time in seconds
+pub(crate) fn time_of_day(secs: u64) -> String {
+    let minutes = secs % 86_400 / 60;
+    format!("{:02}:{:02}", minutes / 60, minutes % 60)
+}
+
+/// The UTC `YYYY-MM-DD` date of a unix time in seconds
+pub(crate) fn date(secs: u64) -> String {
+    // Days since 1970-01-01 to a civil date, after Howard Hinnant's `civil_from_days`
+    let days = secs / 86_400 + 719_468;
+    let era = days / 146_097;
+    let day_of_era = days % 146_097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1_460 + day_of_era / 36_524 - day_of_era / 146_096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
@@ -0,0 +1,212 @@
+use anyhow::Result;
+use genai::adapter::AdapterKind;
+use genai::resolver::{AuthData, AuthResolver};
+use genai::{Client as GenAiClient, ModelIden};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fmt;
+
+/// Provider names accepted under `[providers.<name>]`
+const PROVIDERS: [AdapterKind; 8] = [
+    AdapterKind::Gemini,
+    AdapterKind::OpenAI,
+    AdapterKind::Anthropic,
+    AdapterKind::Ollama,
+    AdapterKind::Groq,
+    AdapterKind::Cohere,
+    AdapterKind::Xai,
+    AdapterKind::DeepSeek,
+];
+
+/// `[providers.<name>]` section of the config file
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct ProviderConfig {
+    /// Environment variable holding the API key, instead of the provider's conventional one
+    pub api_key_env: Option<String>,
+    /// Base URL of the provider's OpenAI compatible API embedding requests go to, instead of
+    /// the provider's own, ie `http://gpu-box:11434/v1/`
+    pub endpoint: Option<String>,
+}
+
+/// Where a model's credentials come from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub model: String,
+    pub provider: AdapterKind,
+    /// Variable the key is read from, `None` for keyless providers such as ollama
+    pub key_env: Option<String>,
+    /// Whether `key_env` was set in the config rather than being the provider's default
+    pub configured: bool,
+}
+
+impl fmt::Display for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "'{}' -> {}", self.model, self.provider.as_lower_str())?;
+        match &self.key_env {
+            Some(var) if self.configured => write!(f, ", key from {} (config, redacted)", var),
+            Some(var) => write!(f, ", key from {} (default, redacted)", var),
+            None => write!(f, ", no key required"),
+        }
+    }
+}
+
+/// Rejects `[providers.<name>]` sections that don't name a known provider
+pub fn validate(providers: &HashMap<String, ProviderConfig>) -> Result<()> {
+    for name in providers.keys() {
+        if !PROVIDERS.iter().any(|kind| kind.as_lower_str() == name) {
+            let known: Vec<&str> = PROVIDERS.iter().map(|kind| kind.as_lower_str()).collect();
+            anyhow::bail!(
+                "Unknown provider '[providers.{}]', expected one of {}",
+                name,
+                known.join(", ")
+            );
+        }
+    }
+    Ok(())
+}
+
+/// Resolves which provider and key variable `model` uses, failing if that variable isn't set.
+/// `env` looks up environment variables, so the resolution can be checked without touching the process env
+///
+/// ```
+/// use std::collections::HashMap;
+/// use stellaris_name_gen::providers::{ProviderConfig, resolve};
+///
+/// let mut providers = HashMap::new();
+/// providers.insert(
+///     "gemini".to_string(),
+///     ProviderConfig { api_key_env: Some("MY_GEMINI_KEY".to_string()), ..Default::default() },
+/// );
+/// let env = |var: &str| (var == "MY_GEMINI_KEY").then(|| "secret".to_string());
+///
+/// let gemini = resolve("gemini-2.0-flash", &providers, env).unwrap();
+/// assert_eq!(gemini.key_env.as_deref(), Some("MY_GEMINI_KEY"));
+/// assert!(!gemini.to_string().contains("secret"));
+/// // Local models need no key
+/// assert_eq!(resolve("gemma3:27b", &providers, env).unwrap().key_env, None);
+/// // The error names the variable that was expected
+/// let err = resolve("gpt-4o", &providers, env).unwrap_err();
+/// assert!(err.to_string().contains("OPENAI_API_KEY"));
+/// ```
+pub fn resolve(
+    model: &str,
+    providers: &HashMap<String, ProviderConfig>,
+    env: impl Fn(&str) -> Option<String>,
+) -> Result<Credentials> {
+    resolve_for(model, AdapterKind::from_model(model)?, providers, env)
+}
+
+/// Resolves the provider and key variable of an embedding model, see [`crate::semantic`].
+/// OpenAI's embedding models are named unlike its chat models
+///
+/// ```
+/// use std::collections::HashMap;
+/// use stellaris_name_gen::providers::resolve_embedding;
+///
+/// let env = |var: &str| (var == "OPENAI_API_KEY").then(|| "secret".to_string());
+/// let openai = resolve_embedding("text-embedding-3-small", &HashMap::new(), env).unwrap();
+/// assert_eq!(
+///     openai.to_string(),
+///     "'text-embedding-3-small' -> openai, key from OPENAI_API_KEY (default, redacted)"
+/// );
+/// // Local embedding models need no key
+/// assert_eq!(resolve_embedding("nomic-embed-text", &HashMap::new(), env).unwrap().key_env, None);
+/// ```
+pub fn resolve_embedding(
+    model: &str,
+    providers: &HashMap<String, ProviderConfig>,
+    env: impl Fn(&str) -> Option<String>,
+) -> Result<Credentials> {
+    let provider = if model.starts_with("text-embedding-") {
+        AdapterKind::OpenAI
+    } else {
+        AdapterKind::from_model(model)?
+    };
+    resolve_for(model, provider, providers, env)
+}
+
+/// URL of the embeddings endpoint of an embedding model's provider, from its configured
+/// `endpoint` or the provider's own. Only OpenAI compatible providers embed
+///
+/// ```
+/// use genai::adapter::AdapterKind;
+/// use std::collections::HashMap;
+/// use stellaris_name_gen::providers::{ProviderConfig, embeddings_url};
+///
+/// let mut providers = HashMap::new();
+/// assert_eq!(
+///     embeddings_url(AdapterKind::OpenAI, &providers).unwrap(),
+///     "https://api.openai.com/v1/embeddings"
+/// );
+/// providers.insert(
+///     "ollama".to_string(),
+///     ProviderConfig { endpoint: Some("http://gpu-box:11434/v1/".to_string()), ..Default::default() },
+/// );
+/// assert_eq!(
+///     embeddings_url(AdapterKind::Ollama, &providers).unwrap(),
+///     "http://gpu-box:11434/v1/embeddings"
+/// );
+/// assert!(embeddings_url(AdapterKind::Anthropic, &providers).is_err());
+/// ```
+pub fn embeddings_url(
+    provider: AdapterKind,
+    providers: &HashMap<String, ProviderConfig>,
+) -> Result<String> {
+    let configured = providers
+        .get(provider.as_lower_str())
+        .and_then(|config| config.endpoint.clone());
+    let base = match (configured, provider) {
+        (Some(endpoint), _) => endpoint,
+        (None, AdapterKind::OpenAI) => "https://api.openai.com/v1/".to_string(),
+        (None, AdapterKind::Ollama) => "http://localhost:11434/v1/".to_string(),
+        (None, other) => anyhow::bail!(
+            "{} has no OpenAI compatible embeddings endpoint, set `endpoint` under [providers.{}]",
+            other.as_lower_str(),
+            other.as_lower_str()
+        ),
+    };
+    Ok(format!("{}/embeddings", base.trim_end_matches('/')))
+}
+
+fn resolve_for(
+    model: &str,
+    provider: AdapterKind,
+    providers: &HashMap<String, ProviderConfig>,
+    env: impl Fn(&str) -> Option<String>,
+) -> Result<Credentials> {
+    let configured = providers
+        .get(provider.as_lower_str())
+        .and_then(|config| config.api_key_env.clone());
+    let key_env = configured
+        .clone()
+        .or_else(|| provider.default_key_env_name().map(str::to_string));
+    if let Some(var) = &key_env
+        && env(var).is_none_or(|key| key.trim().is_empty())
+    {
+        anyhow::bail!(
+            "Model '{}' uses provider {} but no API key is set, expected the {} environment variable",
+            model,
+            provider.as_lower_str(),
+            var
+        );
+    }
+    Ok(Credentials {
+        model: model.to_string(),
+        provider,
+        key_env,
+        configured: configured.is_some(),
+    })
+}
+
+/// Builds a genai client reading each provider's key from its configured variable
+pub fn client(providers: &HashMap<String, ProviderConfig>) -> GenAiClient {
+    let providers = providers.clone();
+    let auth = AuthResolver::from_resolver_fn(move |iden: ModelIden| {
+        Ok(providers
+            .get(iden.adapter_kind.as_lower_str())
+            .and_then(|config| config.api_key_env.clone())
+            .map(AuthData::from_env))
+    });
+    GenAiClient::builder().with_auth_resolver(auth).build()
+}
@@ -0,0 +1,287 @@
+//! Prunes stale top-level blocks from a name list that already exists, splicing the text so
+//! every block that is kept stays byte-for-byte as it was, and stale entries from the cache.
+//!
+//! ```
+//! use stellaris_name_gen::VanillaKeys;
+//! use stellaris_name_gen::prune::prune_name_list;
+//!
+//! let existing = "\
+//! ## Hand-tuned, do not regenerate
+//! CUSTOM = {
+//!   ship_names = { MY_KEY }
+//! }
+//!
+//! OLD = {
+//!     ship_names = {
+//!         OLD_VEX
+//!     }
+//! }
+//!
+//! ARK = {
+//!     ship_names = {
+//!         ARK_EMBER
+//!     }
+//! }
+//! ";
+//! let generated = "ARK = {\n    ship_names = {\n        ARK_VEX\n    }\n}\n";
+//!
+//! // Without a key set every block missing from the new output is stale
+//! let pruned = prune_name_list(existing, generated, None);
+//! assert_eq!(pruned.removed, vec!["CUSTOM".to_string(), "OLD".to_string()]);
+//! assert_eq!(pruned.text, "ARK = {\n    ship_names = {\n        ARK_VEX\n    }\n}\n");
+//!
+//! // With the keys the mod localised before, blocks that never used one are hand-added and kept
+//! let mut localised = VanillaKeys::default();
+//! localised
+//!     .extend_from_reader("l_english:\n OLD_VEX:0 \"Vex\"\n ARK_EMBER:0 \"Ember\"\n".as_bytes())
+//!     .unwrap();
+//! let pruned = prune_name_list(existing, generated, Some(&localised));
+//! assert_eq!(pruned.removed, vec!["OLD".to_string()]);
+//! assert_eq!(pruned.kept, vec!["CUSTOM".to_string()]);
+//! assert!(pruned.text.starts_with(
+//!     "# Hand-tuned, do not regenerate\nCUSTOM = {\n  ship_names = { MY_KEY }\n}\n\nARK = {\n"
+//! ));
+//! ```
+//!
+//! The cache is pruned of the entries no block of the structure maps to anymore, ie those of
+//! deleted blocks or of a lore profile since edited. Translations and `[titles]` entries are
+//! never pruned, as the structure alone doesn't tell which are still in use:
+//!
+//! ```
+//! use stellaris_name_gen::prune::stale_cache_keys;
+//! use stellaris_name_gen::{BlockKey, CachedBlock, InMemoryCache, LoreProfiles, NameCache};
+//!
+//! let structure = "NAME = {\n    # Ship names\n    ships = {\n    }\n    # Fleet names\n    # era: ancient\n    fleets = {\n    }\n}\n";
+//! let mut cache = InMemoryCache::new();
+//! for key in [
+//!     "NAME_ships",
+//!     "NAME_ships~scores",
+//!     "NAME_fleets@era-ancient",
+//!     // Another era of a current block is kept for when the block switches back
+//!     "NAME_fleets@era-stellar",
+//!     "NAME_armies",
+//!     "NAME_armies~scores",
+//!     "NAME_SHIP_VEX~de~0123abcd",
+//!     "titles_RULER_TITLE",
+//! ] {
+//!     cache.put(&BlockKey::from(key), CachedBlock::new(r#"{"names": ["Vex"]}"#)).unwrap();
+//! }
+//! let stale = stale_cache_keys(structure, &cache, &LoreProfiles::default()).unwrap();
+//! assert_eq!(stale, [BlockKey::from("NAME_armies"), BlockKey::from("NAME_armies~scores")]);
+//! for key in &stale {
+//!     cache.remove(key).unwrap();
+//! }
+//! assert_eq!(cache.keys().unwrap().len(), 6);
+//! ```
+
+use crate::cache::{BlockKey, DERIVED_SUFFIXES, NameCache};
+use crate::lore::LoreProfiles;
+use crate::pipeline::{AI_MODEL, INDENT};
+use crate::quoting::strip_comment;
+use crate::structure::{Segment, parse_structure};
+use crate::vanilla::VanillaKeys;
+use anyhow::Result;
+use std::collections::HashSet;
+use std::ops::Range;
+
+/// A top-level `name = { ... }` block and the bytes it spans, leading comments included
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TopBlock {
+    pub name: String,
+    pub span: Range<usize>,
+}
+
+/// Result of [`prune_name_list`]
+#[derive(Debug, Clone, Default)]
+pub struct Pruned {
+    /// The existing file with stale blocks removed and generated blocks replaced or appended
+    pub text: String,
+    /// Stale blocks that were removed, in file order
+    pub removed: Vec<String>,
+    /// Blocks missing from the new output that were kept as hand-added
+    pub kept: Vec<String>,
+}
+
+/// Splits a Paradox script into its top-level blocks, skipping comments and quoted strings
+pub fn top_level_blocks(text: &str) -> Vec<TopBlock> {
+    let bytes = text.as_bytes();
+    let mut blocks = Vec::new();
+    let mut depth = 0usize;
+    let mut open: Option<(String, usize)> = None;
+    let mut idx = 0;
+    while idx < bytes.len() {
+        match bytes[idx] {
+            b'#' => {
+                idx = line_end(text, idx);
+                continue;
+            }
+            b'"' => {
+                idx = text[idx + 1..]
+                    .find('"')
+                    .map_or(bytes.len(), |end| idx + end + 2);
+                continue;
+            }
+            b'{' => {
+                if depth == 0 {
+                    open = block_start(text, idx);
+                }
+                depth += 1;
+            }
+            b'}' if depth > 0 => {
+                depth -= 1;
+                if depth == 0
+                    && let Some((name, start)) = open.take()
+                {
+                    // Closing braces alone on their line take the line break with them
+                    let rest = &text[idx + 1..line_end(text, idx)];
+                    let end = if rest.trim().is_empty() || rest.trim_start().starts_with('#') {
+                        (line_end(text, idx) + 1).min(bytes.len())
+                    } else {
+                        idx + 1
+                    };
+                    blocks.push(TopBlock {
+                        name,
+                        span: start..end,
+                    });
+                    idx = end;
+                    continue;
+                }
+            }
+            _ => {}
+        }
+        idx += 1;
+    }
+    blocks
+}
+
+/// Index of the line break ending the line holding `idx`, or the end of the text
+fn line_end(text: &str, idx: usize) -> usize {
+    text[idx..].find('\n').map_or(text.len(), |end| idx + end)
+}
+
+/// Index of the start of the line holding `idx`
+fn line_start(text: &str, idx: usize) -> usize {
+    text[..idx].rfind('\n').map_or(0, |start| start + 1)
+}
+
+/// Name of the block opened by the brace at `brace`, and where its text starts: the line of
+/// its name, extended over the comment lines directly above it
+fn block_start(text: &str, brace: usize) -> Option<(String, usize)> {
+    let head = text[..brace].trim_end().strip_suffix('=')?.trim_end();
+    let name_start = head
+        .rfind(|c: char| c.is_whitespace() || c == '}')
+        .map_or(0, |idx| idx + 1);
+    let name = &head[name_start..];
+    if name.is_empty() {
+        return None;
+    }
+    let mut start = line_start(text, name_start);
+    while start > 0 {
+        let above = line_start(text, start - 1);
+        if !text[above..start].trim_start().starts_with('#') {
+            break;
+        }
+        start = above;
+    }
+    Some((name.to_string(), start))
+}
+
+/// Merges a freshly generated name list into an existing one.
+///
+/// Blocks present in both are replaced by their generated text in place, generated blocks the
+/// file lacks are appended, and blocks missing from the new output are removed as stale.
+/// Given the keys the mod localised before, a missing block that uses none of them was never
+/// generated and is kept as hand-added
+pub fn prune_name_list(existing: &str, generated: &str, localised: Option<&VanillaKeys>) -> Pruned {
+    let fresh = top_level_blocks(generated);
+    let mut pruned = Pruned::default();
+    let mut seen = Vec::new();
+    let mut cursor = 0;
+    for block in top_level_blocks(existing) {
+        pruned.text.push_str(&existing[cursor..block.span.start]);
+        cursor = block.span.end;
+        if let Some(new) = fresh.iter().find(|new| new.name == block.name) {
+            pruned.text.push_str(&generated[new.span.clone()]);
+            seen.push(block.name);
+            continue;
+        }
+        let old = &existing[block.span.clone()];
+        let hand_added = localised.is_some_and(|keys| {
+            !old.lines()
+                .map(strip_comment)
+                .flat_map(|line| line.split(|c: char| c.is_whitespace() || "{}=".contains(c)))
+                .any(|word| !word.is_empty() && keys.contains(word))
+        });
+        if hand_added {
+            pruned.text.push_str(old);
+            pruned.kept.push(block.name);
+        } else {
+            pruned.removed.push(block.name);
+            // Take the blank lines separating the removed block from the next one too
+            while cursor < existing.len()
+                && existing[cursor..line_end(existing, cursor)]
+                    .trim()
+                    .is_empty()
+            {
+                cursor = line_end(existing, cursor) + 1;
+            }
+            cursor = cursor.min(existing.len());
+        }
+    }
+    pruned.text.push_str(&existing[cursor..]);
+    for new in fresh.iter().filter(|new| !seen.contains(&new.name)) {
+        if !pruned.text.is_empty() && !pruned.text.ends_with('\n') {
+            pruned.text.push('\n');
+        }
+        pruned.text.push_str(&generated[new.span.clone()]);
+    }
+    pruned
+}
+
+/// Cache entries of blocks, inline values and scores that no block of `structure` maps to
+pub fn stale_cache_keys(
+    structure: &str,
+    cache: &dyn NameCache,
+    lore: &LoreProfiles,
+) -> Result<Vec<BlockKey>> {
+    let (output, jobs) = parse_structure(structure, AI_MODEL, INDENT)?;
+    let mut expected = HashSet::new();
+    for job in &jobs {
+        expected.insert(without_variant(job.cache_key(lore)?.as_str()).to_string());
+        // Kept for the run that moves them to the renamed block
+        for key in job.alias_keys(lore)? {
+            expected.insert(without_variant(key.as_str()).to_string());
+        }
+    }
+    for segment in &output {
+        let Segment::Inline(line) = segment else {
+            continue;
+        };
+        for idx in 0..line.placeholders.len() {
+            expected.insert(line.cache_key(idx, lore)?.as_str().to_string());
+        }
+    }
+    Ok(cache
+        .keys()?
+        .into_iter()
+        .filter(|key| {
+            let base = DERIVED_SUFFIXES
+                .iter()
+                .find_map(|suffix| key.as_str().strip_suffix(suffix))
+                .unwrap_or(key.as_str());
+            // Translations are keyed by localisation key, titles by their config
+            let owned = !base.contains('~') && !base.starts_with("titles_");
+            owned && !expected.contains(without_variant(base))
+        })
+        .collect())
+}
+
+/// A cache key without its `# era:` and `# rarity=` suffixes, so every variant of a block counts
+/// as the block
+fn without_variant(key: &str) -> &str {
+    ["@era-", "@rarity-"]
+        .iter()
+        .filter_map(|suffix| key.find(suffix))
+        .min()
+        .map_or(key, |at| &key[..at])
+}
@@ -0,0 +1,356 @@
+//! Parks a run that used up its provider's daily quota instead of failing it, see
+//! [`crate::GenerationBuilder::wait_for_quota`].
+//!
+//! The first block to hit the quota parks the run, the others queue behind it. The quota is
+//! checked with a tiny request every interval until it is back, then every block sends its
+//! request again. Each check is charged to the run's budget like any request, a budget running
+//! out meanwhile ends the wait. Names generated before are cached all along, so a run interrupted
+//! with Ctrl-C (see [`Interrupt`]) or waiting past its limit picks up where it stopped when
+//! started again. The time parked is left out of the blocks' durations and reported on its own:
+//!
+//! ```
+//! use futures::future::BoxFuture;
+//! use std::sync::atomic::{AtomicUsize, Ordering};
+//! use std::time::Duration;
+//! use stellaris_name_gen::{Generation, GenerationRequest, InMemoryCache, NameGenerator};
+//!
+//! /// Out of quota for the first block request and the first check
+//! #[derive(Default)]
+//! struct Daily(AtomicUsize);
+//!
+//! impl NameGenerator for Daily {
+//!     fn generate<'a>(&'a self, _: &'a GenerationRequest) -> BoxFuture<'a, anyhow::Result<String>> {
+//!         let call = self.0.fetch_add(1, Ordering::SeqCst);
+//!         Box::pin(async move {
+//!             if call < 2 {
+//!                 anyhow::bail!("429 RESOURCE_EXHAUSTED: Quota exceeded for GenerateRequestsPerDay");
+//!             }
+//!             Ok(r#"{"names": ["Vex", "Ember"]}"#.to_string())
+//!         })
+//!     }
+//! }
+//!
+//! let result = tokio::runtime::Runtime::new().unwrap().block_on(
+//!     Generation::builder()
+//!         .structure("NAME = {\n    # Ship names\n    ships = {\n    }\n}\n")
+//!         .lore("")
+//!         .min_names(1)
+//!         .generator(Daily::default())
+//!         .wait_for_quota(Duration::from_secs(60))
+//!         .quota_check_interval(Duration::from_millis(20))
+//!         .cache(InMemoryCache::new())
+//!         .run(),
+//! )
+//! .unwrap();
+//! assert_eq!(result.report.blocks[0].names, 2);
+//! assert!(result.report.quota_wait_ms >= 40);
+//! assert!(result.report.blocks[0].elapsed_ms < result.report.quota_wait_ms);
+//! // The quota is no failure of the provider
+//! assert!(result.report.errors.is_empty());
+//! ```
+//!
+//! A quota still exhausted once the limit has passed fails the run:
+//!
+//! ```
+//! use futures::future::BoxFuture;
+//! use std::time::Duration;
+//! use stellaris_name_gen::quota::QuotaExhausted;
+//! use stellaris_name_gen::{Generation, GenerationRequest, InMemoryCache, NameGenerator};
+//!
+//! struct Spent;
+//!
+//! impl NameGenerator for Spent {
+//!     fn generate<'a>(&'a self, _: &'a GenerationRequest) -> BoxFuture<'a, anyhow::Result<String>> {
+//!         Box::pin(async { anyhow::bail!("insufficient_quota: You exceeded your current quota") })
+//!     }
+//! }
+//!
+//! let error = tokio::runtime::Runtime::new().unwrap().block_on(
+//!     Generation::builder()
+//!         .structure("NAME = {\n    # Ship names\n    ships = {\n    }\n}\n")
+//!         .lore("")
+//!         .generator(Spent)
+//!         .wait_for_quota(Duration::from_millis(50))
+//!         .quota_check_interval(Duration::from_millis(20))
+//!         .cache(InMemoryCache::new())
+//!         .run(),
+//! )
+//! .unwrap_err();
+//! assert!(error.is::<QuotaExhausted>());
+//! assert!(error.to_string().starts_with("Quota of 'gemini-2.5-flash-preview-04-17' still exhausted after"));
+//! ```
+
+use crate::budget::Budget;
+use crate::generator::{GenerationRequest, NameGenerator, ResponseFormat};
+use anyhow::Result;
+use std::fmt;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How often a parked run checks whether its quota is back
+pub const DEFAULT_CHECK_INTERVAL: Duration = Duration::from_secs(15 * 60);
+
+/// Whether a request failed because the provider's daily quota or the account's credit is used
+/// up, rather than a per-minute rate limit that clears by itself
+pub fn quota_exhausted(error: &anyhow::Error) -> bool {
+    let message = format!("{:#}", error).to_lowercase();
+    message.contains("insufficient_quota")
+        || (message.contains("quota")
+            && ["per day", "perday", "daily"]
+                .iter()
+                .any(|needle| message.contains(needle)))
+}
+
+/// The run gave up waiting for the quota, or was interrupted while waiting
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QuotaExhausted {
+    pub model: String,
+    pub waited: Duration,
+    /// Stopped with Ctrl-C rather than by the limit
+    pub interrupted: bool,
+}
+
+impl fmt::Display for QuotaExhausted {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.interrupted {
+            write!(
+                f,
+                "Interrupted while waiting for the quota of '{}'",
+                self.model
+            )?;
+        } else {
+            write!(
+                f,
+                "Quota of '{}' still exhausted after {}",
+                self.model,
+                format_wait(self.waited)
+            )?;
+        }
+        write!(
+            f,
+            ", names generated so far are cached and a rerun picks up from there"
+        )
+    }
+}
+
+impl std::error::Error for QuotaExhausted {}
+
+/// Ctrl-C, listened for once for the whole process. While a run is parked on its quota it ends
+/// the wait, at any other time it ends the process like it would without a listener
+///
+/// ```
+/// use stellaris_name_gen::quota::Interrupt;
+///
+/// let interrupt = Interrupt::default();
+/// let parked = interrupt.clone();
+/// tokio::runtime::Runtime::new().unwrap().block_on(async move {
+///     let waiting = tokio::spawn(async move { parked.interrupted().await });
+///     interrupt.interrupt();
+///     waiting.await.unwrap();
+/// });
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct Interrupt(Arc<InterruptState>);
+
+#[derive(Debug, Default)]
+struct InterruptState {
+    /// Waits parked right now, Ctrl-C only ends the process with none
+    parked: AtomicUsize,
+    interrupted: AtomicBool,
+    notify: tokio::sync::Notify,
+}
+
+impl Interrupt {
+    /// Listens for Ctrl-C from now on, for the rest of the process. Needs a tokio runtime
+    pub fn ctrl_c() -> Self {
+        let interrupt = Self::default();
+        let listener = interrupt.clone();
+        tokio::spawn(async move {
+            while tokio::signal::ctrl_c().await.is_ok() {
+                if listener.0.parked.load(Ordering::SeqCst) == 0 {
+                    // 128 + SIGINT, as if nothing had been listening
+                    std::process::exit(130);
+                }
+                listener.interrupt();
+            }
+        });
+        interrupt
+    }
+
+    /// Ends every wait, those parked now and any parking later
+    pub fn interrupt(&self) {
+        self.0.interrupted.store(true, Ordering::SeqCst);
+        self.0.notify.notify_waiters();
+    }
+
+    /// Completes once interrupted
+    pub async fn interrupted(&self) {
+        loop {
+            // Created before the check so an interrupt in between still wakes it
+            let notified = self.0.notify.notified();
+            if self.0.interrupted.load(Ordering::SeqCst) {
+                return;
+            }
+            notified.await;
+        }
+    }
+
+    /// Counts a wait as parked until the guard is dropped
+    fn park(&self) -> ParkedGuard<'_> {
+        self.0.parked.fetch_add(1, Ordering::SeqCst);
+        ParkedGuard(self)
+    }
+}
+
+struct ParkedGuard<'a>(&'a Interrupt);
+
+impl Drop for ParkedGuard<'_> {
+    fn drop(&mut self) {
+        self.0.0.parked.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Parks the run while the quota is exhausted, shared by every concurrent block
+#[derive(Debug)]
+pub struct QuotaWait {
+    max: Duration,
+    check_every: Duration,
+    /// Held by the block parking the run, the others queue behind it
+    parked: tokio::sync::Mutex<()>,
+    /// Number of waits that ended with the quota back
+    resumed: AtomicU64,
+    waited_ms: AtomicU64,
+    /// Every later wait fails with it once the run gave up
+    gave_up: Mutex<Option<QuotaExhausted>>,
+    interrupt: Interrupt,
+}
+
+impl QuotaWait {
+    pub fn new(max: Duration, check_every: Duration, interrupt: Interrupt) -> Self {
+        Self {
+            max,
+            check_every: check_every.max(Duration::from_millis(1)),
+            parked: tokio::sync::Mutex::new(()),
+            resumed: AtomicU64::new(0),
+            waited_ms: AtomicU64::new(0),
+            gave_up: Mutex::new(None),
+            interrupt,
+        }
+    }
+
+    /// Number of waits that ended with the quota back, taken before sending a request
+    pub fn resumed(&self) -> u64 {
+        self.resumed.load(Ordering::SeqCst)
+    }
+
+    /// Time spent parked over the run
+    pub fn waited(&self) -> Duration {
+        Duration::from_millis(self.waited_ms.load(Ordering::SeqCst))
+    }
+
+    /// Waits until a check request to `model` gets through, each check charged to `budget`.
+    /// `seen` is [`Self::resumed`] from before the failed request, a wait that ended since means
+    /// the quota is back already
+    pub async fn park(
+        &self,
+        seen: u64,
+        generator: &dyn NameGenerator,
+        model: &str,
+        budget: &Budget,
+    ) -> Result<()> {
+        let _parked = self.parked.lock().await;
+        if let Some(gave_up) = self.gave_up.lock().unwrap().clone() {
+            return Err(gave_up.into());
+        }
+        if self.resumed() != seen {
+            return Ok(());
+        }
+        println!(
+            "[Quota] '{}' is out of quota, waiting up to {} for it to reset. Ctrl-C stops the run, names generated so far are cached",
+            model,
+            format_wait(self.max)
+        );
+        let _counted = self.interrupt.park();
+        let started = Instant::now();
+        let check = GenerationRequest {
+            model: model.to_string(),
+            theme: "quota check".to_string(),
+            prompt: "Reply with the single value ok".to_string(),
+            format: ResponseFormat::Value,
+            schema: None,
+            max_names: None,
+            max_tokens: None,
+            temperature: None,
+        };
+        loop {
+            let elapsed = started.elapsed();
+            if elapsed >= self.max {
+                return Err(self.give_up(model, elapsed, false).into());
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(self.check_every.min(self.max - elapsed)) => {}
+                _ = self.interrupt.interrupted() => {
+                    return Err(self.give_up(model, started.elapsed(), true).into());
+                }
+            }
+            if !budget.try_acquire(generator.usage()) {
+                println!(
+                    "[Quota] Budget exhausted while waiting for the quota of '{}'",
+                    model
+                );
+                return Err(self.give_up(model, started.elapsed(), false).into());
+            }
+            match generator.generate(&check).await {
+                Ok(raw) => {
+                    budget.record(&check.prompt, &raw);
+                    break;
+                }
+                Err(e) if quota_exhausted(&e) => println!(
+                    "[Quota] Still exhausted after {}, checking again in {}",
+                    format_wait(started.elapsed()),
+                    format_wait(self.check_every)
+                ),
+                // Any other failure is left to the block's own retries
+                _ => break,
+            }
+        }
+        let waited = started.elapsed();
+        self.add_waited(waited);
+        self.resumed.fetch_add(1, Ordering::SeqCst);
+        println!(
+            "[Quota] '{}' is back after {}, resuming",
+            model,
+            format_wait(waited)
+        );
+        Ok(())
+    }
+
+    fn give_up(&self, model: &str, waited: Duration, interrupted: bool) -> QuotaExhausted {
+        self.add_waited(waited);
+        let gave_up = QuotaExhausted {
+            model: model.to_string(),
+            waited,
+            interrupted,
+        };
+        *self.gave_up.lock().unwrap() = Some(gave_up.clone());
+        gave_up
+    }
+
+    fn add_waited(&self, waited: Duration) {
+        self.waited_ms
+            .fetch_add(waited.as_millis() as u64, Ordering::SeqCst);
+    }
+}
+
+/// A wait for logs, ie `2h05m`, `3m20s` or `40s`
+pub fn format_wait(wait: Duration) -> String {
+    let secs = wait.as_secs();
+    if secs >= 3600 {
+        format!("{}h{:02}m", secs / 3600, secs % 3600 / 60)
+    } else if secs >= 60 {
+        format!("{}m{:02}s", secs / 60, secs % 60)
+    } else {
+        format!("{}s", secs)
+    }
+}
@@ -0,0 +1,412 @@
+//! Quote-aware splitting of structure file lines. Keys and values may be double quoted, a quoted
+//! part keeps its spaces, `=`, `:`, `,` and `#` as written and may hold `\"` and `\\` escapes:
+//!
+//! ```
+//! use stellaris_name_gen::quoting::{split_unquoted, strip_comment, unquote};
+//!
+//! assert_eq!(
+//!     split_unquoted(r#"format = "{name} = class""#, '='),
+//!     Some(("format ", r#" "{name} = class""#))
+//! );
+//! assert_eq!(
+//!     split_unquoted(r#""Key = with equals" = {"#, '='),
+//!     Some((r#""Key = with equals" "#, " {"))
+//! );
+//! assert_eq!(split_unquoted(r#""no = split""#, '='), None);
+//! assert_eq!(strip_comment(r#"title = "Fleet #1" # the first fleet"#), r#"title = "Fleet #1" "#);
+//! assert_eq!(unquote(r#""The \"Old\" Guard""#), r#"The "Old" Guard"#);
+//! assert_eq!(unquote("bare"), "bare");
+//! ```
+//!
+//! The structure parser reads block keys, inserts and hand-written entries through them. A quoted
+//! block key is emitted as written and becomes a file-safe cache key, inserts keep their values
+//! whole and a `#` inside quotes starts no comment:
+//!
+//! ```
+//! use stellaris_name_gen::{BlockKey, Generation, InMemoryCache, MockGenerator};
+//!
+//! let structure = r#"NAME = {
+//!     ## format = "{name} = class"
+//!     ## Ship names
+//!     "Imperial Core Worlds" = {
+//!     }
+//!     ## Fleet names
+//!     "Fleet #1" = { # numbered fleets
+//!     }
+//!     ## Order names
+//!     "The \"Old\" Guard" = {
+//!     }
+//! }
+//! "#;
+//! let result = futures::executor::block_on(
+//!     Generation::builder()
+//!         .structure(structure)
+//!         .lore("")
+//!         .min_names(1)
+//!         .generator(MockGenerator::new(["Vex"]))
+//!         .cache(InMemoryCache::new())
+//!         .run(),
+//! )
+//! .unwrap();
+//! assert!(result.name_list.contains(
+//!     "    \"Imperial Core Worlds\" = {\n        format = \"{name} = class\"\n        VEX,\n    }"
+//! ));
+//! assert!(result.name_list.contains("    \"Fleet #1\" = { # numbered fleets\n        VEX,\n    }"));
+//! let paths: Vec<String> = result.report.blocks.iter().map(|block| block.path.join("/")).collect();
+//! assert_eq!(
+//!     paths,
+//!     [r#"NAME/"Imperial Core Worlds""#, r#"NAME/"Fleet #1""#, r#"NAME/"The \"Old\" Guard""#]
+//! );
+//! let key = BlockKey::from_path(&result.report.blocks[0].path);
+//! assert!(key.as_str().starts_with("NAME_Imperial_Core_Worlds-"));
+//! ```
+//!
+//! Hand-written entries of an `augment-inline` block may hold commas inside their quotes:
+//!
+//! ```
+//! use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+//!
+//! let structure = "NAME = {\n    # Ship names\n    # augment-inline\n    ships = {\n        \"Vex, the First\", Ember\n    }\n}\n";
+//! let result = futures::executor::block_on(
+//!     Generation::builder()
+//!         .structure(structure)
+//!         .lore("")
+//!         .min_names(1)
+//!         .generator(MockGenerator::new(["Vex, the First", "Cinder"]))
+//!         .cache(InMemoryCache::new())
+//!         .run(),
+//! )
+//! .unwrap();
+//! let names: Vec<&str> = result.localisation.iter().map(|(_, name)| name.as_str()).collect();
+//! assert_eq!(names, ["Cinder"]);
+//! ```
+//!
+//! Generated names holding double quotes or backslashes, ie `The "Unbroken" Oath`, are written
+//! the way `--quotes` asks. `escape`, the default, keeps them: quotes are escaped as `\"`, and
+//! backslashes as `\\` in the strings of the name list. Localisation values have no backslash
+//! escape, so names holding one are dropped and listed under their block's `unsafe_names`, and
+//! inline values holding one are stripped and listed under `unsafe_values`. Keys leave both
+//! characters out. Every file the run writes reads back through [`parse_yml`] and
+//! [`check_script`]:
+//!
+//! ```
+//! use stellaris_name_gen::quoting::{QuoteMode, check_script, parse_yml};
+//! use stellaris_name_gen::{Generation, InMemoryCache, LocalisationWriter, MockGenerator, OutputWriter};
+//!
+//! let run = |structure: &str, names: &[&str], mode| {
+//!     futures::executor::block_on(
+//!         Generation::builder()
+//!             .structure(structure)
+//!             .lore("")
+//!             .min_names(1)
+//!             .generator(MockGenerator::new(names.to_vec()))
+//!             .quotes(mode)
+//!             .cache(InMemoryCache::new())
+//!             .run(),
+//!     )
+//!     .unwrap()
+//! };
+//! let structure = "NAME = {\n    # Order names\n    orders = {\n    }\n    motto = \"{{generate: order motto}}\"\n}\n";
+//! let result = run(structure, &["The \"Unbroken\" Oath", "Back\\slash", "Vex"], QuoteMode::Escape);
+//! let block = &result.report.blocks[0];
+//! assert_eq!(
+//!     block.entries,
+//!     [
+//!         ("THE_UNBROKEN_OATH".to_string(), "The \"Unbroken\" Oath".to_string()),
+//!         ("VEX".to_string(), "Vex".to_string()),
+//!     ]
+//! );
+//! assert_eq!(block.unsafe_names, ["Back\\slash"]);
+//! assert_eq!(block.dropped["quotes"], 1);
+//! assert!(result.localisation_yml().contains("    THE_UNBROKEN_OATH:0 \"The \\\"Unbroken\\\" Oath\"\n"));
+//! assert_eq!(parse_yml(&result.localisation_yml()).unwrap(), result.localisation);
+//! // The literal inline value keeps its quotes, escaped
+//! assert!(result.name_list.contains("    motto = \"The \\\"Unbroken\\\" Oath\"\n"));
+//! check_script(&result.name_list).unwrap();
+//!
+//! let dir = std::env::temp_dir().join(format!("namegen-doc-quotes-{}", std::process::id()));
+//! std::fs::create_dir_all(&dir).unwrap();
+//! let writer = LocalisationWriter {
+//!     path: dir.join("names_l_english.yml"),
+//!     ..LocalisationWriter::default()
+//! };
+//! writer.write(&result).unwrap();
+//! let written = std::fs::read_to_string(dir.join("names_l_english.yml")).unwrap();
+//! assert_eq!(parse_yml(&written).unwrap(), result.localisation);
+//! # std::fs::remove_dir_all(&dir).unwrap();
+//!
+//! // A backslash is escaped in the name list, and stripped from a keyed inline value
+//! let structure = "NAME = {\n    # Order names\n    orders = {\n    }\n    motto = \"{{generate: order motto}}\"\n    title = {{generate: order title}}\n}\n";
+//! let result = run(structure, &["Back\\slash", "Vex"], QuoteMode::Escape);
+//! assert!(result.name_list.contains("    motto = \"Back\\\\slash\"\n"));
+//! check_script(&result.name_list).unwrap();
+//! assert_eq!(
+//!     result.report.unsafe_values,
+//!     [("file_structure.txt:6".to_string(), "Back\\slash".to_string())]
+//! );
+//! assert!(result.localisation.iter().any(|(_, value)| value == "Backslash"));
+//! assert_eq!(parse_yml(&result.localisation_yml()).unwrap(), result.localisation);
+//! ```
+//!
+//! `single` turns double quotes into single quotes and `strip` removes them, both removing
+//! backslashes, so every name can be written:
+//!
+//! ```
+//! use stellaris_name_gen::quoting::{QuoteMode, Target};
+//!
+//! let name = "The \"Unbroken\" Oath";
+//! assert_eq!(QuoteMode::Single.apply(name, Target::Localisation).unwrap(), "The 'Unbroken' Oath");
+//! assert_eq!(QuoteMode::Strip.apply(name, Target::Localisation).unwrap(), "The Unbroken Oath");
+//! assert_eq!(QuoteMode::Strip.apply("Back\\slash", Target::Localisation).unwrap(), "Backslash");
+//! assert_eq!(QuoteMode::Escape.apply("Back\\slash", Target::Localisation), None);
+//! assert_eq!(QuoteMode::Escape.apply("Back\\slash", Target::Script).unwrap(), "Back\\slash");
+//! ```
+//!
+//! The validators reject what the game would misread:
+//!
+//! ```
+//! use stellaris_name_gen::quoting::{check_script, parse_yml};
+//!
+//! assert!(parse_yml("l_english:\n VEX:0 \"The \"Old\" Guard\"\n").is_err());
+//! assert!(parse_yml("l_english:\n VEX:0 \"Back\\slash\"\n").is_err());
+//! assert!(parse_yml(" VEX:0 \"Vex\"\n").is_err());
+//! assert_eq!(
+//!     parse_yml("l_english:\n VEX:0 \"\\\"Vex\\\"\" # quoted\n").unwrap(),
+//!     [("VEX".to_string(), "\"Vex\"".to_string())]
+//! );
+//! assert!(check_script("NAME = {\n    motto = \"The \"Old Guard\"\n}\n").is_err());
+//! assert!(check_script("NAME = {\n    motto = \"Old\\Guard\"\n}\n").is_err());
+//! assert!(check_script("NAME = {\n    VEX,\n").is_err());
+//! assert!(check_script("NAME = {\n    motto = \"{ #1\" # {\n}\n").is_ok());
+//! ```
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// Byte offsets of every `needle` outside double quotes. A backslash inside quotes escapes the
+/// next character
+fn unquoted(text: &str, needle: char) -> impl Iterator<Item = usize> + '_ {
+    let mut quoted = false;
+    let mut escaped = false;
+    text.char_indices().filter_map(move |(idx, c)| {
+        if escaped {
+            escaped = false;
+        } else if quoted && c == '\\' {
+            escaped = true;
+        } else if c == '"' {
+            quoted = !quoted;
+        } else if c == needle && !quoted {
+            return Some(idx);
+        }
+        None
+    })
+}
+
+/// Splits `text` around the first `needle` outside quotes, like [`str::split_once`]
+pub fn split_unquoted(text: &str, needle: char) -> Option<(&str, &str)> {
+    let at = unquoted(text, needle).next()?;
+    Some((&text[..at], &text[at + needle.len_utf8()..]))
+}
+
+/// Splits `text` around every `needle` outside quotes, like [`str::split`]
+pub fn split_all_unquoted(text: &str, needle: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut start = 0;
+    for at in unquoted(text, needle) {
+        parts.push(&text[start..at]);
+        start = at + needle.len_utf8();
+    }
+    parts.push(&text[start..]);
+    parts
+}
+
+/// `line` without its trailing comment, the first `#` outside quotes on
+pub fn strip_comment(line: &str) -> &str {
+    split_unquoted(line, '#').map_or(line, |(code, _)| code)
+}
+
+/// `text` trimmed, without its surrounding quotes and with its escapes resolved. Unquoted text
+/// is returned trimmed
+pub fn unquote(text: &str) -> String {
+    let text = text.trim();
+    let Some(inner) = text
+        .strip_prefix('"')
+        .and_then(|rest| rest.strip_suffix('"'))
+        .filter(|_| text.len() > 1)
+    else {
+        return text.to_string();
+    };
+    let mut out = String::with_capacity(inner.len());
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => out.extend(chars.next()),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// What happens to the double quotes and backslashes of generated names
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum QuoteMode {
+    /// Kept and escaped, names an output can't hold are rejected
+    #[default]
+    Escape,
+    /// Double quotes become single quotes, backslashes are removed
+    Single,
+    /// Both are removed
+    Strip,
+}
+
+/// Where a name is written, between quotes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Target {
+    /// A localisation value, which escapes `\"` but has no escape for a backslash
+    Localisation,
+    /// A string of the name list, which escapes `\"` and `\\`
+    Script,
+}
+
+impl QuoteMode {
+    /// The name as the mode writes it into `target`, before escaping. `None` if the target
+    /// can't hold it
+    pub fn apply(self, name: &str, target: Target) -> Option<String> {
+        if !name.contains(['"', '\\']) {
+            return Some(name.to_string());
+        }
+        let name = match self {
+            QuoteMode::Escape => {
+                return (target == Target::Script || !name.contains('\\'))
+                    .then(|| name.to_string());
+            }
+            QuoteMode::Single => name.replace('"', "'").replace('\\', ""),
+            QuoteMode::Strip => name.replace(['"', '\\'], ""),
+        };
+        // `Vex " Ember` would keep both spaces
+        Some(name.split_whitespace().collect::<Vec<_>>().join(" "))
+    }
+}
+
+/// `value` escaped for a quoted string of the name list
+pub fn escape_script(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// `value` escaped for a quoted localisation value
+pub fn escape_yml(value: &str) -> String {
+    value.replace('"', "\\\"")
+}
+
+/// Reads a localisation document back as (key, value), values unescaped. Fails on a missing
+/// `l_<language>:` header, an unquoted or unclosed value, an escape other than `\"` and `\n`,
+/// and anything but a comment after the value
+pub fn parse_yml(text: &str) -> Result<Vec<(String, String)>> {
+    let mut entries = Vec::new();
+    let mut header = false;
+    for (idx, line) in text.lines().enumerate() {
+        let line_no = idx + 1;
+        let line = line.trim_start_matches('\u{feff}').trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        if !header {
+            if !(line.starts_with("l_") && line.ends_with(':')) {
+                anyhow::bail!(
+                    "line {}: expected an l_<language>: header, found '{}'",
+                    line_no,
+                    line
+                );
+            }
+            header = true;
+            continue;
+        }
+        let (key, rest) = line.split_once(':').with_context(|| {
+            format!(
+                "line {}: expected key:0 \"value\", found '{}'",
+                line_no, line
+            )
+        })?;
+        let key = key.trim();
+        if key.is_empty() || key.contains(char::is_whitespace) {
+            anyhow::bail!("line {}: invalid key '{}'", line_no, key);
+        }
+        let rest = rest
+            .trim_start_matches(|c: char| c.is_ascii_digit())
+            .trim_start()
+            .strip_prefix('"')
+            .with_context(|| format!("line {}: value of '{}' is not quoted", line_no, key))?;
+        let mut value = String::new();
+        let mut chars = rest.chars();
+        let mut closed = false;
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' => match chars.next() {
+                    Some('"') => value.push('"'),
+                    Some('n') => value.push('\n'),
+                    other => anyhow::bail!(
+                        "line {}: unsupported escape '\\{}' in the value of '{}'",
+                        line_no,
+                        other.map(String::from).unwrap_or_default(),
+                        key
+                    ),
+                },
+                '"' => {
+                    closed = true;
+                    break;
+                }
+                c => value.push(c),
+            }
+        }
+        if !closed {
+            anyhow::bail!("line {}: value of '{}' is not closed", line_no, key);
+        }
+        let tail = chars.as_str().trim();
+        if !tail.is_empty() && !tail.starts_with('#') {
+            anyhow::bail!("line {}: '{}' after the value of '{}'", line_no, tail, key);
+        }
+        entries.push((key.to_string(), value));
+    }
+    Ok(entries)
+}
+
+/// Checks a name list reads back as Paradox script: every quote closes on its line, escapes
+/// inside quotes are `\"` or `\\`, and braces outside quotes and comments balance
+pub fn check_script(text: &str) -> Result<()> {
+    let mut depth = 0usize;
+    for (idx, line) in text.lines().enumerate() {
+        let line_no = idx + 1;
+        let mut quoted = false;
+        let mut chars = line.chars();
+        while let Some(c) = chars.next() {
+            match c {
+                '\\' if quoted => match chars.next() {
+                    Some('"' | '\\') => {}
+                    other => anyhow::bail!(
+                        "line {}: unsupported escape '\\{}' in '{}'",
+                        line_no,
+                        other.map(String::from).unwrap_or_default(),
+                        line.trim()
+                    ),
+                },
+                '"' => quoted = !quoted,
+                '#' if !quoted => break,
+                '{' if !quoted => depth += 1,
+                '}' if !quoted => {
+                    depth = depth
+                        .checked_sub(1)
+                        .with_context(|| format!("line {}: unmatched '}}'", line_no))?;
+                }
+                _ => {}
+            }
+        }
+        if quoted {
+            anyhow::bail!("line {}: unclosed quote in '{}'", line_no, line.trim());
+        }
+    }
+    if depth > 0 {
+        anyhow::bail!("{} unclosed brace(s)", depth);
+    }
+    Ok(())
+}
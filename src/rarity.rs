@@ -0,0 +1,127 @@
+//! Pushes a block toward unusual, evocative names when its generated names come out too mundane,
+//! set with `# rarity=high` or `--rarity high` for the whole run. The prompt asks for distinctive
+//! names, and with a common-words list, names made up only of common words are dropped:
+//!
+//! ```
+//! use stellaris_name_gen::rarity::CommonWords;
+//!
+//! let common = CommonWords::parse("# everyday words\nstar\nDawn\n\nlight\n");
+//! assert_eq!(common.len(), 3);
+//! assert!(common.is_common("dawn"));
+//! assert!(common.is_common("Star-Light"));
+//! assert!(!common.is_common("Dawn of Vex"));
+//! assert!(!common.is_common("Vex"));
+//! ```
+//!
+//! Blocks of normal rarity keep their names, the report counts the common names dropped:
+//!
+//! ```
+//! use futures::future::BoxFuture;
+//! use stellaris_name_gen::rarity::CommonWords;
+//! use stellaris_name_gen::{Generation, GenerationRequest, InMemoryCache, NameGenerator};
+//!
+//! struct Mundane;
+//!
+//! impl NameGenerator for Mundane {
+//!     fn generate<'a>(&'a self, request: &'a GenerationRequest) -> BoxFuture<'a, anyhow::Result<String>> {
+//!         let unusual = request.prompt.contains("Favor unusual, evocative names");
+//!         assert_eq!(unusual, request.theme == "Flagship names");
+//!         Box::pin(async { Ok(r#"{"names": ["Dawn", "Vex", "Star Light", "Ember"]}"#.to_string()) })
+//!     }
+//! }
+//!
+//! let structure = "NAME = {\n    # Flagship names\n    # rarity=high\n    flagships = {\n    }\n    # Ship names\n    ships = {\n    }\n}\n";
+//! let result = futures::executor::block_on(
+//!     Generation::builder()
+//!         .structure(structure)
+//!         .lore("")
+//!         .min_names(1)
+//!         .generator(Mundane)
+//!         .common_words(CommonWords::parse("dawn\nstar\nlight\n"))
+//!         .cache(InMemoryCache::new())
+//!         .run(),
+//! )
+//! .unwrap();
+//! let blocks = &result.report.blocks;
+//! assert_eq!((blocks[0].names, blocks[0].dropped["common"]), (2, 2));
+//! assert_eq!(blocks[1].names, 4);
+//! ```
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::fs;
+use std::path::Path;
+
+/// How unusual a block's names should be
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Deserialize, Serialize, clap::ValueEnum,
+)]
+#[serde(rename_all = "lowercase")]
+pub enum Rarity {
+    /// Whatever the model comes up with
+    #[default]
+    Normal,
+    /// Unusual, evocative names, common ones are filtered out
+    High,
+}
+
+impl Rarity {
+    /// Parses the value of a `# rarity=` directive
+    pub fn parse(value: &str) -> Result<Self> {
+        match value.trim().to_ascii_lowercase().as_str() {
+            "normal" => Ok(Rarity::Normal),
+            "high" => Ok(Rarity::High),
+            other => anyhow::bail!("Unknown rarity '{}', expected normal|high", other),
+        }
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Rarity::Normal => "normal",
+            Rarity::High => "high",
+        }
+    }
+}
+
+/// Everyday words, names made up only of them read as too common for a `rarity=high` block
+#[derive(Debug, Clone, Default)]
+pub struct CommonWords {
+    words: HashSet<String>,
+}
+
+impl CommonWords {
+    /// Reads a word list, one word per line. Blank lines and `#` comments are skipped
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Ok(Self::parse(&text))
+    }
+
+    pub fn parse(text: &str) -> Self {
+        let words = text
+            .lines()
+            .map(str::trim)
+            .filter(|line| !line.is_empty() && !line.starts_with('#'))
+            .map(str::to_lowercase)
+            .collect();
+        Self { words }
+    }
+
+    pub fn len(&self) -> usize {
+        self.words.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.words.is_empty()
+    }
+
+    /// Whether every word of the name, split on spaces and hyphens, is a common word
+    pub fn is_common(&self, name: &str) -> bool {
+        let mut words = name
+            .split(|c: char| c.is_whitespace() || c == '-')
+            .filter(|word| !word.is_empty())
+            .peekable();
+        words.peek().is_some() && words.all(|word| self.words.contains(&word.to_lowercase()))
+    }
+}
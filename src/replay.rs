@@ -0,0 +1,200 @@
+//! Replaying the parameters a block's earlier names were generated with (`--match-existing`),
+//! so a block generated again months later keeps the style of the rest of the file.
+//!
+//! Every cached block records its model, flavors, sampling temperature and prompt template
+//! version. A block that misses the cache, ie after its lore, era or species class changed, is
+//! matched with the newest cached answer of the same block and each parameter is resolved in
+//! this order:
+//!
+//! - model: the stored model, then the block's `# model:` or the config's `model`, then the
+//!   default. A stored model that is no longer available, because its provider has no key,
+//!   falls back to the next one with a warning
+//! - flavors: `--seed` or `--no-flavor` on the command line, then the stored flavors, then the
+//!   config's `seed` and `flavor`, then a random pick
+//! - temperature: the stored temperature, then the generator's own
+//! - prompt template: always the current one, an older stored version is only reported
+//!
+//! ```
+//! use futures::future::BoxFuture;
+//! use std::sync::{Arc, Mutex};
+//! use stellaris_name_gen::{
+//!     BlockKey, CachedBlock, Generation, GenerationRequest, InMemoryCache, NameCache,
+//!     NameGenerator,
+//! };
+//!
+//! #[derive(Clone, Default)]
+//! struct Recorder(Arc<Mutex<Vec<GenerationRequest>>>);
+//! impl NameGenerator for Recorder {
+//!     fn generate<'a>(&'a self, request: &'a GenerationRequest) -> BoxFuture<'a, anyhow::Result<String>> {
+//!         self.0.lock().unwrap().push(request.clone());
+//!         Box::pin(async { Ok(r#"{"names": ["Vex"]}"#.to_string()) })
+//!     }
+//! }
+//!
+//! // Generated before the block moved to the stellar era
+//! let mut cache = InMemoryCache::new();
+//! let stored = CachedBlock {
+//!     raw: r#"{"names": ["Ember"]}"#.to_string(),
+//!     model: Some("old-local-model".to_string()),
+//!     timestamp: Some(1_700_000_000),
+//!     flavors: vec!["favor harsh consonants".to_string()],
+//!     temperature: Some(0.9),
+//!     prompt_version: Some(0),
+//!     ..CachedBlock::default()
+//! };
+//! cache.put(&BlockKey::from("NAME_ships"), stored).unwrap();
+//!
+//! let structure = "NAME = {\n    # Ship names\n    # era: stellar\n    ships = {\n    }\n}\n";
+//! let recorder = Recorder::default();
+//! let run = |cache: InMemoryCache, explicit_flavors: bool| {
+//!     futures::executor::block_on(
+//!         Generation::builder()
+//!             .structure(structure)
+//!             .lore("")
+//!             .model("local-model")
+//!             .min_names(1)
+//!             .match_existing(true)
+//!             .explicit_flavors(explicit_flavors)
+//!             .flavor(false)
+//!             .generator(recorder.clone())
+//!             .cache(cache)
+//!             .run(),
+//!     )
+//!     .unwrap()
+//! };
+//! let result = run(cache.clone(), false);
+//!
+//! // Stored parameters win over the config and the defaults
+//! let request = recorder.0.lock().unwrap().pop().unwrap();
+//! assert_eq!(request.model, "old-local-model");
+//! assert_eq!(request.temperature, Some(0.9));
+//! assert!(request.prompt.contains("favor harsh consonants"));
+//! let replayed = result.report.blocks[0].replayed.as_ref().unwrap();
+//! assert_eq!(replayed.from, "NAME_ships");
+//! assert_eq!(replayed.applied, ["model", "flavors", "temperature"]);
+//! assert_eq!(replayed.skipped, ["prompt template v0, only v1 exists"]);
+//! assert_eq!(result.report.blocks[0].model, "old-local-model");
+//!
+//! // Flavors given on the command line win over the stored ones
+//! let result = run(cache, true);
+//! let request = recorder.0.lock().unwrap().pop().unwrap();
+//! assert!(!request.prompt.contains("favor harsh consonants"));
+//! let replayed = result.report.blocks[0].replayed.as_ref().unwrap();
+//! assert_eq!(replayed.applied, ["model", "temperature"]);
+//! assert_eq!(replayed.skipped[0], "flavors, set on the command line");
+//! ```
+//!
+//! A stored model whose provider has no key falls back to the block's own model:
+//!
+//! ```
+//! use stellaris_name_gen::providers::ProviderConfig;
+//! use stellaris_name_gen::{BlockKey, CachedBlock, Config, Generation, InMemoryCache, MockGenerator, NameCache};
+//!
+//! let mut cache = InMemoryCache::new();
+//! let stored = CachedBlock {
+//!     raw: r#"{"names": ["Ember"]}"#.to_string(),
+//!     model: Some("gpt-4o".to_string()),
+//!     ..CachedBlock::default()
+//! };
+//! cache.put(&BlockKey::from("NAME_ships"), stored).unwrap();
+//! let mut config = Config::default();
+//! config.providers.insert(
+//!     "openai".to_string(),
+//!     ProviderConfig { api_key_env: Some("NAMEGEN_UNSET_OPENAI_KEY".to_string()), ..Default::default() },
+//! );
+//!
+//! let result = futures::executor::block_on(
+//!     Generation::builder()
+//!         .structure("NAME = {\n    # Ship names\n    # era: stellar\n    ships = {\n    }\n}\n")
+//!         .lore("")
+//!         .model("local-model")
+//!         .config(&config)
+//!         .match_existing(true)
+//!         .generator(MockGenerator::new(["Vex"]))
+//!         .cache(cache)
+//!         .run(),
+//! )
+//! .unwrap();
+//! let block = &result.report.blocks[0];
+//! assert_eq!(block.model, "local-model");
+//! assert_eq!(block.replayed.as_ref().unwrap().skipped, ["model gpt-4o, no longer available"]);
+//! ```
+
+use crate::cache::{BlockKey, CachedBlock};
+use crate::structure::GenerationJob;
+use crate::warning;
+use serde::Serialize;
+
+/// Version of the prompt templates, raised whenever they change how names come out
+pub const PROMPT_VERSION: u32 = 1;
+
+/// Stored parameters a block was generated again with, kept in its report
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct Replayed {
+    /// Cache entry the parameters were read from
+    pub from: String,
+    /// Parameters taken from it, among `model`, `flavors` and `temperature`
+    pub applied: Vec<String>,
+    /// Stored parameters that were left out, each with the reason
+    pub skipped: Vec<String>,
+    /// Flavors to send instead of sampling them
+    #[serde(skip)]
+    pub(crate) flavors: Option<Vec<String>>,
+    /// Temperature to send instead of the generator's own
+    #[serde(skip)]
+    pub(crate) temperature: Option<f64>,
+}
+
+/// Resolves the parameters of `job` against those stored under `from`, switching its model
+/// when the stored one is available
+pub(crate) fn replay(
+    stored: &CachedBlock,
+    from: &BlockKey,
+    job: &mut GenerationJob,
+    explicit_flavors: bool,
+    available: impl Fn(&str) -> bool,
+) -> Replayed {
+    let mut replayed = Replayed {
+        from: from.as_str().to_string(),
+        ..Replayed::default()
+    };
+    if let Some(model) = &stored.model {
+        if available(model) {
+            job.model = model.clone();
+            replayed.applied.push("model".to_string());
+        } else {
+            warning!(
+                "[Match] '{}': stored model '{}' is no longer available, using '{}'",
+                job.path.join("/"),
+                model,
+                job.model
+            );
+            replayed
+                .skipped
+                .push(format!("model {}, no longer available", model));
+        }
+    }
+    if explicit_flavors {
+        if !stored.flavors.is_empty() {
+            replayed
+                .skipped
+                .push("flavors, set on the command line".to_string());
+        }
+    } else {
+        replayed.flavors = Some(stored.flavors.clone());
+        replayed.applied.push("flavors".to_string());
+    }
+    if let Some(temperature) = stored.temperature {
+        replayed.temperature = Some(temperature);
+        replayed.applied.push("temperature".to_string());
+    }
+    if let Some(version) = stored.prompt_version
+        && version != PROMPT_VERSION
+    {
+        replayed.skipped.push(format!(
+            "prompt template v{}, only v{} exists",
+            version, PROMPT_VERSION
+        ));
+    }
+    replayed
+}
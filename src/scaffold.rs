@@ -0,0 +1,358 @@
+//! Starter projects written by `init`: a commented structure file, a starter lore, a config
+//! listing the defaults and a `.env.example`. Existing files are never overwritten.
+//!
+//! Every template parses, generates and packages as is:
+//!
+//! ```
+//! use stellaris_name_gen::scaffold::{self, Template};
+//! use stellaris_name_gen::{
+//!     Config, Generation, InMemoryCache, MockGenerator, ModWriter, OutputWriter, plan,
+//! };
+//!
+//! let dir = std::env::temp_dir().join(format!("namegen-doc-init-{}", std::process::id()));
+//! let _ = std::fs::remove_dir_all(&dir);
+//! let written = scaffold::init(&dir, Template::FullSpecies).unwrap();
+//! assert_eq!(written.len(), 4);
+//!
+//! let structure = std::fs::read_to_string(dir.join("file_structure.txt")).unwrap();
+//! let lore = std::fs::read_to_string(dir.join("lore.txt")).unwrap();
+//! let config = Config::load(&dir.join("namegen.toml")).unwrap();
+//! assert!(plan::generated_blocks(&structure).unwrap() > 10);
+//!
+//! let names: Vec<String> = (1..=12).map(|n| format!("Vex {}", n)).collect();
+//! let result = futures::executor::block_on(
+//!     Generation::builder()
+//!         .structure(structure)
+//!         .lore(lore)
+//!         .config(&config)
+//!         .generator(MockGenerator::new(names))
+//!         .cache(InMemoryCache::new())
+//!         .strict(true)
+//!         .run(),
+//! )
+//! .unwrap();
+//! assert!(result.report.blocks.iter().all(|block| !block.failed));
+//! assert!(result.report.collisions.is_empty());
+//! assert!(result.name_list.contains("    ship_names = {\n        generic = {\n            MY_SPECIES_SHIP_GENERIC_VEX_1,"));
+//! assert!(result.name_list.contains("        default = {\n            weight = 100\n"));
+//!
+//! ModWriter {
+//!     name: "My Species".to_string(),
+//!     dir: dir.join("mod"),
+//!     prune: None,
+//!     split: Default::default(),
+//! }
+//! .write(&result)
+//! .unwrap();
+//! let name_list = std::fs::read_to_string(dir.join("mod/my_species/common/name_lists/my_species.txt")).unwrap();
+//! assert_eq!(name_list.matches('{').count(), name_list.matches('}').count());
+//!
+//! // A second init leaves the project alone
+//! let error = scaffold::init(&dir, Template::Minimal).unwrap_err();
+//! assert!(error.to_string().contains("file_structure.txt"));
+//! # std::fs::remove_dir_all(&dir).unwrap();
+//! ```
+
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Starter project written by `init`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum Template {
+    /// A single block of ship names
+    #[default]
+    Minimal,
+    /// Ship, fleet, planet and character names of a biological species
+    FullSpecies,
+    /// Designations of a machine empire
+    MachineEmpire,
+}
+
+/// Files of a starter project as (path relative to the project folder, contents)
+pub fn files(template: Template) -> Vec<(&'static str, String)> {
+    let (structure, lore, species_class) = match template {
+        Template::Minimal => (MINIMAL_STRUCTURE, MINIMAL_LORE, None),
+        Template::FullSpecies => (FULL_SPECIES_STRUCTURE, FULL_SPECIES_LORE, Some("mammalian")),
+        Template::MachineEmpire => (MACHINE_STRUCTURE, MACHINE_LORE, Some("machine")),
+    };
+    let species_class = match species_class {
+        Some(class) => format!("species-class = \"{}\"", class),
+        None => "# species-class = \"mammalian\"".to_string(),
+    };
+    vec![
+        ("file_structure.txt", structure.to_string()),
+        ("lore.txt", lore.to_string()),
+        (
+            "namegen.toml",
+            CONFIG.replace("{species-class}", &species_class),
+        ),
+        (".env.example", ENV_EXAMPLE.to_string()),
+    ]
+}
+
+/// Writes the files of `template` into `dir`, creating it if needed. Fails without writing
+/// anything if any of them already exists
+pub fn init(dir: &Path, template: Template) -> Result<Vec<PathBuf>> {
+    let files = files(template);
+    let existing: Vec<String> = files
+        .iter()
+        .map(|(path, _)| dir.join(path))
+        .filter(|path| path.exists())
+        .map(|path| path.display().to_string())
+        .collect();
+    if !existing.is_empty() {
+        anyhow::bail!(
+            "Refusing to overwrite {}, move them away or init another folder",
+            existing.join(", ")
+        );
+    }
+    fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    let mut written = Vec::new();
+    for (path, contents) in files {
+        let path = dir.join(path);
+        fs::write(&path, contents)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        written.push(path);
+    }
+    Ok(written)
+}
+
+const MINIMAL_STRUCTURE: &str = r#"# A Stellaris name list, see README.md for every directive
+# Plain comments above a block are its theme, the prompt sent to the model
+# Comments naming a directive, such as prefix below, set it for the block and its children
+# A blank comment line ends a theme, so notes like these never reach the model
+#
+NAME = {
+    # prefix: MY_
+    ship_names = {
+        # Ship names of a young spacefaring empire, proud and hopeful
+        generic = {
+        }
+    }
+}
+"#;
+
+const MINIMAL_LORE: &str = "\
+Describe your empire here, the model reads it before naming anything.
+
+Who they are, where they come from, what they value and fear, how their language sounds.
+A few sentences already go a long way, a few pages go further.
+";
+
+const FULL_SPECIES_STRUCTURE: &str = r#"# Name list of a biological species, see README.md for every directive
+# Plain comments above a block are its theme, the prompt sent to the model
+# Comments naming a directive set it for the block and its children, ie prefix
+# Comments holding an equals sign are written into the block as they are, ie weight
+# A blank comment line ends a theme, so notes like these never reach the model
+#
+MY_SPECIES = {
+    # prefix: MY_SPECIES_
+    randomized = yes
+
+    # A plus appends to the inherited prefix, MY_SPECIES_SHIP_ and so on
+    #
+    # prefix: +SHIP
+    ship_names = {
+        # Ship names of any class, proud words of the species' language
+        # prefix: +GENERIC
+        generic = {
+        }
+        # Corvette names, swift hunting animals and their calls
+        # prefix: +CORVETTE
+        corvette = {
+        }
+        # Destroyer names, storms and sharp weather
+        # prefix: +DESTROYER
+        destroyer = {
+        }
+        # Cruiser names, heroes of old songs
+        # prefix: +CRUISER
+        cruiser = {
+        }
+        # Battleship names, mountains, ancestors and great oaths
+        # prefix: +BATTLESHIP
+        battleship = {
+        }
+        # Science ship names, famous thinkers and explorers
+        # prefix: +SCIENCE
+        science = {
+        }
+        # Construction ship names, builders and tools
+        # prefix: +CONSTRUCTOR
+        constructor = {
+        }
+        # Colony ship names, hopes and promised lands
+        # prefix: +COLONIZER
+        colonizer = {
+        }
+    }
+
+    # prefix: +FLEET
+    fleet_names = {
+        # Fleet names, banners and rallying cries
+        random_names = {
+        }
+        sequential_name = "%O% Fleet"
+    }
+
+    # prefix: +PLANET
+    planet_names = {
+        # prefix: +GENERIC
+        generic = {
+            # Planet names of any climate, places from the species' myths
+            names = {
+            }
+        }
+        # prefix: +DESERT
+        pc_desert = {
+            # Desert world names, heat, dunes and mirages
+            names = {
+            }
+        }
+        # prefix: +OCEAN
+        pc_ocean = {
+            # Ocean world names, tides, depths and sea creatures
+            names = {
+            }
+        }
+    }
+
+    # prefix: +CHAR
+    character_names = {
+        # weight = 100
+        default = {
+            # Given names of males, short and strong
+            # prefix: +MALE
+            first_names_male = {
+            }
+            # Given names of females, flowing and bright
+            # prefix: +FEMALE
+            first_names_female = {
+            }
+            # Family names, old clans and their trades
+            # prefix: +FAMILY
+            second_names = {
+            }
+            # Names taken by male rulers, grand and ancient
+            # prefix: +REGNAL_MALE
+            regnal_first_names_male = {
+            }
+            # Names taken by female rulers, grand and ancient
+            # prefix: +REGNAL_FEMALE
+            regnal_first_names_female = {
+            }
+            # Dynasty names of rulers, great houses
+            # prefix: +REGNAL_FAMILY
+            regnal_second_names = {
+            }
+        }
+    }
+}
+"#;
+
+const FULL_SPECIES_LORE: &str = "\
+The Vessari are warm-blooded plains dwellers who left their homeworld in living memory.
+They lived in herds for most of their history, so names honour the herd, the seasons and the
+long migrations across the grass seas. Their language is soft and rounded, rich in vowels and
+doubled consonants, and every name of note once belonged to an ancestor.
+
+Replace this with your own species: their history, beliefs, language and what they hold dear.
+";
+
+const MACHINE_STRUCTURE: &str = r#"# Name list of a machine empire, see README.md for every directive
+# Plain comments above a block are its theme, the prompt sent to the model
+# Comments naming a directive set it for the block and its children, ie prefix
+# Comments holding an equals sign are written into the block as they are, ie weight
+# A blank comment line ends a theme, so notes like these never reach the model
+#
+MY_MACHINES = {
+    # prefix: MY_MACHINES_
+    randomized = yes
+
+    # prefix: +SHIP
+    ship_names = {
+        # Designations of warships, unit codes and optimisation goals
+        # prefix: +GENERIC
+        generic = {
+        }
+        # Designations of science vessels, survey protocols and sensor terms
+        # prefix: +SCIENCE
+        science = {
+        }
+        # Designations of construction drones, fabrication routines
+        # prefix: +CONSTRUCTOR
+        constructor = {
+        }
+    }
+
+    # prefix: +FLEET
+    fleet_names = {
+        # Fleet designations, task groups and directives
+        random_names = {
+        }
+        sequential_name = "Unit Group %C%"
+    }
+
+    # prefix: +PLANET
+    planet_names = {
+        # prefix: +GENERIC
+        generic = {
+            # Planet designations, catalogue entries and resource grades
+            names = {
+            }
+        }
+    }
+
+    # prefix: +CHAR
+    character_names = {
+        # weight = 100
+        default = {
+            # Designations of drones and processing units, short codes and serial numbers
+            # prefix: +UNIT
+            first_names_male = {
+            }
+            # Designations of drones and processing units, short codes and serial numbers
+            # prefix: +UNIT
+            first_names_female = {
+            }
+        }
+    }
+}
+"#;
+
+const MACHINE_LORE: &str = "\
+The Continuum is a machine intelligence that outlived its creators and now expands for the
+sake of efficiency. It names nothing for beauty. Designations are functional, built from
+letters, serial numbers and technical terms, with the odd fragment of its creators' language
+kept out of habit.
+
+Replace this with your own machines: their origin, directives and how they label the galaxy.
+";
+
+const CONFIG: &str = r#"# Settings of every run, flags on the command line win over them. Every key is optional,
+# commented ones show their default. README.md lists them all
+
+# model = "gemini-2.5-flash-preview-04-17" # used by blocks without a `# model:` directive
+# min-names = 10                          # blocks keeping fewer names are marked failed
+# concurrency = 1                         # blocks generated at once
+# unique-scope = "block"                  # block, category or global
+# flavor = true                           # add stylistic flavors to prompts
+{species-class}
+
+[key-style]
+# separator = "_"
+# case = "upper"                          # upper, lower or preserve
+# hash = "off"                            # off, fallback or always
+
+[cache]
+# backend = "fs"                          # fs, sqlite or memory
+# dir = "cache"
+"#;
+
+const ENV_EXAMPLE: &str = "\
+# Copy to .env and fill in the key of the provider your model uses, Ollama needs none
+GEMINI_API_KEY=
+# OPENAI_API_KEY=
+# ANTHROPIC_API_KEY=
+";
@@ -0,0 +1,157 @@
+//! Drops names meaning nearly the same as a name kept before them, ie `Quiet Grief` after
+//! `Silent Sorrow`, which no edit distance catches. Every name of a block is embedded, and a
+//! name whose cosine similarity to a kept name reaches the threshold joins that name's cluster:
+//!
+//! ```
+//! use std::collections::BTreeMap;
+//! use stellaris_name_gen::semantic::{cosine, dedup};
+//!
+//! assert!((cosine(&[1.0, 0.0], &[2.0, 0.0]) - 1.0).abs() < 1e-9);
+//! assert_eq!(cosine(&[1.0, 0.0], &[0.0, 1.0]), 0.0);
+//! assert_eq!(cosine(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+//!
+//! let embeddings: BTreeMap<String, Vec<f32>> = [
+//!     ("Silent Sorrow", vec![1.0, 0.1]),
+//!     ("Iron Dawn", vec![0.0, 1.0]),
+//!     ("Quiet Grief", vec![0.95, 0.15]),
+//! ]
+//! .into_iter()
+//! .map(|(name, vector)| (name.to_string(), vector))
+//! .collect();
+//! let mut names = vec!["Silent Sorrow".to_string(), "Iron Dawn".to_string(), "Quiet Grief".to_string()];
+//! let clusters = dedup(&mut names, &embeddings, 0.9, &[]);
+//! assert_eq!(names, ["Silent Sorrow", "Iron Dawn"]);
+//! assert_eq!(clusters[0].kept, "Silent Sorrow");
+//! assert_eq!(clusters[0].dropped, ["Quiet Grief"]);
+//! ```
+//!
+//! The pass costs a call to an embedding model per block, so it only runs with
+//! `--semantic-dedup`. Embeddings are cached by name beside the block, later runs only embed
+//! the names they haven't seen:
+//!
+//! ```
+//! use futures::future::BoxFuture;
+//! use std::sync::Arc;
+//! use std::sync::atomic::{AtomicUsize, Ordering};
+//! use stellaris_name_gen::{FsCache, Generation, GenerationRequest, NameGenerator};
+//!
+//! /// Embeds names by their first letter, counting the names it was asked for
+//! struct Initials(Arc<AtomicUsize>);
+//!
+//! impl NameGenerator for Initials {
+//!     fn generate<'a>(&'a self, _: &'a GenerationRequest) -> BoxFuture<'a, anyhow::Result<String>> {
+//!         Box::pin(async { Ok(r#"{"names": ["Silent Sorrow", "Iron Dawn", "Sorrowful Silence"]}"#.to_string()) })
+//!     }
+//!
+//!     fn embed<'a>(&'a self, _: &'a str, names: &'a [String]) -> BoxFuture<'a, anyhow::Result<Vec<Vec<f32>>>> {
+//!         self.0.fetch_add(names.len(), Ordering::SeqCst);
+//!         let vectors = names
+//!             .iter()
+//!             .map(|name| if name.starts_with('S') { vec![1.0, 0.0] } else { vec![0.0, 1.0] })
+//!             .collect();
+//!         Box::pin(async move { Ok(vectors) })
+//!     }
+//! }
+//!
+//! let dir = std::env::temp_dir().join(format!("namegen-doc-semantic-{}", std::process::id()));
+//! let _ = std::fs::remove_dir_all(&dir);
+//! let embedded = Arc::new(AtomicUsize::new(0));
+//! let run = || {
+//!     futures::executor::block_on(
+//!         Generation::builder()
+//!             .structure("NAME = {\n    # Ship names\n    ships = {\n    }\n}\n")
+//!             .lore("")
+//!             .min_names(1)
+//!             .semantic_dedup(true)
+//!             .generator(Initials(embedded.clone()))
+//!             .cache(FsCache::new(&dir))
+//!             .run(),
+//!     )
+//!     .unwrap()
+//! };
+//! let result = run();
+//! let names: Vec<&str> = result.localisation.iter().map(|(_, name)| name.as_str()).collect();
+//! assert_eq!(names, ["Silent Sorrow", "Iron Dawn"]);
+//! let block = &result.report.blocks[0];
+//! assert_eq!(block.dropped["semantic"], 1);
+//! assert_eq!(block.semantic[0].dropped, ["Sorrowful Silence"]);
+//! // The second run reads every embedding from the cache
+//! run();
+//! assert_eq!(embedded.load(Ordering::SeqCst), 3);
+//! # std::fs::remove_dir_all(&dir).unwrap();
+//! ```
+
+use serde::Serialize;
+use std::collections::BTreeMap;
+
+/// Similarity from which two names count as the same, see [`dedup`]
+pub const DEFAULT_THRESHOLD: f64 = 0.9;
+
+/// Model the names are embedded with unless `--embedding-model` names another
+pub const DEFAULT_EMBEDDING_MODEL: &str = "text-embedding-3-small";
+
+/// The embedding model and the similarity the pass drops names at
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SemanticDedup {
+    pub model: String,
+    pub threshold: f64,
+}
+
+/// A kept name and the names dropped as meaning the same
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SemanticCluster {
+    pub kept: String,
+    pub dropped: Vec<String>,
+}
+
+/// Cosine similarity of two vectors, 0 if either is all zeros
+pub fn cosine(a: &[f32], b: &[f32]) -> f64 {
+    let (mut dot, mut norm_a, mut norm_b) = (0.0, 0.0, 0.0);
+    for (x, y) in a.iter().zip(b) {
+        let (x, y) = (f64::from(*x), f64::from(*y));
+        dot += x * y;
+        norm_a += x * x;
+        norm_b += y * y;
+    }
+    if norm_a == 0.0 || norm_b == 0.0 {
+        return 0.0;
+    }
+    dot / (norm_a.sqrt() * norm_b.sqrt())
+}
+
+/// Drops every name at least `threshold` similar to a name kept before it, in order, adding it
+/// to the cluster of the most similar kept name. Names in `keep` and names without an
+/// embedding are always kept. Returns the clusters that took any name
+pub fn dedup(
+    names: &mut Vec<String>,
+    embeddings: &BTreeMap<String, Vec<f32>>,
+    threshold: f64,
+    keep: &[String],
+) -> Vec<SemanticCluster> {
+    let mut kept: Vec<(String, Option<&Vec<f32>>)> = Vec::new();
+    let mut clusters: Vec<SemanticCluster> = Vec::new();
+    for name in std::mem::take(names) {
+        let embedding = embeddings.get(&name);
+        let closest = embedding
+            .filter(|_| !keep.iter().any(|pin| pin.eq_ignore_ascii_case(&name)))
+            .and_then(|embedding| {
+                kept.iter()
+                    .filter_map(|(other, vector)| Some((other, cosine(embedding, (*vector)?))))
+                    .filter(|(_, similarity)| *similarity >= threshold)
+                    .max_by(|a, b| a.1.total_cmp(&b.1))
+                    .map(|(other, _)| other.clone())
+            });
+        match closest {
+            Some(other) => match clusters.iter_mut().find(|cluster| cluster.kept == other) {
+                Some(cluster) => cluster.dropped.push(name),
+                None => clusters.push(SemanticCluster {
+                    kept: other,
+                    dropped: vec![name],
+                }),
+            },
+            None => kept.push((name, embedding)),
+        }
+    }
+    names.extend(kept.into_iter().map(|(name, _)| name));
+    clusters
+}
@@ -0,0 +1,50 @@
+use crate::cache::NameCache;
+use crate::json::parse_names;
+use crate::lore::LoreProfiles;
+use crate::pipeline::{AI_MODEL, INDENT};
+use crate::structure::{GenerationJob, parse_structure};
+use anyhow::{Context, Result};
+use std::fs;
+use std::path::Path;
+
+/// Writes every block's cached names, sorted and grouped per block path, so two snapshots diff cleanly
+pub fn write_snapshot(
+    structure: &str,
+    cache: &dyn NameCache,
+    lore: &LoreProfiles,
+    output: &Path,
+) -> Result<()> {
+    let (_, jobs) = parse_structure(structure, AI_MODEL, INDENT)?;
+    let mut jobs: Vec<&GenerationJob> = jobs.iter().collect();
+    jobs.sort_by_key(|job| job.path.join("/"));
+
+    let mut out = String::new();
+    for job in jobs {
+        out.push_str(&format!("== {} ==\n", job.path.join("/")));
+        out.push_str(&format!("theme: {}\n", job.theme));
+        match cache.get(&job.cache_key(lore)?) {
+            Some(block) => match parse_names(&block.raw) {
+                Ok(names) => {
+                    let mut names: Vec<String> = names
+                        .iter()
+                        .map(|name| name.trim().to_string())
+                        .filter(|name| !name.is_empty())
+                        .collect();
+                    names.sort_by(|a, b| a.to_lowercase().cmp(&b.to_lowercase()).then(a.cmp(b)));
+                    names.dedup();
+                    out.push_str(&format!("count: {}\n", names.len()));
+                    for name in names {
+                        out.push_str(&format!("{}\n", name));
+                    }
+                }
+                Err(e) => out.push_str(&format!("(unreadable cache: {})\n", e)),
+            },
+            None => out.push_str("(not cached)\n"),
+        }
+        out.push('\n');
+    }
+
+    fs::write(output, out).with_context(|| format!("Failed to write {}", output.display()))?;
+    println!("[Snapshot] Wrote '{}'", output.display());
+    Ok(())
+}
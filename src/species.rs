@@ -0,0 +1,195 @@
+//! Naming hints per species class, added to the prompt of every block generated for the class
+//! on top of its theme.
+//!
+//! ```
+//! use stellaris_name_gen::species::SpeciesClasses;
+//!
+//! let classes = SpeciesClasses::default();
+//! assert_eq!(
+//!     classes.hint("avian").unwrap(),
+//!     "favor airy vowels, references to wind, flight and song"
+//! );
+//! assert_eq!(classes.hint("Avian").unwrap(), classes.hint("avian").unwrap());
+//!
+//! // Typos are caught before any request, with the closest class
+//! let error = classes.hint("avain").unwrap_err();
+//! assert_eq!(error.to_string(), "Unknown species class 'avain', did you mean 'avian'?");
+//!
+//! // The config replaces built-in hints and adds classes of its own
+//! let classes = classes.with_hints([
+//!     ("avian".to_string(), "favor shrill, whistling names".to_string()),
+//!     ("crystalline".to_string(), "favor sharp, ringing sounds".to_string()),
+//! ]);
+//! assert_eq!(classes.hint("avian").unwrap(), "favor shrill, whistling names");
+//! assert_eq!(classes.hint("crystalline").unwrap(), "favor sharp, ringing sounds");
+//! ```
+//!
+//! The class comes from `#@ species-class:`, inherited by nested blocks, or the run's
+//! `--species-class`. The hint a block was generated with is recorded in its cache metadata,
+//! and a cached block whose hint no longer matches is generated again:
+//!
+//! ```
+//! use stellaris_name_gen::{BlockKey, FsCache, Generation, MockGenerator, NameCache};
+//!
+//! let dir = std::env::temp_dir().join(format!("namegen-doc-species-{}", std::process::id()));
+//! let _ = std::fs::remove_dir_all(&dir);
+//! let run = |class: &str, names: &[&str]| {
+//!     let result = futures::executor::block_on(
+//!         Generation::builder()
+//!             .structure("#@ species-class: fungoid\nNAME = {\n    # Ship names\n    ships = {\n    }\n}\n")
+//!             .lore("")
+//!             .generator(MockGenerator::new(names.to_vec()))
+//!             .species_hint("fungoid", class)
+//!             .cache(FsCache::new(&dir))
+//!             .run(),
+//!     )
+//!     .unwrap();
+//!     result.localisation[0].1.clone()
+//! };
+//! assert_eq!(run("favor damp, earthy names", &["Sporehold"]), "Sporehold");
+//! let block = FsCache::new(&dir).get(&BlockKey::from("NAME_ships")).unwrap();
+//! assert_eq!(block.species_hint.as_deref(), Some("fungoid: favor damp, earthy names"));
+//! // Same hint, cached names
+//! assert_eq!(run("favor damp, earthy names", &["Mycelia"]), "Sporehold");
+//! // Changed hint, new names
+//! assert_eq!(run("favor names of rot and bloom", &["Mycelia"]), "Mycelia");
+//! # std::fs::remove_dir_all(&dir).unwrap();
+//! ```
+
+use anyhow::Result;
+use std::collections::BTreeMap;
+
+/// Hints of the species classes available without any configuration
+pub const BUILTIN_SPECIES_CLASSES: &[(&str, &str)] = &[
+    (
+        "humanoid",
+        "favor names that sound like human languages and history",
+    ),
+    (
+        "mammalian",
+        "favor warm, rounded sounds, references to herds, dens and seasons",
+    ),
+    (
+        "reptilian",
+        "favor hissing sibilants, references to scales, sun and stone",
+    ),
+    (
+        "avian",
+        "favor airy vowels, references to wind, flight and song",
+    ),
+    (
+        "arthropoid",
+        "favor clicking consonants, references to hives, carapaces and swarms",
+    ),
+    (
+        "molluscoid",
+        "favor soft, wet sounds, references to shells, tides and spirals",
+    ),
+    (
+        "fungoid",
+        "favor damp, earthy sounds, references to spores, decay and networks",
+    ),
+    (
+        "plantoid",
+        "favor slow, gentle sounds, references to roots, seeds and light",
+    ),
+    (
+        "lithoid",
+        "favor heavy, grinding sounds, references to minerals, strata and ages",
+    ),
+    (
+        "necroid",
+        "favor solemn, hollow sounds, references to death, ritual and remembrance",
+    ),
+    (
+        "aquatic",
+        "favor liquid, flowing sounds, references to currents, depths and reefs",
+    ),
+    (
+        "toxoid",
+        "favor harsh, acrid sounds, references to venom, smog and mutation",
+    ),
+    (
+        "machine",
+        "favor designations, numbers and technical terms over organic words",
+    ),
+];
+
+/// Species classes by name to their hint, the built-in ones unless replaced
+#[derive(Debug, Clone)]
+pub struct SpeciesClasses {
+    hints: BTreeMap<String, String>,
+}
+
+impl Default for SpeciesClasses {
+    fn default() -> Self {
+        Self {
+            hints: BUILTIN_SPECIES_CLASSES
+                .iter()
+                .map(|(class, hint)| (class.to_string(), hint.to_string()))
+                .collect(),
+        }
+    }
+}
+
+impl SpeciesClasses {
+    /// Replaces the hints of known classes and adds the others, ie from `[species-classes]`
+    pub fn with_hints(mut self, hints: impl IntoIterator<Item = (String, String)>) -> Self {
+        self.hints.extend(
+            hints
+                .into_iter()
+                .map(|(class, hint)| (class.to_lowercase(), hint)),
+        );
+        self
+    }
+
+    /// Hint of a class, any case. Unknown classes fail with the closest known one
+    pub fn hint(&self, class: &str) -> Result<&str> {
+        let class = class.trim().to_lowercase();
+        if let Some(hint) = self.hints.get(&class) {
+            return Ok(hint);
+        }
+        match self.closest(&class) {
+            Some(known) => anyhow::bail!(
+                "Unknown species class '{}', did you mean '{}'?",
+                class,
+                known
+            ),
+            None => anyhow::bail!(
+                "Unknown species class '{}', expected one of {}",
+                class,
+                self.classes().collect::<Vec<_>>().join(", ")
+            ),
+        }
+    }
+
+    /// Every known class, sorted
+    pub fn classes(&self) -> impl Iterator<Item = &str> {
+        self.hints.keys().map(String::as_str)
+    }
+
+    /// The known class fewest edits away, if close enough to be a typo
+    fn closest(&self, class: &str) -> Option<&str> {
+        self.classes()
+            .map(|known| (edit_distance(class, known), known))
+            .filter(|(distance, _)| *distance <= 2)
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, known)| known)
+    }
+}
+
+/// Levenshtein distance between two words, in characters
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diagonal = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let substituted = diagonal + usize::from(ca != *cb);
+            diagonal = row[j + 1];
+            row[j + 1] = substituted.min(row[j] + 1).min(diagonal + 1);
+        }
+    }
+    row[b.len()]
+}
@@ -0,0 +1,429 @@
+//! Accumulates streamed chunks, guarding against keep-alive whitespace and content re-sent
+//! after a reconnect.
+//!
+//! ```
+//! use stellaris_name_gen::stream::StreamAccumulator;
+//!
+//! let mut acc = StreamAccumulator::new();
+//! acc.push(r#"{"names": ["Lantern", "#);
+//! // Keep-alive whitespace is dropped
+//! acc.push("   \n");
+//! acc.push(r#""Vigil", "Cinder"#);
+//! // A reconnect re-sends the tail it already delivered
+//! acc.push(r#""Vigil", "Cinder", "Ember"]}"#);
+//! assert_eq!(acc.text(), r#"{"names": ["Lantern", "Vigil", "Cinder", "Ember"]}"#);
+//! assert_eq!(acc.dropped_whitespace(), 1);
+//! assert_eq!(acc.trimmed_overlap(), r#""Vigil", "Cinder"#.len());
+//! ```
+//!
+//! Short coincidental overlaps, such as a chunk starting with the quote that ended the previous
+//! one, are legitimate content and kept:
+//!
+//! ```
+//! use stellaris_name_gen::stream::StreamAccumulator;
+//!
+//! let mut acc = StreamAccumulator::new();
+//! for chunk in [r#"["a", ""#, r#""]"#, " "] {
+//!     acc.push(chunk);
+//! }
+//! assert_eq!(acc.text(), r#"["a", ""]"#);
+//! assert_eq!(acc.trimmed_overlap(), 0);
+//!
+//! // Totals reported by the provider are checked against what was received
+//! assert_eq!(acc.total_mismatch(acc.received()), None);
+//! assert_eq!(acc.total_mismatch(3), Some((acc.received(), 3)));
+//!
+//! // Line based answers keep line breaks that arrive as their own chunk
+//! let mut acc = StreamAccumulator::for_lines();
+//! for chunk in ["Lantern", " \n ", "Vigil", "  "] {
+//!     acc.push(chunk);
+//! }
+//! assert_eq!(acc.text(), "Lantern\nVigil");
+//!
+//! // Whitespace streamed inside a name is part of it
+//! let mut acc = StreamAccumulator::new();
+//! for chunk in [r#"{"names": ["Iron"#, " ", r#"Star", "#, " ", r#""Ember"]}"#] {
+//!     acc.push(chunk);
+//! }
+//! assert_eq!(acc.text(), r#"{"names": ["Iron Star", "Ember"]}"#);
+//! assert_eq!(acc.dropped_whitespace(), 1);
+//! ```
+//!
+//! Names are picked out of the accumulating text as soon as their closing quote arrives, for
+//! progress and to stop a stream early:
+//!
+//! ```
+//! use stellaris_name_gen::stream::NameExtractor;
+//!
+//! let mut names = NameExtractor::new();
+//! assert!(names.update(r#"{"names": ["Lan"#).is_empty());
+//! assert_eq!(names.update(r#"{"names": ["Lantern", "The \"Vigil\"", "Cin"#), ["Lantern", "The \"Vigil\""]);
+//! // Keys and the fields of custom schema objects are not names
+//! names.update(r#"{"names": ["Lantern", "The \"Vigil\"", "Cinder", {"name": "Ember", "rarity": "rare"}]}"#);
+//! assert_eq!(names.names(), ["Lantern", "The \"Vigil\"", "Cinder", "Ember"]);
+//! assert_eq!(names.recent(3), ["The \"Vigil\"", "Cinder", "Ember"]);
+//!
+//! // Line based answers yield a name per completed line
+//! let mut lines = NameExtractor::for_lines();
+//! assert_eq!(lines.update("1. Lantern\n2. Vig"), ["Lantern"]);
+//! ```
+//!
+//! A block needing a known number of names stops its stream once they arrived, the names
+//! collected making up a complete answer:
+//!
+//! ```
+//! use stellaris_name_gen::ResponseFormat;
+//! use stellaris_name_gen::stream::NameStream;
+//!
+//! let names: Vec<String> = (1..=500).map(|n| format!("Vessel {}", n)).collect();
+//! let answer = serde_json::json!({ "names": names }).to_string();
+//! // 40 names needed plus a margin of 5
+//! let mut stream = NameStream::new(ResponseFormat::Names, Some(45));
+//! for chunk in answer.as_bytes().chunks(7) {
+//!     stream.push(std::str::from_utf8(chunk).unwrap());
+//!     if stream.is_complete() {
+//!         break;
+//!     }
+//! }
+//! assert_eq!(stream.names().len(), 45);
+//! assert!(stream.accumulator().received() < answer.len() / 5);
+//! let early = stream.early_answer().unwrap();
+//! let parsed: serde_json::Value = serde_json::from_str(&early).unwrap();
+//! assert_eq!(parsed["names"].as_array().unwrap().len(), 45);
+//! assert_eq!(parsed["names"][44], "Vessel 45");
+//!
+//! // Custom schema objects count once closed, and are answered whole
+//! let mut stream = NameStream::new(ResponseFormat::Names, Some(1));
+//! stream.push(r#"{"names": [{"name": "Vex", "rarity""#);
+//! assert!(!stream.is_complete());
+//! stream.push(r#": "rare"}, {"name": "Em"#);
+//! assert_eq!(
+//!     stream.early_answer().unwrap(),
+//!     r#"{"names": [{"name": "Vex", "rarity": "rare"}]}"#
+//! );
+//! ```
+
+use crate::generator::ResponseFormat;
+
+/// Overlaps shorter than this are assumed to be coincidence rather than re-sent content
+const MIN_OVERLAP: usize = 16;
+/// Longest tail searched for re-sent content
+const MAX_OVERLAP: usize = 4096;
+
+/// Sanitizes and concatenates streamed chunks
+#[derive(Debug, Clone, Default)]
+pub struct StreamAccumulator {
+    combined: String,
+    /// Bytes of every chunk as received, before any sanitation
+    received: usize,
+    dropped_whitespace: usize,
+    trimmed_overlap: usize,
+    /// Keeps a line break for whitespace chunks holding one, for line based answers
+    keep_newlines: bool,
+    /// The text so far ends inside a JSON string, where whitespace is content
+    in_string: bool,
+    escaped: bool,
+}
+
+impl StreamAccumulator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// For one-name-per-line answers, where a whitespace chunk may be the only line break
+    pub fn for_lines() -> Self {
+        Self {
+            keep_newlines: true,
+            ..Self::default()
+        }
+    }
+
+    /// Appends a chunk, returning the part that was actually added
+    pub fn push<'a>(&mut self, chunk: &'a str) -> &'a str {
+        self.received += chunk.len();
+        if chunk.trim().is_empty() && !self.in_string {
+            if self.keep_newlines && chunk.contains('\n') {
+                self.combined.push('\n');
+                return "\n";
+            }
+            if !chunk.is_empty() {
+                self.dropped_whitespace += 1;
+            }
+            return "";
+        }
+        let overlap = self.overlap(chunk);
+        self.trimmed_overlap += overlap;
+        let added = &chunk[overlap..];
+        self.combined.push_str(added);
+        self.scan(added);
+        added
+    }
+
+    /// Follows JSON strings through newly added text, line based answers have none
+    fn scan(&mut self, added: &str) {
+        if self.keep_newlines {
+            return;
+        }
+        for c in added.chars() {
+            if self.escaped {
+                self.escaped = false;
+            } else if self.in_string && c == '\\' {
+                self.escaped = true;
+            } else if c == '"' {
+                self.in_string = !self.in_string;
+            }
+        }
+    }
+
+    /// Length of the longest head of `chunk` that repeats the tail of the text so far
+    fn overlap(&self, chunk: &str) -> usize {
+        let longest = MAX_OVERLAP.min(chunk.len()).min(self.combined.len());
+        (MIN_OVERLAP..=longest)
+            .rev()
+            .filter(|&len| chunk.is_char_boundary(len))
+            .find(|&len| self.combined.ends_with(&chunk[..len]))
+            .unwrap_or(0)
+    }
+
+    pub fn text(&self) -> &str {
+        &self.combined
+    }
+
+    pub fn into_text(self) -> String {
+        self.combined
+    }
+
+    /// Bytes received, keep-alive whitespace and re-sent content included
+    pub fn received(&self) -> usize {
+        self.received
+    }
+
+    /// Number of whitespace-only chunks dropped
+    pub fn dropped_whitespace(&self) -> usize {
+        self.dropped_whitespace
+    }
+
+    /// Bytes of re-sent content trimmed
+    pub fn trimmed_overlap(&self) -> usize {
+        self.trimmed_overlap
+    }
+
+    /// Compares a total reported by the provider with the bytes received, as (received, reported)
+    pub fn total_mismatch(&self, reported: usize) -> Option<(usize, usize)> {
+        (reported != self.received).then_some((self.received, reported))
+    }
+}
+
+/// Incrementally extracts completed names from the text of a stream as it accumulates
+#[derive(Debug, Clone, Default)]
+pub struct NameExtractor {
+    names: Vec<String>,
+    /// Each name's entry as it appears in the text, a JSON string or a custom schema object
+    entries: Vec<String>,
+    /// Bytes of the text already scanned
+    pos: usize,
+    lines: bool,
+    /// Open containers, `[` or `{`, each with whether it holds names and where it starts
+    stack: Vec<(u8, bool, usize)>,
+    /// Name of the custom schema object being scanned, taken once the object closes
+    pending: Option<String>,
+    /// Start of the string being scanned, at its opening quote
+    string_start: Option<usize>,
+    escaped: bool,
+    /// The next string in the current object is a value rather than a key
+    after_colon: bool,
+    last_key: String,
+}
+
+impl NameExtractor {
+    /// For JSON answers, `{"names": [...]}` with strings or `{"name": ...}` objects
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// For one-name-per-line answers
+    pub fn for_lines() -> Self {
+        Self {
+            lines: true,
+            ..Self::default()
+        }
+    }
+
+    /// Scans what was added to `text` since the last call, which must have been a prefix of
+    /// it, returning the names completed in the meantime. A custom schema object's name is
+    /// completed along with the object
+    pub fn update(&mut self, text: &str) -> &[String] {
+        let before = self.names.len();
+        if self.lines {
+            while let Some(end) = text[self.pos..].find('\n') {
+                let line = crate::json::clean_line(&text[self.pos..self.pos + end]);
+                if !line.is_empty() {
+                    self.names.push(line);
+                }
+                self.pos += end + 1;
+            }
+            return &self.names[before..];
+        }
+        let bytes = text.as_bytes();
+        while self.pos < bytes.len() {
+            let idx = self.pos;
+            self.pos += 1;
+            let byte = bytes[idx];
+            if let Some(start) = self.string_start {
+                match byte {
+                    _ if self.escaped => self.escaped = false,
+                    b'\\' => self.escaped = true,
+                    b'"' => {
+                        self.string_start = None;
+                        let raw = &text[start..=idx];
+                        let value: String = serde_json::from_str(raw).unwrap_or_default();
+                        self.string_done(value, raw);
+                    }
+                    _ => {}
+                }
+                continue;
+            }
+            match byte {
+                b'"' => self.string_start = Some(idx),
+                b'[' => {
+                    let names = match self.stack.last() {
+                        None => true,
+                        Some((b'{', _, _)) => self.after_colon && self.last_key == "names",
+                        Some(_) => false,
+                    };
+                    self.stack.push((b'[', names, idx));
+                }
+                b'{' => {
+                    let entry = matches!(self.stack.last(), Some((b'[', true, _)));
+                    self.stack.push((b'{', entry, idx));
+                    self.after_colon = false;
+                }
+                b']' | b'}' => {
+                    if let Some((b'{', true, start)) = self.stack.pop()
+                        && let Some(name) = self.pending.take()
+                    {
+                        self.names.push(name);
+                        self.entries.push(text[start..=idx].to_string());
+                    }
+                    self.after_colon = false;
+                }
+                b':' => self.after_colon = true,
+                b',' => self.after_colon = false,
+                _ => {}
+            }
+        }
+        &self.names[before..]
+    }
+
+    fn string_done(&mut self, value: String, raw: &str) {
+        match self.stack.last() {
+            Some((b'[', true, _)) => {
+                self.names.push(value);
+                self.entries.push(raw.to_string());
+            }
+            Some((b'{', true, _)) if self.after_colon && self.last_key == "name" => {
+                self.pending = Some(value)
+            }
+            Some((b'{', _, _)) if !self.after_colon => self.last_key = value,
+            _ => {}
+        }
+    }
+
+    /// Every name completed so far
+    pub fn names(&self) -> &[String] {
+        &self.names
+    }
+
+    /// The last `count` names completed
+    pub fn recent(&self, count: usize) -> &[String] {
+        &self.names[self.names.len().saturating_sub(count)..]
+    }
+}
+
+/// A streamed answer followed chunk by chunk, complete once it holds the names needed
+#[derive(Debug, Clone)]
+pub struct NameStream {
+    accumulator: StreamAccumulator,
+    /// `None` for answers that are not names, ie scores
+    extractor: Option<NameExtractor>,
+    target: Option<usize>,
+    lines: bool,
+}
+
+impl NameStream {
+    /// Follows an answer in `format`, complete after `target` names when there is one
+    pub fn new(format: ResponseFormat, target: Option<usize>) -> Self {
+        let lines = format == ResponseFormat::Lines;
+        Self {
+            accumulator: if lines {
+                StreamAccumulator::for_lines()
+            } else {
+                StreamAccumulator::new()
+            },
+            extractor: match format {
+                ResponseFormat::Names | ResponseFormat::JsonNames => Some(NameExtractor::new()),
+                ResponseFormat::Lines => Some(NameExtractor::for_lines()),
+                ResponseFormat::Scores | ResponseFormat::Value | ResponseFormat::Translations => {
+                    None
+                }
+            },
+            target,
+            lines,
+        }
+    }
+
+    /// Whether names are picked out of the answer at all
+    pub fn tracks_names(&self) -> bool {
+        self.extractor.is_some()
+    }
+
+    /// Appends a chunk, returning the part that was actually added
+    pub fn push<'a>(&mut self, chunk: &'a str) -> &'a str {
+        let added = self.accumulator.push(chunk);
+        if let Some(extractor) = &mut self.extractor {
+            extractor.update(self.accumulator.text());
+        }
+        added
+    }
+
+    /// Every name completed so far
+    pub fn names(&self) -> &[String] {
+        self.extractor.as_ref().map_or(&[], NameExtractor::names)
+    }
+
+    /// The last `count` names completed
+    pub fn recent(&self, count: usize) -> &[String] {
+        self.extractor
+            .as_ref()
+            .map_or(&[], |extractor| extractor.recent(count))
+    }
+
+    /// Whether the target number of names arrived, so the rest of the stream can be dropped
+    pub fn is_complete(&self) -> bool {
+        self.target
+            .is_some_and(|target| self.names().len() >= target)
+    }
+
+    /// The first `target` names as a complete answer of the requested format, once complete.
+    /// Custom schema objects are kept whole, every field included
+    pub fn early_answer(&self) -> Option<String> {
+        let target = self.target.filter(|_| self.is_complete())?;
+        let extractor = self.extractor.as_ref()?;
+        Some(if self.lines {
+            extractor.names[..target].join("\n")
+        } else {
+            format!(
+                "{{\"names\": [{}]}}",
+                extractor.entries[..target].join(", ")
+            )
+        })
+    }
+
+    pub fn accumulator(&self) -> &StreamAccumulator {
+        &self.accumulator
+    }
+
+    pub fn into_accumulator(self) -> StreamAccumulator {
+        self.accumulator
+    }
+}
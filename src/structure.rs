@@ -0,0 +1,837 @@
+use crate::cache::BlockKey;
+use crate::compose::parse_compose;
+use crate::directives::{
+    Decoration, Directives, Era, ScoreFilter, SortMode, parse_alias, parse_anchor, parse_flag,
+    parse_initials, parse_names,
+};
+use crate::distribution::Distribution;
+use crate::euphony::NameStyle;
+use crate::inline::{InlineLine, parse_placeholders};
+use crate::keys::{KeyStyle, block_prefix};
+use crate::lore::LoreProfiles;
+use crate::ordinals::OrdinalExpand;
+use crate::quoting::{split_all_unquoted, split_unquoted, strip_comment, unquote};
+use crate::rarity::Rarity;
+use crate::replay::Replayed;
+use anyhow::{Context, Result};
+
+/// Holds parsing context for each block in the structure file
+struct ContextEntry {
+    /// Indentation of the block's children, from its first child line or else its opening line
+    /// and the indentation unit
+    child_indent: String,
+    /// Output position of its opening line
+    started_at: usize,
+    /// Output position of the line after its opening line and inserts
+    opened_at: usize,
+    directives: Directives,
+    has_data: bool,
+    /// Holds a `randomized = no` line of its own, which keeps it in order like the insert does
+    randomized_no: bool,
+    /// Hand-written entries of the block, seeds of an `augment-inline` block
+    seeds: Vec<String>,
+    /// Line of its `#@ insert-here` and the output position the names are spliced at
+    anchor: Option<(usize, usize)>,
+    /// Only comments followed its opening line so far, which describe it unless a child
+    /// block follows them
+    fresh: bool,
+    child_count: usize,
+    path: Vec<String>,
+}
+
+/// A themed leaf block waiting on generation
+pub(crate) struct GenerationJob {
+    pub(crate) path: Vec<String>,
+    pub(crate) theme: String,
+    pub(crate) prefix: String,
+    /// `None` without a sort directive, leaving the order to the run's shuffle seed
+    pub(crate) sort: Option<SortMode>,
+    /// Phonetic style the names are scored and filtered toward after generation
+    pub(crate) style: Option<NameStyle>,
+    /// Initial letters the names are balanced toward, from `# distribution:`
+    pub(crate) distribution: Option<Distribution>,
+    /// Kept in order in game (`randomized = no`)
+    pub(crate) sequential: bool,
+    pub(crate) decoration: Option<Decoration>,
+    /// Uppercase initials the block's names must not start with
+    pub(crate) avoid_initials: Vec<char>,
+    pub(crate) model: String,
+    /// Keys come from the abbreviated name while the display value is spelled out
+    pub(crate) abbreviate: bool,
+    /// Fewest names the block may end up with, overriding the run's minimum
+    pub(crate) min_names: Option<usize>,
+    /// Optional second pass rating and pruning the names
+    pub(crate) score_filter: Option<ScoreFilter>,
+    /// Lore profile selected with `# lore:`, `None` for the default lore
+    pub(crate) lore: Option<String>,
+    /// Path of a JSON schema replacing the built-in names schema
+    pub(crate) schema: Option<String>,
+    /// `#@ key-style:` options applied over the run's key style
+    pub(crate) key_style: Option<String>,
+    /// Hand-written entries an `augment-inline` block adds to, never repeated by its names
+    pub(crate) seeds: Vec<String>,
+    /// Draft names the block is promoted from, shown to the model as examples of the style
+    /// wanted, see [`crate::draft`]
+    pub(crate) examples: Vec<String>,
+    /// Names the blocks ahead of it in its unique scope already hold, listed as taken in the
+    /// prompt, see [`crate::unique`]
+    pub(crate) taken: Vec<String>,
+    /// Its entries are translated by a translation pass, see [`crate::translate`]
+    pub(crate) translatable: bool,
+    /// `#@ species-class:` of the block or an ancestor, overriding the run's class
+    pub(crate) species_class: Option<String>,
+    /// Period the names belong to, from `# era:` of the block or an ancestor. Cached apart from
+    /// other eras of the same block
+    pub(crate) era: Option<Era>,
+    /// Names needed, from `# count:`. The stream stops once they arrived and the block keeps
+    /// no more than this many
+    pub(crate) max_names: Option<usize>,
+    /// Names a sequential block starts with, from `#@ first:`, whether generated or not
+    pub(crate) first: Vec<String>,
+    /// `key = value` lines written ahead of the names, only if the block emits any
+    pub(crate) if_generated: Vec<String>,
+    /// Parameters stored with the block's earlier names, replayed by `--match-existing`
+    pub(crate) replayed: Option<Replayed>,
+    /// `# rarity=` of the block or an ancestor, `None` follows the run's rarity. High rarity
+    /// blocks are cached apart from the others
+    pub(crate) rarity: Option<Rarity>,
+    /// Paths the block was cached under before a rename, from `# alias:` of the block or its
+    /// ancestors. Their cache moves to the block's own key the first time it is missing
+    pub(crate) aliases: Vec<Vec<String>>,
+    /// Numbered variants every name is emitted as, from `#@ ordinal-expand:`. `max_names`
+    /// then counts the base names
+    pub(crate) ordinal_expand: Option<OrdinalExpand>,
+    /// Jobs of the child blocks whose names are combined, from `# compose:`. Such a block asks
+    /// for no names of its own
+    pub(crate) compose: Vec<usize>,
+    /// Combinations of the pools' names, set once the pools are generated
+    pub(crate) composed: Option<Vec<String>>,
+    pub(crate) child_indent: String,
+}
+
+impl GenerationJob {
+    /// A job outside of any structure file, with no directives
+    pub(crate) fn standalone(path: Vec<String>, theme: String, model: String) -> Self {
+        Self {
+            path,
+            theme,
+            prefix: String::new(),
+            sort: None,
+            style: None,
+            distribution: None,
+            sequential: true,
+            decoration: None,
+            avoid_initials: Vec::new(),
+            model,
+            abbreviate: false,
+            min_names: None,
+            score_filter: None,
+            lore: None,
+            schema: None,
+            key_style: None,
+            seeds: Vec::new(),
+            examples: Vec::new(),
+            taken: Vec::new(),
+            translatable: false,
+            species_class: None,
+            era: None,
+            first: Vec::new(),
+            if_generated: Vec::new(),
+            replayed: None,
+            rarity: None,
+            max_names: None,
+            aliases: Vec::new(),
+            ordinal_expand: None,
+            compose: Vec::new(),
+            composed: None,
+            child_indent: String::new(),
+        }
+    }
+
+    /// Key of this block's cached AI output, which also covers the content of its lore profile
+    pub(crate) fn cache_key(&self, lore: &LoreProfiles) -> Result<BlockKey> {
+        self.cache_key_at(&self.path, lore)
+    }
+
+    /// Keys the block was cached under at each of its aliases, in order
+    pub(crate) fn alias_keys(&self, lore: &LoreProfiles) -> Result<Vec<BlockKey>> {
+        self.aliases
+            .iter()
+            .map(|alias| self.cache_key_at(alias, lore))
+            .collect()
+    }
+
+    /// Key of this block's cached AI output, were it at `path`
+    fn cache_key_at(&self, path: &[String], lore: &LoreProfiles) -> Result<BlockKey> {
+        let key = BlockKey::from_path(path);
+        match &self.lore {
+            Some(profile) => lore
+                .cache_key(key, profile)
+                .with_context(|| format!("Invalid lore for '{}'", self.path.join("/"))),
+            None => Ok(lore.default_key(key)),
+        }
+        .map(|key| match self.era {
+            Some(era) => BlockKey::from(format!("{}@era-{}", key.as_str(), era.as_str()).as_str()),
+            None => key,
+        })
+        .map(|key| match self.rarity {
+            Some(Rarity::High) => BlockKey::from(format!("{}@rarity-high", key.as_str()).as_str()),
+            _ => key,
+        })
+    }
+}
+
+/// A line of the output, the slot a job's entries are spliced into, or a line with placeholders
+pub(crate) enum Segment {
+    Line(String),
+    Generated(usize),
+    Inline(InlineLine),
+}
+
+/// Parses the structure file into output segments and the generation jobs they reference.
+/// Blocks without a child line to take their indentation from indent by the file's unit, or
+/// `indent` if no block shows one
+pub(crate) fn parse_structure(
+    structure: &str,
+    default_model: &str,
+    indent: &str,
+) -> Result<(Vec<Segment>, Vec<GenerationJob>)> {
+    let mut stack: Vec<ContextEntry> = Vec::new();
+    let mut pending = Directives::default();
+    let mut output: Vec<Segment> = Vec::new();
+    let mut jobs: Vec<GenerationJob> = Vec::new();
+    let all_lines: Vec<&str> = structure.lines().collect();
+    let unit = indent_unit(&all_lines).unwrap_or_else(|| indent.to_string());
+
+    for (line_no, raw_line) in all_lines.iter().copied().enumerate() {
+        let trimmed = raw_line.trim();
+
+        if let Some(comment) = trimmed.strip_prefix('#') {
+            let keep = parse_anchor(comment)
+                .with_context(|| format!("file_structure.txt:{}", line_no + 1))?;
+            if let Some(keep) = keep {
+                settle(&mut stack, &mut pending, &mut output, line_no)?;
+                let Some(ctx) = stack.last_mut() else {
+                    anyhow::bail!(
+                        "file_structure.txt:{}: insert-here outside of a block",
+                        line_no + 1
+                    );
+                };
+                if let Some((first, _)) = ctx.anchor {
+                    anyhow::bail!(
+                        "file_structure.txt:{}: '{}' already has an insert-here on line {}",
+                        line_no + 1,
+                        ctx.path.join("/"),
+                        first
+                    );
+                }
+                ctx.anchor = Some((line_no + 1, output.len()));
+                if keep {
+                    output.push(Segment::Line(raw_line.to_string()));
+                }
+                continue;
+            }
+            pending
+                .record(comment)
+                .with_context(|| format!("file_structure.txt:{}", line_no + 1))?;
+            continue;
+        }
+        pending.end_theme();
+        // Quoted keys and values may hold `=`, `,` and `#` of their own
+        let code = strip_comment(trimmed).trim_end();
+
+        if code.ends_with('{') {
+            let key = split_unquoted(code, '=')
+                .map(|(a, _)| a.trim())
+                .unwrap_or(code)
+                .to_string();
+            let mut path = if let Some(parent) = stack.last() {
+                parent.path.clone()
+            } else {
+                Vec::new()
+            };
+            path.push(key.clone());
+            let mut directives = std::mem::take(&mut pending);
+            // Resolved here so descendants inherit this block's prefix, not their own
+            let inherited = stack
+                .last()
+                .and_then(|parent| parent.directives.prefix.clone());
+            directives.prefix = resolve_prefix(directives.prefix.take(), inherited, &path);
+            if let Some(parent) = stack.last() {
+                directives.model = directives.model.or_else(|| parent.directives.model.clone());
+                directives.lore = directives.lore.or_else(|| parent.directives.lore.clone());
+                directives.key_style = directives
+                    .key_style
+                    .or_else(|| parent.directives.key_style.clone());
+                directives.translatable = directives
+                    .translatable
+                    .or_else(|| parent.directives.translatable.clone());
+                directives.species_class = directives
+                    .species_class
+                    .or_else(|| parent.directives.species_class.clone());
+                directives.era = directives.era.or_else(|| parent.directives.era.clone());
+                directives.rarity = directives
+                    .rarity
+                    .or_else(|| parent.directives.rarity.clone());
+                // A renamed block's descendants were cached under its old paths too
+                for alias in &parent.directives.aliases {
+                    directives.aliases.push(format!("{}/{}", alias, key));
+                }
+            }
+
+            // Reuse the block's own whitespace so tabs and spaces never mix
+            let child_indent = first_child_indent(&all_lines, line_no)
+                .unwrap_or_else(|| format!("{}{}", leading(raw_line), unit));
+            let started_at = output.len();
+            output.push(Segment::Line(raw_line.to_string()));
+            for kv in &directives.kv_inserts {
+                output.push(Segment::Line(format!("{}{}", child_indent, kv)));
+            }
+            normalize_if_generated(&mut directives.if_generated, line_no)?;
+            if let Some(parent) = stack.last_mut() {
+                parent.fresh = false;
+            }
+            stack.push(ContextEntry {
+                child_indent,
+                started_at,
+                opened_at: output.len(),
+                directives,
+                has_data: false,
+                randomized_no: false,
+                seeds: Vec::new(),
+                anchor: None,
+                fresh: true,
+                child_count: 0,
+                path,
+            });
+            continue;
+        }
+
+        if !trimmed.is_empty() {
+            settle(&mut stack, &mut pending, &mut output, line_no)?;
+        }
+
+        if code == "}" {
+            // Augmented blocks generate on top of their hand-written entries
+            let augment = match stack
+                .last()
+                .and_then(|ctx| ctx.directives.augment_inline.as_deref())
+            {
+                Some(flag) => parse_flag(flag).with_context(|| {
+                    format!("file_structure.txt:{}: invalid augment-inline", line_no + 1)
+                })?,
+                None => false,
+            };
+            let anchor = stack
+                .last()
+                .and_then(|ctx| Some((ctx.anchor?.0, ctx.path.join("/"))));
+            let closing = stack.last().map(|ctx| (ctx.path.clone(), ctx.started_at));
+            let queued = jobs.len();
+            if stack
+                .last()
+                .is_some_and(|ctx| ctx.directives.compose.is_some())
+                && let Some(ctx) = stack.pop()
+            {
+                let job = compose_job(ctx, &jobs, &mut output, default_model)?;
+                jobs.push(job);
+            } else if let Some(ctx) = stack.pop()
+                && ctx.child_count == 0
+                && (!ctx.has_data || augment)
+                && let Some(theme) = ctx.directives.theme.clone()
+            {
+                let block = ctx.path.join("/");
+                let sort = match &ctx.directives.sort {
+                    Some(sort) => SortMode::parse(sort, &ctx.path)
+                        .map(Some)
+                        .with_context(|| format!("Invalid sort for '{}'", block))?,
+                    None => None,
+                };
+                let style = match &ctx.directives.style {
+                    Some(style) => Some(
+                        NameStyle::parse(style)
+                            .with_context(|| format!("Invalid style for '{}'", block))?,
+                    ),
+                    None => None,
+                };
+                let distribution = match &ctx.directives.distribution {
+                    Some(distribution) => Some(
+                        Distribution::parse(distribution)
+                            .with_context(|| format!("Invalid distribution for '{}'", block))?,
+                    ),
+                    None => None,
+                };
+                let sequential = ctx.directives.is_sequential() || ctx.randomized_no;
+                let decoration = match &ctx.directives.decorate {
+                    Some(template) => Some(
+                        Decoration::parse(template, sequential)
+                            .with_context(|| format!("Invalid decoration for '{}'", block))?,
+                    ),
+                    None => None,
+                };
+                let avoid_initials = match &ctx.directives.avoid_initials {
+                    Some(letters) => parse_initials(letters)
+                        .with_context(|| format!("Invalid avoid-initials for '{}'", block))?,
+                    None => Vec::new(),
+                };
+                let score_filter = match &ctx.directives.score_filter {
+                    Some(filter) => Some(
+                        ScoreFilter::parse(filter)
+                            .with_context(|| format!("Invalid score-filter for '{}'", block))?,
+                    ),
+                    None => None,
+                };
+                let (min_names, max_names) = parse_bounds(&ctx.directives, &block)?;
+                let ordinal_expand = match &ctx.directives.ordinal_expand {
+                    Some(value) => Some(
+                        OrdinalExpand::parse(value)
+                            .with_context(|| format!("Invalid ordinal-expand for '{}'", block))?,
+                    ),
+                    None => None,
+                };
+                // The count covers the variants, the model is asked for the base names fitting in it
+                let max_names = match (max_names, ordinal_expand) {
+                    (Some(count), Some(ordinals)) if count < ordinals.count => anyhow::bail!(
+                        "'{}' keeps {} names, fewer than the {} variants of a single name",
+                        block,
+                        count,
+                        ordinals.count
+                    ),
+                    (Some(count), Some(ordinals)) => Some(count / ordinals.count),
+                    (max_names, _) => max_names,
+                };
+                if let Some(options) = &ctx.directives.key_style {
+                    KeyStyle::default()
+                        .with_options(options)
+                        .with_context(|| format!("Invalid key-style for '{}'", block))?;
+                }
+                let abbreviate = match &ctx.directives.abbreviate {
+                    Some(flag) => parse_flag(flag)
+                        .with_context(|| format!("Invalid abbreviate for '{}'", block))?,
+                    None => false,
+                };
+                let translatable = match &ctx.directives.translatable {
+                    Some(flag) => parse_flag(flag)
+                        .with_context(|| format!("Invalid translatable for '{}'", block))?,
+                    None => false,
+                };
+                let era = match &ctx.directives.era {
+                    Some(era) => Some(
+                        Era::parse(era).with_context(|| format!("Invalid era for '{}'", block))?,
+                    ),
+                    None => None,
+                };
+                let rarity = match &ctx.directives.rarity {
+                    Some(rarity) => Some(
+                        Rarity::parse(rarity)
+                            .with_context(|| format!("Invalid rarity for '{}'", block))?,
+                    ),
+                    None => None,
+                };
+                let first = match &ctx.directives.first {
+                    Some(names) => parse_names(names)
+                        .with_context(|| format!("Invalid first for '{}'", block))?,
+                    None => Vec::new(),
+                };
+                if !first.is_empty() && !sequential {
+                    anyhow::bail!(
+                        "'{}' lists first names but the game shuffles it, add randomized = no",
+                        block
+                    );
+                }
+                if let Some(count) = max_names
+                    && first.len() > count
+                {
+                    anyhow::bail!(
+                        "'{}' lists {} first names but keeps only {}",
+                        block,
+                        first.len(),
+                        count
+                    );
+                }
+                // Names listed first go through the block's filters like generated ones
+                for name in &first {
+                    if name
+                        .chars()
+                        .find(|c| c.is_alphabetic())
+                        .is_some_and(|c| c.to_uppercase().any(|c| avoid_initials.contains(&c)))
+                    {
+                        anyhow::bail!("'{}' lists '{}' first but avoids its initial", block, name);
+                    }
+                    if augment && ctx.seeds.iter().any(|seed| seed.eq_ignore_ascii_case(name)) {
+                        anyhow::bail!(
+                            "'{}' lists '{}' first but it is already one of its entries",
+                            block,
+                            name
+                        );
+                    }
+                }
+                let mut aliases: Vec<Vec<String>> = Vec::new();
+                for alias in &ctx.directives.aliases {
+                    let alias = parse_alias(alias)
+                        .with_context(|| format!("Invalid alias for '{}'", block))?;
+                    if alias != ctx.path && !aliases.contains(&alias) {
+                        aliases.push(alias);
+                    }
+                }
+                let end = output.len();
+                let at = collapse_blank_lines(
+                    &mut output,
+                    ctx.opened_at,
+                    ctx.anchor.map_or(end, |(_, at)| at),
+                );
+                // Names go at the block's anchor, else after its last line
+                output.insert(at, Segment::Generated(jobs.len()));
+                jobs.push(GenerationJob {
+                    path: ctx.path,
+                    theme,
+                    prefix: ctx.directives.prefix.unwrap_or_default(),
+                    sort,
+                    style,
+                    distribution,
+                    sequential,
+                    decoration,
+                    avoid_initials,
+                    model: ctx
+                        .directives
+                        .model
+                        .unwrap_or_else(|| default_model.to_string()),
+                    abbreviate,
+                    min_names,
+                    score_filter,
+                    lore: ctx.directives.lore,
+                    schema: ctx
+                        .directives
+                        .schema
+                        .map(|path| path.trim_matches('"').to_string()),
+                    key_style: ctx.directives.key_style,
+                    seeds: if augment { ctx.seeds } else { Vec::new() },
+                    examples: Vec::new(),
+                    taken: Vec::new(),
+                    translatable,
+                    species_class: ctx.directives.species_class,
+                    era,
+                    max_names,
+                    first,
+                    if_generated: ctx.directives.if_generated,
+                    replayed: None,
+                    rarity,
+                    aliases,
+                    ordinal_expand,
+                    compose: Vec::new(),
+                    composed: None,
+                    child_indent: ctx.child_indent,
+                });
+            }
+            if let Some((line, block)) = anchor
+                && jobs.len() == queued
+            {
+                anyhow::bail!(
+                    "file_structure.txt:{}: insert-here in '{}', which generates no names",
+                    line,
+                    block
+                );
+            }
+            output.push(Segment::Line(raw_line.to_string()));
+            if let Some(parent) = stack.last_mut() {
+                parent.child_count += 1;
+                parent.has_data = true;
+            }
+            // The pools of a composing block only feed its names, they emit no block of their own
+            if let Some((path, started_at)) = closing
+                && jobs.len() > queued
+                && let Some(parent) = stack.last()
+                && let Some(compose) = &parent.directives.compose
+            {
+                let parts = parse_compose(compose)
+                    .with_context(|| format!("Invalid compose for '{}'", parent.path.join("/")))?;
+                if parts.iter().any(|part| Some(part) == path.last()) {
+                    output.truncate(started_at);
+                }
+            }
+            continue;
+        }
+
+        let placeholders = parse_placeholders(raw_line)
+            .with_context(|| format!("file_structure.txt:{}", line_no + 1))?;
+        if placeholders.is_empty() {
+            output.push(Segment::Line(raw_line.to_string()));
+        } else {
+            let directives = stack.last().map(|ctx| &ctx.directives);
+            output.push(Segment::Inline(InlineLine {
+                line: raw_line.to_string(),
+                line_no: line_no + 1,
+                path: stack.last().map(|ctx| ctx.path.clone()).unwrap_or_default(),
+                prefix: directives
+                    .and_then(|d| d.prefix.clone())
+                    .unwrap_or_default(),
+                model: directives
+                    .and_then(|d| d.model.clone())
+                    .unwrap_or_else(|| default_model.to_string()),
+                lore: directives.and_then(|d| d.lore.clone()),
+                key_style: directives.and_then(|d| d.key_style.clone()),
+                placeholders,
+            }));
+        }
+        if let Some(ctx) = stack.last_mut()
+            && trimmed.replace(' ', "") == "randomized=no"
+        {
+            ctx.randomized_no = true;
+        } else if let Some(ctx) = stack.last_mut()
+            && !code.is_empty()
+        {
+            if split_unquoted(code, '=').is_some() {
+                ctx.has_data = true;
+            } else {
+                // Entries come one per line or separated by `,` or `;`, like other lists
+                ctx.has_data |= code.contains([',', ';']);
+                ctx.seeds.extend(
+                    split_all_unquoted(code, ',')
+                        .into_iter()
+                        .flat_map(|part| split_all_unquoted(part, ';'))
+                        .map(unquote)
+                        .filter(|entry| !entry.is_empty()),
+                );
+            }
+        }
+    }
+    Ok((output, jobs))
+}
+
+/// The job of a block composing names from its child blocks, see [`crate::compose`]. Its names
+/// go at its anchor, else after its last line
+fn compose_job(
+    ctx: ContextEntry,
+    jobs: &[GenerationJob],
+    output: &mut Vec<Segment>,
+    default_model: &str,
+) -> Result<GenerationJob> {
+    let block = ctx.path.join("/");
+    let parts = ctx.directives.compose.as_deref().unwrap_or_default();
+    let parts = parse_compose(parts).with_context(|| format!("Invalid compose for '{}'", block))?;
+    // Combinations are emitted as they are, nothing shapes or filters them like generated names
+    let directives = &ctx.directives;
+    let ignored: Vec<&str> = [
+        ("style", directives.style.is_some()),
+        ("distribution", directives.distribution.is_some()),
+        ("decorate", directives.decorate.is_some()),
+        ("avoid-initials", directives.avoid_initials.is_some()),
+        ("lore", directives.lore.is_some()),
+        ("score-filter", directives.score_filter.is_some()),
+        ("abbreviate", directives.abbreviate.is_some()),
+        ("schema", directives.schema.is_some()),
+        ("augment-inline", directives.augment_inline.is_some()),
+        ("translatable", directives.translatable.is_some()),
+        ("species-class", directives.species_class.is_some()),
+        ("era", directives.era.is_some()),
+        ("first", directives.first.is_some()),
+        ("rarity", directives.rarity.is_some()),
+        ("ordinal-expand", directives.ordinal_expand.is_some()),
+        ("alias", !directives.aliases.is_empty()),
+    ]
+    .into_iter()
+    .filter(|(_, set)| *set)
+    .map(|(directive, _)| directive)
+    .collect();
+    if !ignored.is_empty() {
+        anyhow::bail!(
+            "'{}' composes its names, which {} can't apply to",
+            block,
+            ignored.join(", ")
+        );
+    }
+    let mut compose = Vec::new();
+    for part in &parts {
+        let mut path = ctx.path.clone();
+        path.push(part.clone());
+        let pool = jobs
+            .iter()
+            .position(|job| job.path == path)
+            .with_context(|| {
+                format!(
+                    "'{}' composes '{}', which is not one of its generated blocks",
+                    block, part
+                )
+            })?;
+        compose.push(pool);
+    }
+    let sort = match &ctx.directives.sort {
+        Some(sort) => SortMode::parse(sort, &ctx.path)
+            .map(Some)
+            .with_context(|| format!("Invalid sort for '{}'", block))?,
+        None => None,
+    };
+    let (min_names, max_names) = parse_bounds(&ctx.directives, &block)?;
+    let sequential = ctx.directives.is_sequential() || ctx.randomized_no;
+    let theme = ctx
+        .directives
+        .theme
+        .clone()
+        .unwrap_or_else(|| parts.join(" + "));
+    let model = ctx
+        .directives
+        .model
+        .unwrap_or_else(|| default_model.to_string());
+    let end = output.len();
+    let at = collapse_blank_lines(output, ctx.opened_at, ctx.anchor.map_or(end, |(_, at)| at));
+    output.insert(at, Segment::Generated(jobs.len()));
+    let mut job = GenerationJob::standalone(ctx.path, theme, model);
+    job.prefix = ctx.directives.prefix.unwrap_or_default();
+    job.sort = sort;
+    job.sequential = sequential;
+    job.min_names = min_names;
+    job.max_names = max_names;
+    job.key_style = ctx.directives.key_style;
+    job.if_generated = ctx.directives.if_generated;
+    job.compose = compose;
+    job.child_indent = ctx.child_indent;
+    Ok(job)
+}
+
+/// The `# min:` and `# count:` of a block
+fn parse_bounds(directives: &Directives, block: &str) -> Result<(Option<usize>, Option<usize>)> {
+    let min_names = match &directives.min {
+        Some(min) => Some(
+            min.parse::<usize>()
+                .with_context(|| format!("Invalid min '{}' for '{}'", min, block))?,
+        ),
+        None => None,
+    };
+    let max_names = match &directives.count {
+        Some(count) => Some(
+            count
+                .parse::<usize>()
+                .ok()
+                .filter(|count| *count > 0)
+                .with_context(|| format!("Invalid count '{}' for '{}'", count, block))?,
+        ),
+        None => None,
+    };
+    Ok((min_names, max_names))
+}
+
+/// Leading whitespace of a line
+fn leading(line: &str) -> &str {
+    &line[..line.len() - line.trim_start().len()]
+}
+
+/// Indentation of the first line inside the block opened on line `open`, if it has one
+/// indented deeper than the opening line
+fn first_child_indent(lines: &[&str], open: usize) -> Option<String> {
+    let first = lines[open + 1..]
+        .iter()
+        .find(|line| !line.trim().is_empty())?;
+    let (outer, inner) = (leading(lines[open]), leading(first));
+    (!first.trim_start().starts_with('}') && inner.len() > outer.len() && inner.starts_with(outer))
+        .then(|| inner.to_string())
+}
+
+/// Indentation the file adds per nesting level, from the first block with a child line
+fn indent_unit(lines: &[&str]) -> Option<String> {
+    (0..lines.len())
+        .filter(|&idx| lines[idx].trim_end().ends_with('{'))
+        .find_map(|idx| {
+            let inner = first_child_indent(lines, idx)?;
+            Some(inner[leading(lines[idx]).len()..].to_string())
+        })
+}
+
+/// Collapses the runs of blank lines right before and after position `at`, where the names
+/// of a generated block go, into their first line. Blank lines further from it are
+/// hand-written and kept, and the run before never reaches back past `start`. Returns where
+/// position `at` moved to
+fn collapse_blank_lines(output: &mut Vec<Segment>, start: usize, at: usize) -> usize {
+    let blank =
+        |segment: &Segment| matches!(segment, Segment::Line(line) if line.trim().is_empty());
+    let mut end = at;
+    while end < output.len() && blank(&output[end]) {
+        end += 1;
+    }
+    if end > at + 1 {
+        output.drain(at + 1..end);
+    }
+    let mut first = at;
+    while first > start && blank(&output[first - 1]) {
+        first -= 1;
+    }
+    if at > first + 1 {
+        output.drain(first + 1..at);
+    }
+    at.min(first + 1)
+}
+
+/// Hands the comments written first inside the innermost block over to it, once a line other
+/// than a child block follows them. They win over the comments preceding the block
+fn settle(
+    stack: &mut [ContextEntry],
+    pending: &mut Directives,
+    output: &mut Vec<Segment>,
+    line_no: usize,
+) -> Result<()> {
+    let Some((ctx, parents)) = stack.split_last_mut() else {
+        return Ok(());
+    };
+    if !std::mem::take(&mut ctx.fresh) {
+        return Ok(());
+    }
+    let mut inside = std::mem::take(pending);
+    // Composed on the parent's prefix, as if written before the block
+    if let Some(written) = inside.prefix.take() {
+        let inherited = parents
+            .last()
+            .and_then(|parent| parent.directives.prefix.clone());
+        ctx.directives.prefix = resolve_prefix(Some(written), inherited, &ctx.path);
+    }
+    normalize_if_generated(&mut inside.if_generated, line_no)?;
+    // Nothing but comments were read since the opening line and its inserts
+    for kv in &inside.kv_inserts {
+        output.push(Segment::Line(format!("{}{}", ctx.child_indent, kv)));
+    }
+    ctx.directives.apply_inside(inside);
+    Ok(())
+}
+
+/// The prefix of a block from its own `# prefix:` and the one it inherits. `+PART` appends
+/// `PART_` to the inherited prefix, `-` clears it for the block and its descendants, `auto`
+/// derives it from the block's key and anything else replaces it
+fn resolve_prefix(
+    written: Option<String>,
+    inherited: Option<String>,
+    path: &[String],
+) -> Option<String> {
+    let Some(written) = written else {
+        return inherited;
+    };
+    if written == "-" {
+        return None;
+    }
+    if written == "auto" {
+        return Some(block_prefix(path));
+    }
+    let Some(part) = written.strip_prefix('+') else {
+        return Some(written);
+    };
+    let part = part.trim().trim_matches('_');
+    if part.is_empty() {
+        return inherited;
+    }
+    match inherited.as_deref().map(|base| base.trim_end_matches('_')) {
+        Some(base) if !base.is_empty() => Some(format!("{}_{}_", base, part)),
+        _ => Some(format!("{}_", part)),
+    }
+}
+
+/// Checks `# if-generated:` inserts are `key = value` and spaces them like other inserts. They
+/// are written once the block's names are known, with the names
+fn normalize_if_generated(inserts: &mut [String], line_no: usize) -> Result<()> {
+    for kv in inserts {
+        let (k, v) = split_unquoted(kv, '=').with_context(|| {
+            format!(
+                "file_structure.txt:{}: expected if-generated: key = value, got '{}'",
+                line_no + 1,
+                kv
+            )
+        })?;
+        *kv = format!("{} = {}", k.trim(), v.trim());
+    }
+    Ok(())
+}
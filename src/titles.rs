@@ -0,0 +1,136 @@
+//! Ruler titles, government adjectives and other localisation entries with keys of their own,
+//! generated from the `[titles]` config table without going through the structure file.
+//!
+//! ```
+//! use stellaris_name_gen::titles::TitlesConfig;
+//! use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+//!
+//! let config: TitlesConfig = toml::from_str(
+//!     r#"
+//!     namespace = "ARK"
+//!     [[keys]]
+//!     key = "RULER_TITLE_{n}"
+//!     theme = "Titles of a matriarchal ruler"
+//!     count = 2
+//!     "#,
+//! )
+//! .unwrap();
+//! let tasks = config.tasks().unwrap();
+//! assert_eq!(tasks[1].key, "ARK_RULER_TITLE_2");
+//!
+//! let generation = Generation::builder()
+//!     .structure("")
+//!     .lore("")
+//!     .generator(MockGenerator::new(["High \"Eternal\" Matriarch", "Mother Regent", "Warden"]))
+//!     .cache(InMemoryCache::new())
+//!     .build()
+//!     .unwrap();
+//! let result = futures::executor::block_on(generation.run_tasks(&tasks)).unwrap();
+//! assert_eq!(result.report.blocks[0].path, vec!["titles", "ARK_RULER_TITLE_1"]);
+//! assert!(result.localisation_yml().contains(
+//!     "    ARK_RULER_TITLE_1:0 \"High \\\"Eternal\\\" Matriarch\"\n    ARK_RULER_TITLE_2:0 \"Mother Regent\"\n"
+//! ));
+//! ```
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+
+/// A single localisation key and the theme its value is generated from
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LocalisationTask {
+    pub key: String,
+    pub theme: String,
+    /// Model used instead of the run's default
+    pub model: Option<String>,
+}
+
+impl LocalisationTask {
+    pub fn new(key: impl Into<String>, theme: impl Into<String>) -> Self {
+        Self {
+            key: key.into(),
+            theme: theme.into(),
+            model: None,
+        }
+    }
+}
+
+/// The `[titles]` config table
+#[derive(Debug, Clone, Default, Deserialize, Serialize)]
+#[serde(default, rename_all = "kebab-case")]
+pub struct TitlesConfig {
+    /// Prepended to every key with a `_`, ie `ARK`
+    pub namespace: Option<String>,
+    /// The `[[titles.keys]]` entries
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub keys: Vec<TitleKey>,
+}
+
+/// A key pattern, ie `RULER_TITLE_{n}`, and what its values are about
+#[derive(Debug, Clone, Deserialize, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub struct TitleKey {
+    /// `{n}` is replaced by 1..=count, required when count is above 1
+    pub key: String,
+    pub theme: String,
+    #[serde(default = "one")]
+    pub count: usize,
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+fn one() -> usize {
+    1
+}
+
+impl TitlesConfig {
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// Expands every key pattern into its namespaced keys, in config order
+    pub fn tasks(&self) -> Result<Vec<LocalisationTask>> {
+        let namespace = self
+            .namespace
+            .as_deref()
+            .map(|namespace| namespace.trim_end_matches('_'))
+            .filter(|namespace| !namespace.is_empty());
+        let mut tasks = Vec::new();
+        for title in &self.keys {
+            let context = || format!("Invalid title key '{}'", title.key);
+            if title.count > 1 && !title.key.contains("{n}") {
+                anyhow::bail!(
+                    "Title key '{}' has a count of {} but no {{n}} to number it",
+                    title.key,
+                    title.count
+                );
+            }
+            for n in 1..=title.count {
+                let key = title.key.replace("{n}", &n.to_string());
+                validate_key(&key).with_context(context)?;
+                tasks.push(LocalisationTask {
+                    key: match namespace {
+                        Some(namespace) => format!("{}_{}", namespace, key),
+                        None => key,
+                    },
+                    theme: title.theme.clone(),
+                    model: title.model.clone(),
+                });
+            }
+        }
+        Ok(tasks)
+    }
+}
+
+/// Keys are written as is, so only characters localisation files accept in keys are allowed
+fn validate_key(key: &str) -> Result<()> {
+    if key.is_empty() {
+        anyhow::bail!("Empty key");
+    }
+    if let Some(c) = key
+        .chars()
+        .find(|c| !(c.is_ascii_alphanumeric() || "_.-".contains(*c)))
+    {
+        anyhow::bail!("'{}' is not allowed in a localisation key", c);
+    }
+    Ok(())
+}
@@ -0,0 +1,148 @@
+//! Translates the localisation of `#@ translatable` blocks into the game's other languages.
+//!
+//! Names are proper nouns and stay as generated, so blocks are untranslatable unless marked.
+//! Descriptive entries such as station names are worth a machine translation over English
+//! strings. Every language gets a full localisation, entries that were not translated keep
+//! their English value:
+//!
+//! ```
+//! use futures::future::BoxFuture;
+//! use stellaris_name_gen::translate::Language;
+//! use stellaris_name_gen::{
+//!     Generation, GenerationRequest, InMemoryCache, NameGenerator, ResponseFormat,
+//! };
+//!
+//! /// Names a block, then "translates" by shouting
+//! struct Shouting;
+//!
+//! impl NameGenerator for Shouting {
+//!     fn generate<'a>(&'a self, request: &'a GenerationRequest) -> BoxFuture<'a, anyhow::Result<String>> {
+//!         let answer = match request.format {
+//!             ResponseFormat::Translations => {
+//!                 // Every key to translate is a property of the schema, described by its value
+//!                 let keys = &request.schema.as_ref().unwrap()["properties"]["translations"]["properties"];
+//!                 let translations: serde_json::Map<String, serde_json::Value> = keys
+//!                     .as_object()
+//!                     .unwrap()
+//!                     .iter()
+//!                     .map(|(key, spec)| (key.clone(), spec["description"].as_str().unwrap().to_uppercase().into()))
+//!                     .collect();
+//!                 serde_json::json!({ "translations": translations }).to_string()
+//!             }
+//!             _ if request.theme == "Station names" => r#"{"names": ["Deep Harbor"]}"#.to_string(),
+//!             _ => r#"{"names": ["Vex"]}"#.to_string(),
+//!         };
+//!         Box::pin(async move { Ok(answer) })
+//!     }
+//! }
+//!
+//! let structure = "NAME = {\n    # Station names\n    # translatable\n    stations = {\n    }\n    # Leader names\n    leaders = {\n    }\n}\n";
+//! let generation = Generation::builder()
+//!     .structure(structure)
+//!     .lore("")
+//!     .generator(Shouting)
+//!     .cache(InMemoryCache::new())
+//!     .build()
+//!     .unwrap();
+//! let mut result = futures::executor::block_on(generation.run()).unwrap();
+//! futures::executor::block_on(generation.translate(&mut result, &[Language::German])).unwrap();
+//! assert_eq!(
+//!     result.translated_yml(Language::German).unwrap(),
+//!     "l_german:\n    DEEP_HARBOR:0 \"DEEP HARBOR\"\n    VEX:0 \"Vex\"\n"
+//! );
+//! assert_eq!(result.report.translations[0].translated, 1);
+//! assert_eq!(result.report.translations[0].fallback, 0);
+//!
+//! // Translations are cached per key, language and English value
+//! futures::executor::block_on(generation.translate(&mut result, &[Language::German])).unwrap();
+//! assert_eq!(result.report.translations[1].cached, 1);
+//! ```
+
+use serde::{Deserialize, Serialize};
+
+/// A language the game ships localisation for, besides English
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    Deserialize,
+    Serialize,
+    clap::ValueEnum,
+)]
+#[serde(rename_all = "snake_case")]
+pub enum Language {
+    #[value(name = "braz_por")]
+    BrazPor,
+    French,
+    German,
+    Polish,
+    Russian,
+    Spanish,
+    #[value(name = "simp_chinese")]
+    SimpChinese,
+    Japanese,
+    Korean,
+}
+
+impl Language {
+    /// Identifier the game uses, ie `german` in `l_german` and `localisation/german/`
+    pub fn id(&self) -> &'static str {
+        match self {
+            Language::BrazPor => "braz_por",
+            Language::French => "french",
+            Language::German => "german",
+            Language::Polish => "polish",
+            Language::Russian => "russian",
+            Language::Spanish => "spanish",
+            Language::SimpChinese => "simp_chinese",
+            Language::Japanese => "japanese",
+            Language::Korean => "korean",
+        }
+    }
+
+    /// Name of the language as given to the model
+    pub fn name(&self) -> &'static str {
+        match self {
+            Language::BrazPor => "Brazilian Portuguese",
+            Language::French => "French",
+            Language::German => "German",
+            Language::Polish => "Polish",
+            Language::Russian => "Russian",
+            Language::Spanish => "Spanish",
+            Language::SimpChinese => "Simplified Chinese",
+            Language::Japanese => "Japanese",
+            Language::Korean => "Korean",
+        }
+    }
+}
+
+/// Outcome of translating a run into one language
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TranslationReport {
+    pub language: Language,
+    /// Entries holding a translation, cached ones included
+    pub translated: usize,
+    /// Translations read from the cache
+    pub cached: usize,
+    /// Entries of translatable blocks that kept their English value
+    pub fallback: usize,
+    /// Why entries fell back, one message per failed block
+    pub errors: Vec<String>,
+}
+
+impl TranslationReport {
+    pub fn new(language: Language) -> Self {
+        Self {
+            language,
+            translated: 0,
+            cached: 0,
+            fallback: 0,
+            errors: Vec::new(),
+        }
+    }
+}
@@ -0,0 +1,80 @@
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+
+/// How far name uniqueness reaches across blocks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize, clap::ValueEnum)]
+#[serde(rename_all = "lowercase")]
+pub enum UniqueScope {
+    /// Names only need to be unique within their own block
+    #[default]
+    Block,
+    /// Names are unique across every block of the same category. A block under none of the
+    /// configured path prefixes is in the category of its top-level block, ie `NAME` for
+    /// `NAME/ship_names/military`
+    Category,
+    /// Names are unique across the whole run
+    Global,
+}
+
+impl UniqueScope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            UniqueScope::Block => "block",
+            UniqueScope::Category => "category",
+            UniqueScope::Global => "global",
+        }
+    }
+}
+
+/// Tracks names already emitted per scope. Fed in document order, so the outcome never depends
+/// on which block finished generating first
+pub(crate) struct UniqueNames<'a> {
+    scope: UniqueScope,
+    /// Block path prefixes (`NAME/ship_names`) to category names
+    categories: &'a HashMap<String, String>,
+    seen: HashMap<String, HashSet<String>>,
+}
+
+impl<'a> UniqueNames<'a> {
+    pub(crate) fn new(scope: UniqueScope, categories: &'a HashMap<String, String>) -> Self {
+        Self {
+            scope,
+            categories,
+            seen: HashMap::new(),
+        }
+    }
+
+    /// Category of the longest configured path prefix of a block, `None` if none matches
+    pub(crate) fn configured_category(&self, path: &[String]) -> Option<String> {
+        let joined = path.join("/");
+        self.categories
+            .iter()
+            .filter(|(prefix, _)| joined == **prefix || joined.starts_with(&format!("{}/", prefix)))
+            .max_by_key(|(prefix, _)| prefix.len())
+            .map(|(_, category)| category.clone())
+    }
+
+    /// Category of a block, the configured one or else its first path component
+    pub(crate) fn category(&self, path: &[String]) -> String {
+        self.configured_category(path)
+            .unwrap_or_else(|| path.first().cloned().unwrap_or_default())
+    }
+
+    /// The scope a block's names are unique in, shared by the blocks of the same scope
+    pub(crate) fn scope_id(&self, path: &[String]) -> String {
+        match self.scope {
+            UniqueScope::Block => path.join("/"),
+            UniqueScope::Category => self.category(path),
+            UniqueScope::Global => String::new(),
+        }
+    }
+
+    /// Records `name` for the block at `path`, returning false if its scope already has it
+    pub(crate) fn admit(&mut self, path: &[String], name: &str) -> bool {
+        let scope_id = self.scope_id(path);
+        self.seen
+            .entry(scope_id)
+            .or_default()
+            .insert(name.to_lowercase())
+    }
+}
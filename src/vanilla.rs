@@ -0,0 +1,228 @@
+//! Localisation keys of the base game, so generated keys never override a vanilla string, and of
+//! the mod's own hand-written localisation.
+//!
+//! ```
+//! use stellaris_name_gen::VanillaKeys;
+//!
+//! let keys = VanillaKeys::load("samples/vanilla_localisation".as_ref(), None).unwrap();
+//! // BOMs, comments and `:0` numbering are tolerated, matching ignores case and trailing whitespace
+//! assert!(keys.contains("AURORA"));
+//! assert!(keys.contains("aurora "));
+//! assert!(keys.contains("SHIP_CLASS_CORVETTE"));
+//! assert!(keys.contains("PREFIX_VIGIL"));
+//! assert!(!keys.contains("COMMENTED_OUT"));
+//! assert_eq!(keys.rename("AURORA"), Some("AURORA_2".to_string()));
+//! assert_eq!(keys.rename("BOREALIS"), None);
+//!
+//! // Generated keys matching a vanilla key are renamed and reported
+//! use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator};
+//!
+//! let result = futures::executor::block_on(
+//!     Generation::builder()
+//!         .structure("NAME = {\n    # Ship names\n    ships = {\n    }\n}\n")
+//!         .lore("")
+//!         .generator(MockGenerator::new(["Aurora", "Borealis"]))
+//!         .cache(InMemoryCache::new())
+//!         .vanilla_keys(keys)
+//!         .run(),
+//! )
+//! .unwrap();
+//! assert!(result.name_list.contains("        AURORA_2,\n        BOREALIS,\n"));
+//! assert_eq!(result.localisation[0], ("AURORA_2".to_string(), "Aurora".to_string()));
+//! assert_eq!(
+//!     result.report.vanilla_collisions,
+//!     vec![("AURORA".to_string(), "AURORA_2".to_string())]
+//! );
+//! ```
+//!
+//! The mod's own hand-written localisation is reserved the same way with
+//! [`crate::GenerationBuilder::existing_keys`]. A suffix is only handed out if it is free in both
+//! key sets, so a generated key is renamed the same way on every run:
+//!
+//! ```
+//! use stellaris_name_gen::vanilla::yml_files;
+//! use stellaris_name_gen::{Generation, InMemoryCache, MockGenerator, VanillaKeys};
+//!
+//! let files = yml_files("samples/mod_localisation/mod_events_l_english.yml".as_ref()).unwrap();
+//! let existing = VanillaKeys::from_files(&files).unwrap();
+//! assert!(existing.contains("EMBER_2"));
+//! let vanilla = VanillaKeys::load("samples/vanilla_localisation".as_ref(), None).unwrap();
+//!
+//! let run = || {
+//!     futures::executor::block_on(
+//!         Generation::builder()
+//!             .structure("NAME = {\n    # Ship names\n    ships = {\n    }\n}\n")
+//!             .lore("")
+//!             .generator(MockGenerator::new(["Aurora", "Ember", "Vex"]))
+//!             .cache(InMemoryCache::new())
+//!             .vanilla_keys(vanilla.clone())
+//!             .existing_keys(existing.clone())
+//!             .run(),
+//!     )
+//!     .unwrap()
+//! };
+//! let result = run();
+//! let keys: Vec<&str> = result.localisation.iter().map(|(key, _)| key.as_str()).collect();
+//! // AURORA_2 and EMBER_2 are the mod's own
+//! assert_eq!(keys, ["AURORA_3", "EMBER_3", "VEX"]);
+//! assert_eq!(
+//!     result.report.vanilla_collisions,
+//!     vec![("AURORA".to_string(), "AURORA_3".to_string())]
+//! );
+//! assert_eq!(
+//!     result.report.existing_collisions,
+//!     vec![("EMBER".to_string(), "EMBER_3".to_string())]
+//! );
+//! assert_eq!(run().localisation, result.localisation);
+//! ```
+
+use crate::hash::digest;
+use anyhow::{Context, Result};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{BufRead, BufReader};
+use std::path::{Path, PathBuf};
+use std::time::UNIX_EPOCH;
+
+/// Every localisation key found in the game's `localisation` folder
+#[derive(Debug, Clone, Default)]
+pub struct VanillaKeys {
+    /// Normalised with [`normalize`]
+    keys: HashSet<String>,
+}
+
+/// Keys compare case-insensitively and without surrounding whitespace, as the game resolves them
+fn normalize(key: &str) -> String {
+    key.trim().to_lowercase()
+}
+
+/// Key of a `key:0 "value"` (or `key: "value"`) yml entry, `None` for headers, comments and blanks
+fn entry_key(line: &str) -> Option<&str> {
+    let line = line.trim_start_matches('\u{feff}').trim();
+    if line.starts_with('#') {
+        return None;
+    }
+    let (key, rest) = line.split_once(':')?;
+    let rest = rest
+        .trim_start_matches(|c: char| c.is_ascii_digit())
+        .trim_start();
+    (!key.is_empty() && rest.starts_with('"')).then_some(key.trim())
+}
+
+impl VanillaKeys {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds every key of a yml document
+    pub fn extend_from_reader(&mut self, reader: impl BufRead) -> Result<()> {
+        for line in reader.lines() {
+            if let Some(key) = entry_key(&line?) {
+                self.keys.insert(normalize(key));
+            }
+        }
+        Ok(())
+    }
+
+    /// Parses every `.yml` file below `dir`, or the file it names. With a `cache_dir`, the key set is stored there
+    /// under a hash of the directory listing and reused until a file changes
+    pub fn load(dir: &Path, cache_dir: Option<&Path>) -> Result<Self> {
+        let files = yml_files(dir)?;
+        let mut listing = String::new();
+        for file in &files {
+            let meta = fs::metadata(file)?;
+            let modified = meta
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .map(|elapsed| elapsed.as_secs())
+                .unwrap_or_default();
+            listing.push_str(&format!(
+                "{}\t{}\t{}\n",
+                file.display(),
+                meta.len(),
+                modified
+            ));
+        }
+        let cache_file =
+            cache_dir.map(|cache_dir| cache_dir.join(format!("vanilla-{}.keys", digest(&listing))));
+        if let Some(cache_file) = &cache_file
+            && let Ok(cached) = fs::read_to_string(cache_file)
+        {
+            let keys = cached.lines().map(str::to_string).collect();
+            return Ok(Self { keys });
+        }
+
+        let keys = Self::from_files(&files)?;
+        if let Some(cache_file) = &cache_file {
+            if let Some(parent) = cache_file.parent() {
+                fs::create_dir_all(parent).ok();
+            }
+            let mut sorted: Vec<&String> = keys.keys.iter().collect();
+            sorted.sort();
+            let text: String = sorted.iter().map(|key| format!("{}\n", key)).collect();
+            fs::write(cache_file, text)
+                .with_context(|| format!("Failed to write {}", cache_file.display()))?;
+        }
+        Ok(keys)
+    }
+
+    /// Parses the given yml files, without caching
+    pub fn from_files(files: &[PathBuf]) -> Result<Self> {
+        let mut keys = Self::new();
+        for file in files {
+            let reader = BufReader::new(
+                File::open(file).with_context(|| format!("Failed to read {}", file.display()))?,
+            );
+            keys.extend_from_reader(reader)
+                .with_context(|| format!("Failed to parse {}", file.display()))?;
+        }
+        Ok(keys)
+    }
+
+    pub fn contains(&self, key: &str) -> bool {
+        self.keys.contains(&normalize(key))
+    }
+
+    pub fn len(&self) -> usize {
+        self.keys.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.keys.is_empty()
+    }
+
+    /// A suffixed replacement for a key that collides with a vanilla one, `None` if it is free.
+    /// The same key always maps to the same replacement
+    pub fn rename(&self, key: &str) -> Option<String> {
+        if !self.contains(key) {
+            return None;
+        }
+        (2..)
+            .map(|n| format!("{}_{}", key, n))
+            .find(|candidate| !self.contains(candidate))
+    }
+}
+
+/// The `.yml` files below `path` in order, or `path` itself if it is a file
+pub fn yml_files(path: &Path) -> Result<Vec<PathBuf>> {
+    if path.is_file() {
+        return Ok(vec![path.to_path_buf()]);
+    }
+    let mut files = Vec::new();
+    collect_yml(path, &mut files).with_context(|| format!("Failed to list {}", path.display()))?;
+    files.sort();
+    Ok(files)
+}
+
+fn collect_yml(dir: &Path, files: &mut Vec<PathBuf>) -> Result<()> {
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        if path.is_dir() {
+            collect_yml(&path, files)?;
+        } else if path.extension().is_some_and(|ext| ext == "yml") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
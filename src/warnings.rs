@@ -0,0 +1,260 @@
+//! Keeps repeated warnings from burying the rest of the output. Every warning goes through
+//! [`warning!`](crate::warning), which prints the first [`THRESHOLD`] of a kind as they come and
+//! only counts the rest. A kind is the warning's format string, so `[AI Warning] Streaming
+//! error: {}` is one kind whatever the error. Once a block finishes, each kind it repeated is
+//! summed up in one `[Warnings]` line, the run's own at its end, and every message in full lands
+//! under `warnings` in the report, and in the log [`write_log`] writes. `--show-all-warnings`
+//! prints them all as they come instead. Warnings often quote generated names, so they are
+//! printed through [`Console::text`] like any name:
+//!
+//! ```
+//! use stellaris_name_gen::warning;
+//! use stellaris_name_gen::warnings::{self, THRESHOLD};
+//!
+//! for name in ["Vex", "Ember", "Dawn", "Cinder", "Ash"] {
+//!     warning!("[ASCII] 'NAME/ships': '{}' has no ASCII form", name);
+//! }
+//! let repeated = warnings::take();
+//! assert_eq!(repeated.len(), 1);
+//! assert_eq!(repeated[0].category, "[ASCII] 'NAME/ships': '…' has no ASCII form");
+//! assert_eq!(repeated[0].count, 5);
+//! assert_eq!(repeated[0].messages[4], "[ASCII] 'NAME/ships': 'Ash' has no ASCII form");
+//! assert_eq!(
+//!     repeated[0].summary(),
+//!     "[Warnings] \"[ASCII] 'NAME/ships': '…' has no ASCII form\" 5 times, 2 not shown (see report)"
+//! );
+//!
+//! // Up to the threshold every warning is printed and none is summed up
+//! for _ in 0..THRESHOLD {
+//!     warning!("[AI Warning] Stream started twice");
+//! }
+//! assert!(warnings::take().is_empty());
+//!
+//! // Warnings raised while a block's future runs are counted for the block
+//! futures::executor::block_on(warnings::scoped("NAME/ships", async {
+//!     for _ in 0..10 {
+//!         warning!("[AI Warning] Streaming error: {}", "connection reset");
+//!     }
+//! }));
+//! let repeated = warnings::take();
+//! assert_eq!((repeated[0].block.as_str(), repeated[0].count), ("NAME/ships", 10));
+//!
+//! warnings::show_all(true);
+//! for _ in 0..10 {
+//!     warning!("[AI Warning] Stream ended without starting");
+//! }
+//! assert!(warnings::take().is_empty());
+//! ```
+
+use crate::console::Console;
+use anyhow::{Context as _, Result};
+use serde::Serialize;
+use std::cell::RefCell;
+use std::collections::BTreeMap;
+use std::fs;
+use std::future::Future;
+use std::path::Path;
+use std::pin::Pin;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{LazyLock, Mutex};
+use std::task::{Context, Poll};
+
+/// Warnings of a kind printed as they come, before the rest are only counted
+pub const THRESHOLD: usize = 3;
+
+/// Print every warning as it comes, see [`show_all`]
+static SHOW_ALL: AtomicBool = AtomicBool::new(false);
+
+/// Warnings of the run by block and format string
+static LOG: Mutex<BTreeMap<(String, &'static str), RepeatedWarning>> = Mutex::new(BTreeMap::new());
+
+/// Every warning of the process in full, in the order they came, see [`write_log`]
+static HISTORY: Mutex<Vec<String>> = Mutex::new(Vec::new());
+
+/// The terminal warnings are printed to
+static CONSOLE: LazyLock<Console> = LazyLock::new(Console::detect);
+
+thread_local! {
+    /// Block whose future is polled on this thread, see [`scoped`]
+    static BLOCK: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Prints a warning through the aggregation of [`crate::warnings`], taking the arguments of
+/// `eprintln!`
+#[macro_export]
+macro_rules! warning {
+    ($format:literal $($arg:tt)*) => {
+        $crate::warnings::emit($format, format!($format $($arg)*))
+    };
+}
+
+/// Warnings of a kind a block or the run raised
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct RepeatedWarning {
+    /// Path of the block, empty for warnings of the run itself
+    pub block: String,
+    /// The warning with its details left out, ie `[AI Warning] Streaming error: …`
+    pub category: String,
+    pub count: usize,
+    /// Every one of them in full
+    pub messages: Vec<String>,
+}
+
+impl RepeatedWarning {
+    /// The line summing the warnings up
+    pub fn summary(&self) -> String {
+        let block = if self.block.is_empty() {
+            String::new()
+        } else {
+            format!("'{}': ", self.block)
+        };
+        format!(
+            "[Warnings] {}\"{}\" {} times, {} not shown (see report)",
+            block,
+            self.category,
+            self.count,
+            self.count.saturating_sub(THRESHOLD)
+        )
+    }
+}
+
+/// Prints every warning as it comes instead of counting repeats
+pub fn show_all(enabled: bool) {
+    SHOW_ALL.store(enabled, Ordering::Relaxed);
+}
+
+/// Records and maybe prints a warning, use [`warning!`](crate::warning)
+#[doc(hidden)]
+pub fn emit(format: &'static str, message: String) {
+    HISTORY.lock().unwrap().push(message.clone());
+    if SHOW_ALL.load(Ordering::Relaxed) {
+        print(&message);
+        return;
+    }
+    let block = BLOCK
+        .with(|block| block.borrow().clone())
+        .unwrap_or_default();
+    let mut log = LOG.lock().unwrap();
+    let warning = log
+        .entry((block.clone(), format))
+        .or_insert_with(|| RepeatedWarning {
+            block,
+            category: category(format),
+            count: 0,
+            messages: Vec::new(),
+        });
+    warning.count += 1;
+    if warning.count <= THRESHOLD {
+        print(&message);
+    }
+    warning.messages.push(message);
+}
+
+/// Takes the kinds of warning repeated past the threshold since the last call, summing up
+/// those of the run itself. Blocks summed theirs up as they finished
+pub fn take() -> Vec<RepeatedWarning> {
+    let log = std::mem::take(&mut *LOG.lock().unwrap());
+    let repeated: Vec<RepeatedWarning> = log
+        .into_values()
+        .filter(|warning| warning.count > THRESHOLD)
+        .collect();
+    for warning in repeated.iter().filter(|warning| warning.block.is_empty()) {
+        print(&warning.summary());
+    }
+    repeated
+}
+
+/// Writes every warning raised so far in full, one per line, whether it was printed or only
+/// counted:
+///
+/// ```
+/// use stellaris_name_gen::warning;
+/// use stellaris_name_gen::warnings;
+///
+/// for name in ["Vex", "Ember", "Dawn", "Cinder", "Ash"] {
+///     warning!("[Filter] 'NAME/ships': dropped '{}'", name);
+/// }
+/// let path = std::env::temp_dir().join("namegen-warnings").join("warnings.log");
+/// warnings::write_log(&path).unwrap();
+/// let log = std::fs::read_to_string(&path).unwrap();
+/// assert_eq!(log.lines().count(), 5);
+/// assert_eq!(log.lines().last(), Some("[Filter] 'NAME/ships': dropped 'Ash'"));
+/// ```
+pub fn write_log(path: &Path) -> Result<()> {
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).with_context(|| format!("Failed to create {}", dir.display()))?;
+    }
+    let log: String = HISTORY
+        .lock()
+        .unwrap()
+        .iter()
+        .map(|message| format!("{}\n", message))
+        .collect();
+    fs::write(path, log).with_context(|| format!("Failed to write {}", path.display()))
+}
+
+/// Counts the warnings raised while `future` runs for `block`, summing them up once it is done
+pub fn scoped<F: Future>(block: impl Into<String>, future: F) -> Scoped<F> {
+    Scoped {
+        block: block.into(),
+        future: Box::pin(future),
+    }
+}
+
+/// A future counting its warnings for a block, see [`scoped`]
+pub struct Scoped<F> {
+    block: String,
+    future: Pin<Box<F>>,
+}
+
+impl<F: Future> Future for Scoped<F> {
+    type Output = F::Output;
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        let previous = BLOCK.with(|block| block.replace(Some(self.block.clone())));
+        let poll = self.future.as_mut().poll(cx);
+        BLOCK.with(|block| *block.borrow_mut() = previous);
+        if poll.is_ready() {
+            let log = LOG.lock().unwrap();
+            for ((block, _), warning) in log.iter() {
+                if *block == self.block && warning.count > THRESHOLD {
+                    print(&warning.summary());
+                }
+            }
+        }
+        poll
+    }
+}
+
+/// Writes a warning to stderr, cleaned up for the terminal
+fn print(message: &str) {
+    eprintln!("{}", CONSOLE.text(message));
+}
+
+/// A format string with its arguments left out, `{}` standing for `…`
+fn category(format: &str) -> String {
+    let mut out = String::new();
+    let mut chars = format.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                out.push('}');
+            }
+            '{' => {
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        break;
+                    }
+                }
+                out.push('…');
+            }
+            c => out.push(c),
+        }
+    }
+    out
+}